@@ -0,0 +1,8 @@
+// canvas の描画を Worker に移し、シミュレーションをメインスレッドの外で動かす
+const canvas = document.getElementById("canvas") as HTMLCanvasElement;
+const offscreen = canvas.transferControlToOffscreen();
+const worker = new Worker(new URL("./simulation.worker.ts", import.meta.url), { type: "module" });
+
+const options = { disk_num: 3000, width: 500, height: 500, disk_size: 4, collision: true };
+worker.postMessage({ canvas: offscreen, options }, [offscreen]);
+worker.onmessage = (event) => console.error(event.data);
@@ -0,0 +1,15 @@
+import init, { init_gl_offscreen, Screen } from './../../wasm/pkg'
+
+// Screen が GC されると描画が止まるので Worker のスコープで保持しておく
+let screen: Screen | undefined;
+
+self.onmessage = async (event: MessageEvent<{ canvas: OffscreenCanvas; options: object }>) => {
+  await init();
+  try {
+    screen = init_gl_offscreen(event.data.canvas, event.data.options);
+    // Worker の requestAnimationFrame で回る
+    screen.start();
+  } catch (e) {
+    self.postMessage(String(e));
+  }
+};
@@ -59,28 +59,30 @@ const reducer = (state: CanvasState, action: Actions) => {
 }
 
 function App() {
-  const t = useRef<number>(0);
   const glInstance = useRef<Screen | null>(null);
-  const req = useRef<number | null>(null);
   const [animate, setAnimate] = useState<boolean>(false);
+  const [error, setError] = useState<string | null>(null);
   const [state, dispatch] = useReducer(reducer, canvasState)
 
-  const doFrame = () => {
-    if (!glInstance.current) return;
-    t.current += 1;
-    glInstance.current.do_frame();
-    req.current = requestAnimationFrame(doFrame);
-  }
-
   const initGl = () => {
-    glInstance.current = init_gl({
-      canvas_id: state.id,
-      disk_num: state.diskNum,
-      width: state.width,
-      height: state.height,
-      disk_size: state.diskSize,
-    });
-    glInstance.current.do_frame();
+    // 前の Screen のループを止めてGLリソースを解放してから作り直す
+    glInstance.current?.destroy();
+    glInstance.current = null;
+    try {
+      glInstance.current = init_gl({
+        canvas_id: state.id,
+        disk_num: state.diskNum,
+        width: state.width,
+        height: state.height,
+        disk_size: state.diskSize,
+      });
+      setError(null);
+      glInstance.current.do_frame();
+      if (animate) glInstance.current.start();
+    } catch (e) {
+      glInstance.current = null;
+      setError(String(e));
+    }
   }
 
   useEffect(() => {
@@ -96,14 +98,22 @@ function App() {
     initGl();
   }, [glInstance, state])
 
+  const saveScreenshot = () => {
+    if (!glInstance.current) return;
+    const link = document.createElement("a");
+    link.href = glInstance.current.capture_png();
+    link.download = "disks.png";
+    link.click();
+  }
+
   const toggleAnimationState = useCallback(() => {
-    if (animate === true && req.current !== null) {
+    if (!glInstance.current) return;
+    if (animate) {
+      glInstance.current.stop();
       setAnimate(false);
-      cancelAnimationFrame(req.current);
-      req.current = null;
     } else {
+      glInstance.current.start();
       setAnimate(true);
-      doFrame();
     }
   }, [animate]);
   
@@ -113,6 +123,7 @@ function App() {
         <button onClick={toggleAnimationState}>
           {animate === true ? 'stop' : 'start'}
         </button>
+        <button onClick={saveScreenshot}>save screenshot</button>
         <select value={state.diskSize} onChange={(e) => dispatch({ type: "DISK_SIZE_CHANGE", payload: e.target.value })}>
           <option value="4">4</option>
           <option value="8">8</option>
@@ -136,6 +147,7 @@ function App() {
           <option value="1000">1000</option>
         </select>
       </div>
+      {error !== null && <p className="error">{error}</p>}
       <canvas id={state.id} width={state.width} height={state.height} />
     </div>
   )
@@ -0,0 +1,14 @@
+import init, { init_gl } from './../wasm/pkg'
+
+// 2つの canvas でそれぞれ独立した Screen を動かす (衝突判定の有無の比較)
+const main = async () => {
+  await init();
+  const options = { disk_num: 300, width: 400, height: 400, disk_size: 8, seed: 1 };
+  const screens = [
+    init_gl({ ...options, canvas_id: "without-collision", collision: false }),
+    init_gl({ ...options, canvas_id: "with-collision", collision: true }),
+  ];
+  screens.forEach((screen) => screen.start());
+}
+
+main();
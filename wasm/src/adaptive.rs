@@ -0,0 +1,136 @@
+use crate::simulation::MAX_DT_MS;
+
+// adaptive_target_fps を指定しない場合の目標
+pub const DEFAULT_ADAPTIVE_FPS: u32 = 60;
+// ディスクの数を見直す間隔 (ms)。毎フレームではなく、この間のフレームの平均で決める
+pub const ADAPT_INTERVAL_MS: f64 = 2000.;
+// 手動でディスクの数を変えてから見直しを再開するまでの時間 (ms)
+pub const SUSPEND_MS: f64 = 5000.;
+// フレームの間隔の平均が予算 (1000 / 目標 FPS) のこの倍を超えたら減らす
+const SLOW_RATIO: f64 = 1.2;
+// 間隔の平均が予算のこの倍以下で、処理時間の平均が予算のこの割合未満なら増やす
+const FAST_RATIO: f64 = 1.05;
+const HEADROOM_RATIO: f64 = 0.5;
+// 1回に減らす・増やす数の今の数に対する割合 (少なくとも1個)
+const SHRINK_FRACTION: f64 = 0.2;
+const GROW_FRACTION: f64 = 0.1;
+// 減らした後は、余裕のある見直しがこの回数続くまで増やさない (増減を繰り返さないように)
+const GROW_AFTER_SHRINK: u32 = 3;
+
+/**
+ * フレームの間隔と処理時間から、目標の FPS を保てるディスクの数を決める (Options.adaptive)
+ * 遅ければまとめて減らし、十分な余裕が続けば少しずつ戻す。数は min_disks - max_disks に収める
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdaptiveQuality {
+    budget_ms: f64,
+    min_disks: u32,
+    max_disks: u32,
+    // 今の見直しの区間の開始時刻と直前のフレームの時刻
+    window_start: f64,
+    last_frame: Option<f64>,
+    // 区間内のフレームの間隔・処理時間の合計とフレーム数
+    interval_sum: f64,
+    work_sum: f64,
+    frames: u32,
+    // 減らした後、増やすまでに残っている余裕のある見直しの回数
+    cooldown: u32,
+    // suspend で止めている間は、この時刻まで見直さない
+    resume_at: Option<f64>,
+}
+
+impl AdaptiveQuality {
+    pub fn new(target_fps: u32, min_disks: u32, max_disks: u32) -> Result<AdaptiveQuality, String> {
+        if target_fps == 0 {
+            return Err(String::from("adaptive_target_fps must be at least 1"));
+        }
+        if !(1..=max_disks).contains(&min_disks) {
+            return Err(format!(
+                "adaptive disk bounds must be 1 <= min_disks <= max_disks: [{}, {}]",
+                min_disks, max_disks
+            ));
+        }
+        Ok(AdaptiveQuality {
+            budget_ms: 1000. / target_fps as f64,
+            min_disks,
+            max_disks,
+            window_start: 0.,
+            last_frame: None,
+            interval_sum: 0.,
+            work_sum: 0.,
+            frames: 0,
+            cooldown: 0,
+            resume_at: None,
+        })
+    }
+
+    /**
+     * now (ms) から SUSPEND_MS の間は見直さない (手動でディスクの数を変えたときに呼ぶ)
+     */
+    pub fn suspend(&mut self, now: f64) {
+        self.resume_at = Some(now + SUSPEND_MS);
+        self.last_frame = None;
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.resume_at.is_some()
+    }
+
+    /**
+     * now (ms) に始まり work_ms かかったフレームを記録する
+     * 見直しの区間が終わり、数を変えるべきなら新しいディスクの数を返す
+     * MAX_DT_MS より長い間隔 (非表示のタブ・止めていたループ) は数えず、そこから区間をやり直す
+     */
+    pub fn record(&mut self, now: f64, work_ms: f64, disk_count: u32) -> Option<u32> {
+        if let Some(resume_at) = self.resume_at {
+            if now < resume_at {
+                return None;
+            }
+            self.resume_at = None;
+        }
+        match self.last_frame {
+            Some(last) if now >= last && now - last <= MAX_DT_MS => {
+                self.interval_sum += now - last;
+                self.work_sum += work_ms;
+                self.frames += 1;
+            }
+            _ => self.restart(now),
+        }
+        self.last_frame = Some(now);
+        if self.frames == 0 || now - self.window_start < ADAPT_INTERVAL_MS {
+            return None;
+        }
+        let interval = self.interval_sum / self.frames as f64;
+        let work = self.work_sum / self.frames as f64;
+        self.restart(now);
+        // 範囲の外にある数 (手動で変えたもの) は、その向きへはさらに動かさない
+        if interval > self.budget_ms * SLOW_RATIO {
+            self.cooldown = GROW_AFTER_SHRINK;
+            if disk_count <= self.min_disks {
+                return None;
+            }
+            let batch = ((disk_count as f64 * SHRINK_FRACTION) as u32).max(1);
+            Some(disk_count.saturating_sub(batch).max(self.min_disks))
+        } else if interval <= self.budget_ms * FAST_RATIO && work < self.budget_ms * HEADROOM_RATIO
+        {
+            if self.cooldown > 0 {
+                self.cooldown -= 1;
+                return None;
+            }
+            if disk_count >= self.max_disks {
+                return None;
+            }
+            let batch = ((disk_count as f64 * GROW_FRACTION) as u32).max(1);
+            Some(disk_count.saturating_add(batch).min(self.max_disks))
+        } else {
+            None
+        }
+    }
+
+    fn restart(&mut self, now: f64) {
+        self.window_start = now;
+        self.interval_sum = 0.;
+        self.work_sum = 0.;
+        self.frames = 0;
+    }
+}
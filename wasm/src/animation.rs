@@ -0,0 +1,103 @@
+use crate::dom_utils;
+use crate::simulation::FRAME_MS;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+type FrameCallback = Closure<dyn FnMut(f64)>;
+
+/**
+ * 予約中のフレーム (requestAnimationFrame と setTimeout で取り消し方が違う)
+ */
+#[derive(Clone, Copy, Debug)]
+enum Pending {
+    Frame(i32),
+    Timeout(i32),
+}
+
+impl Pending {
+    fn cancel(self) {
+        let (name, id) = match self {
+            Pending::Frame(id) => ("cancelAnimationFrame", id),
+            Pending::Timeout(id) => ("clearTimeout", id),
+        };
+        if dom_utils::call_global(name, &JsValue::from(id)).is_err() {
+            warn!("failed to cancel animation frame {}", id);
+        }
+    }
+}
+
+// Worker (OffscreenCanvas) でも動くよう、window ではなくグローバルスコープの関数を使う
+// requestAnimationFrame のない Worker では、60fps 相当の setTimeout で代わりに進める
+fn request_frame(callback: &FrameCallback) -> Option<Pending> {
+    let id = |value: JsValue| value.as_f64().map(|id| id as i32);
+    match dom_utils::call_global("requestAnimationFrame", callback.as_ref()) {
+        Ok(value) => id(value).map(Pending::Frame),
+        Err(_) => dom_utils::set_timeout(callback.as_ref(), FRAME_MS)
+            .ok()
+            .and_then(id)
+            .map(Pending::Timeout),
+    }
+}
+
+/**
+ * requestAnimationFrame のループ
+ * コールバックは自分自身を次のフレームに登録し直すため、Rc で自己参照している
+ * stop (または drop) で予約中のフレームを取り消し、循環参照を切ってクロージャを解放する
+ */
+#[derive(Debug)]
+pub struct AnimationLoop {
+    handle: Rc<Cell<Option<Pending>>>,
+    callback: Rc<RefCell<Option<FrameCallback>>>,
+}
+
+impl AnimationLoop {
+    /**
+     * on_frame には rAF のタイムスタンプ (ms) が渡される (setTimeout で進める場合は performance.now())
+     */
+    pub fn start(mut on_frame: impl FnMut(f64) + 'static) -> Result<AnimationLoop, String> {
+        let handle = Rc::new(Cell::new(None));
+        let callback: Rc<RefCell<Option<FrameCallback>>> = Rc::new(RefCell::new(None));
+
+        let next_handle = handle.clone();
+        let next_callback = callback.clone();
+        *callback.borrow_mut() = Some(Closure::wrap(Box::new(move |timestamp: f64| {
+            // setTimeout は引数を渡さないので NaN になる
+            let timestamp = if timestamp.is_finite() {
+                timestamp
+            } else {
+                dom_utils::now()
+            };
+            on_frame(timestamp);
+            // stop 済みならクロージャは取り除かれているので再登録しない
+            if let Some(callback) = next_callback.borrow().as_ref() {
+                next_handle.set(request_frame(callback));
+            }
+        }) as Box<dyn FnMut(f64)>));
+
+        let first = callback.borrow().as_ref().and_then(request_frame);
+        match first {
+            Some(pending) => {
+                handle.set(Some(pending));
+                Ok(AnimationLoop { handle, callback })
+            }
+            None => {
+                callback.borrow_mut().take();
+                Err("neither requestAnimationFrame nor setTimeout is available".to_string())
+            }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(pending) = self.handle.take() {
+            pending.cancel();
+        }
+        self.callback.borrow_mut().take();
+    }
+}
+
+impl Drop for AnimationLoop {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
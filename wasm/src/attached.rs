@@ -0,0 +1,276 @@
+use crate::dom_utils::{self, BlendMode, DynamicBuffer, EventListener, GlContext, Shape};
+use crate::simulation::{size_runs, Disk};
+use crate::view::View;
+use crate::{create_point_program, PointProgram};
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use web_sys::WebGlRenderingContext;
+
+/**
+ * 各ビューに描く1フレーム分の内容 (メインの canvas と同じ CPU 側の頂点をそれぞれのバッファへ転送する)
+ */
+pub struct ViewFrame<'a> {
+    // interleave_vertices でまとめた座標・色・大きさの比
+    pub vertices: &'a [f32],
+    pub disks: &'a [Disk],
+    pub disk_size: f64,
+    // シミュレーションの領域の大きさ (視点を省略したビューはこれ全体を表示する)
+    pub world_width: f64,
+    pub world_height: f64,
+    pub shape: Shape,
+    pub blend_mode: BlendMode,
+    pub blend: bool,
+    pub alpha: f32,
+    pub layered: bool,
+}
+
+/**
+ * Screen::attach_view で足した別の canvas (メインと同じシミュレーションを、その canvas のコンテキストで描く)
+ * WebGL のリソースはコンテキストをまたいで使えないので、プログラム・バッファはビューごとに作る
+ */
+#[derive(Debug)]
+pub struct AttachedView {
+    canvas_id: String,
+    gl: GlContext,
+    points: PointProgram,
+    buffer: DynamicBuffer,
+    shader_sources: dom_utils::ShaderSources,
+    width: u32,
+    height: u32,
+    pixel_ratio: f64,
+    // None なら領域全体に合わせる
+    view: Option<View>,
+    background: [f32; 4],
+    // webglcontextlost から webglcontextrestored までは描かない (メインの canvas の描画は止めない)
+    lost: Rc<Cell<bool>>,
+    // 復元された後、次に描くときにプログラム・バッファを作り直す
+    restored: Rc<Cell<bool>>,
+    _listeners: Vec<EventListener>,
+}
+
+impl AttachedView {
+    pub fn new(
+        canvas_id: &str,
+        (width, height): (u32, u32),
+        pixel_ratio: f64,
+        view: Option<View>,
+        background: [f32; 4],
+        shader_sources: &dom_utils::ShaderSources,
+    ) -> Result<AttachedView, String> {
+        let attributes = dom_utils::ContextAttributes {
+            alpha: background[3] < 1.,
+            ..dom_utils::ContextAttributes::default()
+        };
+        let gl =
+            dom_utils::get_webgl_context_by_id(canvas_id, width, height, pixel_ratio, &attributes)?;
+        let points = create_point_program(&gl, shader_sources, width as f64, height as f64)?;
+        let buffer = DynamicBuffer::new(&gl)?;
+        let lost = Rc::new(Cell::new(false));
+        let restored = Rc::new(Cell::new(false));
+        let listeners = match gl
+            .canvas()
+            .and_then(|canvas| canvas.dyn_into::<web_sys::EventTarget>().ok())
+        {
+            Some(target) => watch_context(&target, &lost, &restored)
+                .map_err(|e| format!("failed to watch the context of #{}: {:?}", canvas_id, e))?,
+            None => Vec::new(),
+        };
+        Ok(AttachedView {
+            canvas_id: canvas_id.to_string(),
+            gl,
+            points,
+            buffer,
+            shader_sources: shader_sources.clone(),
+            width,
+            height,
+            pixel_ratio,
+            view,
+            background,
+            lost,
+            restored,
+            _listeners: listeners,
+        })
+    }
+
+    pub fn canvas_id(&self) -> &str {
+        &self.canvas_id
+    }
+
+    pub fn is_context_lost(&self) -> bool {
+        self.lost.get()
+    }
+
+    pub fn set_view(&mut self, view: Option<View>) {
+        self.view = view;
+    }
+
+    /**
+     * 点の描画用のシェーダを差し替える (Screen::set_fragment_shader などでメインの canvas と一緒に変える)
+     * リンクに失敗したら今のプログラムのまま。コンテキストの消失中は、復元したときに新しいシェーダで作る
+     */
+    pub fn set_shader_sources(&mut self, sources: &dom_utils::ShaderSources) -> Result<(), String> {
+        if !self.lost.get() {
+            let points =
+                create_point_program(&self.gl, sources, self.width as f64, self.height as f64)
+                    .map_err(|e| format!("view #{}: {}", self.canvas_id, e))?;
+            dom_utils::release_program(&self.gl, &self.points.program);
+            self.points = points;
+        }
+        self.shader_sources = sources.clone();
+        Ok(())
+    }
+
+    // コンテキストの復元後に、消えたプログラム・バッファを作り直す
+    fn rebuild(&mut self) -> Result<(), String> {
+        dom_utils::forget_lost_programs(&self.gl);
+        self.points = create_point_program(
+            &self.gl,
+            &self.shader_sources,
+            self.width as f64,
+            self.height as f64,
+        )?;
+        self.buffer = DynamicBuffer::new(&self.gl)?;
+        Ok(())
+    }
+
+    /**
+     * frame の内容をこのビューの canvas に描く (コンテキストの消失中は何もしない)
+     */
+    pub fn draw(&mut self, frame: &ViewFrame) {
+        if self.lost.get() {
+            return;
+        }
+        if self.restored.take() {
+            if let Err(e) = self.rebuild() {
+                error!("failed to restore the view on #{}: {}", self.canvas_id, e);
+                self.lost.set(true);
+                return;
+            }
+        }
+        let gl = &self.gl;
+        let points = &self.points;
+        let [r, g, b, a] = self.background;
+        gl.clear_color(r * a, g * a, b * a, a);
+        gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+
+        let view = self.view.unwrap_or_else(|| {
+            View::fit(
+                frame.world_width,
+                frame.world_height,
+                self.width as f64,
+                self.height as f64,
+            )
+        });
+        let point_scale = self.pixel_ratio * view.scale();
+        gl.use_program(Some(&points.program));
+        dom_utils::set_view_uniforms(
+            gl,
+            &points.uniforms,
+            &view,
+            self.width as f64,
+            self.height as f64,
+        );
+        dom_utils::set_shape_uniforms(gl, &points.uniforms, frame.shape);
+        dom_utils::set_edge_uniform(gl, &points.uniforms, frame.disk_size * point_scale);
+        self.buffer.upload(gl, frame.vertices);
+        let stride = (dom_utils::VERTEX_FLOATS * std::mem::size_of::<f32>()) as i32;
+        let offset = |floats: usize| (floats * std::mem::size_of::<f32>()) as i32;
+        gl.vertex_attrib_pointer(
+            points.attrib_coords,
+            3,
+            WebGlRenderingContext::FLOAT,
+            false,
+            stride,
+            0,
+        );
+        gl.enable_vertex_attrib_array(points.attrib_coords);
+        gl.vertex_attrib_pointer(
+            points.attrib_color,
+            4,
+            WebGlRenderingContext::FLOAT,
+            false,
+            stride,
+            offset(dom_utils::VERTEX_COLOR_OFFSET),
+        );
+        gl.enable_vertex_attrib_array(points.attrib_color);
+        gl.uniform1f(points.uniform_alpha.as_ref(), frame.alpha);
+        gl.uniform1f(points.uniform_glow.as_ref(), frame.blend_mode.glow());
+        if frame.blend {
+            dom_utils::enable_blend(gl, frame.blend_mode);
+        }
+        if frame.layered {
+            gl.clear(WebGlRenderingContext::DEPTH_BUFFER_BIT);
+            gl.enable(WebGlRenderingContext::DEPTH_TEST);
+            gl.depth_func(WebGlRenderingContext::LEQUAL);
+        }
+        let count = frame.vertices.len() / dom_utils::VERTEX_FLOATS;
+        if let Some(attrib_scale) = points.attrib_scale {
+            gl.vertex_attrib_pointer(
+                attrib_scale,
+                1,
+                WebGlRenderingContext::FLOAT,
+                false,
+                stride,
+                offset(dom_utils::VERTEX_SCALE_OFFSET),
+            );
+            gl.enable_vertex_attrib_array(attrib_scale);
+            gl.uniform1f(
+                Some(&points.uniform_point_size),
+                (frame.disk_size * point_scale) as f32,
+            );
+            gl.draw_arrays(WebGlRenderingContext::POINTS, 0, count as i32);
+        } else {
+            for &(start, run, size) in size_runs(frame.disks, frame.disk_size).iter() {
+                gl.uniform1f(
+                    Some(&points.uniform_point_size),
+                    (size * point_scale) as f32,
+                );
+                gl.draw_arrays(WebGlRenderingContext::POINTS, start as i32, run as i32);
+            }
+        }
+        if frame.layered {
+            gl.disable(WebGlRenderingContext::DEPTH_TEST);
+        }
+        if frame.blend {
+            gl.disable(WebGlRenderingContext::BLEND);
+        }
+    }
+
+    /**
+     * プログラム・バッファを消し、コンテキストの監視をやめる
+     */
+    pub fn delete(self) {
+        if !self.lost.get() {
+            dom_utils::release_program(&self.gl, &self.points.program);
+            self.buffer.delete(&self.gl);
+        }
+    }
+}
+
+/**
+ * ビューの canvas のコンテキストの消失・復元を、メインの Scene とは別に監視する
+ */
+fn watch_context(
+    target: &web_sys::EventTarget,
+    lost: &Rc<Cell<bool>>,
+    restored: &Rc<Cell<bool>>,
+) -> Result<Vec<EventListener>, wasm_bindgen::JsValue> {
+    let on_lost = {
+        let lost = lost.clone();
+        EventListener::new(target, "webglcontextlost", move |event| {
+            event.prevent_default();
+            warn!("webgl context of an attached view lost");
+            lost.set(true);
+        })?
+    };
+    let on_restored = {
+        let (lost, restored) = (lost.clone(), restored.clone());
+        EventListener::new(target, "webglcontextrestored", move |_| {
+            info!("webgl context of an attached view restored");
+            lost.set(false);
+            restored.set(true);
+        })?
+    };
+    Ok(vec![on_lost, on_restored])
+}
@@ -0,0 +1,213 @@
+use crate::collision::Grid;
+use crate::simulation::{Circle, Disk};
+use serde::{Deserialize, Serialize};
+
+/**
+ * ディスクの動き方
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Behavior {
+    // 外から与えた力以外では速度を変えない (以前の挙動)
+    #[default]
+    Ballistic,
+    // 近くのディスクとの分離・整列・結合で向きを変える群れ
+    Boids,
+}
+
+impl Behavior {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Behavior::Ballistic => "ballistic",
+            Behavior::Boids => "boids",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Result<Behavior, String> {
+        match mode {
+            "ballistic" => Ok(Behavior::Ballistic),
+            "boids" => Ok(Behavior::Boids),
+            _ => Err(format!(
+                "unknown behavior: {} (expected \"ballistic\" or \"boids\")",
+                mode
+            )),
+        }
+    }
+}
+
+// 各規則の合計を1フレームあたりの速度の変化に直す倍率
+const STEER_RATE: f64 = 0.05;
+// 壁からこの距離 (perception に対する割合) 以内に入ると内側へ向きを変え始める
+const WALL_MARGIN: f64 = 1.;
+// 壁に最も近づいたときに1フレームで加える内向きの速さ
+const WALL_TURN: f64 = 0.5;
+
+/**
+ * boids モードの重みと範囲 (省略した値は既定値)
+ * perception は近くのディスクとみなす中心間の距離、max_speed は1フレームあたりの移動量の上限
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoidsParams {
+    pub separation: f64,
+    pub alignment: f64,
+    pub cohesion: f64,
+    pub perception: f64,
+    pub max_speed: f64,
+}
+
+impl Default for BoidsParams {
+    fn default() -> Self {
+        BoidsParams {
+            separation: 1.5,
+            alignment: 1.,
+            cohesion: 1.,
+            perception: 50.,
+            max_speed: 4.,
+        }
+    }
+}
+
+impl BoidsParams {
+    /**
+     * 重みが 0 以上、perception と max_speed が正か
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, weight) in [
+            ("separation", self.separation),
+            ("alignment", self.alignment),
+            ("cohesion", self.cohesion),
+        ] {
+            if !(weight.is_finite() && weight >= 0.) {
+                return Err(format!(
+                    "boids.{} must be a non-negative number: {}",
+                    name, weight
+                ));
+            }
+        }
+        for (name, value) in [
+            ("perception", self.perception),
+            ("max_speed", self.max_speed),
+        ] {
+            if !(value.is_finite() && value > 0.) {
+                return Err(format!(
+                    "boids.{} must be a positive number: {}",
+                    name, value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// ディスクごとに集めた近くのディスクの情報
+#[derive(Clone, Copy, Default)]
+struct Neighborhood {
+    count: u32,
+    // 中心・速度の合計
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+    // 近い相手ほど強く離れる向きの合計
+    push_x: f64,
+    push_y: f64,
+}
+
+/**
+ * 分離・整列・結合の3つの規則と壁を避ける向きで速度を変え、速さを max_speed までに抑える
+ * 近くのディスクは grid で探す (perception の大きさのセルに振り分け直す)
+ * circle が指定されていれば (arena: "circle")、矩形の壁の代わりにその円の縁を避ける
+ */
+pub fn steer_boids(
+    disks: &mut [Disk],
+    grid: &mut Grid,
+    params: &BoidsParams,
+    width: f64,
+    height: f64,
+    circle: Option<&Circle>,
+    dt_scale: f64,
+) {
+    let perception = params.perception;
+    let mut neighborhoods = vec![Neighborhood::default(); disks.len()];
+    for &(i, j) in grid.pairs_within(disks, width, height, perception) {
+        let (a, b) = (disks[i], disks[j]);
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let distance = dx.hypot(dy);
+        // 中心が一致した相手からは離れる向きが決まらないので、押し合いには数えない
+        let (push_x, push_y) = if distance > 0. {
+            let strength = (1. - distance / perception) / distance;
+            (dx * strength, dy * strength)
+        } else {
+            (0., 0.)
+        };
+        for (index, other, sign) in [(i, b, -1.), (j, a, 1.)] {
+            let n = &mut neighborhoods[index];
+            n.count += 1;
+            n.x += other.x;
+            n.y += other.y;
+            n.vx += other.cos;
+            n.vy += other.sin;
+            n.push_x += push_x * sign;
+            n.push_y += push_y * sign;
+        }
+    }
+    let margin = match circle {
+        Some(circle) => (perception * WALL_MARGIN).min(circle.radius),
+        None => (perception * WALL_MARGIN).min(width / 2.).min(height / 2.),
+    };
+    for (disk, n) in disks.iter_mut().zip(neighborhoods.iter()) {
+        let (mut ax, mut ay) = (0., 0.);
+        if n.count > 0 {
+            let count = n.count as f64;
+            ax += params.separation * n.push_x;
+            ay += params.separation * n.push_y;
+            ax += params.alignment * (n.vx / count - disk.cos);
+            ay += params.alignment * (n.vy / count - disk.sin);
+            ax += params.cohesion * (n.x / count - disk.x) / perception;
+            ay += params.cohesion * (n.y / count - disk.y) / perception;
+        }
+        disk.cos += ax * STEER_RATE * dt_scale;
+        disk.sin += ay * STEER_RATE * dt_scale;
+        if margin > 0. {
+            let (tx, ty) = match circle {
+                Some(circle) => rim_turn(disk.x, disk.y, circle, margin),
+                None => (
+                    wall_turn(disk.x, width, margin),
+                    wall_turn(disk.y, height, margin),
+                ),
+            };
+            disk.cos += tx * dt_scale;
+            disk.sin += ty * dt_scale;
+        }
+        let speed = disk.cos.hypot(disk.sin);
+        if speed > params.max_speed {
+            let scale = params.max_speed / speed;
+            disk.cos *= scale;
+            disk.sin *= scale;
+        }
+    }
+}
+
+// 壁から margin 以内にいれば、近いほど強く内側へ向ける速度の変化 (1フレームあたり)
+fn wall_turn(position: f64, bound: f64, margin: f64) -> f64 {
+    if position < margin {
+        WALL_TURN * (1. - position.max(0.) / margin)
+    } else if position > bound - margin {
+        -WALL_TURN * (1. - (bound - position).max(0.) / margin)
+    } else {
+        0.
+    }
+}
+
+// 円の縁から margin 以内にいれば、近いほど強く中心へ向ける速度の変化 (wall_turn の円の領域版)
+fn rim_turn(x: f64, y: f64, circle: &Circle, margin: f64) -> (f64, f64) {
+    let (dx, dy) = (x - circle.x, y - circle.y);
+    let distance = dx.hypot(dy);
+    let depth = circle.radius - distance;
+    if distance <= 0. || depth >= margin {
+        return (0., 0.);
+    }
+    let turn = WALL_TURN * (1. - depth.max(0.) / margin);
+    (-dx / distance * turn, -dy / distance * turn)
+}
@@ -0,0 +1,303 @@
+use crate::dom_utils::{self, DrawUniforms, GlContext, Instancing};
+use web_sys::{WebGlBuffer, WebGlProgram, WebGlRenderingContext, WebGlUniformLocation};
+
+/**
+ * ディスクの描き方
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    // gl.POINTS + gl_PointSize (ALIASED_POINT_SIZE_RANGE の上限を超えられない)
+    Points,
+    // ディスクごとに三角形2枚を描き、フラグメントシェーダで円に切り抜く
+    // 点の大きさの上限がなく、中心が画面外に出ても欠けない ("quads" とも書ける)
+    Circles,
+    // Circles と同じ四角形を1つだけ用意し、中心・色・大きさをディスクごとの属性にして
+    // インスタンス描画する (頂点の展開が要らない)
+    Instanced,
+}
+
+impl RenderMode {
+    pub fn parse(mode: &str) -> Result<RenderMode, String> {
+        match mode {
+            "points" => Ok(RenderMode::Points),
+            "circles" | "quads" => Ok(RenderMode::Circles),
+            "instanced" => Ok(RenderMode::Instanced),
+            _ => Err(format!(
+                "unknown render mode: {} (expected \"points\", \"circles\", \"quads\" or \"instanced\")",
+                mode
+            )),
+        }
+    }
+}
+
+// 1ディスクあたり2つの三角形 (6頂点) の四隅
+const CORNERS: [[f32; 2]; 6] = [
+    [-1., -1.],
+    [1., -1.],
+    [1., 1.],
+    [-1., -1.],
+    [1., 1.],
+    [-1., 1.],
+];
+const VERTICES_PER_DISK: usize = CORNERS.len();
+
+/**
+ * render_mode: "circles" | "instanced" の描画に必要なGLリソース
+ */
+#[derive(Debug)]
+pub struct CirclePass {
+    program: WebGlProgram,
+    buffer_centers: WebGlBuffer,
+    buffer_corners: WebGlBuffer,
+    buffer_colors: WebGlBuffer,
+    buffer_scales: WebGlBuffer,
+    attrib_coords: u32,
+    attrib_corner: u32,
+    attrib_color: u32,
+    attrib_scale: u32,
+    uniform_radius: WebGlUniformLocation,
+    uniform_alpha: WebGlUniformLocation,
+    uniform_glow: WebGlUniformLocation,
+    uniforms: DrawUniforms,
+    // "instanced" のときのみ
+    instancing: Option<Instancing>,
+
+    centers: Vec<f32>,
+    corners: Vec<f32>,
+    colors: Vec<f32>,
+    scales: Vec<f32>,
+}
+
+impl CirclePass {
+    pub fn new(
+        gl: &GlContext,
+        width: u32,
+        height: u32,
+        antialias: bool,
+        instanced: bool,
+    ) -> Result<CirclePass, String> {
+        let instancing = if instanced {
+            Some(gl.instancing()?)
+        } else {
+            None
+        };
+        let program = dom_utils::create_circle_program(gl, antialias)?;
+        gl.use_program(Some(&program));
+        let uniform = |name: &str| dom_utils::uniform_location(gl, &program, name);
+        let uniform_radius = uniform("u_radius")?;
+        let uniform_alpha = uniform("u_alpha")?;
+        let uniform_glow = uniform("u_glow")?;
+        dom_utils::set_size_uniforms(gl, &program, width as f64, height as f64)?;
+        let buffer = || {
+            gl.create_buffer()
+                .ok_or_else(|| String::from("failed to create buffer."))
+        };
+        Ok(CirclePass {
+            attrib_coords: dom_utils::attrib_location(gl, &program, "a_coords")?,
+            attrib_corner: dom_utils::attrib_location(gl, &program, "a_corner")?,
+            attrib_color: dom_utils::attrib_location(gl, &program, "a_color")?,
+            attrib_scale: dom_utils::attrib_location(gl, &program, "a_scale")?,
+            buffer_centers: buffer()?,
+            buffer_corners: buffer()?,
+            buffer_colors: buffer()?,
+            buffer_scales: buffer()?,
+            uniform_radius,
+            uniform_alpha,
+            uniform_glow,
+            uniforms: DrawUniforms::new(gl, &program),
+            instancing,
+            program,
+            centers: Vec::new(),
+            corners: Vec::new(),
+            colors: Vec::new(),
+            scales: Vec::new(),
+        })
+    }
+
+    pub fn instanced(&self) -> bool {
+        self.instancing.is_some()
+    }
+
+    pub fn program(&self) -> &WebGlProgram {
+        &self.program
+    }
+
+    pub fn uniforms(&self) -> &DrawUniforms {
+        &self.uniforms
+    }
+
+    /**
+     * canvas の論理サイズが変わったときに呼ぶ
+     */
+    pub fn resize(&self, gl: &GlContext, width: u32, height: u32) -> Result<(), String> {
+        dom_utils::set_size_uniforms(gl, &self.program, width as f64, height as f64)
+    }
+
+    pub fn delete(&self, gl: &GlContext) {
+        dom_utils::delete_program(gl, &self.program);
+        for buffer in [
+            &self.buffer_centers,
+            &self.buffer_corners,
+            &self.buffer_colors,
+            &self.buffer_scales,
+        ] {
+            gl.delete_buffer(Some(buffer));
+        }
+    }
+
+    fn upload(&self, gl: &GlContext, buffer: &WebGlBuffer, data: &[f32], attrib: u32, size: i32) {
+        gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(buffer));
+        gl.buffer_data_f32(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            data,
+            WebGlRenderingContext::STREAM_DRAW,
+        );
+        self.bind_attrib(gl, buffer, attrib, size);
+    }
+
+    fn bind_attrib(&self, gl: &GlContext, buffer: &WebGlBuffer, attrib: u32, size: i32) {
+        gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(buffer));
+        gl.vertex_attrib_pointer(attrib, size, WebGlRenderingContext::FLOAT, false, 0, 0);
+        gl.enable_vertex_attrib_array(attrib);
+    }
+
+    /**
+     * ディスクの中心・色を頂点ごとに展開して描画する
+     * positions は1diskあたりxyの2値、colors は1diskあたりrgbaの4値
+     * colors_dirty が false なら前回の色を使い回す
+     * 半径 radius * scales[i] で描く (scales は1diskあたり1値)
+     * alpha は不透明度 (ブレンドの設定は呼び出し側で行う)、glow は中心から縁へ色を暗くする割合
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        gl: &GlContext,
+        positions: &[f32],
+        colors: &[f32],
+        colors_dirty: bool,
+        scales: &[f32],
+        radius: f64,
+        alpha: f32,
+        glow: f32,
+    ) {
+        gl.use_program(Some(&self.program));
+        gl.uniform1f(Some(&self.uniform_radius), radius as f32);
+        gl.uniform1f(Some(&self.uniform_alpha), alpha);
+        gl.uniform1f(Some(&self.uniform_glow), glow);
+        if let Some(instancing) = self.instancing.clone() {
+            self.draw_instanced(gl, &instancing, positions, colors, colors_dirty, scales);
+            return;
+        }
+        let disk_count = positions.len() / 2;
+
+        self.centers.clear();
+        for center in positions.chunks(2) {
+            for _ in 0..VERTICES_PER_DISK {
+                self.centers.extend_from_slice(center);
+            }
+        }
+        self.upload(
+            gl,
+            &self.buffer_centers,
+            &self.centers,
+            self.attrib_coords,
+            2,
+        );
+
+        // 四隅は頂点数が変わったときだけ作り直す
+        if self.corners.len() != disk_count * VERTICES_PER_DISK * 2 {
+            self.corners.clear();
+            for _ in 0..disk_count {
+                self.corners.extend(CORNERS.iter().flatten());
+            }
+            self.upload(
+                gl,
+                &self.buffer_corners,
+                &self.corners,
+                self.attrib_corner,
+                2,
+            );
+        } else {
+            self.bind_attrib(gl, &self.buffer_corners, self.attrib_corner, 2);
+        }
+
+        if colors_dirty || self.colors.len() != disk_count * VERTICES_PER_DISK * 4 {
+            self.colors.clear();
+            for rgba in colors.chunks(4) {
+                for _ in 0..VERTICES_PER_DISK {
+                    self.colors.extend_from_slice(rgba);
+                }
+            }
+            self.upload(gl, &self.buffer_colors, &self.colors, self.attrib_color, 4);
+        } else {
+            self.bind_attrib(gl, &self.buffer_colors, self.attrib_color, 4);
+        }
+
+        self.scales.clear();
+        for &scale in scales {
+            self.scales
+                .extend(std::iter::repeat_n(scale, VERTICES_PER_DISK));
+        }
+        self.upload(gl, &self.buffer_scales, &self.scales, self.attrib_scale, 1);
+
+        gl.draw_arrays(
+            WebGlRenderingContext::TRIANGLES,
+            0,
+            (disk_count * VERTICES_PER_DISK) as i32,
+        );
+    }
+
+    /**
+     * 四隅1組をすべてのディスクで共有し、中心・色・大きさはディスクごとに1つずつ進める
+     */
+    fn draw_instanced(
+        &mut self,
+        gl: &GlContext,
+        instancing: &Instancing,
+        positions: &[f32],
+        colors: &[f32],
+        colors_dirty: bool,
+        scales: &[f32],
+    ) {
+        let disk_count = positions.len() / 2;
+
+        if self.corners.len() != VERTICES_PER_DISK * 2 {
+            self.corners.clear();
+            self.corners.extend(CORNERS.iter().flatten());
+            self.upload(
+                gl,
+                &self.buffer_corners,
+                &self.corners,
+                self.attrib_corner,
+                2,
+            );
+        } else {
+            self.bind_attrib(gl, &self.buffer_corners, self.attrib_corner, 2);
+        }
+        self.upload(gl, &self.buffer_centers, positions, self.attrib_coords, 2);
+        if colors_dirty || self.colors.len() != colors.len() {
+            self.colors.clear();
+            self.colors.extend_from_slice(colors);
+            self.upload(gl, &self.buffer_colors, &self.colors, self.attrib_color, 4);
+        } else {
+            self.bind_attrib(gl, &self.buffer_colors, self.attrib_color, 4);
+        }
+        self.upload(gl, &self.buffer_scales, scales, self.attrib_scale, 1);
+
+        let per_disk = [self.attrib_coords, self.attrib_color, self.attrib_scale];
+        for &attrib in per_disk.iter() {
+            gl.vertex_attrib_divisor(instancing, attrib, 1);
+        }
+        gl.draw_arrays_instanced(
+            instancing,
+            WebGlRenderingContext::TRIANGLES,
+            0,
+            VERTICES_PER_DISK as i32,
+            disk_count as i32,
+        );
+        // 他のパスの描画に影響しないよう戻す
+        for &attrib in per_disk.iter() {
+            gl.vertex_attrib_divisor(instancing, attrib, 0);
+        }
+    }
+}
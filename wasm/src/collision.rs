@@ -0,0 +1,372 @@
+use crate::simulation::Disk;
+
+/**
+ * 反発係数と、それ以下の速さでぶつかったら跳ね返さない (静止させる) 速さ
+ * rest_speed は coefficient が 1.0 未満のときだけ使う (弾性衝突はいつでも弾性衝突のまま)
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Restitution {
+    pub coefficient: f64,
+    pub rest_speed: f64,
+}
+
+impl Restitution {
+    pub fn elastic() -> Restitution {
+        Restitution {
+            coefficient: 1.,
+            rest_speed: 0.,
+        }
+    }
+
+    /**
+     * 法線方向に speed でぶつかったときに使う反発係数
+     */
+    pub fn at(&self, speed: f64) -> f64 {
+        if self.coefficient < 1. && speed < self.rest_speed {
+            0.
+        } else {
+            self.coefficient
+        }
+    }
+}
+
+/**
+ * 接触している2つのディスクの速度を、中心を結ぶ法線方向の2体衝突として更新する
+ * 反発係数 restitution (1.0 で弾性衝突、0.0 で法線方向の相対速度がなくなる) を法線成分に掛け、
+ * 質量比に応じて速度を配分する。運動量は restitution によらず保存される
+ * 近づいている場合のみ速度を変え、変えたかどうかを返す
+ */
+pub fn resolve_collision(a: &mut Disk, b: &mut Disk, restitution: f64) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let distance = dx.hypot(dy);
+    // 中心が完全に一致する場合は法線が定まらないので x 軸方向とする
+    let (nx, ny) = if distance > 0. {
+        (dx / distance, dy / distance)
+    } else {
+        (1., 0.)
+    };
+    let relative = (b.cos - a.cos) * nx + (b.sin - a.sin) * ny;
+    if relative >= 0. {
+        return false;
+    }
+    let total_mass = a.mass + b.mass;
+    let impulse_a = relative * (1. + restitution) * (b.mass / total_mass);
+    let impulse_b = relative * (1. + restitution) * (a.mass / total_mass);
+    a.cos += impulse_a * nx;
+    a.sin += impulse_a * ny;
+    b.cos -= impulse_b * nx;
+    b.sin -= impulse_b * ny;
+    true
+}
+
+/**
+ * 2つのディスクが重なっていれば押し戻し、質量に応じて法線方向の速度成分をやり取りする
+ * 弾性衝突なので運動量は保存される (等質量なら法線方向の速度成分を交換するのと同じ)
+ * size はディスク固有の大きさがない場合の半径
+ */
+pub fn resolve_pair(a: &mut Disk, b: &mut Disk, size: f64) -> bool {
+    resolve_pair_with(a, b, size, 1.)
+}
+
+/**
+ * resolve_pair と同じだが、反発係数 restitution で速度を更新する
+ */
+pub fn resolve_pair_with(a: &mut Disk, b: &mut Disk, size: f64, restitution: f64) -> bool {
+    // collides: false のグループのディスクは押し戻さない
+    if a.ghost || b.ghost {
+        return false;
+    }
+    let min_distance = a.size_or(size) + b.size_or(size);
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let distance_sq = dx * dx + dy * dy;
+    if distance_sq >= min_distance * min_distance {
+        return false;
+    }
+    let distance = distance_sq.sqrt();
+    // 中心が完全に一致する場合は法線が定まらないので x 軸方向に分離する
+    let (nx, ny) = if distance > 0. {
+        (dx / distance, dy / distance)
+    } else {
+        (1., 0.)
+    };
+    // 軽い方ほど大きく押し戻す
+    let total_mass = a.mass + b.mass;
+    let share_a = b.mass / total_mass;
+    let share_b = a.mass / total_mass;
+    let overlap = min_distance - distance;
+    a.x -= nx * overlap * share_a;
+    a.y -= ny * overlap * share_a;
+    b.x += nx * overlap * share_b;
+    b.y += ny * overlap * share_b;
+
+    // 押し戻しは法線方向なので、押し戻した後の中心からも同じ法線が求まる
+    resolve_collision(a, b, restitution);
+    true
+}
+
+// 中心を結ぶ方向に近づいている速さ (離れていれば 0 以下)
+fn normal_speed(a: &Disk, b: &Disk) -> f64 {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let distance = dx.hypot(dy);
+    if distance > 0. {
+        -((b.cos - a.cos) * dx + (b.sin - a.sin) * dy) / distance
+    } else {
+        (a.cos - b.cos).max(0.)
+    }
+}
+
+fn pair_mut<T>(items: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
+    let (head, tail) = items.split_at_mut(j);
+    (&mut head[i], &mut tail[0])
+}
+
+/**
+ * 2つのディスクが重なっているか (size はディスク固有の大きさがない場合の半径)
+ */
+pub fn overlaps(a: &Disk, b: &Disk, size: f64) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let min_distance = a.size_or(size) + b.size_or(size);
+    dx * dx + dy * dy < min_distance * min_distance
+}
+
+/**
+ * 重なっているペア (i < j) を総当たりで列挙する
+ */
+pub fn overlapping_pairs_brute_force(disks: &[Disk], size: f64) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in 0..disks.len() {
+        for j in (i + 1)..disks.len() {
+            if overlaps(&disks[i], &disks[j], size) {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+/**
+ * 全ペアを総当たりで判定する (O(n²))
+ */
+pub fn resolve_brute_force(disks: &mut [Disk], size: f64) -> usize {
+    let mut collisions = 0;
+    for j in 1..disks.len() {
+        for i in 0..j {
+            let (a, b) = pair_mut(disks, i, j);
+            if resolve_pair(a, b, size) {
+                collisions += 1;
+            }
+        }
+    }
+    collisions
+}
+
+/**
+ * 一様グリッドによる空間ハッシュ
+ * セルの大きさを一番大きいディスクの直径にしておけば、衝突相手は隣接セルにしか存在しない
+ * セルが直径より小さい場合は、その分だけ探索する近傍を広げる
+ */
+#[derive(Debug, Default)]
+pub struct Grid {
+    cols: usize,
+    rows: usize,
+    cell_size: f64,
+    // 固定のセルサイズ (None なら一番大きいディスクの直径)
+    fixed_cell_size: Option<f64>,
+    // 何セル先まで衝突相手を探すか
+    reach: usize,
+    // セルごとの開始位置 (長さ cols * rows + 1)
+    cell_start: Vec<usize>,
+    // セル順に並べたディスクのインデックス
+    cell_items: Vec<usize>,
+    disk_cell: Vec<usize>,
+    pairs: Vec<(usize, usize)>,
+}
+
+impl Grid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * セルサイズを固定したグリッド (主にテスト用)
+     */
+    pub fn with_cell_size(cell_size: f64) -> Self {
+        Self {
+            fixed_cell_size: Some(cell_size),
+            ..Self::default()
+        }
+    }
+
+    fn cell_of(&self, x: f64, y: f64) -> (usize, usize) {
+        let cx = (x / self.cell_size).floor().max(0.) as usize;
+        let cy = (y / self.cell_size).floor().max(0.) as usize;
+        (cx.min(self.cols - 1), cy.min(self.rows - 1))
+    }
+
+    /**
+     * ディスクをセルに振り分け直す (確保済みの領域を再利用する)
+     */
+    pub fn rebuild(&mut self, disks: &[Disk], width: f64, height: f64, size: f64) {
+        let largest = disks
+            .iter()
+            .map(|disk| disk.size_or(size))
+            .fold(0., f64::max);
+        self.build_cells(disks, width, height, largest * 2.);
+    }
+
+    // 中心間の距離 diameter 以内の相手が reach セル以内に入るようにディスクを振り分ける
+    fn build_cells(&mut self, disks: &[Disk], width: f64, height: f64, diameter: f64) {
+        self.cell_size = self.fixed_cell_size.unwrap_or(diameter).max(1.);
+        // 境界ちょうどのディスクも隣のセルから届くよう、切り上げた上で最低1セル見る
+        self.reach = ((diameter / self.cell_size).ceil() as usize).max(1);
+        self.cols = ((width / self.cell_size).ceil() as usize).max(1);
+        self.rows = ((height / self.cell_size).ceil() as usize).max(1);
+        let cell_count = self.cols * self.rows;
+
+        self.cell_start.clear();
+        self.cell_start.resize(cell_count + 1, 0);
+        self.disk_cell.clear();
+        for disk in disks.iter() {
+            let (cx, cy) = self.cell_of(disk.x, disk.y);
+            let cell = cy * self.cols + cx;
+            self.disk_cell.push(cell);
+            self.cell_start[cell + 1] += 1;
+        }
+        for cell in 0..cell_count {
+            self.cell_start[cell + 1] += self.cell_start[cell];
+        }
+        // counting sort: 各セルの末尾から書き込み位置を詰めていく
+        self.cell_items.clear();
+        self.cell_items.resize(disks.len(), 0);
+        for (index, &cell) in self.disk_cell.iter().enumerate() {
+            let slot = self.cell_start[cell + 1] - 1;
+            self.cell_items[slot] = index;
+            self.cell_start[cell + 1] -= 1;
+        }
+        // 上のループで cell_start[cell + 1] は各セルの先頭まで戻っているので1つずらす
+        self.cell_start.rotate_left(1);
+        self.cell_start[cell_count] = disks.len();
+    }
+
+    /**
+     * 隣接セルに含まれる候補ペア (i < j) を列挙する
+     */
+    fn collect_pairs(&mut self) {
+        self.pairs.clear();
+        for (i, &cell) in self.disk_cell.iter().enumerate() {
+            let cx = cell % self.cols;
+            let cy = cell / self.cols;
+            let reach = self.reach;
+            for ny in cy.saturating_sub(reach)..=(cy + reach).min(self.rows - 1) {
+                for nx in cx.saturating_sub(reach)..=(cx + reach).min(self.cols - 1) {
+                    let neighbor = ny * self.cols + nx;
+                    let items =
+                        &self.cell_items[self.cell_start[neighbor]..self.cell_start[neighbor + 1]];
+                    for &j in items {
+                        if j > i {
+                            self.pairs.push((i, j));
+                        }
+                    }
+                }
+            }
+        }
+        // 総当たりと同じ順序で解決するため (j, i) の順に並べる
+        self.pairs.sort_unstable_by_key(|&(i, j)| (j, i));
+    }
+
+    /**
+     * 直近の resolve / overlapping_pairs で距離を判定した候補ペアの数 (総当たりなら n(n-1)/2)
+     */
+    pub fn pair_tests(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /**
+     * グリッドを使って重なっているペア (i < j) を列挙する
+     */
+    pub fn overlapping_pairs(
+        &mut self,
+        disks: &[Disk],
+        width: f64,
+        height: f64,
+        size: f64,
+    ) -> Vec<(usize, usize)> {
+        self.rebuild(disks, width, height, size);
+        self.collect_pairs();
+        let mut pairs: Vec<(usize, usize)> = self
+            .pairs
+            .iter()
+            .copied()
+            .filter(|&(i, j)| overlaps(&disks[i], &disks[j], size))
+            .collect();
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /**
+     * 中心間の距離が radius 未満のペア (i < j) を列挙する
+     * セルの大きさを radius にして振り分け直すので、直後の resolve で元に戻る
+     */
+    pub fn pairs_within(
+        &mut self,
+        disks: &[Disk],
+        width: f64,
+        height: f64,
+        radius: f64,
+    ) -> &[(usize, usize)] {
+        self.build_cells(disks, width, height, radius);
+        self.collect_pairs();
+        let radius_sq = radius * radius;
+        self.pairs.retain(|&(i, j)| {
+            let dx = disks[j].x - disks[i].x;
+            let dy = disks[j].y - disks[i].y;
+            dx * dx + dy * dy < radius_sq
+        });
+        &self.pairs
+    }
+
+    /**
+     * グリッドを再構築し、候補ペアのみ衝突判定する
+     */
+    pub fn resolve(&mut self, disks: &mut [Disk], width: f64, height: f64, size: f64) -> usize {
+        self.resolve_with(
+            disks,
+            width,
+            height,
+            size,
+            Restitution::elastic(),
+            |_, _, _, _, _| {},
+        )
+    }
+
+    /**
+     * resolve と同じだが、反発係数 restitution で速度を更新し (接近する速さが rest_speed 未満なら 0 とする)、
+     * 衝突したペアごとに on_collision(i, j, 解決後の a, b, 衝突前の相対速度の大きさ) を呼ぶ
+     */
+    pub fn resolve_with(
+        &mut self,
+        disks: &mut [Disk],
+        width: f64,
+        height: f64,
+        size: f64,
+        restitution: Restitution,
+        mut on_collision: impl FnMut(usize, usize, &Disk, &Disk, f64),
+    ) -> usize {
+        self.rebuild(disks, width, height, size);
+        self.collect_pairs();
+        let mut collisions = 0;
+        for &(i, j) in self.pairs.iter() {
+            let (a, b) = pair_mut(disks, i, j);
+            let speed = (b.cos - a.cos).hypot(b.sin - a.sin);
+            if resolve_pair_with(a, b, size, restitution.at(normal_speed(a, b))) {
+                collisions += 1;
+                on_collision(i, j, a, b, speed);
+            }
+        }
+        collisions
+    }
+}
@@ -0,0 +1,436 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/**
+ * ディスクの色の決め方
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    // 初期化時にランダムに決めた色
+    Random,
+    // ユーザー指定のパレットを順番に割り当てる
+    Static,
+    // 速さに応じて毎フレーム色を変える
+    Speed,
+    // ディスクごとの色相を時間とともに回す
+    Cycle,
+}
+
+impl ColorMode {
+    /**
+     * parse で受け付ける名前 ("velocity" は "speed" になる)
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Random => "random",
+            ColorMode::Static => "static",
+            ColorMode::Speed => "speed",
+            ColorMode::Cycle => "cycle",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Result<ColorMode, String> {
+        match mode {
+            "random" => Ok(ColorMode::Random),
+            "static" => Ok(ColorMode::Static),
+            // "velocity" は "speed" の別名
+            "speed" | "velocity" => Ok(ColorMode::Speed),
+            "cycle" => Ok(ColorMode::Cycle),
+            _ => Err(format!(
+                "unknown color mode: {} (expected \"random\", \"static\", \"speed\", \"velocity\" or \"cycle\")",
+                mode
+            )),
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_rgb(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        lerp(a[0], b[0], t),
+        lerp(a[1], b[1], t),
+        lerp(a[2], b[2], t),
+    ]
+}
+
+// "speed" モードで赤になる速さの既定値
+pub const DEFAULT_MAX_VELOCITY: f64 = 4.;
+
+const SLOW: [f32; 3] = [0., 0., 1.];
+const MIDDLE: [f32; 3] = [0., 1., 0.];
+const FAST: [f32; 3] = [1., 0., 0.];
+
+/**
+ * 0.0 - 1.0 の値を 青 -> 緑 -> 赤 のグラデーションに変換する
+ * 範囲外の値は端に丸める
+ */
+pub fn speed_gradient(t: f64) -> [f32; 3] {
+    let t = if t.is_nan() {
+        0.
+    } else {
+        t.clamp(0., 1.) as f32
+    };
+    if t < 0.5 {
+        lerp_rgb(SLOW, MIDDLE, t * 2.)
+    } else {
+        lerp_rgb(MIDDLE, FAST, (t - 0.5) * 2.)
+    }
+}
+
+/**
+ * 速さ speed を [min, max] の範囲で speed_gradient の色にする (min 以下は青、max 以上は赤)
+ */
+pub fn speed_color(speed: f64, min: f64, max: f64) -> [f32; 3] {
+    speed_gradient((speed - min) / (max - min))
+}
+
+// "cycle" モードで色相を回す速さの既定値 (度 / 秒)
+pub const DEFAULT_CYCLE_SPEED: f64 = 30.;
+// "cycle" モードでディスクごとの色相をずらす角度 (黄金角。何個並べても色相が偏らない)
+const GOLDEN_ANGLE: f64 = 137.507_764_050_037_85;
+
+/**
+ * HSV (色相は度、彩度・明度は 0.0 - 1.0) を rgb (0.0 - 1.0) にする
+ * 色相は 360 度で一周し、負の値や 360 以上の値も折り返す。彩度・明度は範囲外を端に丸める
+ */
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [f32; 3] {
+    let hue = if hue.is_finite() {
+        hue.rem_euclid(360.)
+    } else {
+        0.
+    };
+    let saturation = saturation.clamp(0., 1.);
+    let value = value.clamp(0., 1.);
+    let chroma = value * saturation;
+    let sector = hue / 60.;
+    let x = chroma * (1. - (sector % 2. - 1.).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    let m = value - chroma;
+    [(r + m) as f32, (g + m) as f32, (b + m) as f32]
+}
+
+/**
+ * "cycle" モードで色相 hue のディスクの色 (色相を phase 度回したもの)
+ */
+pub fn cycle_color(hue: f64, phase: f64) -> [f32; 3] {
+    hsv_to_rgb(hue + phase, 1., 1.)
+}
+
+/**
+ * "cycle" モードのディスクごとの色相 (度)
+ * 足したディスクには順に黄金角ずつずらした色相を割り当て、ディスクの削除・合体に合わせて詰め直す
+ * (番号から色相を決めると、前のディスクが消えたときに後ろのディスクの色が一斉に変わってしまう)
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiskHues {
+    hues: Vec<f64>,
+    // これまでに割り当てた色相の数
+    assigned: usize,
+}
+
+impl DiskHues {
+    pub fn hues(&self) -> &[f64] {
+        &self.hues
+    }
+
+    /**
+     * ディスクの数 len に合わせる (増えた分に新しい色相を割り当て、減った分は末尾から捨てる)
+     */
+    pub fn sync(&mut self, len: usize) {
+        self.hues.truncate(len);
+        while self.hues.len() < len {
+            self.hues
+                .push((self.assigned as f64 * GOLDEN_ANGLE).rem_euclid(360.));
+            self.assigned += 1;
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.hues.len() {
+            self.hues.remove(index);
+        }
+    }
+
+    /**
+     * 末尾のディスクが index に移る削除
+     */
+    pub fn swap_remove(&mut self, index: usize) {
+        if index < self.hues.len() {
+            self.hues.swap_remove(index);
+        }
+    }
+
+    /**
+     * ディスクをまとめて置き換えた (reset)。最初の色相から割り当て直す
+     */
+    pub fn clear(&mut self) {
+        self.hues.clear();
+        self.assigned = 0;
+    }
+}
+
+/**
+ * 速さの色の範囲 [min, max] が 0 <= min < max か
+ */
+pub fn validate_speed_range(min: f64, max: f64) -> Result<(), String> {
+    if !(min.is_finite() && max.is_finite() && min >= 0. && min < max) {
+        return Err(format!(
+            "speed color range must satisfy 0 <= min_velocity < max_velocity: [{}, {}]",
+            min, max
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * JS から渡された色 (1diskあたりrgbの3値) が disk_count 個分あり、すべて 0.0 - 1.0 か
+ */
+pub fn validate_colors(colors: &[f32], disk_count: usize) -> Result<(), String> {
+    if colors.len() != disk_count * 3 {
+        return Err(format!(
+            "expected {} color values (3 per disk for {} disks), got {}",
+            disk_count * 3,
+            disk_count,
+            colors.len()
+        ));
+    }
+    if let Some(index) = colors.iter().position(|c| !(0. ..=1.).contains(c)) {
+        return Err(format!(
+            "color value {} of disk {} must be in [0, 1]: {}",
+            index % 3,
+            index / 3,
+            colors[index]
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * 色の指定 ([r, g, b, a] (0.0 - 1.0) か "#rgb" / "#rrggbb" / "#rrggbbaa" の文字列)
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColorSpec {
+    Rgba([f32; 4]),
+    Css(String),
+}
+
+impl ColorSpec {
+    pub fn to_rgba(&self) -> Result<[f32; 4], String> {
+        let rgba = match self {
+            ColorSpec::Rgba(rgba) => *rgba,
+            ColorSpec::Css(css) => parse_css_color(css)?,
+        };
+        validate_rgba(rgba)?;
+        Ok(rgba)
+    }
+}
+
+/**
+ * 各成分が 0.0 - 1.0 か
+ */
+pub fn validate_rgba(rgba: [f32; 4]) -> Result<(), String> {
+    if rgba.iter().all(|c| (0. ..=1.).contains(c)) {
+        Ok(())
+    } else {
+        Err(format!("color components must be in [0, 1]: {:?}", rgba))
+    }
+}
+
+/**
+ * "#rgb" / "#rrggbb" / "#rrggbbaa" を [r, g, b, a] (0.0 - 1.0) にする
+ */
+pub fn parse_css_color(css: &str) -> Result<[f32; 4], String> {
+    let invalid = || {
+        format!(
+            "invalid color: {:?} (expected \"#rgb\", \"#rrggbb\" or \"#rrggbbaa\")",
+            css
+        )
+    };
+    let hex = css.strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let digits: Vec<u8> = match hex.len() {
+        // 1桁の成分は2桁に伸ばす ("f" -> "ff")
+        3 => hex
+            .chars()
+            .map(|c| c.to_digit(16).unwrap_or(0) as u8 * 17)
+            .collect(),
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0))
+            .collect(),
+        _ => return Err(invalid()),
+    };
+    let component = |i: usize| digits.get(i).map_or(1., |&d| d as f32 / 255.);
+    Ok([component(0), component(1), component(2), component(3)])
+}
+
+// 組み込みのパレットの色 (等間隔に並べ、間は線形に補間する)
+const VIRIDIS: [[f32; 3]; 9] = [
+    [0.267, 0.005, 0.329],
+    [0.278, 0.175, 0.483],
+    [0.230, 0.322, 0.546],
+    [0.173, 0.449, 0.558],
+    [0.128, 0.567, 0.551],
+    [0.153, 0.683, 0.498],
+    [0.360, 0.785, 0.388],
+    [0.668, 0.862, 0.196],
+    [0.993, 0.906, 0.144],
+];
+const WARM: [[f32; 3]; 4] = [
+    [0.55, 0.05, 0.15],
+    [0.9, 0.25, 0.1],
+    [1., 0.6, 0.05],
+    [1., 0.9, 0.35],
+];
+const COOL: [[f32; 3]; 4] = [
+    [0.1, 0.1, 0.5],
+    [0.05, 0.4, 0.8],
+    [0.1, 0.75, 0.85],
+    [0.6, 1., 0.85],
+];
+const PASTEL: [[f32; 3]; 6] = [
+    [1., 0.7, 0.7],
+    [1., 0.85, 0.65],
+    [1., 1., 0.7],
+    [0.7, 1., 0.75],
+    [0.7, 0.85, 1.],
+    [0.85, 0.75, 1.],
+];
+const MONO: [[f32; 3]; 2] = [[0.25, 0.25, 0.25], [1., 1., 1.]];
+
+/**
+ * ディスクに割り当てる色の選び方
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Palette {
+    // rgb をそれぞれ一様に選ぶ (以前の挙動)
+    Random,
+    Viridis,
+    Warm,
+    Cool,
+    Pastel,
+    Mono,
+    // palette_colors で指定した色 (ディスクにはどれか1色を、"speed" モードでは間を補間して使う)
+    Custom(Vec<[f32; 3]>),
+}
+
+impl Palette {
+    pub fn parse(name: &str) -> Result<Palette, String> {
+        match name {
+            "random" => Ok(Palette::Random),
+            "viridis" => Ok(Palette::Viridis),
+            "warm" => Ok(Palette::Warm),
+            "cool" => Ok(Palette::Cool),
+            "pastel" => Ok(Palette::Pastel),
+            "mono" => Ok(Palette::Mono),
+            _ => Err(format!(
+                "unknown palette: {} (expected \"random\", \"viridis\", \"warm\", \"cool\", \"pastel\" or \"mono\")",
+                name
+            )),
+        }
+    }
+
+    /**
+     * parse で受け付ける名前 (palette_colors から作ったパレットは None)
+     */
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Palette::Random => Some("random"),
+            Palette::Viridis => Some("viridis"),
+            Palette::Warm => Some("warm"),
+            Palette::Cool => Some("cool"),
+            Palette::Pastel => Some("pastel"),
+            Palette::Mono => Some("mono"),
+            Palette::Custom(_) => None,
+        }
+    }
+
+    /**
+     * "#rrggbb" などの文字列の並びからパレットを作る (アルファは使わない)
+     */
+    pub fn custom(colors: &[String]) -> Result<Palette, String> {
+        if colors.is_empty() {
+            return Err(String::from("palette_colors must not be empty"));
+        }
+        colors
+            .iter()
+            .map(|css| parse_css_color(css).map(|[r, g, b, _]| [r, g, b]))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Palette::Custom)
+    }
+
+    fn stops(&self) -> &[[f32; 3]] {
+        match self {
+            Palette::Random => &[],
+            Palette::Viridis => &VIRIDIS,
+            Palette::Warm => &WARM,
+            Palette::Cool => &COOL,
+            Palette::Pastel => &PASTEL,
+            Palette::Mono => &MONO,
+            Palette::Custom(colors) => colors,
+        }
+    }
+
+    /**
+     * 0.0 - 1.0 の位置の色 (範囲外は端に丸める)。"random" は speed_gradient と同じ 青 -> 緑 -> 赤
+     * "speed" モードの色にも使う
+     */
+    pub fn at(&self, t: f64) -> [f32; 3] {
+        let stops = self.stops();
+        if stops.is_empty() {
+            return speed_gradient(t);
+        }
+        let t = if t.is_nan() { 0. } else { t.clamp(0., 1.) };
+        let position = t * (stops.len() - 1) as f64;
+        let index = (position as usize).min(stops.len() - 1);
+        match stops.get(index + 1) {
+            Some(&next) => lerp_rgb(stops[index], next, (position - index as f64) as f32),
+            None => stops[index],
+        }
+    }
+
+    /**
+     * 速さ speed を [min, max] の範囲で at の色にする ("speed" モード)
+     */
+    pub fn speed_color(&self, speed: f64, min: f64, max: f64) -> [f32; 3] {
+        self.at((speed - min) / (max - min))
+    }
+
+    /**
+     * disk_num 個のディスクの色 (1diskあたりrgbの3値)
+     * "random" は random_colors と同じ乱数の使い方をし、カスタムのパレットはどれか1色を選ぶ
+     */
+    pub fn colors<R: Rng + ?Sized>(&self, disk_num: u32, rng: &mut R) -> Vec<f32> {
+        match self {
+            Palette::Random => random_colors(disk_num, rng),
+            Palette::Custom(colors) => (0..disk_num)
+                .flat_map(|_| colors[rng.gen_range(0, colors.len())])
+                .collect(),
+            _ => (0..disk_num)
+                .flat_map(|_| self.at(rng.gen_range(0., 1.)))
+                .collect(),
+        }
+    }
+}
+
+/**
+ * 1diskあたりに3値(rgb)のランダムな色を割り当てる
+ */
+pub fn random_colors<R: Rng + ?Sized>(disk_num: u32, rng: &mut R) -> Vec<f32> {
+    (0..(disk_num * 3))
+        .map(|_| rng.gen_range(0., 1.) as f32)
+        .collect()
+}
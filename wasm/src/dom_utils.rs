@@ -1,36 +1,946 @@
-use wasm_bindgen::JsCast;
+use crate::view::View;
+use std::cell::RefCell;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
-    Document, HtmlCanvasElement, WebGlProgram, WebGlRenderingContext, WebGlShader, Window,
+    AngleInstancedArrays, Document, HtmlCanvasElement, HtmlImageElement, ImageBitmap,
+    OffscreenCanvas, Performance, WebGl2RenderingContext, WebGlBuffer, WebGlProgram,
+    WebGlRenderingContext, WebGlShader, WebGlTexture, WebGlUniformLocation, Window,
 };
 
-static VERTEX_SHADER: &'static str = r#"
-    attribute vec2 a_coords;
-    attribute vec3 a_color;
+// a_scale はディスクごとの大きさの disk_size に対する比 (u_pointsize は disk_size の大きさ)
+// u_view_offset / u_view_scale は描画だけに掛ける視点 (ワールド座標から canvas 上の座標への変換)
+// a_color はディスクごとの rgba。アルファは v_alpha で渡し、フラグメントシェーダで u_alpha に掛ける
+// a_coords の z は set_disk_layer で決めた深度 (既定の層は 0.0)
+static VERTEX_SHADER: &str = r#"
+    attribute vec3 a_coords;
+    attribute vec4 a_color;
+    attribute float a_scale;
     varying vec3 v_color;
+    varying float v_alpha;
     uniform float u_pointsize;
     uniform float u_width;
     uniform float u_height;
+    uniform vec2 u_view_offset;
+    uniform float u_view_scale;
     void main() {
-       float x = -1.0 + 2.0*(a_coords.x / u_width);
-       float y = 1.0 - 2.0*(a_coords.y / u_height);
-       gl_Position = vec4(x, y, 0.0, 1.0);
-       v_color = a_color;
-       gl_PointSize = u_pointsize;
+       vec2 view = (a_coords.xy - u_view_offset) * u_view_scale;
+       float x = -1.0 + 2.0*(view.x / u_width);
+       float y = 1.0 - 2.0*(view.y / u_height);
+       gl_Position = vec4(x, y, a_coords.z, 1.0);
+       v_color = a_color.rgb;
+       v_alpha = a_color.a;
+       gl_PointSize = u_pointsize * a_scale;
+    }
+"#;
+
+// u_glow は "additive" モードで 1.0 になり、色を中心から縁へ暗くして柔らかく光る点にする (0.0 なら色はそのまま)
+// u_shape は点の形 (0: circle, 1: square, 2: ring, 3: glow)。ring は中心から半径の u_ring_inner 倍までを抜く
+static FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    varying vec3 v_color;
+    varying float v_alpha;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    void main() {
+       float distanceFromCenter = distance( gl_PointCoord, vec2(0.5,0.5) );
+       if ( u_shape != 1 && distanceFromCenter >= 0.5 ) {
+           discard;  // don't draw this pixel!
+       }
+       if ( u_shape == 2 && distanceFromCenter < u_ring_inner * 0.5 ) {
+           discard;
+       }
+       float shapeAlpha = u_shape == 3 ? 1.0 - smoothstep(0.0, 0.5, distanceFromCenter) : 1.0;
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 0.5, distanceFromCenter), u_glow);
+       gl_FragColor = vec4(v_color * glow, shapeAlpha * u_alpha * v_alpha);
     }
 "#;
 
-static FRAGMENT_SHADER: &'static str = r#"
+// antialias 用。縁を discard せず、アルファを滑らかに落とす (ブレンドが必要)
+// u_edge は1ピクセルの幅の直径に対する割合で、縁のおよそ1ピクセルだけをぼかす (内側のアルファは 1.0 のまま)
+static SMOOTH_FRAGMENT_SHADER: &str = r#"
     precision mediump float;
     varying vec3 v_color;
+    varying float v_alpha;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    uniform float u_edge;
+    void main() {
+       float distanceFromCenter = distance( gl_PointCoord, vec2(0.5,0.5) );
+       float alpha = u_shape == 1 ? 1.0 : 1.0 - smoothstep(0.5 - u_edge, 0.5, distanceFromCenter);
+       if ( u_shape == 2 ) {
+           alpha *= smoothstep(u_ring_inner * 0.5 - u_edge, u_ring_inner * 0.5, distanceFromCenter);
+       } else if ( u_shape == 3 ) {
+           alpha = 1.0 - smoothstep(0.0, 0.5, distanceFromCenter);
+       }
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 0.5, distanceFromCenter), u_glow);
+       gl_FragColor = vec4(v_color * glow, alpha * u_alpha * v_alpha);
+    }
+"#;
+
+// `#version` ディレクティブはシェーダの先頭行に置く必要がある
+static VERTEX_SHADER_300: &str = r#"#version 300 es
+    in vec3 a_coords;
+    in vec4 a_color;
+    in float a_scale;
+    out vec3 v_color;
+    out float v_alpha;
+    uniform float u_pointsize;
+    uniform float u_width;
+    uniform float u_height;
+    uniform vec2 u_view_offset;
+    uniform float u_view_scale;
+    void main() {
+       vec2 view = (a_coords.xy - u_view_offset) * u_view_scale;
+       float x = -1.0 + 2.0*(view.x / u_width);
+       float y = 1.0 - 2.0*(view.y / u_height);
+       gl_Position = vec4(x, y, a_coords.z, 1.0);
+       v_color = a_color.rgb;
+       v_alpha = a_color.a;
+       gl_PointSize = u_pointsize * a_scale;
+    }
+"#;
+
+static FRAGMENT_SHADER_300: &str = r#"#version 300 es
+    precision mediump float;
+    in vec3 v_color;
+    in float v_alpha;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    out vec4 frag_color;
     void main() {
        float distanceFromCenter = distance( gl_PointCoord, vec2(0.5,0.5) );
-       if ( distanceFromCenter >= 0.5 ) {
+       if ( u_shape != 1 && distanceFromCenter >= 0.5 ) {
+           discard;  // don't draw this pixel!
+       }
+       if ( u_shape == 2 && distanceFromCenter < u_ring_inner * 0.5 ) {
+           discard;
+       }
+       float shapeAlpha = u_shape == 3 ? 1.0 - smoothstep(0.0, 0.5, distanceFromCenter) : 1.0;
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 0.5, distanceFromCenter), u_glow);
+       frag_color = vec4(v_color * glow, shapeAlpha * u_alpha * v_alpha);
+    }
+"#;
+
+static SMOOTH_FRAGMENT_SHADER_300: &str = r#"#version 300 es
+    precision mediump float;
+    in vec3 v_color;
+    in float v_alpha;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    uniform float u_edge;
+    out vec4 frag_color;
+    void main() {
+       float distanceFromCenter = distance( gl_PointCoord, vec2(0.5,0.5) );
+       float alpha = u_shape == 1 ? 1.0 : 1.0 - smoothstep(0.5 - u_edge, 0.5, distanceFromCenter);
+       if ( u_shape == 2 ) {
+           alpha *= smoothstep(u_ring_inner * 0.5 - u_edge, u_ring_inner * 0.5, distanceFromCenter);
+       } else if ( u_shape == 3 ) {
+           alpha = 1.0 - smoothstep(0.0, 0.5, distanceFromCenter);
+       }
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 0.5, distanceFromCenter), u_glow);
+       frag_color = vec4(v_color * glow, alpha * u_alpha * v_alpha);
+    }
+"#;
+
+// render_mode: "circles" 用。ディスクごとに4隅を展開した四角形を描き、円の外側を捨てる
+static CIRCLE_VERTEX_SHADER: &str = r#"
+    attribute vec2 a_coords;
+    attribute vec2 a_corner;
+    attribute vec4 a_color;
+    attribute float a_scale;
+    varying vec3 v_color;
+    varying float v_alpha;
+    varying vec2 v_local;
+    uniform float u_radius;
+    uniform float u_width;
+    uniform float u_height;
+    uniform vec2 u_view_offset;
+    uniform float u_view_scale;
+    void main() {
+       vec2 position = (a_coords + a_corner * u_radius * a_scale - u_view_offset) * u_view_scale;
+       float x = -1.0 + 2.0*(position.x / u_width);
+       float y = 1.0 - 2.0*(position.y / u_height);
+       gl_Position = vec4(x, y, 0.0, 1.0);
+       v_color = a_color.rgb;
+       v_alpha = a_color.a;
+       v_local = a_corner;
+    }
+"#;
+
+static CIRCLE_FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    varying vec3 v_color;
+    varying float v_alpha;
+    varying vec2 v_local;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    void main() {
+       if ( u_shape != 1 && length(v_local) >= 1.0 ) {
+           discard;  // don't draw this pixel!
+       }
+       if ( u_shape == 2 && length(v_local) < u_ring_inner ) {
+           discard;
+       }
+       float shapeAlpha = u_shape == 3 ? 1.0 - smoothstep(0.0, 1.0, length(v_local)) : 1.0;
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 1.0, length(v_local)), u_glow);
+       gl_FragColor = vec4(v_color * glow, shapeAlpha * u_alpha * v_alpha);
+    }
+"#;
+
+// v_local は中心からの距離が半径で 1.0 になるので、u_edge (直径に対する割合) を2倍した幅でぼかす
+static CIRCLE_SMOOTH_FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    varying vec3 v_color;
+    varying float v_alpha;
+    varying vec2 v_local;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    uniform float u_edge;
+    void main() {
+       float alpha = u_shape == 1 ? 1.0 : 1.0 - smoothstep(1.0 - 2.0 * u_edge, 1.0, length(v_local));
+       if ( u_shape == 2 ) {
+           alpha *= smoothstep(u_ring_inner - 2.0 * u_edge, u_ring_inner, length(v_local));
+       } else if ( u_shape == 3 ) {
+           alpha = 1.0 - smoothstep(0.0, 1.0, length(v_local));
+       }
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 1.0, length(v_local)), u_glow);
+       gl_FragColor = vec4(v_color * glow, alpha * u_alpha * v_alpha);
+    }
+"#;
+
+static CIRCLE_VERTEX_SHADER_300: &str = r#"#version 300 es
+    in vec2 a_coords;
+    in vec2 a_corner;
+    in vec4 a_color;
+    in float a_scale;
+    out vec3 v_color;
+    out float v_alpha;
+    out vec2 v_local;
+    uniform float u_radius;
+    uniform float u_width;
+    uniform float u_height;
+    uniform vec2 u_view_offset;
+    uniform float u_view_scale;
+    void main() {
+       vec2 position = (a_coords + a_corner * u_radius * a_scale - u_view_offset) * u_view_scale;
+       float x = -1.0 + 2.0*(position.x / u_width);
+       float y = 1.0 - 2.0*(position.y / u_height);
+       gl_Position = vec4(x, y, 0.0, 1.0);
+       v_color = a_color.rgb;
+       v_alpha = a_color.a;
+       v_local = a_corner;
+    }
+"#;
+
+static CIRCLE_FRAGMENT_SHADER_300: &str = r#"#version 300 es
+    precision mediump float;
+    in vec3 v_color;
+    in float v_alpha;
+    in vec2 v_local;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    out vec4 frag_color;
+    void main() {
+       if ( u_shape != 1 && length(v_local) >= 1.0 ) {
            discard;  // don't draw this pixel!
        }
-       gl_FragColor = vec4(v_color, 1.0);
+       if ( u_shape == 2 && length(v_local) < u_ring_inner ) {
+           discard;
+       }
+       float shapeAlpha = u_shape == 3 ? 1.0 - smoothstep(0.0, 1.0, length(v_local)) : 1.0;
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 1.0, length(v_local)), u_glow);
+       frag_color = vec4(v_color * glow, shapeAlpha * u_alpha * v_alpha);
+    }
+"#;
+
+static CIRCLE_SMOOTH_FRAGMENT_SHADER_300: &str = r#"#version 300 es
+    precision mediump float;
+    in vec3 v_color;
+    in float v_alpha;
+    in vec2 v_local;
+    uniform float u_alpha;
+    uniform float u_glow;
+    uniform int u_shape;
+    uniform float u_ring_inner;
+    uniform float u_edge;
+    out vec4 frag_color;
+    void main() {
+       float alpha = u_shape == 1 ? 1.0 : 1.0 - smoothstep(1.0 - 2.0 * u_edge, 1.0, length(v_local));
+       if ( u_shape == 2 ) {
+           alpha *= smoothstep(u_ring_inner - 2.0 * u_edge, u_ring_inner, length(v_local));
+       } else if ( u_shape == 3 ) {
+           alpha = 1.0 - smoothstep(0.0, 1.0, length(v_local));
+       }
+       float glow = mix(1.0, 1.0 - smoothstep(0.0, 1.0, length(v_local)), u_glow);
+       frag_color = vec4(v_color * glow, alpha * u_alpha * v_alpha);
     }
 "#;
 
+// texture_url 用。頂点シェーダは通常の点と共通で、gl_PointCoord でテクスチャを引く
+static SPRITE_FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    varying vec3 v_color;
+    varying float v_alpha;
+    uniform sampler2D u_texture;
+    uniform float u_alpha;
+    void main() {
+       vec4 texel = texture2D(u_texture, gl_PointCoord);
+       if ( texel.a <= 0.0 ) {
+           discard;
+       }
+       gl_FragColor = vec4(texel.rgb * v_color, texel.a * u_alpha * v_alpha);
+    }
+"#;
+
+static SPRITE_FRAGMENT_SHADER_300: &str = r#"#version 300 es
+    precision mediump float;
+    in vec3 v_color;
+    in float v_alpha;
+    uniform sampler2D u_texture;
+    uniform float u_alpha;
+    out vec4 frag_color;
+    void main() {
+       vec4 texel = texture(u_texture, gl_PointCoord);
+       if ( texel.a <= 0.0 ) {
+           discard;
+       }
+       frag_color = vec4(texel.rgb * v_color, texel.a * u_alpha * v_alpha);
+    }
+"#;
+
+// trail 用。画面全体を覆う四角形を半透明で塗る
+static FADE_VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    void main() {
+       gl_Position = vec4(a_position, 0.0, 1.0);
+    }
+"#;
+
+static FADE_FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform vec4 u_color;
+    void main() {
+       gl_FragColor = u_color;
+    }
+"#;
+
+static FADE_VERTEX_SHADER_300: &str = r#"#version 300 es
+    in vec2 a_position;
+    void main() {
+       gl_Position = vec4(a_position, 0.0, 1.0);
+    }
+"#;
+
+static FADE_FRAGMENT_SHADER_300: &str = r#"#version 300 es
+    precision mediump float;
+    uniform vec4 u_color;
+    out vec4 frag_color;
+    void main() {
+       frag_color = u_color;
+    }
+"#;
+
+// overlay 用。クリップ座標で渡したドットを単色の正方形で描く
+static OVERLAY_VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    uniform float u_pointsize;
+    void main() {
+       gl_Position = vec4(a_position, 0.0, 1.0);
+       gl_PointSize = u_pointsize;
+    }
+"#;
+
+static OVERLAY_VERTEX_SHADER_300: &str = r#"#version 300 es
+    in vec2 a_position;
+    uniform float u_pointsize;
+    void main() {
+       gl_Position = vec4(a_position, 0.0, 1.0);
+       gl_PointSize = u_pointsize;
+    }
+"#;
+
+/**
+ * WebGL2 / WebGL1 のコンテキストを同じインターフェースで扱うためのラッパー
+ */
+#[derive(Debug, Clone)]
+pub enum GlContext {
+    WebGl2(WebGl2RenderingContext),
+    WebGl(WebGlRenderingContext),
+}
+
+/**
+ * GlContext::instancing() で取得する、インスタンス描画に使う拡張 (WebGL2 では不要なので None)
+ */
+#[derive(Clone, Debug)]
+pub struct Instancing {
+    angle: Option<AngleInstancedArrays>,
+}
+
+/**
+ * 毎フレーム書き換える頂点バッファ
+ * DYNAMIC_DRAW で確保した領域を buffer_sub_data で上書きし、足りなくなったときだけ倍々に広げて確保し直す
+ */
+#[derive(Debug)]
+pub struct DynamicBuffer {
+    buffer: WebGlBuffer,
+    // 確保済みの f32 の数
+    capacity: usize,
+}
+
+impl DynamicBuffer {
+    pub fn new(context: &GlContext) -> Result<DynamicBuffer, String> {
+        let buffer = context
+            .create_buffer()
+            .ok_or_else(|| String::from("failed to create buffer."))?;
+        Ok(DynamicBuffer {
+            buffer,
+            capacity: 0,
+        })
+    }
+
+    pub fn bind(&self, context: &GlContext) {
+        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&self.buffer));
+    }
+
+    /**
+     * バッファをバインドし、data を先頭から書き込む
+     */
+    pub fn upload(&mut self, context: &GlContext, data: &[f32]) {
+        self.bind(context);
+        if data.len() > self.capacity {
+            self.capacity = grown_capacity(self.capacity, data.len());
+            context.buffer_data_with_size(
+                WebGlRenderingContext::ARRAY_BUFFER,
+                (self.capacity * std::mem::size_of::<f32>()) as i32,
+                WebGlRenderingContext::DYNAMIC_DRAW,
+            );
+        }
+        if !data.is_empty() {
+            context.buffer_sub_data_f32(WebGlRenderingContext::ARRAY_BUFFER, data);
+        }
+    }
+
+    pub fn delete(&self, context: &GlContext) {
+        context.delete_buffer(Some(&self.buffer));
+    }
+}
+
+// 点の描画で1頂点 (1ディスク) に並べる f32 の数 [x, y, z, r, g, b, a, scale] と、色・大きさの比の先頭の位置
+pub const VERTEX_FLOATS: usize = 8;
+pub const VERTEX_COLOR_OFFSET: usize = 3;
+pub const VERTEX_SCALE_OFFSET: usize = 7;
+// 色・層・大きさの比が座標より少ない場合 (記録の再生中など) に使う値
+const MISSING_COLOR: [f32; 4] = [1., 0., 0., 1.];
+const MISSING_SCALE: f32 = 1.;
+
+/**
+ * 層 layer のディスクを描く深度 (クリップ座標の z)。層が大きいほど手前になり、既定の層 0 は 0.0
+ */
+pub fn layer_depth(layer: u8) -> f32 {
+    -(layer as f32) / 256.
+}
+
+/**
+ * 座標 (x, y, 層の深度)・色 (rgba)・大きさの比を1頂点ずつ交互に並べた頂点データを out に作り直す (out は毎フレーム使い回す)
+ * 頂点の数は座標の数で決まる。層が足りないディスクは層 0 にする
+ */
+pub fn interleave_vertices(
+    out: &mut Vec<f32>,
+    positions: &[f32],
+    layers: &[u8],
+    colors: &[f32],
+    scales: &[f32],
+) {
+    out.clear();
+    out.reserve(positions.len() / 2 * VERTEX_FLOATS);
+    let mut layers = layers.iter();
+    let mut colors = colors.chunks_exact(4);
+    let mut scales = scales.iter();
+    for position in positions.chunks_exact(2) {
+        out.extend_from_slice(position);
+        out.push(layer_depth(layers.next().copied().unwrap_or(0)));
+        out.extend_from_slice(colors.next().unwrap_or(&MISSING_COLOR));
+        out.push(scales.next().copied().unwrap_or(MISSING_SCALE));
+    }
+}
+
+/**
+ * capacity 個の領域に needed 個を収めるために確保し直す大きさ (倍々に広げ、最低でも needed)
+ */
+pub fn grown_capacity(capacity: usize, needed: usize) -> usize {
+    if needed <= capacity {
+        capacity
+    } else {
+        needed.max(capacity * 2)
+    }
+}
+
+// 両コンテキストはメソッド名・シグネチャが同一なので、分岐だけをまとめる
+macro_rules! with_gl {
+    ($ctx:expr, $gl:ident => $body:expr) => {
+        match $ctx {
+            GlContext::WebGl2($gl) => $body,
+            GlContext::WebGl($gl) => $body,
+        }
+    };
+}
+
+impl GlContext {
+    /**
+     * JS 側のコンテキストオブジェクト (同一性の比較に使う)
+     */
+    pub fn as_js(&self) -> &JsValue {
+        with_gl!(self, gl => gl.as_ref())
+    }
+
+    /**
+     * ブラウザが実際に与えたコンテキスト属性 (getContextAttributes。コンテキストが失われていれば null)
+     */
+    pub fn context_attributes(&self) -> JsValue {
+        with_gl!(self, gl => gl.get_context_attributes())
+            .map(JsValue::from)
+            .unwrap_or(JsValue::NULL)
+    }
+
+    /**
+     * コンテキストを取得した canvas (HTML canvas か OffscreenCanvas)
+     */
+    pub fn canvas(&self) -> Option<js_sys::Object> {
+        with_gl!(self, gl => gl.canvas())
+    }
+
+    pub fn backend(&self) -> &'static str {
+        match self {
+            GlContext::WebGl2(_) => "webgl2",
+            GlContext::WebGl(_) => "webgl",
+        }
+    }
+
+    /**
+     * 取得できた WebGL のバージョン (2 | 1)
+     */
+    pub fn version(&self) -> u32 {
+        match self {
+            GlContext::WebGl2(_) => 2,
+            GlContext::WebGl(_) => 1,
+        }
+    }
+
+    pub fn shader_sources(&self, antialias: bool) -> (&'static str, &'static str) {
+        match (self, antialias) {
+            (GlContext::WebGl2(_), false) => (VERTEX_SHADER_300, FRAGMENT_SHADER_300),
+            (GlContext::WebGl2(_), true) => (VERTEX_SHADER_300, SMOOTH_FRAGMENT_SHADER_300),
+            (GlContext::WebGl(_), false) => (VERTEX_SHADER, FRAGMENT_SHADER),
+            (GlContext::WebGl(_), true) => (VERTEX_SHADER, SMOOTH_FRAGMENT_SHADER),
+        }
+    }
+
+    fn circle_shader_sources(&self, antialias: bool) -> (&'static str, &'static str) {
+        match (self, antialias) {
+            (GlContext::WebGl2(_), false) => (CIRCLE_VERTEX_SHADER_300, CIRCLE_FRAGMENT_SHADER_300),
+            (GlContext::WebGl2(_), true) => {
+                (CIRCLE_VERTEX_SHADER_300, CIRCLE_SMOOTH_FRAGMENT_SHADER_300)
+            }
+            (GlContext::WebGl(_), false) => (CIRCLE_VERTEX_SHADER, CIRCLE_FRAGMENT_SHADER),
+            (GlContext::WebGl(_), true) => (CIRCLE_VERTEX_SHADER, CIRCLE_SMOOTH_FRAGMENT_SHADER),
+        }
+    }
+
+    fn sprite_shader_sources(&self) -> (&'static str, &'static str) {
+        match self {
+            GlContext::WebGl2(_) => (VERTEX_SHADER_300, SPRITE_FRAGMENT_SHADER_300),
+            GlContext::WebGl(_) => (VERTEX_SHADER, SPRITE_FRAGMENT_SHADER),
+        }
+    }
+
+    // フラグメントシェーダは fade と共通 (u_color で塗るだけ)
+    fn overlay_shader_sources(&self) -> (&'static str, &'static str) {
+        match self {
+            GlContext::WebGl2(_) => (OVERLAY_VERTEX_SHADER_300, FADE_FRAGMENT_SHADER_300),
+            GlContext::WebGl(_) => (OVERLAY_VERTEX_SHADER, FADE_FRAGMENT_SHADER),
+        }
+    }
+
+    fn fade_shader_sources(&self) -> (&'static str, &'static str) {
+        match self {
+            GlContext::WebGl2(_) => (FADE_VERTEX_SHADER_300, FADE_FRAGMENT_SHADER_300),
+            GlContext::WebGl(_) => (FADE_VERTEX_SHADER, FADE_FRAGMENT_SHADER),
+        }
+    }
+
+    pub fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        with_gl!(self, gl => gl.viewport(x, y, width, height))
+    }
+
+    pub fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        with_gl!(self, gl => gl.clear_color(r, g, b, a))
+    }
+
+    pub fn clear(&self, mask: u32) {
+        with_gl!(self, gl => gl.clear(mask))
+    }
+
+    pub fn enable(&self, cap: u32) {
+        with_gl!(self, gl => gl.enable(cap))
+    }
+
+    pub fn disable(&self, cap: u32) {
+        with_gl!(self, gl => gl.disable(cap))
+    }
+
+    pub fn depth_func(&self, func: u32) {
+        with_gl!(self, gl => gl.depth_func(func))
+    }
+
+    pub fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32) {
+        with_gl!(self, gl => gl.blend_func_separate(src_rgb, dst_rgb, src_alpha, dst_alpha))
+    }
+
+    pub fn create_texture(&self) -> Option<WebGlTexture> {
+        with_gl!(self, gl => gl.create_texture())
+    }
+
+    pub fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>) {
+        with_gl!(self, gl => gl.bind_texture(target, texture))
+    }
+
+    pub fn active_texture(&self, texture: u32) {
+        with_gl!(self, gl => gl.active_texture(texture))
+    }
+
+    pub fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        with_gl!(self, gl => gl.tex_parameteri(target, pname, param))
+    }
+
+    /**
+     * 読み込み済みの画像をバインド中のテクスチャへ RGBA で転送する
+     */
+    pub fn tex_image_2d_with_image(&self, image: &HtmlImageElement) -> Result<(), JsValue> {
+        let target = WebGlRenderingContext::TEXTURE_2D;
+        let format = WebGlRenderingContext::RGBA;
+        let type_ = WebGlRenderingContext::UNSIGNED_BYTE;
+        match self {
+            GlContext::WebGl2(gl) => gl.tex_image_2d_with_u32_and_u32_and_html_image_element(
+                target,
+                0,
+                format as i32,
+                format,
+                type_,
+                image,
+            ),
+            GlContext::WebGl(gl) => gl.tex_image_2d_with_u32_and_u32_and_image(
+                target,
+                0,
+                format as i32,
+                format,
+                type_,
+                image,
+            ),
+        }
+    }
+
+    /**
+     * ImageBitmap をバインド中のテクスチャへ RGBA で転送する
+     */
+    pub fn tex_image_2d_with_image_bitmap(&self, bitmap: &ImageBitmap) -> Result<(), JsValue> {
+        let target = WebGlRenderingContext::TEXTURE_2D;
+        let format = WebGlRenderingContext::RGBA;
+        let type_ = WebGlRenderingContext::UNSIGNED_BYTE;
+        with_gl!(self, gl => gl.tex_image_2d_with_u32_and_u32_and_image_bitmap(
+            target,
+            0,
+            format as i32,
+            format,
+            type_,
+            bitmap,
+        ))
+    }
+
+    pub fn delete_buffer(&self, buffer: Option<&WebGlBuffer>) {
+        with_gl!(self, gl => gl.delete_buffer(buffer))
+    }
+
+    pub fn delete_texture(&self, texture: Option<&WebGlTexture>) {
+        with_gl!(self, gl => gl.delete_texture(texture))
+    }
+
+    fn get_attached_shaders(&self, program: &WebGlProgram) -> Option<js_sys::Array> {
+        with_gl!(self, gl => gl.get_attached_shaders(program))
+    }
+
+    fn detach_shader(&self, program: &WebGlProgram, shader: &WebGlShader) {
+        with_gl!(self, gl => gl.detach_shader(program, shader))
+    }
+
+    fn delete_shader(&self, shader: Option<&WebGlShader>) {
+        with_gl!(self, gl => gl.delete_shader(shader))
+    }
+
+    fn delete_program(&self, program: Option<&WebGlProgram>) {
+        with_gl!(self, gl => gl.delete_program(program))
+    }
+
+    pub fn create_buffer(&self) -> Option<WebGlBuffer> {
+        with_gl!(self, gl => gl.create_buffer())
+    }
+
+    pub fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>) {
+        with_gl!(self, gl => gl.bind_buffer(target, buffer))
+    }
+
+    /**
+     * f32のスライスをバインド中のバッファへ転送する
+     */
+    pub fn buffer_data_f32(&self, target: u32, data: &[f32], usage: u32) {
+        // `view` はwasmメモリを直接参照するため、転送が終わるまでアロケーションを挟まないこと
+        unsafe {
+            let view = js_sys::Float32Array::view(data);
+            with_gl!(self, gl => gl.buffer_data_with_array_buffer_view(target, &view, usage))
+        }
+    }
+
+    /**
+     * バインド中のバッファを size バイト確保し直す (内容は未定義)
+     */
+    pub fn buffer_data_with_size(&self, target: u32, size: i32, usage: u32) {
+        with_gl!(self, gl => gl.buffer_data_with_i32(target, size, usage))
+    }
+
+    /**
+     * f32のスライスをバインド中のバッファの先頭から上書きする (確保し直さない。バッファの大きさ以内であること)
+     */
+    pub fn buffer_sub_data_f32(&self, target: u32, data: &[f32]) {
+        // `view` はwasmメモリを直接参照するため、転送が終わるまでアロケーションを挟まないこと
+        unsafe {
+            let view = js_sys::Float32Array::view(data);
+            with_gl!(self, gl => gl.buffer_sub_data_with_i32_and_array_buffer_view(target, 0, &view))
+        }
+    }
+
+    pub fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        with_gl!(self, gl => gl.vertex_attrib_pointer_with_i32(index, size, type_, normalized, stride, offset))
+    }
+
+    pub fn enable_vertex_attrib_array(&self, index: u32) {
+        with_gl!(self, gl => gl.enable_vertex_attrib_array(index))
+    }
+
+    pub fn disable_vertex_attrib_array(&self, index: u32) {
+        with_gl!(self, gl => gl.disable_vertex_attrib_array(index))
+    }
+
+    pub fn uniform1f(&self, location: Option<&WebGlUniformLocation>, x: f32) {
+        with_gl!(self, gl => gl.uniform1f(location, x))
+    }
+
+    pub fn uniform2f(&self, location: Option<&WebGlUniformLocation>, x: f32, y: f32) {
+        with_gl!(self, gl => gl.uniform2f(location, x, y))
+    }
+
+    pub fn uniform1i(&self, location: Option<&WebGlUniformLocation>, x: i32) {
+        with_gl!(self, gl => gl.uniform1i(location, x))
+    }
+
+    pub fn uniform4f(
+        &self,
+        location: Option<&WebGlUniformLocation>,
+        x: f32,
+        y: f32,
+        z: f32,
+        w: f32,
+    ) {
+        with_gl!(self, gl => gl.uniform4f(location, x, y, z, w))
+    }
+
+    pub fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        with_gl!(self, gl => gl.draw_arrays(mode, first, count))
+    }
+
+    pub fn get_error(&self) -> u32 {
+        with_gl!(self, gl => gl.get_error())
+    }
+
+    /**
+     * インスタンス描画の準備 (WebGL2 は標準機能、WebGL1 は ANGLE_instanced_arrays 拡張を使う)
+     */
+    pub fn instancing(&self) -> Result<Instancing, String> {
+        match self {
+            GlContext::WebGl2(_) => Ok(Instancing { angle: None }),
+            GlContext::WebGl(gl) => gl
+                .get_extension("ANGLE_instanced_arrays")
+                .ok()
+                .flatten()
+                .and_then(|ext| ext.dyn_into::<AngleInstancedArrays>().ok())
+                .map(|ext| Instancing { angle: Some(ext) })
+                .ok_or_else(|| String::from("ANGLE_instanced_arrays is not supported")),
+        }
+    }
+
+    /**
+     * divisor 個のインスタンスごとに属性 index を1つ進める (0 なら頂点ごと)
+     * WebGL1 には VAO がなく他のプログラムにも効くので、描画後は 0 に戻すこと
+     */
+    pub fn vertex_attrib_divisor(&self, instancing: &Instancing, index: u32, divisor: u32) {
+        match (self, &instancing.angle) {
+            (GlContext::WebGl2(gl), _) => gl.vertex_attrib_divisor(index, divisor),
+            (GlContext::WebGl(_), Some(ext)) => ext.vertex_attrib_divisor_angle(index, divisor),
+            (GlContext::WebGl(_), None) => {}
+        }
+    }
+
+    pub fn draw_arrays_instanced(
+        &self,
+        instancing: &Instancing,
+        mode: u32,
+        first: i32,
+        count: i32,
+        instances: i32,
+    ) {
+        match (self, &instancing.angle) {
+            (GlContext::WebGl2(gl), _) => gl.draw_arrays_instanced(mode, first, count, instances),
+            (GlContext::WebGl(_), Some(ext)) => {
+                ext.draw_arrays_instanced_angle(mode, first, count, instances)
+            }
+            (GlContext::WebGl(_), None) => {}
+        }
+    }
+
+    pub fn use_program(&self, program: Option<&WebGlProgram>) {
+        with_gl!(self, gl => gl.use_program(program))
+    }
+
+    /**
+     * gl_PointSize の上限 (ALIASED_POINT_SIZE_RANGE の最大値、px)。取得できなければ 1.0 (仕様上の最小の保証)
+     */
+    pub fn max_point_size(&self) -> f64 {
+        let range =
+            with_gl!(self, gl => gl.get_parameter(WebGlRenderingContext::ALIASED_POINT_SIZE_RANGE));
+        range
+            .ok()
+            .and_then(|range| range.dyn_into::<js_sys::Float32Array>().ok())
+            .filter(|range| range.length() == 2)
+            .map(|range| range.get_index(1) as f64)
+            .unwrap_or(1.)
+    }
+
+    /**
+     * コンテキストの描画先の HTML canvas (OffscreenCanvas の場合は None)
+     */
+    pub fn html_canvas(&self) -> Option<HtmlCanvasElement> {
+        with_gl!(self, gl => gl.canvas())
+            .and_then(|canvas| canvas.dyn_into::<HtmlCanvasElement>().ok())
+    }
+
+    /**
+     * 描画バッファ全体 (drawingBufferWidth x drawingBufferHeight) の RGBA を上の行から順に読み出す
+     * GL の原点は左下なので、読み出した後に行を上下反転する
+     */
+    pub fn read_pixels_rgba(&self) -> Result<Vec<u8>, JsValue> {
+        let (width, height) =
+            with_gl!(self, gl => (gl.drawing_buffer_width(), gl.drawing_buffer_height()));
+        let row = width as usize * 4;
+        let mut pixels = vec![0u8; row * height as usize];
+        with_gl!(self, gl => gl.read_pixels_with_opt_u8_array(
+            0,
+            0,
+            width,
+            height,
+            WebGlRenderingContext::RGBA,
+            WebGlRenderingContext::UNSIGNED_BYTE,
+            Some(&mut pixels),
+        ))?;
+        let rows = height as usize;
+        for top in 0..rows / 2 {
+            let (upper, lower) = pixels.split_at_mut((rows - 1 - top) * row);
+            upper[top * row..(top + 1) * row].swap_with_slice(&mut lower[..row]);
+        }
+        Ok(pixels)
+    }
+
+    pub fn is_program(&self, program: &WebGlProgram) -> bool {
+        with_gl!(self, gl => gl.is_program(Some(program)))
+    }
+
+    pub fn get_attrib_location(&self, program: &WebGlProgram, name: &str) -> i32 {
+        with_gl!(self, gl => gl.get_attrib_location(program, name))
+    }
+
+    pub fn get_uniform_location(
+        &self,
+        program: &WebGlProgram,
+        name: &str,
+    ) -> Option<WebGlUniformLocation> {
+        with_gl!(self, gl => gl.get_uniform_location(program, name))
+    }
+
+    fn bind_attrib_location(&self, program: &WebGlProgram, index: u32, name: &str) {
+        with_gl!(self, gl => gl.bind_attrib_location(program, index, name))
+    }
+
+    fn create_shader(&self, shader_type: u32) -> Option<WebGlShader> {
+        with_gl!(self, gl => gl.create_shader(shader_type))
+    }
+
+    fn shader_source(&self, shader: &WebGlShader, source: &str) {
+        with_gl!(self, gl => gl.shader_source(shader, source))
+    }
+
+    fn compile_shader(&self, shader: &WebGlShader) {
+        with_gl!(self, gl => gl.compile_shader(shader))
+    }
+
+    fn get_shader_parameter(&self, shader: &WebGlShader, pname: u32) -> JsValue {
+        with_gl!(self, gl => gl.get_shader_parameter(shader, pname))
+    }
+
+    fn get_shader_info_log(&self, shader: &WebGlShader) -> Option<String> {
+        with_gl!(self, gl => gl.get_shader_info_log(shader))
+    }
+
+    fn create_program(&self) -> Option<WebGlProgram> {
+        with_gl!(self, gl => gl.create_program())
+    }
+
+    fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader) {
+        with_gl!(self, gl => gl.attach_shader(program, shader))
+    }
+
+    fn link_program(&self, program: &WebGlProgram) {
+        with_gl!(self, gl => gl.link_program(program))
+    }
+
+    fn get_program_parameter(&self, program: &WebGlProgram, pname: u32) -> JsValue {
+        with_gl!(self, gl => gl.get_program_parameter(program, pname))
+    }
+
+    fn get_program_info_log(&self, program: &WebGlProgram) -> Option<String> {
+        with_gl!(self, gl => gl.get_program_info_log(program))
+    }
+}
+
 pub fn window() -> Option<Window> {
     web_sys::window()
 }
@@ -39,64 +949,973 @@ pub fn document() -> Option<Document> {
     window().and_then(|w| w.document())
 }
 
+/**
+ * グローバルスコープ (window または Worker の self) のプロパティ
+ */
+pub fn global_property(name: &str) -> Option<JsValue> {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from(name))
+        .ok()
+        .filter(|value| !value.is_undefined())
+}
+
+/**
+ * グローバルスコープの関数を呼ぶ (Worker でも動くように window を経由しない)
+ */
+pub fn call_global(name: &str, arg: &JsValue) -> Result<JsValue, JsValue> {
+    let function = global_property(name)
+        .and_then(|f| f.dyn_into::<js_sys::Function>().ok())
+        .ok_or_else(|| JsValue::from(format!("{} is not available", name).as_str()))?;
+    function.call1(&js_sys::global(), arg)
+}
+
+/**
+ * グローバルスコープの setTimeout で callback を delay_ms 後に呼ぶ (戻り値は clearTimeout に渡す番号)
+ */
+pub fn set_timeout(callback: &JsValue, delay_ms: f64) -> Result<JsValue, JsValue> {
+    let function = global_property("setTimeout")
+        .and_then(|f| f.dyn_into::<js_sys::Function>().ok())
+        .ok_or_else(|| JsValue::from("setTimeout is not available"))?;
+    function.call2(&js_sys::global(), callback, &JsValue::from(delay_ms))
+}
+
+/**
+ * addEventListener で登録したリスナー。drop すると removeEventListener で外す
+ */
+pub struct EventListener {
+    target: web_sys::EventTarget,
+    name: &'static str,
+    closure: Closure<dyn FnMut(web_sys::Event)>,
+}
+
+impl EventListener {
+    pub fn new(
+        target: &web_sys::EventTarget,
+        name: &'static str,
+        on_event: impl FnMut(web_sys::Event) + 'static,
+    ) -> Result<EventListener, JsValue> {
+        let closure = Closure::wrap(Box::new(on_event) as Box<dyn FnMut(web_sys::Event)>);
+        target.add_event_listener_with_callback(name, closure.as_ref().unchecked_ref())?;
+        Ok(EventListener {
+            target: target.clone(),
+            name,
+            closure,
+        })
+    }
+}
+
+impl std::fmt::Debug for EventListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventListener")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        let _ = self
+            .target
+            .remove_event_listener_with_callback(self.name, self.closure.as_ref().unchecked_ref());
+    }
+}
+
+/**
+ * ResizeObserver で要素の content box の大きさ (CSS の px) を監視する。drop すると disconnect する
+ * on_resize(width, height) は監視を始めたときにも一度呼ばれる
+ */
+pub struct ResizeWatcher {
+    observer: web_sys::ResizeObserver,
+    _closure: Closure<dyn FnMut(js_sys::Array)>,
+}
+
+impl ResizeWatcher {
+    pub fn new(
+        target: &web_sys::Element,
+        mut on_resize: impl FnMut(f64, f64) + 'static,
+    ) -> Result<ResizeWatcher, JsValue> {
+        let closure = Closure::wrap(Box::new(move |entries: js_sys::Array| {
+            // 同じ要素についてまとめて届いた場合は最後の大きさだけ使う
+            let last = entries.get(entries.length().saturating_sub(1));
+            if let Ok(entry) = last.dyn_into::<web_sys::ResizeObserverEntry>() {
+                let rect = entry.content_rect();
+                on_resize(rect.width(), rect.height());
+            }
+        }) as Box<dyn FnMut(js_sys::Array)>);
+        let observer = web_sys::ResizeObserver::new(closure.as_ref().unchecked_ref())?;
+        observer.observe(target);
+        Ok(ResizeWatcher {
+            observer,
+            _closure: closure,
+        })
+    }
+}
+
+impl std::fmt::Debug for ResizeWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResizeWatcher").finish()
+    }
+}
+
+impl Drop for ResizeWatcher {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+/**
+ * size_canvas で設定した CSS の width / height を外し、ページの CSS で大きさを決めさせる (auto_resize 用)
+ */
+pub fn release_css_size(context: &GlContext) {
+    if let Some(canvas) = context
+        .canvas()
+        .and_then(|canvas| canvas.dyn_into::<HtmlCanvasElement>().ok())
+    {
+        let style = canvas.style();
+        let _ = style.remove_property("width");
+        let _ = style.remove_property("height");
+    }
+}
+
+/**
+ * target に detail 付きの CustomEvent を送る
+ */
+pub fn dispatch_custom_event(
+    target: &web_sys::EventTarget,
+    name: &str,
+    detail: &JsValue,
+) -> Result<(), JsValue> {
+    let init = web_sys::CustomEventInit::new();
+    init.set_detail(detail);
+    let event = web_sys::CustomEvent::new_with_event_init_dict(name, &init)?;
+    target.dispatch_event(&event)?;
+    Ok(())
+}
+
+/**
+ * 高分解能タイマー (ms)。Performance APIが使えない環境では0を返す
+ */
+pub fn now() -> f64 {
+    global_property("performance")
+        .and_then(|p| p.dyn_into::<Performance>().ok())
+        .map(|p| p.now())
+        .unwrap_or(0.)
+}
+
 pub fn canvas(id: &str) -> Option<HtmlCanvasElement> {
-    document()
+    find_canvas(id).ok()
+}
+
+/**
+ * id の要素を canvas として取得する (見つからない・canvas でない場合はそれぞれのエラー)
+ */
+pub fn find_canvas(id: &str) -> Result<HtmlCanvasElement, String> {
+    let element = document()
         .and_then(|d| d.get_element_by_id(id))
-        .and_then(|el| el.dyn_into::<HtmlCanvasElement>().ok())
+        .ok_or_else(|| format!("canvas element not found: #{}", id))?;
+    element.dyn_into::<HtmlCanvasElement>().map_err(|element| {
+        format!(
+            "#{} is a <{}>, not a <canvas>",
+            id,
+            element.tag_name().to_lowercase()
+        )
+    })
+}
+
+/**
+ * マウスイベントの clientX / clientY を canvas の論理座標 (width x height) に変換する
+ * CSS で拡大縮小されていても、表示されている大きさ (getBoundingClientRect) との比で戻す
+ */
+pub fn client_to_canvas(
+    canvas: &HtmlCanvasElement,
+    event: &web_sys::MouseEvent,
+    width: f64,
+    height: f64,
+) -> Option<(f64, f64)> {
+    client_point_to_canvas(
+        canvas,
+        event.client_x() as f64,
+        event.client_y() as f64,
+        width,
+        height,
+    )
+}
+
+/**
+ * client_to_canvas と同じだが、clientX / clientY を直接受け取る (タッチなど)
+ */
+pub fn client_point_to_canvas(
+    canvas: &HtmlCanvasElement,
+    client_x: f64,
+    client_y: f64,
+    width: f64,
+    height: f64,
+) -> Option<(f64, f64)> {
+    let rect = canvas.get_bounding_client_rect();
+    if rect.width() <= 0. || rect.height() <= 0. {
+        return None;
+    }
+    Some((
+        (client_x - rect.left()) * width / rect.width(),
+        (client_y - rect.top()) * height / rect.height(),
+    ))
+}
+
+/**
+ * getContext に渡すコンテキスト属性 (None の属性は渡さずブラウザの既定にする)
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ContextAttributes {
+    // 描画バッファをフレーム間で保持する (trail のように前フレームに重ね描きする場合に必要)
+    pub preserve_drawing_buffer: bool,
+    // 描画バッファにアルファを持たせ、ページの背景を透けさせる (背景色が半透明の場合に必要)
+    pub alpha: bool,
+    // マルチサンプリングによるアンチエイリアス (シェーダの antialias とは別)
+    pub antialias: Option<bool>,
+    pub premultiplied_alpha: Option<bool>,
+    // "default" | "high-performance" | "low-power"
+    pub power_preference: Option<String>,
+    // 表示の遅延を減らす (対応していないブラウザでは無視される)
+    pub desynchronized: Option<bool>,
+}
+
+impl ContextAttributes {
+    fn to_js(&self) -> JsValue {
+        let attributes = js_sys::Object::new();
+        let set = |name: &str, value: JsValue| {
+            let _ = js_sys::Reflect::set(&attributes, &JsValue::from(name), &value);
+        };
+        set(
+            "preserveDrawingBuffer",
+            JsValue::from(self.preserve_drawing_buffer),
+        );
+        set("alpha", JsValue::from(self.alpha));
+        // set_disk_layer の重なり順は深度テストで決めるため、深度バッファを必ず持たせる
+        set("depth", JsValue::TRUE);
+        if let Some(antialias) = self.antialias {
+            set("antialias", JsValue::from(antialias));
+        }
+        if let Some(premultiplied_alpha) = self.premultiplied_alpha {
+            set("premultipliedAlpha", JsValue::from(premultiplied_alpha));
+        }
+        if let Some(power_preference) = self.power_preference.as_deref() {
+            set("powerPreference", JsValue::from(power_preference));
+        }
+        if let Some(desynchronized) = self.desynchronized {
+            set("desynchronized", JsValue::from(desynchronized));
+        }
+        attributes.into()
+    }
 }
 
-pub fn get_webgl_context_by_id(id: &str, width: u32, height: u32) -> Option<WebGlRenderingContext> {
-    canvas(id)
-        .and_then(|c| c.get_context("webgl").ok())
-        .and_then(|c| c.unwrap().dyn_into::<WebGlRenderingContext>().ok())
-        .and_then(|c| {
-            c.viewport(0, 0, width as i32, height as i32);
-            Some(c)
+// WebGL1 として試すコンテキスト名 (古いブラウザは experimental-webgl のみ対応)
+const WEBGL1_CONTEXT_IDS: [&str; 2] = ["webgl", "experimental-webgl"];
+
+/**
+ * WebGL2を優先し、取得できなければWebGL1にフォールバックする
+ */
+fn get_context(
+    get_context_with_options: impl Fn(&str, &JsValue) -> Result<Option<js_sys::Object>, JsValue>,
+    attributes: &ContextAttributes,
+) -> Option<GlContext> {
+    let attributes = attributes.to_js();
+    let context = |id: &str| get_context_with_options(id, &attributes).ok().flatten();
+    let webgl2 = context("webgl2")
+        .and_then(|c| c.dyn_into::<WebGl2RenderingContext>().ok())
+        .map(GlContext::WebGl2);
+    webgl2.or_else(|| {
+        WEBGL1_CONTEXT_IDS.iter().find_map(|id| {
+            context(id)
+                .and_then(|c| c.dyn_into::<WebGlRenderingContext>().ok())
+                .map(GlContext::WebGl)
         })
+    })
+}
+
+/**
+ * window.devicePixelRatio (Worker など取得できない環境では 1.0)
+ */
+pub fn device_pixel_ratio() -> f64 {
+    global_property("devicePixelRatio")
+        .and_then(|ratio| ratio.as_f64())
+        .filter(|ratio| ratio.is_finite() && *ratio > 0.)
+        .unwrap_or(1.)
+}
+
+/**
+ * 論理サイズ width x height (CSS ピクセル) に対する描画バッファの大きさ (物理ピクセル)
+ */
+pub fn buffer_size(width: u32, height: u32, pixel_ratio: f64) -> (u32, u32) {
+    let scale = |length: u32| (length as f64 * pixel_ratio).round() as u32;
+    (scale(width), scale(height))
+}
+
+/**
+ * canvas の大きさを決める唯一の場所 (init_gl・resize・dpr の変更で共通)
+ * 描画バッファ (width / height 属性) を論理サイズ * pixel_ratio の物理ピクセルにし、
+ * HTML canvas なら CSS 上の大きさを論理サイズに保つ (OffscreenCanvas はメインスレッド側の要素で決まる)
+ * 設定した描画バッファの大きさを返す。描画バッファの内容は消える
+ */
+pub fn size_canvas(
+    canvas: &js_sys::Object,
+    width: u32,
+    height: u32,
+    pixel_ratio: f64,
+) -> Result<(u32, u32), String> {
+    let (buffer_width, buffer_height) = buffer_size(width, height, pixel_ratio);
+    if let Some(canvas) = canvas.dyn_ref::<HtmlCanvasElement>() {
+        canvas.set_width(buffer_width);
+        canvas.set_height(buffer_height);
+        let style = canvas.style();
+        let _ = style.set_property("width", &format!("{}px", width));
+        let _ = style.set_property("height", &format!("{}px", height));
+        Ok((canvas.width(), canvas.height()))
+    } else if let Some(canvas) = canvas.dyn_ref::<OffscreenCanvas>() {
+        canvas.set_width(buffer_width);
+        canvas.set_height(buffer_height);
+        Ok((canvas.width(), canvas.height()))
+    } else {
+        Err(String::from("expected a <canvas> or an OffscreenCanvas"))
+    }
+}
+
+/**
+ * コンテキストの canvas の大きさを size_canvas で変え、viewport をそれに合わせる
+ */
+pub fn resize_canvas(
+    context: &GlContext,
+    width: u32,
+    height: u32,
+    pixel_ratio: f64,
+) -> Result<(), String> {
+    let canvas = context
+        .canvas()
+        .ok_or_else(|| String::from("the WebGL context has no canvas"))?;
+    let (buffer_width, buffer_height) = size_canvas(&canvas, width, height, pixel_ratio)?;
+    context.viewport(0, 0, buffer_width as i32, buffer_height as i32);
+    Ok(())
+}
+
+/**
+ * 描画のたびに設定する、ユーザー指定のシェーダでは省略できる uniform の位置
+ * 毎フレーム探さないよう、プログラムをリンクしたときに一度だけ調べておく (宣言していないものは None)
+ */
+#[derive(Clone, Debug, Default)]
+pub struct DrawUniforms {
+    view_offset: Option<WebGlUniformLocation>,
+    view_scale: Option<WebGlUniformLocation>,
+    edge: Option<WebGlUniformLocation>,
+    shape: Option<WebGlUniformLocation>,
+    ring_inner: Option<WebGlUniformLocation>,
+}
+
+impl DrawUniforms {
+    pub fn new(context: &GlContext, program: &WebGlProgram) -> DrawUniforms {
+        DrawUniforms {
+            view_offset: optional_uniform_location(context, program, "u_view_offset"),
+            view_scale: optional_uniform_location(context, program, "u_view_scale"),
+            edge: optional_uniform_location(context, program, "u_edge"),
+            shape: optional_uniform_location(context, program, "u_shape"),
+            ring_inner: optional_uniform_location(context, program, "u_ring_inner"),
+        }
+    }
+}
+
+/**
+ * u_view_offset / u_view_scale を使うプログラムに視点を設定する (uniforms のプログラムを use_program してから呼ぶ)
+ * ユーザー指定のシェーダが宣言していなければ何もしない (視点に追従しない)
+ */
+pub fn set_view_uniforms(
+    context: &GlContext,
+    uniforms: &DrawUniforms,
+    view: &View,
+    width: f64,
+    height: f64,
+) {
+    let (ox, oy) = view.offset(width, height);
+    if let Some(offset) = uniforms.view_offset.as_ref() {
+        context.uniform2f(Some(offset), ox as f32, oy as f32);
+    }
+    if let Some(scale) = uniforms.view_scale.as_ref() {
+        context.uniform1f(Some(scale), view.scale() as f32);
+    }
+}
+
+/**
+ * u_width / u_height を使うプログラムに canvas の論理サイズを設定する
+ */
+pub fn set_size_uniforms(
+    context: &GlContext,
+    program: &WebGlProgram,
+    width: f64,
+    height: f64,
+) -> Result<(), String> {
+    context.use_program(Some(program));
+    let uniform = |name: &str| uniform_location(context, program, name);
+    context.uniform1f(Some(&uniform("u_width")?), width as f32);
+    context.uniform1f(Some(&uniform("u_height")?), height as f32);
+    Ok(())
+}
+
+/**
+ * width, height は論理サイズ (CSS ピクセル)。viewport は物理ピクセルで設定する
+ */
+pub fn get_webgl_context_by_id(
+    id: &str,
+    width: u32,
+    height: u32,
+    pixel_ratio: f64,
+    attributes: &ContextAttributes,
+) -> Result<GlContext, String> {
+    let canvas = find_canvas(id)?;
+    size_canvas(&canvas, width, height, pixel_ratio)?;
+    let context = get_context(
+        |id, options| canvas.get_context_with_context_options(id, options),
+        attributes,
+    )
+    .ok_or_else(|| {
+        format!(
+            "neither WebGL2 nor WebGL is available on #{} (tried \"webgl2\", \"webgl\", \"experimental-webgl\")",
+            id
+        )
+    })?;
+    context.viewport(0, 0, canvas.width() as i32, canvas.height() as i32);
+    Ok(context)
+}
+
+/**
+ * Worker に転送された OffscreenCanvas からコンテキストを取得する (window / document を使わない)
+ * CSS 上の大きさはメインスレッド側の canvas 要素で決まるので、描画バッファの大きさだけ設定する
+ */
+pub fn get_webgl_context_offscreen(
+    canvas: &OffscreenCanvas,
+    width: u32,
+    height: u32,
+    pixel_ratio: f64,
+    attributes: &ContextAttributes,
+) -> Result<GlContext, String> {
+    size_canvas(canvas, width, height, pixel_ratio)?;
+    let context = get_context(
+        |id, options| canvas.get_context_with_context_options(id, options),
+        attributes,
+    )
+    .ok_or_else(|| String::from("neither WebGL2 nor WebGL is available on the OffscreenCanvas"))?;
+    context.viewport(0, 0, canvas.width() as i32, canvas.height() as i32);
+    Ok(context)
+}
+
+/**
+ * 呼び出し側が取得済みのコンテキストを使う (canvas の大きさと viewport は get_webgl_context_by_id と同じく合わせる)
+ * コンテキスト属性は取得したときのままなので、trail などに必要な属性は取得時に指定しておく
+ */
+pub fn adopt_webgl_context(
+    context: GlContext,
+    width: u32,
+    height: u32,
+    pixel_ratio: f64,
+) -> Result<GlContext, String> {
+    resize_canvas(&context, width, height, pixel_ratio)?;
+    Ok(context)
 }
 
 pub fn get_shader(
-    context: &WebGlRenderingContext,
+    context: &GlContext,
     shader_type: u32,
     source: &str,
-) -> Option<WebGlShader> {
-    let shader = context.create_shader(shader_type)?;
+) -> Result<WebGlShader, String> {
+    let shader = context
+        .create_shader(shader_type)
+        .ok_or_else(|| String::from("failed to create shader."))?;
     context.shader_source(&shader, source);
     context.compile_shader(&shader);
     let compile_is_success = context
         .get_shader_parameter(&shader, WebGlRenderingContext::COMPILE_STATUS)
-        .as_bool()?;
+        .as_bool()
+        .unwrap_or(false);
     if !compile_is_success {
-        panic!("failed to compile.");
+        let info = context.get_shader_info_log(&shader).unwrap_or_default();
+        let stage = if shader_type == WebGlRenderingContext::VERTEX_SHADER {
+            "vertex"
+        } else {
+            "fragment"
+        };
+        let mut message = format!("failed to compile {} shader: {}", stage, info.trim_end());
+        if let Some((line, text)) = shader_error_line(&info, source) {
+            message.push_str(&format!("\n  at line {}: {}", line, text));
+        }
+        error!("{}", message);
+        return Err(message);
     }
-    Some(shader)
+    Ok(shader)
 }
 
-pub fn create_program(context: &WebGlRenderingContext) -> Option<WebGlProgram> {
+/**
+ * シェーダの情報ログ ("ERROR: 0:12: ..." の形式) から最初のエラーの行番号を探し、その行のソースと一緒に返す
+ */
+pub fn shader_error_line<'a>(info_log: &str, source: &'a str) -> Option<(usize, &'a str)> {
+    info_log.lines().find_map(|entry| {
+        let rest = entry.trim_start().strip_prefix("ERROR:")?;
+        let mut fields = rest.trim_start().splitn(3, ':');
+        fields.next()?.trim().parse::<usize>().ok()?;
+        let line = fields.next()?.trim().parse::<usize>().ok()?;
+        let text = source.lines().nth(line.checked_sub(1)?)?;
+        Some((line, text.trim()))
+    })
+}
+
+/**
+ * 頂点・フラグメントシェーダをコンパイルしてプログラムをリンクする
+ * attributes は並び順の番号に固定してからリンクする (ドライバによって番号が変わらないように)
+ */
+fn build_program(
+    context: &GlContext,
+    vertex_source: &str,
+    fragment_source: &str,
+    attributes: &[&str],
+) -> Result<WebGlProgram, String> {
     let fragment_shader = get_shader(
-        &context,
+        context,
         WebGlRenderingContext::FRAGMENT_SHADER,
-        FRAGMENT_SHADER,
+        fragment_source,
     )?;
-    let vertex_shader = get_shader(
-        &context,
-        WebGlRenderingContext::VERTEX_SHADER,
-        VERTEX_SHADER,
-    )?;
-    let shader_program = context.create_program()?;
+    let vertex_shader = get_shader(context, WebGlRenderingContext::VERTEX_SHADER, vertex_source)?;
+    let shader_program = context
+        .create_program()
+        .ok_or_else(|| String::from("failed to create program."))?;
 
     context.attach_shader(&shader_program, &vertex_shader);
     context.attach_shader(&shader_program, &fragment_shader);
+    // シェーダに無い名前を指定してもエラーにはならない
+    for (index, name) in attributes.iter().enumerate() {
+        context.bind_attrib_location(&shader_program, index as u32, name);
+    }
     context.link_program(&shader_program);
 
     let shader_is_created = context
         .get_program_parameter(&shader_program, WebGlRenderingContext::LINK_STATUS)
-        .as_bool()?;
+        .as_bool()
+        .unwrap_or(false);
     if !shader_is_created {
-        panic!("failed to create shader.");
+        let info = context
+            .get_program_info_log(&shader_program)
+            .unwrap_or_default();
+        let message = format!("failed to link program: {}", info.trim_end());
+        error!("{}", message);
+        return Err(message);
+    }
+    Ok(shader_program)
+}
+
+/**
+ * ユーザー指定のシェーダのソース (None の方は組み込みのものを使う)
+ * WebGL2 では組み込みのシェーダは `#version 300 es` なので、片方だけ差し替える場合は版を揃えること
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ShaderSources {
+    pub vertex: Option<String>,
+    pub fragment: Option<String>,
+    // 組み込みのフラグメントシェーダで縁を滑らかにするか (描画時にブレンドが必要)
+    pub antialias: bool,
+}
+
+// 各プログラムの attribute をリンク前に固定する番号の順 (点・スプライト、円、全画面・線分・オーバーレイ)
+const POINT_ATTRIBUTES: [&str; 3] = ["a_coords", "a_color", "a_scale"];
+const CIRCLE_ATTRIBUTES: [&str; 4] = ["a_coords", "a_corner", "a_color", "a_scale"];
+const POSITION_ATTRIBUTES: [&str; 1] = ["a_position"];
+
+// 描画処理が参照する attribute / uniform
+const REQUIRED_ATTRIBUTES: [&str; 2] = ["a_coords", "a_color"];
+const REQUIRED_UNIFORMS: [&str; 3] = ["u_width", "u_height", "u_pointsize"];
+
+/**
+ * 描画に必要な attribute / uniform がプログラムに残っているか確かめる
+ * シェーダ内で使われていない変数はリンク時に消えるため、宣言だけでなく使用も必要
+ */
+fn validate_program(context: &GlContext, program: &WebGlProgram) -> Result<(), String> {
+    for name in REQUIRED_ATTRIBUTES.iter() {
+        attrib_location(context, program, name)?;
     }
-    context.use_program(Some(&shader_program));
-    let vertex_position_attribute = context.get_attrib_location(&shader_program, "aVertexPosition");
-    context.enable_vertex_attrib_array(vertex_position_attribute as u32);
-    Some(shader_program)
+    for name in REQUIRED_UNIFORMS.iter() {
+        uniform_location(context, program, name)?;
+    }
+    Ok(())
+}
+
+/**
+ * 描画に必要な attribute の番号。リンク時に消えていれば (-1) その名前を示すエラーにする
+ */
+pub fn attrib_location(
+    context: &GlContext,
+    program: &WebGlProgram,
+    name: &str,
+) -> Result<u32, String> {
+    optional_attrib_location(context, program, name)
+        .ok_or_else(|| format!("shader does not expose attribute `{}`", name))
+}
+
+/**
+ * 無くても描ける attribute の番号 (リンク時に消えていれば None。何が消えたかは debug で出す)
+ */
+pub fn optional_attrib_location(
+    context: &GlContext,
+    program: &WebGlProgram,
+    name: &str,
+) -> Option<u32> {
+    let location = context.get_attrib_location(program, name);
+    if location < 0 {
+        debug!(
+            "attribute `{}` is not used by the shader (optimized out)",
+            name
+        );
+        return None;
+    }
+    Some(location as u32)
+}
+
+/**
+ * 描画に必要な uniform の位置。見つからなければその名前を示すエラーにする
+ */
+pub fn uniform_location(
+    context: &GlContext,
+    program: &WebGlProgram,
+    name: &str,
+) -> Result<WebGlUniformLocation, String> {
+    context
+        .get_uniform_location(program, name)
+        .ok_or_else(|| format!("shader does not expose uniform `{}`", name))
+}
+
+/**
+ * 無くても描ける uniform の位置 (見つからなければ None。何が消えたかは debug で出す)
+ */
+pub fn optional_uniform_location(
+    context: &GlContext,
+    program: &WebGlProgram,
+    name: &str,
+) -> Option<WebGlUniformLocation> {
+    let location = context.get_uniform_location(program, name);
+    if location.is_none() {
+        debug!(
+            "uniform `{}` is not used by the shader (optimized out)",
+            name
+        );
+    }
+    location
+}
+
+/**
+ * コンパイル済みのプログラムと、それを使っている Screen の数
+ * GLのプログラムはコンテキストごとのものなので、コンテキスト自体をキーに含める
+ */
+struct CachedProgram {
+    context: JsValue,
+    vertex_source: String,
+    fragment_source: String,
+    program: WebGlProgram,
+    users: usize,
+}
+
+thread_local! {
+    static PROGRAM_CACHE: RefCell<Vec<CachedProgram>> = const { RefCell::new(Vec::new()) };
+}
+
+/**
+ * 点の描画用のプログラムを返す
+ * 同じコンテキスト (同じ canvas) で同じシェーダのプログラムがあれば、コンパイルせずに使い回す
+ * 使い終わったら release_program で返すこと
+ */
+pub fn create_program(context: &GlContext, custom: &ShaderSources) -> Result<WebGlProgram, String> {
+    let (default_vertex, default_fragment) = context.shader_sources(custom.antialias);
+    let vertex_source = custom.vertex.as_deref().unwrap_or(default_vertex);
+    let fragment_source = custom.fragment.as_deref().unwrap_or(default_fragment);
+    let cached = PROGRAM_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let entry = cache.iter_mut().find(|entry| {
+            entry.context == *context.as_js()
+                && entry.vertex_source == vertex_source
+                && entry.fragment_source == fragment_source
+        })?;
+        entry.users += 1;
+        Some(entry.program.clone())
+    });
+    if let Some(program) = cached {
+        return Ok(program);
+    }
+
+    let shader_program = build_program(context, vertex_source, fragment_source, &POINT_ATTRIBUTES)?;
+    validate_program(context, &shader_program)?;
+    PROGRAM_CACHE.with(|cache| {
+        cache.borrow_mut().push(CachedProgram {
+            context: context.as_js().clone(),
+            vertex_source: vertex_source.to_string(),
+            fragment_source: fragment_source.to_string(),
+            program: shader_program.clone(),
+            users: 1,
+        })
+    });
+    Ok(shader_program)
+}
+
+/**
+ * コンテキストの消失で無効になったプログラムをキャッシュから取り除く (削除はしない)
+ * 復元後に create_program を呼べば作り直される
+ */
+pub fn forget_lost_programs(context: &GlContext) {
+    PROGRAM_CACHE.with(|cache| {
+        cache
+            .borrow_mut()
+            .retain(|entry| entry.context != *context.as_js() || context.is_program(&entry.program))
+    });
+}
+
+/**
+ * create_program で得たプログラムを返す。使っている Screen がなくなったら削除する
+ */
+pub fn release_program(context: &GlContext, program: &WebGlProgram) {
+    let unused = PROGRAM_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        match cache.iter().position(|entry| entry.program == *program) {
+            Some(index) => {
+                cache[index].users -= 1;
+                if cache[index].users == 0 {
+                    cache.swap_remove(index);
+                    true
+                } else {
+                    false
+                }
+            }
+            None => true,
+        }
+    });
+    if unused {
+        delete_program(context, program);
+    }
+}
+
+/**
+ * プログラムと、それにアタッチされているシェーダを削除する
+ */
+pub fn delete_program(context: &GlContext, program: &WebGlProgram) {
+    let shaders = context.get_attached_shaders(program).unwrap_or_default();
+    for shader in shaders.iter() {
+        if let Ok(shader) = shader.dyn_into::<WebGlShader>() {
+            context.detach_shader(program, &shader);
+            context.delete_shader(Some(&shader));
+        }
+    }
+    context.delete_program(Some(program));
+}
+
+/**
+ * render_mode: "circles" 用のプログラム
+ */
+pub fn create_circle_program(context: &GlContext, antialias: bool) -> Result<WebGlProgram, String> {
+    let (vertex_source, fragment_source) = context.circle_shader_sources(antialias);
+    build_program(context, vertex_source, fragment_source, &CIRCLE_ATTRIBUTES)
+}
+
+/**
+ * ディスクを重ねるときの色の合成方法
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    // 不透明度に応じて下の色と混ぜる
+    Normal,
+    // 下の色に足し合わせる (重なった部分が明るく光る)
+    Additive,
+}
+
+impl BlendMode {
+    /**
+     * 組み込みのシェーダの u_glow に渡す値
+     */
+    pub fn glow(&self) -> f32 {
+        match self {
+            BlendMode::Normal => 0.,
+            BlendMode::Additive => 1.,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlendMode::Normal => "normal",
+            BlendMode::Additive => "additive",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Result<BlendMode, String> {
+        match mode {
+            "normal" => Ok(BlendMode::Normal),
+            "additive" => Ok(BlendMode::Additive),
+            _ => Err(format!(
+                "unknown blend mode: {} (expected \"normal\" or \"additive\")",
+                mode
+            )),
+        }
+    }
+}
+
+// shape: "ring" の既定の太さ (半径に対する割合)
+pub const DEFAULT_RING_THICKNESS: f64 = 0.3;
+
+/**
+ * 組み込みのシェーダで描くディスクの形
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    // 縁で切り抜いた円 (既定)
+    Circle,
+    // 切り抜かない四角形
+    Square,
+    // 外側から半径の thickness 倍の幅だけを残した輪
+    Ring { thickness: f64 },
+    // 中心から縁へ不透明度が下がる柔らかい光 (ブレンドが必要)
+    Glow,
+}
+
+impl Shape {
+    /**
+     * thickness は "ring" の太さ (半径に対する割合、0.0 より大きく 1.0 以下。省略すると DEFAULT_RING_THICKNESS)
+     */
+    pub fn parse(shape: &str, thickness: Option<f64>) -> Result<Shape, String> {
+        let thickness = thickness.unwrap_or(DEFAULT_RING_THICKNESS);
+        if !(thickness > 0. && thickness <= 1.) {
+            return Err(format!("ring_thickness must be in (0, 1]: {}", thickness));
+        }
+        match shape {
+            "circle" => Ok(Shape::Circle),
+            "square" => Ok(Shape::Square),
+            "ring" => Ok(Shape::Ring { thickness }),
+            "glow" => Ok(Shape::Glow),
+            _ => Err(format!(
+                "unknown shape: {} (expected \"circle\", \"square\", \"ring\" or \"glow\")",
+                shape
+            )),
+        }
+    }
+
+    /**
+     * parse で受け付ける名前と、"ring" の太さ (それ以外は None)
+     */
+    pub fn name(&self) -> (&'static str, Option<f64>) {
+        match *self {
+            Shape::Circle => ("circle", None),
+            Shape::Square => ("square", None),
+            Shape::Ring { thickness } => ("ring", Some(thickness)),
+            Shape::Glow => ("glow", None),
+        }
+    }
+
+    /**
+     * 組み込みのシェーダの u_shape に渡す値
+     */
+    pub fn index(&self) -> i32 {
+        match self {
+            Shape::Circle => 0,
+            Shape::Square => 1,
+            Shape::Ring { .. } => 2,
+            Shape::Glow => 3,
+        }
+    }
+
+    /**
+     * u_ring_inner に渡す、輪の内側の半径 (半径に対する割合。ring 以外は 0.0)
+     */
+    pub fn ring_inner(&self) -> f32 {
+        match self {
+            Shape::Ring { thickness } => (1. - thickness) as f32,
+            _ => 0.,
+        }
+    }
+
+    pub fn needs_blend(&self) -> bool {
+        *self == Shape::Glow
+    }
+}
+
+/**
+ * u_shape / u_ring_inner を使うプログラムに形を設定する (uniforms のプログラムを use_program してから呼ぶ)
+ * ユーザー指定のシェーダが宣言していなければ何もしない
+ */
+pub fn set_shape_uniforms(context: &GlContext, uniforms: &DrawUniforms, shape: Shape) {
+    if let Some(index) = uniforms.shape.as_ref() {
+        context.uniform1i(Some(index), shape.index());
+    }
+    if let Some(inner) = uniforms.ring_inner.as_ref() {
+        context.uniform1f(Some(inner), shape.ring_inner());
+    }
+}
+
+/**
+ * antialias の縁をぼかす幅 u_edge を、描画バッファ上のディスクの直径 (ピクセル) から設定する
+ * u_edge の無いプログラム (ハードエッジのシェーダ、ユーザー指定のシェーダ) では何もしない
+ */
+pub fn set_edge_uniform(context: &GlContext, uniforms: &DrawUniforms, diameter: f64) {
+    if let Some(edge) = uniforms.edge.as_ref() {
+        context.uniform1f(Some(edge), (1. / diameter.max(2.)) as f32);
+    }
+}
+
+/**
+ * アルファでブレンドする (antialias の縁、opacity など)。描画後は BLEND を無効に戻すこと
+ * アルファ成分は加算して不透明のまま保つ (ページの背景が透けないように)
+ */
+pub fn enable_blend(context: &GlContext, mode: BlendMode) {
+    let dst_rgb = match mode {
+        BlendMode::Normal => WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        BlendMode::Additive => WebGlRenderingContext::ONE,
+    };
+    context.enable(WebGlRenderingContext::BLEND);
+    context.blend_func_separate(
+        WebGlRenderingContext::SRC_ALPHA,
+        dst_rgb,
+        WebGlRenderingContext::ONE,
+        WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+    );
+}
+
+/**
+ * texture_url 用のプログラム
+ */
+pub fn create_sprite_program(context: &GlContext) -> Result<WebGlProgram, String> {
+    let (vertex_source, fragment_source) = context.sprite_shader_sources();
+    build_program(context, vertex_source, fragment_source, &POINT_ATTRIBUTES)
+}
+
+/**
+ * FPS・ディスク数の表示用のプログラム
+ */
+pub fn create_overlay_program(context: &GlContext) -> Result<WebGlProgram, String> {
+    let (vertex_source, fragment_source) = context.overlay_shader_sources();
+    build_program(
+        context,
+        vertex_source,
+        fragment_source,
+        &POSITION_ATTRIBUTES,
+    )
+}
+
+/**
+ * trail 用の全画面フェードのプログラム
+ */
+pub fn create_fade_program(context: &GlContext) -> Result<WebGlProgram, String> {
+    let (vertex_source, fragment_source) = context.fade_shader_sources();
+    build_program(
+        context,
+        vertex_source,
+        fragment_source,
+        &POSITION_ATTRIBUTES,
+    )
+}
+
+/**
+ * 速度ベクトルの表示用のプログラム (クリップ座標の頂点を u_color で塗る。シェーダは fade と共通)
+ */
+pub fn create_line_program(context: &GlContext) -> Result<WebGlProgram, String> {
+    let (vertex_source, fragment_source) = context.fade_shader_sources();
+    build_program(
+        context,
+        vertex_source,
+        fragment_source,
+        &POSITION_ATTRIBUTES,
+    )
 }
@@ -0,0 +1,145 @@
+use crate::simulation::{FRAME_MS, MAX_DT_MS};
+
+// emitter モードの既定値 (1秒あたりに出すディスクの数、寿命 (秒)、同時に存在するディスクの上限)
+pub const DEFAULT_EMIT_RATE: f64 = 30.;
+pub const DEFAULT_LIFETIME: f64 = 5.;
+pub const DEFAULT_MAX_DISKS: u32 = 500;
+
+/**
+ * 一定の割合でディスクを出し、寿命を過ぎたものを消す emitter モードの管理
+ * ages はシミュレーションのディスクと同じ順に並べた経過時間 (ms) で、
+ * 消すときはディスク・色と同じ番号を swap_remove して並びを揃える
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Emitter {
+    rate: f64,
+    lifetime_ms: f64,
+    max_disks: usize,
+    origin: (f64, f64),
+    ages: Vec<f64>,
+    // 出しきれていない端数 (次のステップに持ち越す)
+    pending: f64,
+}
+
+impl Emitter {
+    /**
+     * rate は1秒あたりに出すディスクの数、lifetime は寿命 (秒)、origin はディスクを出す位置
+     */
+    pub fn new(
+        rate: f64,
+        lifetime: f64,
+        max_disks: u32,
+        origin: (f64, f64),
+    ) -> Result<Emitter, String> {
+        if !(rate.is_finite() && rate > 0.) {
+            return Err(format!("emit_rate must be a positive number: {}", rate));
+        }
+        if !(lifetime.is_finite() && lifetime > 0.) {
+            return Err(format!("lifetime must be a positive number: {}", lifetime));
+        }
+        if max_disks == 0 {
+            return Err(String::from("max_disks must be at least 1"));
+        }
+        if !(origin.0.is_finite() && origin.1.is_finite()) {
+            return Err(format!(
+                "emit_origin must be finite: ({}, {})",
+                origin.0, origin.1
+            ));
+        }
+        Ok(Emitter {
+            rate,
+            lifetime_ms: lifetime * 1000.,
+            max_disks: max_disks as usize,
+            origin,
+            ages: Vec::with_capacity(max_disks as usize),
+            pending: 0.,
+        })
+    }
+
+    pub fn origin(&self) -> (f64, f64) {
+        self.origin
+    }
+
+    pub fn max_disks(&self) -> usize {
+        self.max_disks
+    }
+
+    pub fn ages(&self) -> &[f64] {
+        &self.ages
+    }
+
+    /**
+     * ディスクの数に合わせる (add_disk・import_state など emitter 以外で増減した場合)
+     * 外から足したディスクは今出したものとして扱う
+     */
+    pub fn sync(&mut self, disk_count: usize) {
+        self.ages.resize(disk_count, 0.);
+    }
+
+    /**
+     * 全ディスクの経過時間を dt_ms 進める (シミュレーションと同じく MAX_DT_MS で頭打ちにする)
+     */
+    pub fn age(&mut self, dt_ms: f64) {
+        let dt_ms = clamp_dt(dt_ms);
+        for age in self.ages.iter_mut() {
+            *age += dt_ms;
+        }
+    }
+
+    /**
+     * 寿命を過ぎたディスクがあれば、その番号を1つ返す
+     */
+    pub fn next_expired(&self) -> Option<usize> {
+        self.ages.iter().position(|&age| age >= self.lifetime_ms)
+    }
+
+    /**
+     * index 番目を消し、最後のディスクをそこへ移す (Vec::swap_remove と同じ)
+     */
+    pub fn swap_remove(&mut self, index: usize) {
+        if index < self.ages.len() {
+            self.ages.swap_remove(index);
+        }
+    }
+
+    /**
+     * index 番目を消し、後ろを詰める (remove_disk で並びを保って消した場合)
+     */
+    pub fn remove(&mut self, index: usize) {
+        if index < self.ages.len() {
+            self.ages.remove(index);
+        }
+    }
+
+    /**
+     * dt_ms の間に出すディスクの数 (上限を超える分は捨て、持ち越さない)
+     * 返した数だけ push すること
+     */
+    pub fn spawn_count(&mut self, dt_ms: f64) -> usize {
+        self.pending += self.rate * clamp_dt(dt_ms) / 1000.;
+        let count = self.pending.floor();
+        self.pending -= count;
+        let room = self.max_disks.saturating_sub(self.ages.len());
+        if count as usize > room {
+            self.pending = 0.;
+            room
+        } else {
+            count as usize
+        }
+    }
+
+    /**
+     * 出したディスクの経過時間を 0 から数え始める
+     */
+    pub fn push(&mut self) {
+        self.ages.push(0.);
+    }
+}
+
+fn clamp_dt(dt_ms: f64) -> f64 {
+    if dt_ms.is_finite() {
+        dt_ms.clamp(0., MAX_DT_MS)
+    } else {
+        FRAME_MS
+    }
+}
@@ -0,0 +1,167 @@
+use crate::simulation::Disk;
+use serde::Serialize;
+
+// 1フレームに JS へ渡すイベントの数の既定の上限 (これを超えた分は捨てる)
+pub const DEFAULT_MAX_EVENTS_PER_FRAME: u32 = 1000;
+
+/**
+ * シミュレーション中に起きた出来事の種類
+ */
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    // bounce モードで壁に当たった
+    Wall,
+    // ディスク同士がぶつかった
+    Collision,
+}
+
+/**
+ * JS のコールバックへ渡すイベント ({type, indices, x, y, speed})
+ * x, y は壁ならディスクの中心、衝突なら2つの中心の中点
+ * speed は壁なら当たったディスクの速さ、衝突なら2つの相対速度の大きさ
+ */
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SimEvent {
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+    pub indices: Vec<u32>,
+    pub x: f64,
+    pub y: f64,
+    pub speed: f64,
+}
+
+impl SimEvent {
+    pub fn wall(index: usize, disk: &Disk) -> SimEvent {
+        SimEvent {
+            kind: EventKind::Wall,
+            indices: vec![index as u32],
+            x: disk.x,
+            y: disk.y,
+            speed: disk.cos.hypot(disk.sin),
+        }
+    }
+
+    pub fn collision(i: usize, j: usize, a: &Disk, b: &Disk, speed: f64) -> SimEvent {
+        SimEvent {
+            kind: EventKind::Collision,
+            indices: vec![i as u32, j as u32],
+            x: (a.x + b.x) / 2.,
+            y: (a.y + b.y) / 2.,
+            speed,
+        }
+    }
+}
+
+/**
+ * どの種類のイベントを集めるか (既定ではどちらも集めない)
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventMask {
+    pub wall: bool,
+    pub collision: bool,
+}
+
+impl EventMask {
+    pub const ALL: EventMask = EventMask {
+        wall: true,
+        collision: true,
+    };
+
+    pub fn any(&self) -> bool {
+        self.wall || self.collision
+    }
+
+    pub fn accepts(&self, kind: EventKind) -> bool {
+        match kind {
+            EventKind::Wall => self.wall,
+            EventKind::Collision => self.collision,
+        }
+    }
+
+    pub fn union(&self, other: EventMask) -> EventMask {
+        EventMask {
+            wall: self.wall || other.wall,
+            collision: self.collision || other.collision,
+        }
+    }
+}
+
+/**
+ * コールバック・canvas へまだ渡していないイベント (フレームごとにまとめて渡す)
+ * 衝突が大量に起きたフレームでページが固まらないよう max 個までに抑え、溢れたら古いものから捨てる
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventQueue {
+    events: Vec<SimEvent>,
+    max: usize,
+    // 前回 take_dropped してから上限を超えて捨てた数
+    dropped: usize,
+}
+
+impl Default for EventQueue {
+    fn default() -> EventQueue {
+        EventQueue::new(DEFAULT_MAX_EVENTS_PER_FRAME)
+    }
+}
+
+impl EventQueue {
+    pub fn new(max: u32) -> EventQueue {
+        EventQueue {
+            events: Vec::new(),
+            max: max as usize,
+            dropped: 0,
+        }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max as u32
+    }
+
+    /**
+     * 上限を変える。今溜まっているものが上限を超えていれば古いものから捨てる
+     */
+    pub fn set_max(&mut self, max: u32) {
+        self.max = max as usize;
+        self.trim();
+    }
+
+    pub fn events(&self) -> &[SimEvent] {
+        &self.events
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /**
+     * events を後ろに足し、上限を超えた分を古いものから捨てる
+     */
+    pub fn extend(&mut self, events: impl IntoIterator<Item = SimEvent>) {
+        self.events.extend(events);
+        self.trim();
+    }
+
+    /**
+     * 前回呼んでから上限を超えて捨てた数を返し、数え直す
+     */
+    pub fn take_dropped(&mut self) -> usize {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /**
+     * 溜まったイベントと捨てた数を消す (上限はそのまま)
+     */
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.dropped = 0;
+    }
+
+    fn trim(&mut self) {
+        let excess = self.events.len().saturating_sub(self.max);
+        if excess > 0 {
+            self.events.drain(..excess);
+            self.dropped += excess;
+        }
+    }
+}
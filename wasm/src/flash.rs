@@ -0,0 +1,120 @@
+// bounce_flash の既定の色 (白)
+pub const DEFAULT_FLASH_COLOR: [f32; 3] = [1., 1., 1.];
+
+/**
+ * 壁や他のディスクに当たったディスクを一瞬 highlight の色にし、duration_ms かけて元の色へ戻す
+ * 残り時間はディスクの番号順に持ち、当たったことのあるディスクの分まで伸ばす
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct BounceFlash {
+    duration_ms: f64,
+    highlight: [f32; 3],
+    // ディスクごとの残り時間 (ms)。0 なら光っていない
+    timers: Vec<f64>,
+    // 前のフレームで色を混ぜたか (光り終えたフレームにも元の色へ戻すため)
+    lit: bool,
+}
+
+impl BounceFlash {
+    /**
+     * duration は秒 (0 より大きい有限の数)
+     */
+    pub fn new(duration: f64, highlight: [f32; 3]) -> Result<BounceFlash, String> {
+        if !(duration.is_finite() && duration > 0.) {
+            return Err(format!(
+                "bounce_flash must be a positive number of seconds: {}",
+                duration
+            ));
+        }
+        Ok(BounceFlash {
+            duration_ms: duration * 1000.,
+            highlight,
+            timers: Vec::new(),
+            lit: false,
+        })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration_ms / 1000.
+    }
+
+    /**
+     * index のディスクを光らせる (光っている途中でも最初からやり直し、明るさは重ねない)
+     */
+    pub fn hit(&mut self, index: usize) {
+        if index >= self.timers.len() {
+            self.timers.resize(index + 1, 0.);
+        }
+        self.timers[index] = self.duration_ms;
+    }
+
+    /**
+     * dt_ms だけ時間を進める
+     */
+    pub fn advance(&mut self, dt_ms: f64) {
+        if !(dt_ms.is_finite() && dt_ms > 0.) {
+            return;
+        }
+        for timer in self.timers.iter_mut() {
+            *timer = (*timer - dt_ms).max(0.);
+        }
+    }
+
+    /**
+     * index のディスクの光り具合 (当たった直後が 1.0、光っていなければ 0.0)
+     */
+    pub fn level(&self, index: usize) -> f32 {
+        self.timers
+            .get(index)
+            .map_or(0., |&timer| (timer / self.duration_ms) as f32)
+    }
+
+    fn active(&self) -> bool {
+        self.timers.iter().any(|&timer| timer > 0.)
+    }
+
+    /**
+     * このフレームで色を塗り直す必要があるか (光っているか、前のフレームで光っていた)
+     */
+    pub fn needs_apply(&self) -> bool {
+        self.lit || self.active()
+    }
+
+    /**
+     * 元の色 (1ディスクあたり rgba の4値) を光り具合に応じて highlight の色へ寄せる (アルファは変えない)
+     */
+    pub fn apply(&mut self, colors: &mut [f32]) {
+        for (rgba, &timer) in colors.chunks_mut(4).zip(&self.timers) {
+            if timer <= 0. {
+                continue;
+            }
+            let level = (timer / self.duration_ms) as f32;
+            for (c, &h) in rgba.iter_mut().zip(&self.highlight) {
+                *c += (h - *c) * level;
+            }
+        }
+        self.lit = self.active();
+    }
+
+    // 以下はディスクの削除に合わせて残り時間を詰める (光ったことのないディスクの分は持たない)
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.timers.len() {
+            self.timers.remove(index);
+        }
+    }
+
+    /**
+     * 末尾のディスクが index に移る削除 (len は削除する前のディスクの数)
+     */
+    pub fn swap_remove(&mut self, index: usize, len: usize) {
+        if index < self.timers.len() {
+            self.timers.resize(len.max(index + 1), 0.);
+            self.timers.swap_remove(index);
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.timers.truncate(len);
+    }
+}
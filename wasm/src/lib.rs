@@ -1,11 +1,81 @@
+#[macro_use]
+mod logger;
+pub mod adaptive;
+mod animation;
+mod attached;
+pub mod boids;
+mod circles;
+pub mod collision;
+pub mod color;
 mod dom_utils;
+pub mod emitter;
+pub mod events;
+pub mod flash;
+pub mod merge;
+pub mod obstacle;
+mod options;
+mod overlay;
+pub mod perf;
+pub mod recording;
+pub mod simulation;
+mod sprite;
+pub mod state;
+pub mod stats;
+pub mod timestep;
+pub mod tracking;
+mod trail;
 mod utils;
+mod velocity;
+pub mod view;
+pub mod world;
 
+use adaptive::AdaptiveQuality;
+use animation::AnimationLoop;
+use attached::{AttachedView, ViewFrame};
+use boids::{Behavior, BoidsParams};
+use circles::{CirclePass, RenderMode};
+pub use collision::Restitution;
+pub use color::random_colors;
+use color::{ColorMode, ColorSpec, DiskHues, Palette, DEFAULT_CYCLE_SPEED, DEFAULT_MAX_VELOCITY};
+pub use dom_utils::{
+    buffer_size, grown_capacity, interleave_vertices, layer_depth, shader_error_line, Shape,
+    VERTEX_FLOATS,
+};
+use dom_utils::{BlendMode, DynamicBuffer, GlContext};
+use emitter::Emitter;
+use events::{EventKind, EventMask, EventQueue, SimEvent, DEFAULT_MAX_EVENTS_PER_FRAME};
+use flash::BounceFlash;
+use logger::Level;
+pub use logger::{LogThrottle, Subsystem, THROTTLE_MS};
+use merge::CollisionResponse;
+use obstacle::ObstacleShape;
+pub use options::{ContextAttributeOptions, Options, ScreenOptions, ViewOptions};
+use overlay::{MarkerPass, OutlinePass, OverlayPass};
+use perf::{PerfMarks, PerfPhase};
+use rand::rngs::StdRng;
 use rand::Rng;
-use serde::{Deserialize, Serialize};
+use recording::{Input, InputRecorder, InputRecording, InputReplay, Playback, Recorder, Recording};
+pub use simulation::{
+    apply_attractors, apply_drag, apply_jitter, apply_pointer_force, apply_radial_impulse,
+    bounce_disks, bounce_disks_with, clamp_speeds, create_rng, create_std_rng, gaussian,
+    init_disks, init_groups, max_non_overlapping, motion_substeps, place_without_overlap,
+    place_without_overlap_in, size_runs, step_disks, step_disks_scaled, sweep_disk,
+    sweep_disk_with, time_scale_substeps, wrap_disks, Arena, Attractor, Boundary, Bounds, Circle,
+    Disk, DiskInfo, GroupSpec, Simulation, SimulationState, SpawnPattern, VelocityDistribution,
+    ATTRACTOR_MIN_DISTANCE, DEFAULT_POINTER_FORCE, DEFAULT_POINTER_RADIUS, DEFAULT_SPEED_RANGE,
+    FRAME_MS, FULL_ANGLE_RANGE, MAX_DT_MS, MAX_SUBSTEPS, MAX_TIME_SCALE,
+};
+use sprite::{SpritePass, TextureSource};
+use std::cell::RefCell;
+use std::rc::Rc;
+use timestep::{FixedStep, FrameLimiter};
+use tracking::DiskTracker;
+use trail::TrailPass;
+use velocity::VelocityPass;
+use view::View;
 use wasm_bindgen::prelude::*;
-use web_sys::console::log_1;
-use web_sys::{WebGlBuffer, WebGlRenderingContext, WebGlUniformLocation};
+use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlUniformLocation};
+use world::World;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
@@ -13,232 +83,4963 @@ use web_sys::{WebGlBuffer, WebGlRenderingContext, WebGlUniformLocation};
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-macro_rules! log {
-    ( $( $t:tt )* ) => {
-        web_sys::console::log_1(&format!( $( $t )* ).into());
+/**
+ * @deprecated `log_message` を使うこと
+ */
+#[wasm_bindgen]
+pub fn output_log(s: &str) {
+    info!("Hello {}", s);
+}
+
+// background を指定しない場合の背景色 (rgba、不透明の黒)
+const BACKGROUND: [f32; 4] = [0., 0., 0., 1.];
+
+/**
+ * 描画とシミュレーションの状態
+ * Screen と rAF ループの両方から触るため Rc<RefCell<_>> で共有する
+ */
+#[derive(Debug)]
+struct Scene {
+    gl: GlContext,
+    program: WebGlProgram,
+    uniform_point_size: WebGlUniformLocation,
+    // 座標・色・大きさの比を交互に並べた頂点バッファ (VERTEX_FLOATS 個ずつ)
+    buffer_vertices: DynamicBuffer,
+
+    attrib_coords: u32,
+    attrib_color: u32,
+    // 組み込みのシェーダの a_scale (ユーザー指定のシェーダには無いことがあり、その場合は None)
+    attrib_scale: Option<u32>,
+
+    color_mode: ColorMode,
+    // "speed" モードで青・赤になる速さ
+    min_velocity: f64,
+    max_velocity: f64,
+    // "cycle" モードで色相を回す速さ (度 / 秒) と、シミュレーションを進めた分だけ回した色相
+    cycle_speed: f64,
+    cycle_phase: f64,
+    // add_random_disks・randomize_velocities で選ぶ速度の決め方 (spawn_at は速さの範囲だけを使う)
+    velocities: VelocityDistribution,
+    // 初期化に使った乱数生成器 (seed を指定すれば、後から足すディスクや色も再現できる)
+    rng: StdRng,
+    random_colors: Vec<f32>,
+    // ディスクごとの不透明度 (disk_alpha で初期化し、set_disk_color で個別に変える)
+    disk_alphas: Vec<f32>,
+    disk_alpha: f32,
+    // 不透明度が 1.0 未満のディスクがあればブレンドして描く
+    translucent: bool,
+    // "cycle" モードのディスクごとの色相 (disk_alphas と同じくディスクの削除・合体に合わせて詰め直す)
+    disk_hues: DiskHues,
+    // ディスクごとの層 (set_disk_layer で変えたディスクまでの分だけ持ち、足りない分は層 0)
+    // 0 より大きい層があれば深度テストで手前に描く
+    disk_layers: Vec<u8>,
+    // bounce_flash のときのみ。壁・衝突で光らせたディスクの残り時間
+    flash: Option<BounceFlash>,
+    // DiskRef の世代と track で追うディスクの番号
+    tracker: DiskTracker,
+    static_palette: Vec<[f32; 3]>,
+    // random_colors を選ぶパレット ("speed" モードの色の並びにも使う)
+    palette: Palette,
+    // 初期化に使ったグループ (add_disk_to_group で同じ大きさ・質量・色のディスクを足す)
+    groups: Vec<GroupSpec>,
+    mass_from_radius: bool,
+    // GPUへ転送する色 (1diskあたりrgbaの4値)
+    colors: Vec<f32>,
+    colors_dirty: bool,
+    // GPUへ転送する座標 (x0, y0, x1, y1, ...)。positions_ptr で JS に公開する
+    positions: Vec<f32>,
+    // positions_ptr のビューを作り直す必要があるたびに増やす番号と、それを判定する (先頭アドレス, 要素数, wasm のメモリのページ数)
+    positions_generation: u32,
+    positions_location: (usize, usize, usize),
+    // GPUへ転送するディスクごとの大きさの disk_size に対する比
+    scales: Vec<f32>,
+    // buffer_vertices へ転送する頂点データ (毎フレーム positions・colors・scales から作り直す)
+    vertices: Vec<f32>,
+
+    // render_mode: "circles" のときのみ生成される
+    circles: Option<CirclePass>,
+    // texture_url を指定したときのみ生成される ("points" モードのみ)
+    sprite: Option<SpritePass>,
+    // FPS・ディスク数の表示 (overlay_enabled(true) で生成される)
+    overlay: Option<OverlayPass>,
+    overlay_on: bool,
+    // 速度ベクトルの表示 (set_debug_overlay(true) で生成され、以後は使い回す)
+    velocity: Option<VelocityPass>,
+    velocity_on: bool,
+    // 線分の長さ = 速度 (1フレームあたりの移動量) * velocity_scale
+    velocity_scale: f64,
+    // 引力点の表示 (set_show_attractors(true) で生成される)
+    markers: Option<MarkerPass>,
+    markers_on: bool,
+    // circle の領域の縁の表示 (set_show_arena(true) で生成される)
+    outline: Option<OutlinePass>,
+    outline_on: bool,
+    // 障害物の外形の表示 (set_show_obstacles(true) で生成される)
+    obstacle_outline: Option<OutlinePass>,
+    obstacles_on: bool,
+    // 背景色 (rgba 0.0 - 1.0)
+    background: [f32; 4],
+    // 描画だけに掛けるパン・ズーム (シミュレーションの座標は変えない)
+    view: View,
+    // 描画バッファがアルファを持つか (持たなければ背景色のアルファは無視される)
+    transparent: bool,
+    // 残像の強さ (0.0 で毎フレーム全消去、1.0 で一切消さない)
+    trail: f64,
+    // コンテキストを preserveDrawingBuffer で作ったか (作っていなければ残像は残らない)
+    preserve_drawing_buffer: bool,
+    trail_pass: TrailPass,
+    // 最初のフレームは trail の有無によらず全消去する
+    needs_clear: bool,
+    // 縁をぼかすシェーダを使っているか (描画時にブレンドする)
+    antialias: bool,
+    // ディスクの不透明度 (0.0 - 1.0) と重ね方
+    opacity: f64,
+    blend_mode: BlendMode,
+    // 組み込みのシェーダで描くディスクの形
+    shape: Shape,
+    // 組み込みのシェーダの u_alpha (ユーザー指定のシェーダには無いことがある)
+    uniform_alpha: Option<WebGlUniformLocation>,
+    // 組み込みのシェーダの u_glow ("additive" モードで点を柔らかく光らせる)
+    uniform_glow: Option<WebGlUniformLocation>,
+    // 視点・縁の幅など、描画のたびに設定する uniform の位置
+    draw_uniforms: dom_utils::DrawUniforms,
+
+    sim: Simulation,
+    // 一時停止中はフレームごとの描画だけ行い、シミュレーションを進めない
+    paused: bool,
+    // pause_when_hidden でページが非表示の間 true (シミュレーションも描画も止める)
+    hidden: bool,
+    // auto_resize なら canvas の CSS の大きさに合わせて描画バッファを変える
+    auto_resize: bool,
+    // ResizeObserver から届いた CSS の大きさ (次のフレームの最初に1回だけ反映する)
+    pending_resize: Option<(u32, u32)>,
+    // 一時停止中に受け取った apply_impulse / apply_impulse_at (再開後の最初のステップの前に順に加える)
+    queued_impulses: Vec<Input>,
+    resize_watcher: Option<dom_utils::ResizeWatcher>,
+    timer: stats::FrameTimer,
+    // false なら frame() で処理時間を計測しない
+    collect_stats: bool,
+    // Options.perf_marks のときのみ。frame() の区間を performance.mark / measure で囲む
+    perf_marks: Option<PerfMarks>,
+    // Options.debug / set_log_level のレベル。info 以上 (info・debug) のときだけ描画後に gl.getError を調べる
+    log_level: Level,
+    // タイムスタンプを渡すフレーム (do_frame(timestamp) と start() のループ) の固定刻み (ループ開始時にリセットする)
+    fixed_step: FixedStep,
+    // start() のループで描くフレームを target_fps までに間引く
+    frame_limiter: FrameLimiter,
+    // adaptive のときのみ。フレームの時間を見てディスクの数を増減する
+    adaptive: Option<AdaptiveQuality>,
+    // 固定刻みで最後のステップを進める直前の座標と、描画時にそこから補間する割合
+    previous_positions: Vec<f32>,
+    interpolation: Option<f32>,
+    // release() でGLリソースを解放済みか
+    released: bool,
+    // 論理ピクセル (シミュレーションの座標) あたりの描画バッファのピクセル数
+    pixel_ratio: f64,
+    // start_recording 中のみ
+    recorder: Option<Recorder>,
+    // 記録中にエラーで止めた場合の、それまでの記録 (stop_recording で返す)
+    stopped_recording: Option<Recording>,
+    // play_recording 中はシミュレーションを進めず、記録した座標を描く
+    playback: Option<Playback>,
+    // start_input_recording / replay からのステップ数
+    tick: u64,
+    // start_input_recording 中のみ
+    input_recorder: Option<InputRecorder>,
+    // replay 中は記録した操作を与え、記録した時間ずつ進める
+    replay: Option<InputReplay>,
+    // emitter モードのみ。ステップごとにディスクを出し、寿命を過ぎたものを消す
+    emitter: Option<Emitter>,
+    // シミュレーションの時間の倍率 (1.0 で等速、負で逆再生)
+    time_scale: f64,
+    // on_event で登録したコールバックと、購読するイベントの種類
+    event_callback: Option<js_sys::Function>,
+    event_mask: EventMask,
+    // set_on_wall_bounce / set_on_collision で登録した、種類ごとのコールバック
+    wall_callback: Option<js_sys::Function>,
+    collision_callback: Option<js_sys::Function>,
+    // 衝突のたびに "disk-collision" を送る canvas (emit_events: true を指定しなければ None)
+    collision_target: Option<web_sys::EventTarget>,
+    // まだコールバック・canvas へ渡していないイベント (1フレームに max_events_per_frame 個まで)
+    pending_events: EventQueue,
+
+    // コンテキストの消失から復元するときに、GLリソースを作り直すための設定
+    shader_sources: dom_utils::ShaderSources,
+    // スプライトの画像 (コンテキストの復元時に作り直す)
+    texture: Option<TextureSource>,
+    // webglcontextlost から webglcontextrestored までの間は描画しない
+    context_lost: bool,
+    // on_context_change で登録したコールバック ("lost" | "restored" で呼ばれる)
+    context_callback: Option<js_sys::Function>,
+    context_listeners: Vec<dom_utils::EventListener>,
+    // attach_view で足した別の canvas (番号と一緒に、足した順)
+    views: Vec<(u32, AttachedView)>,
+    next_view_id: u32,
+    // init_gl / reset_with で最後に使ったオプション (reset で同じディスクを作り直す)
+    options: Options,
+}
+
+/**
+ * 点の描画用のプログラムと、描画で使う attribute / uniform の位置
+ */
+#[derive(Debug)]
+struct PointProgram {
+    program: WebGlProgram,
+    attrib_coords: u32,
+    attrib_color: u32,
+    attrib_scale: Option<u32>,
+    uniform_point_size: WebGlUniformLocation,
+    uniform_alpha: Option<WebGlUniformLocation>,
+    uniform_glow: Option<WebGlUniformLocation>,
+    uniforms: dom_utils::DrawUniforms,
+}
+
+fn create_point_program(
+    context: &GlContext,
+    shader_sources: &dom_utils::ShaderSources,
+    width: f64,
+    height: f64,
+) -> Result<PointProgram, String> {
+    let program = dom_utils::create_program(context, shader_sources)?;
+    context.use_program(Some(&program));
+    // create_program で a_coords / a_color / u_width / u_height / u_pointsize があることは確認済み
+    let uniform_point_size = dom_utils::uniform_location(context, &program, "u_pointsize")?;
+    dom_utils::set_size_uniforms(context, &program, width, height)?;
+    Ok(PointProgram {
+        attrib_coords: dom_utils::attrib_location(context, &program, "a_coords")?,
+        attrib_color: dom_utils::attrib_location(context, &program, "a_color")?,
+        attrib_scale: dom_utils::optional_attrib_location(context, &program, "a_scale"),
+        uniform_point_size,
+        uniform_alpha: dom_utils::optional_uniform_location(context, &program, "u_alpha"),
+        uniform_glow: dom_utils::optional_uniform_location(context, &program, "u_glow"),
+        uniforms: dom_utils::DrawUniforms::new(context, &program),
+        program,
+    })
+}
+
+/**
+ * 1フレーム分の、JS へ渡すイベント
+ */
+struct EventBatch {
+    callback: Option<(js_sys::Function, JsValue)>,
+    wall: Option<(js_sys::Function, JsValue)>,
+    collision: Option<(js_sys::Function, JsValue)>,
+    collisions: Option<(web_sys::EventTarget, Vec<JsValue>)>,
+}
+
+impl Scene {
+    /**
+     * 描画を伴わずにシミュレーションを dt_ms ミリ秒分進める
+     */
+    pub fn step_dt(&mut self, dt_ms: f64) {
+        let mut dt_ms = dt_ms;
+        if let Some(mut replay) = self.replay.take() {
+            while let Some(input) = replay.next_input(self.tick) {
+                self.apply_input(&input);
+            }
+            if replay.finished(self.tick) {
+                // 記録の最後のステップまで進めたら、その状態のまま止める
+                self.paused = true;
+                return;
+            }
+            dt_ms = replay.dt_ms();
+            self.replay = Some(replay);
+        }
+        // 記録する場合も、加えたステップの操作として残す
+        for input in std::mem::take(&mut self.queued_impulses) {
+            self.apply_input(&input);
+        }
+        if let Some(recorder) = self.input_recorder.as_mut() {
+            recorder.step(self.tick, dt_ms);
+        }
+        self.tick += 1;
+        // 一時停止中は進めないので、止めたフレームの色も止まる
+        if self.color_mode == ColorMode::Cycle && dt_ms.is_finite() {
+            self.cycle_phase = (self.cycle_phase
+                + self.cycle_speed * dt_ms.clamp(0., MAX_DT_MS) * self.time_scale / 1000.)
+                .rem_euclid(360.);
+        }
+        if let Some(flash) = self.flash.as_mut() {
+            flash.advance(dt_ms.min(MAX_DT_MS));
+        }
+        // 倍率が 1 を超える分は分割して進め、1回の移動で壁をすり抜けないようにする
+        let (substeps, substep_ms) = time_scale_substeps(dt_ms, self.time_scale);
+        if let Some(emitter) = self.emitter.take() {
+            self.emitter = Some(self.emit(emitter, substep_ms * substeps as f64));
+        }
+        let reverse = self.time_scale < 0.;
+        if reverse {
+            self.sim.reverse_velocities();
+        }
+        for _ in 0..substeps {
+            self.sim.step_dt(substep_ms);
+            if self.sim.last_substeps() == MAX_SUBSTEPS {
+                throttled!(
+                    Level::Debug,
+                    Subsystem::Physics,
+                    "disks move too fast to split the step finely enough; capped at {} substeps",
+                    MAX_SUBSTEPS
+                );
+            }
+            self.collect_step_events();
+            self.apply_merges();
+        }
+        if reverse {
+            self.sim.reverse_velocities();
+        }
+        self.timer.count_frame(
+            self.sim.disks(),
+            self.sim.last_collisions() as usize,
+            self.sim.last_pair_tests() as usize,
+        );
+    }
+
+    // 直近の sim.step_dt で合体したディスクの色・不透明度・経過時間を、合体した順に詰め直す
+    fn apply_merges(&mut self) {
+        if self.sim.merges().is_empty() {
+            return;
+        }
+        for merge in self.sim.merges() {
+            merge::apply_merge_colors(&mut self.random_colors, merge);
+            if merge.removed < self.disk_alphas.len() {
+                self.disk_alphas.remove(merge.removed);
+            }
+            if merge.removed < self.disk_layers.len() {
+                self.disk_layers.remove(merge.removed);
+            }
+            self.disk_hues.remove(merge.removed);
+            if let Some(flash) = self.flash.as_mut() {
+                flash.remove(merge.removed);
+            }
+            if let Some(emitter) = self.emitter.as_mut() {
+                emitter.remove(merge.removed);
+            }
+            self.tracker.merge(merge);
+        }
+        self.colors_changed();
+    }
+
+    // 直近の sim.step_dt で起きたことを記録する (イベントは1フレームの上限まで溜める)
+    fn collect_step_events(&mut self) {
+        if !self.sim.recovered().is_empty() {
+            throttled!(
+                Level::Warn,
+                Subsystem::Physics,
+                "reset {} disk(s) with a NaN or infinite position/velocity: {:?} ({})",
+                self.sim.recovered().len(),
+                self.sim.recovered(),
+                self.sim.force_summary()
+            );
+        }
+        if let Some(report) = self.sim.invariant_violation() {
+            throttled!(
+                Level::Warn,
+                Subsystem::Physics,
+                "kinetic energy {} at tick {}: {} -> {} (delta {:e}, momentum delta {:?}, collisions {:?})",
+                if report.energy_delta > 0. { "grew" } else { "changed" },
+                report.tick,
+                report.energy_before,
+                report.energy_after,
+                report.energy_delta,
+                report.momentum_delta,
+                report.collisions
+            );
+        }
+        if let Some(flash) = self.flash.as_mut() {
+            for event in self.sim.events() {
+                for &index in event.indices.iter() {
+                    flash.hit(index as usize);
+                }
+            }
+        }
+        let listened = self.listened_events();
+        if listened.any() {
+            self.pending_events.extend(
+                self.sim
+                    .events()
+                    .iter()
+                    .filter(|event| listened.accepts(event.kind))
+                    .cloned(),
+            );
+        }
+    }
+
+    /**
+     * イベントのコールバックを登録する (None で解除し、保持していた Function を手放す)
+     * コールバックがない間はシミュレーション側でもイベントを集めない
+     */
+    fn set_event_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.event_callback = callback;
+        self.pending_events.clear();
+        self.apply_event_mask();
+    }
+
+    fn set_event_mask(&mut self, mask: EventMask) {
+        self.event_mask = mask;
+        self.apply_event_mask();
+    }
+
+    fn set_wall_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.wall_callback = callback;
+        self.apply_event_mask();
+    }
+
+    fn set_collision_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.collision_callback = callback;
+        self.apply_event_mask();
+    }
+
+    // コールバックと canvas の CustomEvent のどれかが必要とする種類だけ集める (bounce_flash なら両方)
+    fn apply_event_mask(&mut self) {
+        let mut mask = self.listened_events();
+        if self.flash.is_some() {
+            mask = mask.union(EventMask::ALL);
+        }
+        self.sim.set_event_mask(mask.wall, mask.collision);
+    }
+
+    // コールバックと canvas の CustomEvent へ渡す種類
+    fn listened_events(&self) -> EventMask {
+        let callback = match self.event_callback {
+            Some(_) => self.event_mask,
+            None => EventMask::default(),
+        };
+        let typed = EventMask {
+            wall: self.wall_callback.is_some(),
+            collision: self.collision_callback.is_some(),
+        };
+        let dom = EventMask {
+            wall: false,
+            collision: self.collision_target.is_some(),
+        };
+        callback.union(typed).union(dom)
+    }
+
+    /**
+     * コールバック・canvas へ渡すイベントを取り出す (渡すものがなければ None)
+     */
+    fn take_events(&mut self) -> Option<EventBatch> {
+        if self.pending_events.is_empty() {
+            return None;
+        }
+        let dropped = self.pending_events.take_dropped();
+        if dropped > 0 {
+            throttled!(
+                Level::Debug,
+                Subsystem::Physics,
+                "dropped {} older event(s) over the per-frame limit of {}",
+                dropped,
+                self.pending_events.max()
+            );
+        }
+        let pending = self.pending_events.events();
+        let select = |callback: &Option<js_sys::Function>, mask: EventMask| {
+            callback.clone().and_then(|callback| {
+                let events: Vec<&SimEvent> = pending
+                    .iter()
+                    .filter(|event| mask.accepts(event.kind))
+                    .collect();
+                (!events.is_empty()).then(|| (callback, utils::to_js(&events)))
+            })
+        };
+        let callback = select(&self.event_callback, self.event_mask);
+        let wall = select(
+            &self.wall_callback,
+            EventMask {
+                wall: true,
+                collision: false,
+            },
+        );
+        let collision = select(
+            &self.collision_callback,
+            EventMask {
+                wall: false,
+                collision: true,
+            },
+        );
+        let collisions = self.collision_target.clone().map(|target| {
+            let details = self
+                .pending_events
+                .events()
+                .iter()
+                .filter(|event| event.kind == EventKind::Collision)
+                .map(utils::to_js)
+                .collect();
+            (target, details)
+        });
+        self.pending_events.clear();
+        Some(EventBatch {
+            callback,
+            wall,
+            collision,
+            collisions,
+        })
+    }
+
+    /**
+     * (x, y) の周囲 radius 以内のディスクを、距離に反比例する強さで外向きに弾く
+     * マウス座標 (canvas 基準) を渡して使う
+     */
+    pub fn apply_force(&mut self, x: f64, y: f64, strength: f64, radius: f64) {
+        self.record_input(Input::Force {
+            x,
+            y,
+            strength,
+            radius,
+        });
+        self.sim.apply_force(x, y, strength, radius);
+    }
+
+    /**
+     * 全ディスクの速度に (dx, dy) を加える。一時停止中は再開後の最初のステップの前に加える
+     */
+    fn apply_impulse(&mut self, dx: f64, dy: f64) -> Result<(), String> {
+        if !(dx.is_finite() && dy.is_finite()) {
+            return Err(format!("impulse must be finite: ({}, {})", dx, dy));
+        }
+        let input = Input::Impulse { dx, dy };
+        if self.paused {
+            self.queued_impulses.push(input);
+        } else {
+            self.apply_input(&input);
+        }
+        Ok(())
+    }
+
+    /**
+     * apply_force と同じだが、値を確かめ、一時停止中は再開後の最初のステップの前に加える
+     */
+    fn apply_impulse_at(
+        &mut self,
+        x: f64,
+        y: f64,
+        strength: f64,
+        radius: f64,
+    ) -> Result<(), String> {
+        if !(x.is_finite() && y.is_finite() && strength.is_finite()) {
+            return Err(format!(
+                "impulse position and strength must be finite: ({}, {}), {}",
+                x, y, strength
+            ));
+        }
+        if !(radius.is_finite() && radius > 0.) {
+            return Err(format!("radius must be a positive number: {}", radius));
+        }
+        let input = Input::Force {
+            x,
+            y,
+            strength,
+            radius,
+        };
+        if self.paused {
+            self.queued_impulses.push(input);
+        } else {
+            self.apply_input(&input);
+        }
+        Ok(())
+    }
+
+    fn add_attractor(&mut self, x: f64, y: f64, strength: f64) -> Result<u32, String> {
+        let id = self.sim.add_attractor(x, y, strength)?;
+        self.record_input(Input::AddAttractor { x, y, strength });
+        Ok(id)
+    }
+
+    fn remove_attractor(&mut self, id: u32) -> bool {
+        self.record_input(Input::RemoveAttractor { id });
+        self.sim.remove_attractor(id)
+    }
+
+    fn clear_attractors(&mut self) {
+        self.record_input(Input::ClearAttractors);
+        self.sim.clear_attractors();
+    }
+
+    fn add_obstacle(&mut self, obstacle: ObstacleShape) -> Result<u32, String> {
+        let id = self.sim.add_obstacle(obstacle)?;
+        self.record_input(Input::AddObstacle { obstacle });
+        Ok(id)
+    }
+
+    fn remove_obstacle(&mut self, id: u32) -> bool {
+        self.record_input(Input::RemoveObstacle { id });
+        self.sim.remove_obstacle(id)
+    }
+
+    fn clear_obstacles(&mut self) {
+        self.record_input(Input::ClearObstacles);
+        self.sim.clear_obstacles();
+    }
+
+    /**
+     * 視点を変える (描画だけに掛かる。None で全体を等倍で表示する)
+     */
+    fn set_view(&mut self, view: Option<View>) {
+        self.view = view.unwrap_or_default();
+        // 残像は前の視点の位置に残るので消す
+        self.needs_clear = true;
+    }
+
+    /**
+     * canvas 上の座標 (CSS px) を今の視点でのワールド座標にする
+     */
+    fn screen_to_world(&self, x: f64, y: f64) -> (f64, f64) {
+        self.view
+            .screen_to_world(x, y, self.sim.width(), self.sim.height())
+    }
+
+    fn set_pointer(&mut self, x: f64, y: f64) {
+        self.record_input(Input::Pointer { x, y });
+        self.sim.set_pointer(x, y);
+    }
+
+    fn clear_pointer(&mut self) {
+        self.record_input(Input::ClearPointer);
+        self.sim.clear_pointer();
+    }
+
+    fn grab(&mut self, x: f64, y: f64) -> Option<u32> {
+        self.record_input(Input::Grab { x, y });
+        self.sim.grab(x, y)
+    }
+
+    fn drag_to(&mut self, x: f64, y: f64) {
+        self.record_input(Input::Drag { x, y });
+        self.sim.drag_to(x, y);
+    }
+
+    // GL リソースを解放する release と区別する
+    fn release_disk(&mut self) {
+        self.record_input(Input::Release);
+        self.sim.release();
+    }
+
+    fn set_touch_points(&mut self, points: &[f64]) -> Result<(), String> {
+        self.sim.set_touch_points(points)?;
+        self.record_input(Input::TouchPoints {
+            points: points.to_vec(),
+        });
+        Ok(())
+    }
+
+    // start_input_recording 中なら、次のステップの前に行った操作として記録する
+    fn record_input(&mut self, input: Input) {
+        if let Some(recorder) = self.input_recorder.as_mut() {
+            recorder.push(self.tick, input);
+        }
+    }
+
+    // 記録した操作を、記録したときと同じメソッドで与える
+    fn apply_input(&mut self, input: &Input) {
+        match *input {
+            Input::Dt { .. } => {}
+            Input::Spawn { x, y, speed } => self.spawn_at(x, y, speed),
+            Input::AddDisk { x, y, vx, vy } => self.add_disk(x, y, vx, vy),
+            Input::AddDiskToGroup {
+                group,
+                x,
+                y,
+                vx,
+                vy,
+            } => {
+                if let Err(e) = self.add_disk_to_group(group, x, y, vx, vy) {
+                    error!("failed to replay add_disk_to_group: {}", e);
+                }
+            }
+            Input::AddRandomDisks { count } => self.add_random_disks(count),
+            Input::RandomizeVelocities => self.randomize_velocities(),
+            Input::Impulse { dx, dy } => {
+                self.record_input(Input::Impulse { dx, dy });
+                self.sim.apply_impulse(dx, dy);
+                self.colors_dirty = true;
+            }
+            Input::ScaleVelocities { factor } => {
+                if let Err(e) = self.scale_velocities(factor) {
+                    error!("failed to replay scale_velocities: {:?}", e);
+                }
+            }
+            Input::TruncateDisks { len } => self.truncate_disks(len),
+            Input::RemoveDisk { index } => {
+                self.remove_disk(index);
+            }
+            Input::Force {
+                x,
+                y,
+                strength,
+                radius,
+            } => self.apply_force(x, y, strength, radius),
+            Input::Pointer { x, y } => self.set_pointer(x, y),
+            Input::ClearPointer => self.clear_pointer(),
+            Input::Grab { x, y } => {
+                self.grab(x, y);
+            }
+            Input::Drag { x, y } => self.drag_to(x, y),
+            Input::Release => self.release_disk(),
+            Input::TouchPoints { ref points } => {
+                if let Err(e) = self.set_touch_points(points) {
+                    error!("failed to replay set_touch_points: {}", e);
+                }
+            }
+            Input::AddAttractor { x, y, strength } => {
+                if let Err(e) = self.add_attractor(x, y, strength) {
+                    error!("failed to replay add_attractor: {}", e);
+                }
+            }
+            Input::RemoveAttractor { id } => {
+                self.remove_attractor(id);
+            }
+            Input::ClearAttractors => self.clear_attractors(),
+            Input::AddObstacle { obstacle } => {
+                if let Err(e) = self.add_obstacle(obstacle) {
+                    error!("failed to replay add_obstacle: {}", e);
+                }
+            }
+            Input::RemoveObstacle { id } => {
+                self.remove_obstacle(id);
+            }
+            Input::ClearObstacles => self.clear_obstacles(),
+            Input::Resize { width, height } => {
+                if let Err(e) = self.resize(width, height) {
+                    error!("failed to replay resize: {:?}", e);
+                }
+            }
+            Input::TimeScale { scale } => {
+                if let Err(e) = self.set_time_scale(scale) {
+                    error!("failed to replay set_time_scale: {}", e);
+                }
+            }
+        }
+    }
+
+    /**
+     * 現在の状態と新しい乱数の種を記録し、以降の操作とステップの時間を記録する
+     * 乱数を種から作り直すので、replay で同じ操作から同じ結果が得られる
+     */
+    fn start_input_recording(&mut self) {
+        let seed = self.rng.gen();
+        self.rng = create_std_rng(Some(seed));
+        self.sim.seed_jitter(self.rng.gen());
+        self.tick = 0;
+        let initial = state::SavedState::new(&self.sim, &self.random_colors);
+        self.input_recorder = Some(InputRecorder::new(seed, initial));
+        // 時間の倍率は保存する状態に含まれないので、最初の操作として記録する
+        self.record_input(Input::TimeScale {
+            scale: self.time_scale,
+        });
+    }
+
+    fn stop_input_recording(&mut self) -> Option<InputRecording> {
+        self.input_recorder.take().map(InputRecorder::finish)
+    }
+
+    /**
+     * 記録開始時の状態・乱数に戻し、記録した操作を同じステップで与えながら再生する
+     * 最後のステップまで進むと一時停止する
+     */
+    fn replay(&mut self, recording: InputRecording) -> Result<(), String> {
+        let replay = InputReplay::new(recording)?;
+        self.input_recorder = None;
+        self.playback = None;
+        self.queued_impulses.clear();
+        self.load_state(replay.recording().initial.clone())?;
+        self.rng = create_std_rng(Some(replay.recording().seed));
+        self.sim.seed_jitter(self.rng.gen());
+        self.sim.clear_pointer();
+        self.tick = 0;
+        self.replay = Some(replay);
+        self.paused = false;
+        Ok(())
+    }
+
+    /**
+     * 各アニメーションフレームごとの処理
+     * timestamp (requestAnimationFrame が渡す値) を指定すると前回からの経過時間を溜め、固定刻み (120Hz) で進めて
+     * 端数の分だけ描画位置を補間する。省略すると 60fps の1フレーム分進める
+     */
+    pub fn do_frame(&mut self, timestamp: Option<f64>) {
+        match timestamp {
+            Some(timestamp) => self.do_frame_at(timestamp),
+            None => self.do_frame_dt(FRAME_MS),
+        }
+    }
+
+    /**
+     * 前フレームからの経過時間 dt_ms を指定してフレームを処理する
+     * リフレッシュレートが違っても同じ速さで動く。dt_ms は MAX_DT_MS で頭打ちにする
+     */
+    pub fn do_frame_dt(&mut self, dt_ms: f64) {
+        if self.hidden {
+            return;
+        }
+        self.interpolation = None;
+        self.frame(dt_ms, if self.paused { 0 } else { 1 });
+    }
+
+    /**
+     * 一時停止中でもシミュレーションを1ステップ (60fps の1フレーム分) 進めて描画する
+     */
+    pub fn step_frame(&mut self) {
+        self.interpolation = None;
+        self.frame(FRAME_MS, 1);
+    }
+
+    fn do_frame_at(&mut self, timestamp: f64) {
+        if self.hidden {
+            return;
+        }
+        let steps = self.fixed_step.advance(timestamp);
+        if self.paused {
+            // 再開したときにまとめて進まないよう、止まっていた間の時間は捨てる
+            self.fixed_step.discard();
+            self.interpolation = None;
+            self.frame(self.fixed_step.step_ms(), 0);
+        } else {
+            self.interpolation = Some(self.fixed_step.alpha() as f32);
+            self.frame(self.fixed_step.step_ms(), steps);
+        }
+    }
+
+    /**
+     * ページの表示・非表示が変わったときに呼ぶ
+     * 非表示の間は do_frame が何もしない。表示に戻ったら、隠れていた間の時間をまとめて進めないよう刻みをリセットする
+     */
+    fn set_hidden(&mut self, hidden: bool) {
+        if self.hidden == hidden {
+            return;
+        }
+        self.hidden = hidden;
+        self.fixed_step.reset();
+        self.interpolation = None;
+        debug!("page {}", if hidden { "hidden" } else { "visible" });
+    }
+
+    // シミュレーションを dt_ms ずつ steps 回進めて描画する (0 回なら描画だけ行う)
+    fn frame(&mut self, dt_ms: f64, steps: u32) {
+        // dispose 後は削除済みのGLオブジェクトを、消失中は無効なコンテキストを触らないよう何もしない
+        // (消失中はシミュレーションも止め、復元後にそのまま再開する)
+        if self.released || self.context_lost {
+            return;
+        }
+        if let Some((width, height)) = self.pending_resize.take() {
+            if (width, height) != (self.sim.width() as u32, self.sim.height() as u32) {
+                if let Err(e) = self.resize(width, height) {
+                    error!("failed to resize to the canvas size: {:?}", e);
+                }
+            }
+        }
+        let started = dom_utils::now();
+        self.perf_begin(PerfPhase::Physics);
+        if self.playback.is_none() {
+            for step in 0..steps {
+                if step + 1 == steps && self.interpolation.is_some() {
+                    self.previous_positions.clear();
+                    self.previous_positions.extend(
+                        self.sim
+                            .disks()
+                            .iter()
+                            .flat_map(|d| [d.x as f32, d.y as f32]),
+                    );
+                }
+                self.step_dt(dt_ms);
+            }
+        }
+        self.perf_end(PerfPhase::Physics);
+        let stepped = dom_utils::now();
+        self.draw();
+        if self.log_level <= Level::Info {
+            self.report_gl_errors();
+        }
+        let drawn = dom_utils::now();
+        if self.collect_stats {
+            self.timer
+                .record(started, stepped - started, drawn - stepped);
+        }
+        self.adapt(started, drawn - started);
+        // 失敗したら記録を外すので、同じエラーを毎フレーム出さない (それまでの記録は stop_recording で取り出せる)
+        if let Err(e) = recording::record_or_stop(
+            &mut self.recorder,
+            &mut self.stopped_recording,
+            &self.positions,
+        ) {
+            error!("recording stopped: {}", e);
+        }
+        if let Some(perf_marks) = self.perf_marks.as_mut() {
+            perf_marks.end_frame();
+        }
+    }
+
+    fn perf_begin(&self, phase: PerfPhase) {
+        if let Some(perf_marks) = self.perf_marks.as_ref() {
+            perf_marks.begin(phase);
+        }
+    }
+
+    fn perf_end(&self, phase: PerfPhase) {
+        if let Some(perf_marks) = self.perf_marks.as_ref() {
+            perf_marks.end(phase);
+        }
+    }
+
+    // adaptive なら now に始まり work_ms かかったフレームを記録し、見直しの結果に合わせて後ろのディスクを増減する
+    // 記録の再生・操作の再現の間は、再現する数を変えないよう何もしない
+    fn adapt(&mut self, now: f64, work_ms: f64) {
+        if self.playback.is_some() || self.replay.is_some() {
+            return;
+        }
+        let count = self.sim.disk_count();
+        let target = match self
+            .adaptive
+            .as_mut()
+            .and_then(|adaptive| adaptive.record(now, work_ms, count))
+        {
+            Some(target) => target,
+            None => return,
+        };
+        tagged!(
+            Level::Debug,
+            Subsystem::Render,
+            "adaptive quality: {} -> {} disks",
+            count,
+            target
+        );
+        if target < count {
+            self.truncate_disks(target as usize);
+        } else {
+            self.add_random_disks(target - count);
+        }
+    }
+
+    // 手動でディスクの数を変えたときに、しばらく adaptive の見直しを止める
+    fn suspend_adaptive(&mut self) {
+        if let Some(adaptive) = self.adaptive.as_mut() {
+            adaptive.suspend(dom_utils::now());
+        }
+    }
+
+    /**
+     * 描画で起きた GL のエラーをすべて取り出して出力する (同じエラーは1秒に1回まで)
+     */
+    fn report_gl_errors(&self) {
+        // コンテキストを失うと CONTEXT_LOST_WEBGL を返し続けるので、回数を区切る
+        for _ in 0..8 {
+            let code = self.gl.get_error();
+            if code == WebGlRenderingContext::NO_ERROR {
+                return;
+            }
+            throttled!(
+                Level::Warn,
+                Subsystem::Render,
+                "gl error 0x{:04x} after drawing",
+                code
+            );
+        }
+    }
+
+    /**
+     * ログレベルを変える ("off" | "error" | "warn" | "info" | "debug" | "trace")
+     * console への出力レベルはすべての Screen で共通
+     */
+    pub fn set_log_level(&mut self, level: &str) -> Result<(), JsValue> {
+        let level = logger::parse_level(level).map_err(|e| JsValue::from(e.as_str()))?;
+        logger::set_level(level);
+        self.log_level = level;
+        Ok(())
+    }
+
+    /**
+     * 統計情報 (フレーム数・衝突数・速さ・処理時間・FPS・ディスク数・運動エネルギー)
+     */
+    pub fn stats(&self) -> JsValue {
+        utils::to_js(&self.frame_stats())
+    }
+
+    /**
+     * 統計情報のリセット
+     */
+    pub fn reset_stats(&mut self) {
+        self.timer.reset();
+    }
+
+    /**
+     * 色の決め方を切り替える ("random" | "static" | "speed" | "velocity")
+     */
+    pub fn set_color_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        let mode = ColorMode::parse(mode).map_err(|e| JsValue::from(e.as_str()))?;
+        if mode == ColorMode::Static && self.static_palette.is_empty() {
+            return Err(JsValue::from("static color mode requires `static_palette`"));
+        }
+        self.color_mode = mode;
+        self.fill_base_colors();
+        Ok(())
+    }
+
+    /**
+     * 色モードに応じて、描画に使う色 (1diskあたり rgba の4値) を作り直す (速さモードは毎フレーム)
+     */
+    fn fill_base_colors(&mut self) {
+        self.disk_alphas
+            .resize(self.sim.disks().len(), self.disk_alpha);
+        self.disk_hues.sync(self.sim.disks().len());
+        self.translucent = self.disk_alphas.iter().any(|&alpha| alpha < 1.);
+        match self.color_mode {
+            ColorMode::Random => {
+                self.colors.clear();
+                for (rgb, &alpha) in self.random_colors.chunks(3).zip(&self.disk_alphas) {
+                    self.colors.extend_from_slice(rgb);
+                    self.colors.push(alpha);
+                }
+            }
+            ColorMode::Static => {
+                let palette = &self.static_palette;
+                self.colors.clear();
+                for (i, &alpha) in self.disk_alphas.iter().enumerate() {
+                    self.colors.extend_from_slice(&palette[i % palette.len()]);
+                    self.colors.push(alpha);
+                }
+            }
+            ColorMode::Speed => self.fill_speed_colors(),
+            ColorMode::Cycle => self.fill_cycle_colors(),
+        }
+        self.colors_dirty = true;
+    }
+
+    fn fill_speed_colors(&mut self) {
+        let (min_velocity, max_velocity) = (self.min_velocity, self.max_velocity);
+        self.colors.clear();
+        for (disk, &alpha) in self.sim.disks().iter().zip(&self.disk_alphas) {
+            let rgb =
+                self.palette
+                    .speed_color(disk.cos.hypot(disk.sin), min_velocity, max_velocity);
+            self.colors.extend_from_slice(&rgb);
+            self.colors.push(alpha);
+        }
+    }
+
+    fn fill_cycle_colors(&mut self) {
+        let phase = self.cycle_phase;
+        self.colors.clear();
+        for (&hue, &alpha) in self.disk_hues.hues().iter().zip(&self.disk_alphas) {
+            self.colors
+                .extend_from_slice(&color::cycle_color(hue, phase));
+            self.colors.push(alpha);
+        }
+    }
+
+    /**
+     * レンダリング処理 (ディスクの後にオーバーレイを重ねる)
+     */
+    fn draw(&mut self) {
+        // buffer-upload は draw_disks の中で転送が終わったところで draw-call に切り替わる
+        self.perf_begin(PerfPhase::BufferUpload);
+        self.draw_disks();
+        self.draw_overlays();
+        self.draw_views();
+        self.perf_end(PerfPhase::DrawCall);
+    }
+
+    /**
+     * attach_view で足した canvas に、メインと同じ頂点を描く (視点・背景・大きさはビューごと)
+     */
+    fn draw_views(&mut self) {
+        if self.views.is_empty() {
+            return;
+        }
+        // circles モードでは頂点をまとめていないので、ビューのためにここでまとめる
+        if self.circles.is_some() {
+            interleave_vertices(
+                &mut self.vertices,
+                &self.positions,
+                &self.disk_layers,
+                &self.colors,
+                &self.scales,
+            );
+        }
+        let blend = self.needs_blend();
+        let frame = ViewFrame {
+            vertices: &self.vertices,
+            disks: self.sim.disks(),
+            disk_size: self.sim.disk_size(),
+            world_width: self.sim.width(),
+            world_height: self.sim.height(),
+            shape: self.shape,
+            blend_mode: self.blend_mode,
+            blend,
+            alpha: self.opacity as f32,
+            layered: !blend && !self.disk_layers.is_empty(),
+        };
+        for (_, view) in self.views.iter_mut() {
+            view.draw(&frame);
+        }
+    }
+
+    /**
+     * canvas_id の canvas にメインと同じシミュレーションを描くビューを足し、その番号を返す
+     */
+    fn attach_view(&mut self, canvas_id: &str, options: &ViewOptions) -> Result<u32, String> {
+        if self.released {
+            return Err(String::from("the screen has been destroyed"));
+        }
+        options.validate()?;
+        if canvas_id == self.options.canvas_id
+            || self
+                .views
+                .iter()
+                .any(|(_, view)| view.canvas_id() == canvas_id)
+        {
+            return Err(format!("#{} is already drawn by this screen", canvas_id));
+        }
+        let background = match options.background.as_ref() {
+            Some(background) => background.to_rgba()?,
+            None => self.background,
+        };
+        let view = AttachedView::new(
+            canvas_id,
+            (
+                options.width.unwrap_or(self.sim.width() as u32),
+                options.height.unwrap_or(self.sim.height() as u32),
+            ),
+            options.dpr.unwrap_or(self.pixel_ratio),
+            options.view()?,
+            background,
+            &self.shader_sources,
+        )?;
+        let id = self.next_view_id;
+        self.next_view_id += 1;
+        self.views.push((id, view));
+        Ok(id)
+    }
+
+    fn set_attached_view(&mut self, id: u32, view: Option<View>) -> Result<(), String> {
+        match self.views.iter_mut().find(|(view_id, _)| *view_id == id) {
+            Some((_, attached)) => {
+                attached.set_view(view);
+                Ok(())
+            }
+            None => Err(format!("no attached view with id {}", id)),
+        }
+    }
+
+    fn detach_view(&mut self, id: u32) -> bool {
+        match self.views.iter().position(|(view_id, _)| *view_id == id) {
+            Some(index) => {
+                self.views.remove(index).1.delete();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn draw_overlays(&mut self) {
+        // 記録の再生中は速度が分からないので描かない
+        if self.velocity_on && self.playback.is_none() {
+            if let Some(velocity) = self.velocity.as_mut() {
+                velocity.draw(
+                    &self.gl,
+                    self.sim.disks(),
+                    &self.view,
+                    self.sim.width(),
+                    self.sim.height(),
+                    self.velocity_scale,
+                );
+            }
+        }
+        if self.outline_on {
+            if let (Some(outline), Some(circle)) = (self.outline.as_mut(), self.sim.arena_circle())
+            {
+                outline.draw(
+                    &self.gl,
+                    &circle,
+                    &self.view,
+                    self.sim.width(),
+                    self.sim.height(),
+                );
+            }
+        }
+        if self.obstacles_on {
+            if let Some(outline) = self.obstacle_outline.as_mut() {
+                outline.draw_obstacles(
+                    &self.gl,
+                    self.sim.obstacles(),
+                    &self.view,
+                    self.sim.width(),
+                    self.sim.height(),
+                );
+            }
+        }
+        if self.markers_on {
+            if let Some(markers) = self.markers.as_mut() {
+                markers.draw(
+                    &self.gl,
+                    self.sim.attractors(),
+                    &self.view,
+                    self.sim.width(),
+                    self.sim.height(),
+                    self.pixel_ratio,
+                );
+            }
+        }
+        if !self.overlay_on {
+            return;
+        }
+        if let Some(overlay) = self.overlay.as_mut() {
+            overlay.record_frame(dom_utils::now());
+            overlay.draw(
+                &self.gl,
+                self.sim.disks().len(),
+                self.sim.width(),
+                self.sim.height(),
+                self.pixel_ratio,
+            );
+        }
+    }
+
+    /**
+     * 不透明度 1.0・通常の重ね方・縁のぼかしなしなら、ブレンドせずに以前と同じ出力になる
+     */
+    fn needs_blend(&self) -> bool {
+        self.antialias
+            || self.opacity < 1.
+            || self.translucent
+            || self.blend_mode != BlendMode::Normal
+            || self.shape.needs_blend()
+    }
+
+    /**
+     * ディスクの不透明度 (0.0 - 1.0)
+     */
+    pub fn set_opacity(&mut self, opacity: f64) -> Result<(), JsValue> {
+        if !(0. ..=1.).contains(&opacity) {
+            return Err(JsValue::from(
+                format!("opacity must be in [0, 1]: {}", opacity).as_str(),
+            ));
+        }
+        self.opacity = opacity;
+        Ok(())
+    }
+
+    /**
+     * ディスクの重ね方 ("normal" | "additive")
+     */
+    pub fn set_blend_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.blend_mode = BlendMode::parse(mode).map_err(|e| JsValue::from(e.as_str()))?;
+        Ok(())
+    }
+
+    /**
+     * ディスクの形 ("circle" | "square" | "ring" | "glow")。ring の太さは今の値を引き継ぐ
+     */
+    pub fn set_shape(&mut self, shape: &str) -> Result<(), JsValue> {
+        let thickness = match self.shape {
+            Shape::Ring { thickness } => Some(thickness),
+            _ => None,
+        };
+        self.shape = Shape::parse(shape, thickness).map_err(|e| JsValue::from(e.as_str()))?;
+        Ok(())
+    }
+
+    /**
+     * 各ディスクの速度ベクトルを線分で表示するかどうか (既定は非表示)
+     */
+    pub fn set_debug_overlay(&mut self, enabled: bool) {
+        // dispose 後にGLリソースを作り直さない
+        if self.released {
+            return;
+        }
+        if enabled && self.velocity.is_none() {
+            match VelocityPass::new(&self.gl) {
+                Ok(velocity) => self.velocity = Some(velocity),
+                Err(e) => {
+                    error!("failed to create velocity overlay: {}", e);
+                    return;
+                }
+            }
+        }
+        self.velocity_on = enabled;
+    }
+
+    /**
+     * 引力点の位置を大きな点で表示するかどうか (既定は非表示)
+     */
+    pub fn set_show_attractors(&mut self, show: bool) {
+        if self.released {
+            return;
+        }
+        if show && self.markers.is_none() {
+            match MarkerPass::new(&self.gl) {
+                Ok(markers) => self.markers = Some(markers),
+                Err(e) => {
+                    error!("failed to create attractor markers: {}", e);
+                    return;
+                }
+            }
+        }
+        self.markers_on = show;
+    }
+
+    /**
+     * circle の領域の縁を線で表示するかどうか (既定は非表示。rect の領域では何も描かない)
+     */
+    pub fn set_show_arena(&mut self, show: bool) {
+        if self.released {
+            return;
+        }
+        if show && self.outline.is_none() {
+            match OutlinePass::new(&self.gl) {
+                Ok(outline) => self.outline = Some(outline),
+                Err(e) => {
+                    error!("failed to create arena outline: {}", e);
+                    return;
+                }
+            }
+        }
+        self.outline_on = show;
+    }
+
+    /**
+     * 障害物の外形を線で表示するかどうか (既定は非表示)
+     */
+    pub fn set_show_obstacles(&mut self, show: bool) {
+        if self.released {
+            return;
+        }
+        if show && self.obstacle_outline.is_none() {
+            match OutlinePass::new(&self.gl) {
+                Ok(outline) => self.obstacle_outline = Some(outline),
+                Err(e) => {
+                    error!("failed to create obstacle outline: {}", e);
+                    return;
+                }
+            }
+        }
+        self.obstacles_on = show;
+    }
+
+    pub fn set_debug_vector_scale(&mut self, scale: f64) -> Result<(), JsValue> {
+        if !(scale.is_finite() && scale > 0.) {
+            return Err(JsValue::from(
+                format!("vector scale must be a positive number: {}", scale).as_str(),
+            ));
+        }
+        self.velocity_scale = scale;
+        Ok(())
+    }
+
+    /**
+     * FPS とディスク数を canvas の左上に表示するかどうか (既定は非表示)
+     */
+    pub fn overlay_enabled(&mut self, on: bool) {
+        if self.released {
+            return;
+        }
+        if on && self.overlay.is_none() {
+            match OverlayPass::new(&self.gl) {
+                Ok(overlay) => self.overlay = Some(overlay),
+                Err(e) => {
+                    error!("failed to create overlay: {}", e);
+                    return;
+                }
+            }
+        }
+        self.overlay_on = on;
+    }
+
+    fn draw_disks(&mut self) {
+        let [r, g, b, a] = self.background;
+        if self.trail > 0. && !self.needs_clear {
+            // trail = 1.0 のときは何も塗らず、前フレームをそのまま残す
+            if self.trail < 1. {
+                self.trail_pass.fade(&self.gl, [r, g, b], 1. - self.trail);
+            }
+        } else {
+            // 描画バッファは premultipliedAlpha (既定) なので、色にアルファを掛けておく
+            self.gl.clear_color(r * a, g * a, b * a, a);
+            self.gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+            self.needs_clear = false;
+        }
+
+        match self.color_mode {
+            ColorMode::Speed => self.fill_speed_colors(),
+            ColorMode::Cycle => self.fill_cycle_colors(),
+            _ => (),
+        }
+        if matches!(self.color_mode, ColorMode::Speed | ColorMode::Cycle) {
+            self.colors_dirty = true;
+        }
+        if self.flash.as_ref().is_some_and(BounceFlash::needs_apply) {
+            // 光らせた色は元の色から毎フレーム作り直す (random / static は前のフレームの色に混ぜてしまわないよう塗り直す)
+            if matches!(self.color_mode, ColorMode::Random | ColorMode::Static) {
+                self.fill_base_colors();
+            }
+            if let Some(flash) = self.flash.as_mut() {
+                flash.apply(&mut self.colors);
+            }
+            self.colors_dirty = true;
+        }
+        let colors_dirty = std::mem::take(&mut self.colors_dirty);
+        self.positions.clear();
+        match self.playback.as_mut() {
+            Some(playback) => self.positions.extend_from_slice(playback.next_frame()),
+            None => {
+                self.positions.extend(
+                    self.sim
+                        .disks()
+                        .iter()
+                        .flat_map(|d| [d.x as f32, d.y as f32]),
+                );
+                if let Some(alpha) = self.interpolation {
+                    // wrap で反対側へ移ったディスクは画面を横切らせない
+                    let max_jump = (self.sim.width().min(self.sim.height()) / 2.) as f32;
+                    timestep::interpolate_positions(
+                        &mut self.positions,
+                        &self.previous_positions,
+                        alpha,
+                        max_jump,
+                    );
+                }
+            }
+        }
+        let disk_size = self.sim.disk_size();
+        self.scales.clear();
+        self.scales.extend(
+            self.sim
+                .disks()
+                .iter()
+                .map(|d| (d.size_or(disk_size) / disk_size) as f32),
+        );
+        let alpha = self.opacity as f32;
+        let blend = self.needs_blend();
+        let (width, height) = (self.sim.width(), self.sim.height());
+        if let Some(circles) = self.circles.as_mut() {
+            if blend {
+                dom_utils::enable_blend(&self.gl, self.blend_mode);
+            }
+            self.gl.use_program(Some(circles.program()));
+            dom_utils::set_view_uniforms(&self.gl, circles.uniforms(), &self.view, width, height);
+            dom_utils::set_shape_uniforms(&self.gl, circles.uniforms(), self.shape);
+            dom_utils::set_edge_uniform(
+                &self.gl,
+                circles.uniforms(),
+                disk_size * self.pixel_ratio * self.view.scale(),
+            );
+            // circles モードの転送は描画と一緒に行うので、ここから先はまとめて draw-call に数える
+            if let Some(perf_marks) = self.perf_marks.as_ref() {
+                perf_marks.end(PerfPhase::BufferUpload);
+                perf_marks.begin(PerfPhase::DrawCall);
+            }
+            circles.draw(
+                &self.gl,
+                &self.positions,
+                &self.colors,
+                colors_dirty,
+                &self.scales,
+                disk_size / 2.,
+                alpha,
+                self.blend_mode.glow(),
+            );
+            if blend {
+                self.gl.disable(WebGlRenderingContext::BLEND);
+            }
+            return;
+        }
+        // テクスチャの読み込みが終わるまでは通常の円で描く
+        let sprite = self.sprite.as_ref().filter(|sprite| sprite.ready());
+        // 画像の透過部分を抜くため、テクスチャで描くときは常にブレンドする
+        let blend = blend || sprite.is_some();
+        let (
+            program,
+            uniforms,
+            attrib_coords,
+            attrib_color,
+            attrib_scale,
+            uniform_point_size,
+            uniform_alpha,
+        ) = match sprite {
+            Some(sprite) => (
+                sprite.program(),
+                sprite.uniforms(),
+                sprite.attrib_coords(),
+                sprite.attrib_color(),
+                sprite.attrib_scale(),
+                sprite.uniform_point_size(),
+                Some(sprite.uniform_alpha()),
+            ),
+            None => (
+                &self.program,
+                &self.draw_uniforms,
+                self.attrib_coords,
+                self.attrib_color,
+                self.attrib_scale,
+                &self.uniform_point_size,
+                self.uniform_alpha.as_ref(),
+            ),
+        };
+        self.gl.use_program(Some(program));
+        dom_utils::set_view_uniforms(&self.gl, uniforms, &self.view, width, height);
+        if sprite.is_none() {
+            dom_utils::set_shape_uniforms(&self.gl, uniforms, self.shape);
+            dom_utils::set_edge_uniform(
+                &self.gl,
+                uniforms,
+                disk_size * self.pixel_ratio * self.view.scale(),
+            );
+        }
+        // 座標・色・大きさの比を1つのバッファにまとめ、1回で転送する
+        interleave_vertices(
+            &mut self.vertices,
+            &self.positions,
+            &self.disk_layers,
+            &self.colors,
+            &self.scales,
+        );
+        self.buffer_vertices.upload(&self.gl, &self.vertices);
+        self.perf_end(PerfPhase::BufferUpload);
+        self.perf_begin(PerfPhase::DrawCall);
+        // trail の描画で属性の割り当てが上書きされている可能性があるので毎回指定し直す
+        let stride = (VERTEX_FLOATS * std::mem::size_of::<f32>()) as i32;
+        let offset = |floats: usize| (floats * std::mem::size_of::<f32>()) as i32;
+        self.gl.vertex_attrib_pointer(
+            attrib_coords,
+            3,
+            WebGlRenderingContext::FLOAT,
+            false,
+            stride,
+            0,
+        );
+        self.gl.enable_vertex_attrib_array(attrib_coords);
+        self.gl.vertex_attrib_pointer(
+            attrib_color,
+            4,
+            WebGlRenderingContext::FLOAT,
+            false,
+            stride,
+            offset(dom_utils::VERTEX_COLOR_OFFSET),
+        );
+        self.gl.enable_vertex_attrib_array(attrib_color);
+
+        self.gl.uniform1f(uniform_alpha, alpha);
+        if sprite.is_none() {
+            self.gl
+                .uniform1f(self.uniform_glow.as_ref(), self.blend_mode.glow());
+        }
+
+        if let Some(sprite) = sprite {
+            sprite.begin(&self.gl);
+        }
+        if blend {
+            dom_utils::enable_blend(&self.gl, self.blend_mode);
+        }
+        // 層の深度で重なり順を決める。同じ層では後から描いたディスクが上になるよう LEQUAL で比べる
+        // ブレンドすると手前のディスクが後ろのディスクを透かさず隠してしまうので、層は使わない
+        let layered = !blend && !self.disk_layers.is_empty();
+        if layered {
+            self.gl.clear(WebGlRenderingContext::DEPTH_BUFFER_BIT);
+            self.gl.enable(WebGlRenderingContext::DEPTH_TEST);
+            self.gl.depth_func(WebGlRenderingContext::LEQUAL);
+        }
+        if let Some(attrib_scale) = attrib_scale {
+            // ディスクごとの大きさは a_scale で渡し、1回で描く
+            self.gl.vertex_attrib_pointer(
+                attrib_scale,
+                1,
+                WebGlRenderingContext::FLOAT,
+                false,
+                stride,
+                offset(dom_utils::VERTEX_SCALE_OFFSET),
+            );
+            self.gl.enable_vertex_attrib_array(attrib_scale);
+            self.gl.uniform1f(
+                Some(uniform_point_size),
+                (disk_size * self.pixel_ratio * self.view.scale()) as f32,
+            );
+            self.gl.draw_arrays(
+                WebGlRenderingContext::POINTS,
+                0,
+                (self.positions.len() / 2) as i32,
+            );
+        } else {
+            // a_scale の無いユーザー指定のシェーダでは、同じ大きさの範囲ごとに u_pointsize を変えて描く
+            for &(start, count, size) in size_runs(self.sim.disks(), disk_size).iter() {
+                self.gl.uniform1f(
+                    Some(uniform_point_size),
+                    (size * self.pixel_ratio * self.view.scale()) as f32,
+                );
+                self.gl
+                    .draw_arrays(WebGlRenderingContext::POINTS, start as i32, count as i32);
+            }
+        }
+        if layered {
+            self.gl.disable(WebGlRenderingContext::DEPTH_TEST);
+        }
+        if blend {
+            self.gl.disable(WebGlRenderingContext::BLEND);
+        }
+    }
+
+    /**
+     * canvas の論理サイズを変える (描画バッファ・viewport・各プログラムの u_width / u_height を更新する)
+     * はみ出したディスクは内側に戻す
+     */
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), JsValue> {
+        if self.released {
+            return Err(JsValue::from("the screen has been destroyed"));
+        }
+        self.sim
+            .resize(width as f64, height as f64)
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        self.record_input(Input::Resize { width, height });
+        // 描画バッファは作り直されて空になるので、残像の有無によらず次のフレームで塗り直す
+        self.needs_clear = true;
+        // 内側に戻したディスクを元の位置から補間しない
+        self.previous_positions.clear();
+        self.resize_gl(width, height)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    fn resize_gl(&self, width: u32, height: u32) -> Result<(), String> {
+        dom_utils::resize_canvas(&self.gl, width, height, self.pixel_ratio)?;
+        if self.auto_resize {
+            dom_utils::release_css_size(&self.gl);
+        }
+        dom_utils::set_size_uniforms(&self.gl, &self.program, width as f64, height as f64)?;
+        if let Some(circles) = self.circles.as_ref() {
+            circles.resize(&self.gl, width, height)?;
+        }
+        if let Some(sprite) = self.sprite.as_ref() {
+            sprite.resize(&self.gl, width, height)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * ディスクを url の画像で描くように切り替える ("points" モードのみ)
+     * 読み込みは非同期で、終わるまでは通常の円で描く。読み込みに失敗した場合はコンソールにエラーを出す
+     */
+    pub fn load_texture(&mut self, url: &str) -> Result<(), JsValue> {
+        self.set_texture(TextureSource::Url(url.to_string()))
+    }
+
+    /**
+     * ディスクを source の画像で描くように切り替える ("points" モードのみ)
+     */
+    fn set_texture(&mut self, source: TextureSource) -> Result<(), JsValue> {
+        if self.released {
+            return Err(JsValue::from("the screen has been destroyed"));
+        }
+        if self.circles.is_some() {
+            return Err(JsValue::from(
+                "textures are only supported in \"points\" render mode",
+            ));
+        }
+        let sprite = SpritePass::new(
+            &self.gl,
+            &source,
+            self.sim.width() as u32,
+            self.sim.height() as u32,
+        )
+        .map_err(|e| JsValue::from(e.as_str()))?;
+        if let Some(old) = self.sprite.replace(sprite) {
+            old.delete(&self.gl);
+        }
+        self.texture = Some(source);
+        Ok(())
+    }
+
+    /**
+     * スプライトをやめて通常の円で描く
+     */
+    fn clear_texture(&mut self) {
+        if let Some(sprite) = self.sprite.take() {
+            sprite.delete(&self.gl);
+        }
+        self.texture = None;
+    }
+
+    /**
+     * 以降のフレームの座標を max_frames まで記録する
+     * 必要な容量が byte_budget (既定 64MiB) を超える場合はエラーを返す
+     */
+    pub fn start_recording(
+        &mut self,
+        max_frames: u32,
+        byte_budget: Option<u32>,
+    ) -> Result<(), JsValue> {
+        let budget = byte_budget.map_or(recording::DEFAULT_BYTE_BUDGET, |b| b as usize);
+        let recorder = Recorder::new(self.sim.disks().len() as u32, max_frames, budget)
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        self.recorder = Some(recorder);
+        self.stopped_recording = None;
+        Ok(())
+    }
+
+    /**
+     * 記録を終了し、記録した座標 ({ disk_count, frames }) を返す (記録していなければ null)
+     * エラーで止まっていた場合は、止まるまでに記録した分を返す
+     */
+    pub fn stop_recording(&mut self) -> JsValue {
+        match self
+            .recorder
+            .take()
+            .map(Recorder::finish)
+            .or_else(|| self.stopped_recording.take())
+        {
+            Some(recording) => utils::to_js(&recording),
+            None => JsValue::NULL,
+        }
+    }
+
+    /**
+     * stop_recording で得た記録を再生する
+     * 再生中はシミュレーションを進めないので、衝突や重力の設定によらず同じ描画になる
+     * looping が false の場合は最後のフレームで止まる
+     */
+    pub fn play_recording(&mut self, data: JsValue, looping: bool) -> Result<(), JsValue> {
+        let recording: Recording = utils::from_js(&data)
+            .map_err(|e| JsValue::from(format!("invalid recording: {}", e).as_str()))?;
+        let playback = Playback::new(recording, looping).map_err(|e| JsValue::from(e.as_str()))?;
+        // 色はディスクごとに持っているので、ディスク数が一致している必要がある
+        if playback.disk_count() as usize != self.sim.disks().len() {
+            return Err(JsValue::from(
+                format!(
+                    "recording has {} disks but this screen has {}",
+                    playback.disk_count(),
+                    self.sim.disks().len()
+                )
+                .as_str(),
+            ));
+        }
+        self.playback = Some(playback);
+        Ok(())
+    }
+
+    /**
+     * 再生をやめてシミュレーションに戻る
+     */
+    pub fn stop_playback(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn set_disk_size(&mut self, disk_size: f64) -> Result<(), JsValue> {
+        self.sim
+            .set_disk_size(disk_size)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn set_speed_scale(&mut self, speed_scale: f64) -> Result<(), JsValue> {
+        self.sim
+            .set_speed_scale(speed_scale)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    fn set_time_scale(&mut self, scale: f64) -> Result<(), String> {
+        if !(scale.is_finite() && scale.abs() <= MAX_TIME_SCALE) {
+            return Err(format!(
+                "time scale must be in [-{max}, {max}]: {}",
+                scale,
+                max = MAX_TIME_SCALE
+            ));
+        }
+        self.record_input(Input::TimeScale { scale });
+        self.time_scale = scale;
+        Ok(())
+    }
+
+    pub fn set_bounds_padding(&mut self, padding: f64) -> Result<(), JsValue> {
+        self.sim
+            .set_bounds_padding(padding)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn set_max_speed(&mut self, max_speed: Option<f64>) -> Result<(), JsValue> {
+        self.sim
+            .set_max_speed(max_speed)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn set_drag(&mut self, drag: f64) -> Result<(), JsValue> {
+        self.sim
+            .set_drag(drag)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn set_jitter(&mut self, jitter: f64) -> Result<(), JsValue> {
+        self.sim
+            .set_jitter(jitter)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn set_force(&mut self, fx: f64, fy: f64) -> Result<(), JsValue> {
+        self.sim
+            .set_force(fx, fy)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn set_gravity(&mut self, gravity: f64) -> Result<(), JsValue> {
+        self.sim
+            .set_gravity(gravity)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 壁での挙動を切り替える ("bounce" | "wrap")
+     */
+    pub fn set_boundary_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        let boundary = Boundary::parse(mode).map_err(|e| JsValue::from(e.as_str()))?;
+        self.sim.set_boundary(boundary);
+        Ok(())
+    }
+
+    /**
+     * 動き方を切り替える ("ballistic" | "boids")。位置・速度はそのまま引き継ぐ
+     */
+    pub fn set_behavior(&mut self, behavior: &str) -> Result<(), JsValue> {
+        let behavior = Behavior::parse(behavior).map_err(|e| JsValue::from(e.as_str()))?;
+        self.sim.set_behavior(behavior);
+        Ok(())
+    }
+
+    pub fn set_boids(&mut self, params: BoidsParams) -> Result<(), JsValue> {
+        self.sim
+            .set_boids(params)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 領域の形を変える (円の外にいるディスクは縁の内側に寄せる)
+     */
+    pub fn set_arena(&mut self, arena: Arena) -> Result<(), JsValue> {
+        self.sim
+            .set_arena(arena)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 残像の強さを変更する (0.0 で毎フレーム全消去、1.0 で一切消さない)
+     * 前フレームを保持するため、init_gl で `trail` を指定して preserveDrawingBuffer 付きのコンテキストにしておく必要がある
+     */
+    pub fn set_trail(&mut self, factor: f64) {
+        self.trail = if factor.is_nan() {
+            0.
+        } else {
+            factor.clamp(0., 1.)
+        };
+        if self.trail > 0. && !self.preserve_drawing_buffer {
+            warn!(
+                "trail has no effect without preserveDrawingBuffer: pass `trail` (e.g. trail: 0) or context_attributes.preserveDrawingBuffer: true to init_gl"
+            );
+        }
+    }
+
+    /**
+     * 直近の描画で使った座標バッファ (x, y が交互に並ぶ f32) の先頭アドレス
+     * JS からは `new Float32Array(memory.buffer, screen.positions_ptr(), screen.positions_len())` でコピーせずに読める
+     * このビューは座標バッファの確保し直しや wasm のメモリの伸長で無効になるので、positions_generation が変わったら作り直すこと
+     */
+    pub fn positions_ptr(&self) -> *const f32 {
+        self.positions.as_ptr()
+    }
+
+    /**
+     * 座標バッファの要素数 (ディスク数 * 2)
+     */
+    pub fn positions_len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /**
+     * 座標バッファが別の場所へ移ったか、要素数が変わったか、wasm のメモリが伸びていれば番号を増やして返す
+     * 前回のビューを作ったときと番号が違えば、そのビューは使えない
+     * (確保し直さずにディスクが減った場合も、長さの違うビューで消えたディスクを読ませないよう番号を増やす)
+     */
+    pub fn positions_generation(&mut self) -> u32 {
+        let location = (
+            self.positions.as_ptr() as usize,
+            self.positions.len(),
+            utils::memory_pages(),
+        );
+        if location != self.positions_location {
+            self.positions_location = location;
+            self.positions_generation = self.positions_generation.wrapping_add(1);
+        }
+        self.positions_generation
+    }
+
+    /**
+     * 点の描画用のシェーダを差し替える (None の方は組み込みのもの)
+     * 新しいプログラムのリンクと検証が済んでから入れ替えるので、失敗したら今のプログラムのまま。ディスクの状態は変えない
+     * attach_view で足したビューも同じシェーダに変える (どれかで失敗したら、すべて元のシェーダに戻す)
+     */
+    fn set_shader_sources(&mut self, sources: dom_utils::ShaderSources) -> Result<(), String> {
+        if self.released || self.context_lost {
+            return Err(String::from(
+                "cannot change shaders: the WebGL context is not available",
+            ));
+        }
+        let (width, height) = (self.sim.width(), self.sim.height());
+        let points = create_point_program(&self.gl, &sources, width, height)?;
+        for index in 0..self.views.len() {
+            if let Err(e) = self.views[index].1.set_shader_sources(&sources) {
+                for (_, view) in self.views[..index].iter_mut() {
+                    if let Err(e) = view.set_shader_sources(&self.shader_sources) {
+                        error!("failed to restore the shaders of a view: {}", e);
+                    }
+                }
+                dom_utils::release_program(&self.gl, &points.program);
+                // 入れ替えなかったので、今のプログラムを使い直す
+                self.gl.use_program(Some(&self.program));
+                return Err(e);
+            }
+        }
+        dom_utils::release_program(&self.gl, &self.program);
+        self.program = points.program;
+        self.attrib_coords = points.attrib_coords;
+        self.attrib_color = points.attrib_color;
+        self.attrib_scale = points.attrib_scale;
+        self.uniform_point_size = points.uniform_point_size;
+        self.uniform_alpha = points.uniform_alpha;
+        self.uniform_glow = points.uniform_glow;
+        self.draw_uniforms = points.uniforms;
+        // reset / reset_with で同じシェーダを使い続けるよう、オプションにも反映する
+        self.options.vertex_shader = sources.vertex.clone();
+        self.options.fragment_shader = sources.fragment.clone();
+        self.shader_sources = sources;
+        debug!("point shaders replaced");
+        Ok(())
+    }
+
+    /**
+     * このコンテキストで使う組み込みのフラグメントシェーダ (antialias の有無・WebGL のバージョンに合わせたもの)
+     */
+    pub fn default_fragment_shader(&self) -> String {
+        self.gl
+            .shader_sources(self.shader_sources.antialias)
+            .1
+            .to_string()
+    }
+
+    /**
+     * 選択されたレンダリングバックエンド ("webgl2" | "webgl")
+     */
+    pub fn backend(&self) -> String {
+        self.gl.backend().to_string()
+    }
+
+    pub fn max_point_size(&self) -> f64 {
+        self.gl.max_point_size()
+    }
+
+    /**
+     * 現在の状態を描き直す (キャプチャ用)
+     * preserveDrawingBuffer なしでは合成後に描画バッファが消えるので、読み出す直前に同期的に描く
+     * 保持している場合は、一度も描いていないか resize で消えたときだけ描く
+     */
+    fn redraw_for_capture(&mut self) -> Result<(), JsValue> {
+        if self.released || self.context_lost {
+            return Err(JsValue::from(
+                "cannot capture: the WebGL context is not available",
+            ));
+        }
+        if !self.preserve_drawing_buffer || self.needs_clear {
+            self.draw();
+        }
+        Ok(())
+    }
+
+    /**
+     * 現在の状態を描き直し、canvas の内容を PNG の data URL にする
+     */
+    pub fn capture_png(&mut self) -> Result<String, JsValue> {
+        self.redraw_for_capture()?;
+        let canvas = self.gl.html_canvas().ok_or_else(|| {
+            JsValue::from(
+                "capture_png requires an HTML canvas; use capture_rgba with OffscreenCanvas",
+            )
+        })?;
+        canvas.to_data_url()
+    }
+
+    /**
+     * 現在の状態を描き直し、描画バッファの RGBA を上の行から順に返す
+     */
+    pub fn capture_rgba(&mut self) -> Result<js_sys::Uint8Array, JsValue> {
+        self.redraw_for_capture()?;
+        let pixels = self.gl.read_pixels_rgba()?;
+        Ok(js_sys::Uint8Array::from(pixels.as_slice()))
+    }
+}
+
+impl Scene {
+    /**
+     * このシーンが作ったバッファ・シェーダ・プログラム・テクスチャを削除する
+     * 2回目以降の呼び出しは何もしない
+     */
+    fn release(&mut self) {
+        if self.released {
+            return;
+        }
+        self.released = true;
+        let gl = &self.gl;
+        dom_utils::release_program(gl, &self.program);
+        self.buffer_vertices.delete(gl);
+        self.trail_pass.delete(gl);
+        if let Some(circles) = self.circles.take() {
+            circles.delete(gl);
+        }
+        // 画像の読み込みを待っているクロージャもここで解放される
+        if let Some(sprite) = self.sprite.take() {
+            sprite.delete(gl);
+        }
+        if let Some(overlay) = self.overlay.take() {
+            overlay.delete(gl);
+        }
+        self.overlay_on = false;
+        if let Some(velocity) = self.velocity.take() {
+            velocity.delete(gl);
+        }
+        self.velocity_on = false;
+        if let Some(markers) = self.markers.take() {
+            markers.delete(gl);
+        }
+        self.markers_on = false;
+        if let Some(outline) = self.outline.take() {
+            outline.delete(gl);
+        }
+        self.outline_on = false;
+        if let Some(outline) = self.obstacle_outline.take() {
+            outline.delete(gl);
+        }
+        self.obstacles_on = false;
+        self.context_listeners.clear();
+        self.resize_watcher = None;
+        for (_, view) in self.views.drain(..) {
+            view.delete();
+        }
+    }
+
+    pub fn frame_stats(&self) -> stats::FrameStats {
+        self.timer.snapshot(self.sim.disks())
+    }
+
+    /**
+     * 復元したコンテキストで init_gl と同じGLリソースを作り直す
+     * 消失したコンテキストのオブジェクトは既に無効なので削除しない。ディスクの状態はそのまま残る
+     */
+    fn restore_context(&mut self) -> Result<(), String> {
+        if self.released {
+            return Ok(());
+        }
+        let (width, height) = (self.sim.width(), self.sim.height());
+        dom_utils::forget_lost_programs(&self.gl);
+        let points = create_point_program(&self.gl, &self.shader_sources, width, height)?;
+        self.program = points.program;
+        self.attrib_coords = points.attrib_coords;
+        self.attrib_color = points.attrib_color;
+        self.attrib_scale = points.attrib_scale;
+        self.uniform_point_size = points.uniform_point_size;
+        self.uniform_alpha = points.uniform_alpha;
+        self.uniform_glow = points.uniform_glow;
+        self.draw_uniforms = points.uniforms;
+        self.buffer_vertices = DynamicBuffer::new(&self.gl)?;
+        self.trail_pass = TrailPass::new(&self.gl)?;
+        if let Some(instanced) = self.circles.as_ref().map(CirclePass::instanced) {
+            self.circles = Some(CirclePass::new(
+                &self.gl,
+                width as u32,
+                height as u32,
+                self.antialias,
+                instanced,
+            )?);
+        }
+        // 画像は読み込み直す。パス類は次に描くときに作り直す
+        self.sprite = None;
+        if let Some(source) = self.texture.clone() {
+            if let Err(e) = self.set_texture(source) {
+                error!("failed to reload texture: {:?}", e);
+            }
+        }
+        self.overlay = None;
+        if self.overlay_on {
+            self.overlay_enabled(true);
+        }
+        self.velocity = None;
+        if self.velocity_on {
+            self.set_debug_overlay(true);
+        }
+        self.markers = None;
+        if self.markers_on {
+            self.set_show_attractors(true);
+        }
+        self.outline = None;
+        if self.outline_on {
+            self.set_show_arena(true);
+        }
+        self.obstacle_outline = None;
+        if self.obstacles_on {
+            self.set_show_obstacles(true);
+        }
+        self.colors_dirty = true;
+        self.needs_clear = true;
+        self.fixed_step.reset();
+        self.context_lost = false;
+        Ok(())
+    }
+
+    /**
+     * ディスクが増えた分だけランダムな色を足し、色を塗り直す
+     */
+    fn colors_changed(&mut self) {
+        let color_len = self.sim.disks().len() * 3;
+        if self.random_colors.len() < color_len {
+            let missing = (color_len - self.random_colors.len()) / 3;
+            self.random_colors
+                .extend(self.palette.colors(missing as u32, &mut self.rng));
+        }
+        self.random_colors.truncate(color_len);
+        self.disk_alphas.truncate(self.sim.disks().len());
+        self.disk_layers.truncate(self.sim.disks().len());
+        self.disk_hues.sync(self.sim.disks().len());
+        if let Some(flash) = self.flash.as_mut() {
+            flash.truncate(self.sim.disks().len());
+        }
+        self.fill_base_colors();
+        // 数が変わった直後は前のステップの座標から補間しない
+        self.previous_positions.clear();
+    }
+
+    /**
+     * パレットを変え、今あるディスクの色もそのパレットから選び直す
+     */
+    pub fn set_palette(&mut self, palette: &str) -> Result<(), JsValue> {
+        self.palette = Palette::parse(palette).map_err(|e| JsValue::from(e.as_str()))?;
+        self.random_colors = self
+            .palette
+            .colors(self.sim.disks().len() as u32, &mut self.rng);
+        self.fill_base_colors();
+        Ok(())
+    }
+
+    /**
+     * "random" モードで使うディスクごとの色を置き換え、"random" モードにする
+     */
+    fn set_colors(&mut self, colors: Vec<f32>) -> Result<(), String> {
+        color::validate_colors(&colors, self.sim.disks().len())?;
+        self.random_colors = colors;
+        self.color_mode = ColorMode::Random;
+        self.fill_base_colors();
+        Ok(())
+    }
+
+    fn set_disk_color(
+        &mut self,
+        index: usize,
+        rgb: [f32; 3],
+        alpha: Option<f32>,
+    ) -> Result<(), String> {
+        let disk_count = self.sim.disks().len();
+        if index >= disk_count {
+            return Err(format!(
+                "disk index {} is out of range ({} disks)",
+                index, disk_count
+            ));
+        }
+        color::validate_colors(&rgb, 1)?;
+        if let Some(alpha) = alpha {
+            if !(0. ..=1.).contains(&alpha) {
+                return Err(format!("alpha must be in [0, 1]: {}", alpha));
+            }
+            self.disk_alphas.resize(disk_count, self.disk_alpha);
+            self.disk_alphas[index] = alpha;
+        }
+        self.random_colors[index * 3..index * 3 + 3].copy_from_slice(&rgb);
+        self.color_mode = ColorMode::Random;
+        self.fill_base_colors();
+        Ok(())
+    }
+
+    fn set_disk_layer(&mut self, index: usize, layer: u8) -> Result<(), String> {
+        let disk_count = self.sim.disks().len();
+        if index >= disk_count {
+            return Err(format!(
+                "disk index {} is out of range ({} disks)",
+                index, disk_count
+            ));
+        }
+        if index >= self.disk_layers.len() {
+            if layer == 0 {
+                return Ok(());
+            }
+            self.disk_layers.resize(index + 1, 0);
+        }
+        self.disk_layers[index] = layer;
+        // 全て層 0 に戻したら深度テストを使わない描き方に戻す
+        if self.disk_layers.iter().all(|&layer| layer == 0) {
+            self.disk_layers.clear();
+        }
+        Ok(())
+    }
+
+    fn add_disk(&mut self, x: f64, y: f64, vx: f64, vy: f64) {
+        self.record_input(Input::AddDisk { x, y, vx, vy });
+        self.sim.add_disk(x, y, vx, vy);
+        self.colors_changed();
+    }
+
+    /**
+     * group 番目のグループと同じ大きさ・質量・色のディスクを (x, y) に足す
+     */
+    fn add_disk_to_group(
+        &mut self,
+        group: usize,
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+    ) -> Result<(), String> {
+        let spec = self.groups.get(group).cloned().ok_or_else(|| {
+            format!(
+                "group {} is out of range ({} groups)",
+                group,
+                self.groups.len()
+            )
+        })?;
+        self.record_input(Input::AddDiskToGroup {
+            group,
+            x,
+            y,
+            vx,
+            vy,
+        });
+        let mut disk = Disk::new(x, y, vx, vy);
+        let disk_size = self.sim.disk_size();
+        spec.apply(
+            &mut disk,
+            group as u8,
+            disk_size,
+            self.mass_from_radius,
+            &mut self.rng,
+        );
+        self.sim.push_disk(disk);
+        self.colors_changed();
+        if let Some(rgb) = spec.colors(1, &mut self.rng) {
+            let index = self.sim.disks().len() - 1;
+            self.random_colors[index * 3..index * 3 + 3].copy_from_slice(&rgb);
+            self.fill_base_colors();
+        }
+        Ok(())
+    }
+
+    /**
+     * groups ごとのディスクの数 (groups を指定しなければ全ディスクの数が1つ)
+     */
+    fn group_counts(&self) -> Vec<u32> {
+        let mut counts = vec![0; self.groups.len()];
+        for disk in self.sim.disks() {
+            if let Some(count) = counts.get_mut(disk.kind as usize) {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
+    fn add_random_disks(&mut self, count: u32) {
+        self.record_input(Input::AddRandomDisks { count });
+        self.sim
+            .add_random_disks(count, self.velocities, &mut self.rng);
+        self.colors_changed();
+    }
+
+    /**
+     * 位置はそのままに、全ディスクの速度を初期化時と同じ決め方で選び直す
+     */
+    fn randomize_velocities(&mut self) {
+        self.record_input(Input::RandomizeVelocities);
+        self.sim
+            .randomize_velocities(self.velocities, &mut self.rng);
+        self.colors_dirty = true;
+    }
+
+    fn scale_velocities(&mut self, factor: f64) -> Result<(), JsValue> {
+        self.sim
+            .scale_velocities(factor)
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        self.record_input(Input::ScaleVelocities { factor });
+        self.colors_dirty = true;
+        Ok(())
+    }
+
+    /**
+     * (x, y) にランダムな向き・色のディスクを1つ足す (speed を省略すると初期化時と同じ範囲から選ぶ)
+     */
+    fn spawn_at(&mut self, x: f64, y: f64, speed: Option<f64>) {
+        self.record_input(Input::Spawn { x, y, speed });
+        self.spawn_disk(x, y, speed);
+        self.colors_changed();
+    }
+
+    // 速さを省略したら speed_range から選ぶ (色は colors_changed で足す)
+    fn spawn_disk(&mut self, x: f64, y: f64, speed: Option<f64>) {
+        let [low, high] = self.velocities.speed_range();
+        let speed = match speed {
+            Some(speed) if speed.is_finite() => speed,
+            _ if low < high => self.rng.gen_range(low, high),
+            _ => low,
+        };
+        self.sim.spawn_at(x, y, speed, &mut self.rng);
+    }
+
+    /**
+     * emitter モードの1ステップ分: 寿命を過ぎたディスクを消し、dt_ms の間に出す分を origin から出す
+     * 消すときは最後のディスクを空いた番号へ移すので、ディスク・色・経過時間を詰め直さない
+     */
+    fn emit(&mut self, mut emitter: Emitter, dt_ms: f64) -> Emitter {
+        emitter.sync(self.sim.disks().len());
+        emitter.age(dt_ms);
+        let mut changed = false;
+        while let Some(index) = emitter.next_expired() {
+            self.sim.swap_remove_disk(index);
+            emitter.swap_remove(index);
+            if self.random_colors.len() >= index * 3 + 3 {
+                let last = self.random_colors.len() - 3;
+                self.random_colors.copy_within(last.., index * 3);
+                self.random_colors.truncate(last);
+            }
+            if index < self.disk_alphas.len() {
+                self.disk_alphas.swap_remove(index);
+            }
+            self.disk_hues.swap_remove(index);
+            if index < self.disk_layers.len() {
+                // 末尾のディスクが index に移るので、層を持っていない分も埋めてから入れ替える
+                self.disk_layers.resize(self.sim.disks().len() + 1, 0);
+                self.disk_layers.swap_remove(index);
+            }
+            if let Some(flash) = self.flash.as_mut() {
+                flash.swap_remove(index, self.sim.disks().len() + 1);
+            }
+            self.tracker.swap_remove(index, self.sim.disks().len() + 1);
+            changed = true;
+        }
+        let (x, y) = emitter.origin();
+        for _ in 0..emitter.spawn_count(dt_ms) {
+            self.spawn_disk(x, y, None);
+            emitter.push();
+            changed = true;
+        }
+        if changed {
+            // 出したディスクの色はここで足す
+            self.colors_changed();
+        }
+        emitter
+    }
+
+    fn remove_disk(&mut self, index: usize) -> bool {
+        self.record_input(Input::RemoveDisk { index });
+        if !self.sim.remove_disk(index) {
+            return false;
+        }
+        self.random_colors.drain(index * 3..index * 3 + 3);
+        if index < self.disk_alphas.len() {
+            self.disk_alphas.remove(index);
+        }
+        if index < self.disk_layers.len() {
+            self.disk_layers.remove(index);
+        }
+        self.disk_hues.remove(index);
+        if let Some(flash) = self.flash.as_mut() {
+            flash.remove(index);
+        }
+        if let Some(emitter) = self.emitter.as_mut() {
+            emitter.remove(index);
+        }
+        self.tracker.remove(index);
+        self.colors_changed();
+        true
+    }
+
+    /**
+     * 先頭から len 個だけ残し、後ろのディスクとその色を取り除く
+     */
+    fn truncate_disks(&mut self, len: usize) {
+        self.record_input(Input::TruncateDisks { len });
+        self.sim.truncate_disks(len);
+        if let Some(emitter) = self.emitter.as_mut() {
+            emitter.sync(len.min(emitter.ages().len()));
+        }
+        self.tracker.truncate(len);
+        self.colors_changed();
+    }
+
+    /**
+     * ディスクの数を count にする (増やす分は add_random_disks と同じく足し、減らす分は後ろから取り除く)
+     */
+    fn set_disk_num(&mut self, count: u32) -> Result<(), String> {
+        if count == 0 {
+            return Err(String::from("disk_num must be at least 1"));
+        }
+        let current = self.sim.disk_count();
+        if count > current {
+            self.add_random_disks(count - current);
+        } else if count < current {
+            self.truncate_disks(count as usize);
+        }
+        Ok(())
+    }
+
+    /**
+     * ディスクの状態を置き換える。数が変わった場合はランダムな色を足すか切り詰める
+     */
+    fn restore(&mut self, state: &SimulationState) -> Result<(), JsValue> {
+        self.sim
+            .restore(state)
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        self.tracker.replace();
+        self.colors_changed();
+        Ok(())
+    }
+
+    fn export_state(&self) -> String {
+        let saved = state::SavedState::new(&self.sim, &self.random_colors);
+        serde_json::to_string(&saved).unwrap_or_default()
+    }
+
+    /**
+     * export_state の JSON で大きさ・設定・ディスク・色を置き換える
+     * 先にすべて確かめるので、不正な JSON では何も変えない
+     */
+    fn import_state(&mut self, json: &str) -> Result<(), String> {
+        let saved: state::SavedState =
+            serde_json::from_str(json).map_err(|e| format!("invalid state: {}", e))?;
+        self.load_state(saved)
+    }
+
+    fn load_state(&mut self, saved: state::SavedState) -> Result<(), String> {
+        saved.validate()?;
+        if saved.width != self.sim.width() || saved.height != self.sim.height() {
+            self.sim.resize(saved.width, saved.height)?;
+            self.needs_clear = true;
+            self.resize_gl(saved.width as u32, saved.height as u32)?;
+        }
+        saved.apply_to(&mut self.sim)?;
+        self.random_colors = saved.colors;
+        self.tracker.replace();
+        self.colors_changed();
+        Ok(())
+    }
+
+    /**
+     * options でディスク・色・シミュレーションの設定を作り直す (プログラム・バッファ・uniform は使い回す)
+     * 一時停止・引力点・力・ポインタ・記録と再生・時間の倍率・視点も初期状態に戻す
+     * 先にすべて確かめるので、エラーの場合は何も変えない
+     */
+    fn reset(&mut self, options: Options) -> Result<(), String> {
+        if self.released {
+            return Err(String::from("the screen has been destroyed"));
+        }
+        options.validate()?;
+        self.options.check_reset(&options)?;
+        let background = match options.background.as_ref() {
+            Some(background) => background.to_rgba()?,
+            None => BACKGROUND,
+        };
+        if background[3] < 1. && !self.transparent {
+            return Err(String::from(
+                "the canvas is opaque; a translucent `background` needs a new screen",
+            ));
+        }
+        let blend_mode = BlendMode::parse(options.blend_mode.as_deref().unwrap_or("normal"))?;
+        let shape = Shape::parse(
+            options.shape.as_deref().unwrap_or("circle"),
+            options.ring_thickness,
+        )?;
+        let log_level = options
+            .debug
+            .as_deref()
+            .map(logger::parse_level)
+            .transpose()?;
+        // auto_resize 中は CSS で決まった今の大きさのまま作り直す
+        let (width, height) = if self.auto_resize {
+            (self.sim.width() as u32, self.sim.height() as u32)
+        } else {
+            (options.width.unwrap_or(500), options.height.unwrap_or(500))
+        };
+        let world = World::build(&options, width, height)?;
+        let adaptive = options.adaptive_quality(world.sim.disk_count())?;
+        let flash = options.bounce_flash()?;
+        if width as f64 != self.sim.width() || height as f64 != self.sim.height() {
+            self.resize_gl(width, height)?;
+        }
+
+        self.sim = world.sim;
+        self.rng = world.rng;
+        self.random_colors = world.random_colors;
+        self.groups = world.groups;
+        self.mass_from_radius = world.mass_from_radius;
+        self.velocities = world.velocities;
+        self.palette = world.palette;
+        self.color_mode = world.color_mode;
+        self.static_palette = world.static_palette;
+        self.min_velocity = world.min_velocity;
+        self.max_velocity = world.max_velocity;
+        self.emitter = world.emitter;
+        self.cycle_speed = options.cycle_speed.unwrap_or(DEFAULT_CYCLE_SPEED);
+        self.cycle_phase = 0.;
+        self.disk_alpha = options.disk_alpha.unwrap_or(1.) as f32;
+        self.disk_alphas.clear();
+        self.disk_layers.clear();
+        self.disk_hues.clear();
+        self.flash = flash;
+        self.tracker.replace();
+        self.velocity_scale = options.velocity_scale.unwrap_or(10.);
+        self.background = background;
+        self.blend_mode = blend_mode;
+        self.shape = shape;
+        self.opacity = options.opacity.unwrap_or(1.);
+        self.view = View::default();
+        self.set_trail(options.trail.unwrap_or(0.));
+        // 前の実行の残像や座標を残さない
+        self.needs_clear = true;
+        self.previous_positions.clear();
+        self.interpolation = None;
+        self.paused = false;
+        self.queued_impulses.clear();
+        self.fixed_step.reset();
+        self.frame_limiter = FrameLimiter::new(options.target_fps.unwrap_or(0));
+        self.adaptive = adaptive;
+        self.recorder = None;
+        self.stopped_recording = None;
+        self.playback = None;
+        self.tick = 0;
+        self.input_recorder = None;
+        self.replay = None;
+        self.time_scale = 1.;
+        self.pending_events.clear();
+        self.pending_events.set_max(
+            options
+                .max_events_per_frame
+                .unwrap_or(DEFAULT_MAX_EVENTS_PER_FRAME),
+        );
+        self.collect_stats = options.collect_stats.unwrap_or(true);
+        self.perf_marks = perf_marks(&options);
+        self.timer.reset();
+        if let Some(level) = log_level {
+            logger::set_level(level);
+            self.log_level = level;
+        }
+        self.fill_base_colors();
+        self.apply_event_mask();
+        self.set_show_attractors(options.show_attractors.unwrap_or(false));
+        self.set_debug_overlay(options.show_velocity.unwrap_or(false));
+        self.set_show_arena(options.show_arena.unwrap_or(false));
+        self.set_show_obstacles(options.show_obstacles.unwrap_or(false));
+        self.options = options;
+        Ok(())
+    }
+
+    /**
+     * 今の設定をオプションの形で返す (省略したキーは既定値で埋め、setter で変えた値も反映する)
+     */
+    fn current_options(&self) -> Options {
+        let mut options = self.options.with_defaults();
+        let sim = &self.sim;
+        options.disk_num = Some(sim.disk_count());
+        options.width = Some(sim.width() as u32);
+        options.height = Some(sim.height() as u32);
+        options.disk_size = Some(sim.disk_size());
+        options.collision = Some(sim.collision());
+        options.on_collision = Some(sim.collision_response().name().into());
+        options.color_mode = Some(self.color_mode.name().into());
+        if let Some(name) = self.palette.name() {
+            options.palette = Some(name.into());
+            options.palette_colors = None;
+        }
+        options.min_velocity = Some(self.min_velocity);
+        options.max_velocity = Some(self.max_velocity);
+        options.cycle_speed = Some(self.cycle_speed);
+        options.background = Some(ColorSpec::Rgba(self.background));
+        options.boundary = Some(sim.boundary().name().into());
+        options.behavior = Some(sim.behavior().name().into());
+        options.boids = Some(sim.boids());
+        let (arena, center, radius) = match sim.arena() {
+            Arena::Rect => ("rect", None, None),
+            Arena::Circle { center, radius } => ("circle", center, radius),
+        };
+        options.arena = Some(arena.into());
+        options.arena_center = center;
+        options.arena_radius = radius;
+        options.show_arena = Some(self.outline_on);
+        options.show_obstacles = Some(self.obstacles_on);
+        options.show_attractors = Some(self.markers_on);
+        options.show_velocity = Some(self.velocity_on);
+        options.velocity_scale = Some(self.velocity_scale);
+        options.opacity = Some(self.opacity);
+        options.bounce_flash = Some(self.flash.as_ref().map_or(0., BounceFlash::duration));
+        options.blend_mode = Some(self.blend_mode.name().into());
+        let (shape, thickness) = self.shape.name();
+        options.shape = Some(shape.into());
+        options.ring_thickness = thickness;
+        if self.preserve_drawing_buffer {
+            options.trail = Some(self.trail);
+        }
+        options.target_fps = Some(self.frame_limiter.target_fps());
+        options.max_events_per_frame = Some(self.pending_events.max());
+        options.collect_stats = Some(self.collect_stats);
+        options.perf_marks = Some(self.perf_marks.is_some());
+        options.gravity = Some(sim.gravity());
+        options.force = Some(sim.force());
+        options.drag = Some(sim.drag());
+        options.jitter = Some(sim.jitter());
+        options.wall_restitution = Some(sim.wall_restitution());
+        options.collision_restitution = Some(sim.collision_restitution());
+        options.rest_speed = sim.rest_speed();
+        options.pointer_force = Some(sim.pointer_force());
+        options.pointer_radius = Some(sim.pointer_radius());
+        options.strict = Some(sim.strict());
+        options.check_invariants = Some(sim.check_invariants());
+        // max_speed は初期速度の上限も兼ねるので、speed_range と組み合わせられない場合は元の値のまま
+        if let Some(max_speed) = sim.max_speed() {
+            if options.speed_range.is_none() && max_speed >= options.speed_range()[0] {
+                options.max_speed = Some(max_speed);
+            }
+        }
+        options
+    }
+
+    /**
+     * partial に書いたキーだけを今の設定から変える (ディスクは作り直さず、位置・速度はそのまま)
+     * 先にすべて確かめるので、エラーの場合は何も変えない
+     */
+    fn update_options(&mut self, partial: &serde_json::Value) -> Result<(), String> {
+        if self.released {
+            return Err(String::from("the screen has been destroyed"));
+        }
+        let current = self.current_options();
+        let next = current.merged(partial)?;
+        let changed = current.check_update(&next)?;
+        let options = self.options.merged(&next.subset(&changed)?)?;
+        let changed = |key: &str| changed.iter().any(|name| name == key);
+
+        let background = match next.background.as_ref() {
+            Some(background) => background.to_rgba()?,
+            None => BACKGROUND,
+        };
+        if background[3] < 1. && !self.transparent {
+            return Err(String::from(
+                "the canvas is opaque; a translucent `background` needs a new screen",
+            ));
+        }
+        let (width, height) = (next.width.unwrap_or(500), next.height.unwrap_or(500));
+        let resized = changed("width") || changed("height");
+        if resized && self.auto_resize {
+            return Err(String::from(
+                "width and height follow the canvas while `auto_resize` is on",
+            ));
+        }
+        let disk_num = next.disk_num.unwrap_or(100);
+        if disk_num == 0 {
+            return Err(String::from("disk_num must be at least 1"));
+        }
+        let color_mode = ColorMode::parse(next.color_mode.as_deref().unwrap_or("random"))?;
+        let palette = next.palette()?;
+        let blend_mode = BlendMode::parse(next.blend_mode.as_deref().unwrap_or("normal"))?;
+        let shape = Shape::parse(
+            next.shape.as_deref().unwrap_or("circle"),
+            next.ring_thickness,
+        )?;
+        let boundary = Boundary::parse(next.boundary.as_deref().unwrap_or("bounce"))?;
+        let behavior = Behavior::parse(next.behavior.as_deref().unwrap_or("ballistic"))?;
+        let arena = next.arena()?;
+        let response = CollisionResponse::parse(next.on_collision.as_deref().unwrap_or("bounce"))?;
+        let velocities = next.velocities()?;
+        let flash = next.bounce_flash()?;
+        let log_level = next.debug.as_deref().map(logger::parse_level).transpose()?;
+
+        // ここから先は確かめた値を反映するだけ (sim の setter も validate 済みの値では失敗しない)
+        if resized {
+            // GL 側は失敗しうるので先に変え、成功してからシミュレーションと記録を変える
+            self.resize_gl(width, height)?;
+            if let Err(e) = self.sim.resize(width as f64, height as f64) {
+                let _ = self.resize_gl(self.sim.width() as u32, self.sim.height() as u32);
+                return Err(e);
+            }
+            self.record_input(Input::Resize { width, height });
+            self.needs_clear = true;
+            self.previous_positions.clear();
+        }
+        if changed("disk_num") {
+            self.set_disk_num(disk_num)?;
+        }
+        if changed("disk_size") {
+            self.sim.set_disk_size(next.disk_size.unwrap_or(32.))?;
+        }
+        if changed("collision") {
+            self.sim.set_collision(next.collision.unwrap_or(false));
+        }
+        if changed("on_collision") {
+            self.sim.set_collision_response(response);
+        }
+        if changed("min_disks") || changed("max_radius") {
+            self.sim
+                .set_merge_limits(next.min_disks.unwrap_or(1), next.max_radius)?;
+        }
+        if changed("boundary") {
+            self.sim.set_boundary(boundary);
+        }
+        if changed("behavior") {
+            self.sim.set_behavior(behavior);
+        }
+        if changed("boids") {
+            self.sim.set_boids(next.boids.unwrap_or_default())?;
+        }
+        if changed("arena") || changed("arena_center") || changed("arena_radius") {
+            self.sim.set_arena(arena)?;
+        }
+        if changed("gravity") {
+            self.sim.set_gravity(next.gravity.unwrap_or(0.))?;
+        }
+        if changed("force") {
+            let (fx, fy) = next.force.unwrap_or((0., 0.));
+            self.sim.set_force(fx, fy)?;
+        }
+        if changed("drag") {
+            self.sim.set_drag(next.drag.unwrap_or(0.))?;
+        }
+        if changed("jitter") {
+            self.sim.set_jitter(next.jitter.unwrap_or(0.))?;
+        }
+        if changed("wall_restitution") {
+            self.sim
+                .set_wall_restitution(next.wall_restitution.unwrap_or(1.))?;
+        }
+        if changed("collision_restitution") {
+            self.sim
+                .set_collision_restitution(next.collision_restitution.unwrap_or(1.))?;
+        }
+        if changed("rest_speed") {
+            self.sim.set_rest_speed(next.rest_speed)?;
+        }
+        if changed("pointer_force") || changed("pointer_radius") {
+            self.sim.set_pointer_force(
+                next.pointer_force.unwrap_or(DEFAULT_POINTER_FORCE),
+                next.pointer_radius.unwrap_or(DEFAULT_POINTER_RADIUS),
+            )?;
+        }
+        if changed("strict") {
+            self.sim.set_strict(next.strict.unwrap_or(false));
+        }
+        if changed("check_invariants") {
+            self.sim
+                .set_check_invariants(next.check_invariants.unwrap_or(false));
+        }
+        if changed("max_speed") {
+            self.sim.set_max_speed(next.max_speed)?;
+        }
+        // 後から足すディスクの初速だけが変わる (今のディスクの速度はそのまま)
+        self.velocities = velocities;
+
+        self.color_mode = color_mode;
+        self.static_palette = next.static_palette.clone().unwrap_or_default();
+        if changed("palette") || changed("palette_colors") {
+            self.random_colors = palette.colors(self.sim.disks().len() as u32, &mut self.rng);
+            self.palette = palette;
+        }
+        self.min_velocity = next.min_velocity.unwrap_or(0.);
+        self.max_velocity = next.max_velocity.unwrap_or(DEFAULT_MAX_VELOCITY);
+        self.cycle_speed = next.cycle_speed.unwrap_or(DEFAULT_CYCLE_SPEED);
+        self.background = background;
+        self.blend_mode = blend_mode;
+        self.shape = shape;
+        self.opacity = next.opacity.unwrap_or(1.);
+        self.velocity_scale = next.velocity_scale.unwrap_or(10.);
+        if changed("trail") {
+            self.set_trail(next.trail.unwrap_or(0.));
+        }
+        if changed("bounce_flash") || changed("flash_color") {
+            self.flash = flash;
+            self.apply_event_mask();
+            self.fill_base_colors();
+        }
+        if changed("target_fps") {
+            self.frame_limiter = FrameLimiter::new(next.target_fps.unwrap_or(0));
+        }
+        self.pending_events.set_max(
+            next.max_events_per_frame
+                .unwrap_or(DEFAULT_MAX_EVENTS_PER_FRAME),
+        );
+        self.collect_stats = next.collect_stats.unwrap_or(true);
+        if changed("perf_marks") {
+            self.perf_marks = perf_marks(&next);
+        }
+        if let (true, Some(level)) = (changed("debug"), log_level) {
+            logger::set_level(level);
+            self.log_level = level;
+        }
+        if ["color_mode", "static_palette", "palette", "palette_colors"]
+            .iter()
+            .any(|key| changed(key))
+        {
+            self.fill_base_colors();
+        }
+        self.set_show_attractors(next.show_attractors.unwrap_or(false));
+        self.set_debug_overlay(next.show_velocity.unwrap_or(false));
+        self.set_show_arena(next.show_arena.unwrap_or(false));
+        self.set_show_obstacles(next.show_obstacles.unwrap_or(false));
+        self.options = options;
+        Ok(())
+    }
+
+    /**
+     * rAF ループから呼ばれるフレーム処理 (do_frame(timestamp) と同じく固定刻みで進める)
+     * タブが非表示だった後などの長い間隔は MAX_DT_MS で頭打ちになる
+     */
+    fn animation_frame(&mut self, timestamp: f64) {
+        if self.collect_stats {
+            self.timer.record_callback(timestamp);
+        }
+        if !self.frame_limiter.ready(timestamp) {
+            return;
+        }
+        self.do_frame_at(timestamp);
+    }
+}
+
+// 衝突のたびに canvas へ送る CustomEvent の名前 (detail は {type, indices, x, y, speed})
+const COLLISION_EVENT: &str = "disk-collision";
+
+/**
+ * 溜まったイベントをまとめてコールバックに渡し、衝突を canvas へ CustomEvent として送る
+ * リスナーから同じ Screen を操作できるよう、Scene の借用を外してから呼ぶ
+ */
+fn dispatch_events(scene: &RefCell<Scene>) {
+    let batch = match scene.borrow_mut().take_events() {
+        Some(batch) => batch,
+        None => return,
+    };
+    // 例外を投げるコールバックがあっても、ログに残してフレームの処理は続ける
+    for (name, callback) in [
+        ("event", batch.callback),
+        ("wall bounce", batch.wall),
+        ("collision", batch.collision),
+    ] {
+        if let Some((callback, events)) = callback {
+            if let Err(e) = callback.call1(&JsValue::NULL, &events) {
+                error!("{} callback threw: {:?}", name, e);
+            }
+        }
+    }
+    if let Some((target, details)) = batch.collisions {
+        for detail in details.iter() {
+            if let Err(e) = dom_utils::dispatch_custom_event(&target, COLLISION_EVENT, detail) {
+                error!("failed to dispatch {}: {:?}", COLLISION_EVENT, e);
+                break;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[wasm_bindgen]
+pub struct Screen {
+    scene: Rc<RefCell<Scene>>,
+    // start() で動いているループ (停止中は None)
+    animation: Option<AnimationLoop>,
+}
+
+#[wasm_bindgen]
+impl Screen {
+    /**
+     * 取得済みの WebGL コンテキストに描く Screen を作る (options は init_gl と同じ。canvas_id は使わない)
+     * ページの要素を id で探さないので、自前で canvas を作るテストや埋め込み先から使える
+     * trail など preserveDrawingBuffer が必要な機能を使う場合は、その属性を付けてコンテキストを取得しておく
+     */
+    pub fn new_with_context(
+        gl: WebGlRenderingContext,
+        options: JsValue,
+    ) -> Result<Screen, JsValue> {
+        create_screen(
+            parse_options(&options)?,
+            ScreenTarget::Context(GlContext::WebGl(gl)),
+        )
+    }
+
+    /**
+     * シミュレーションを1ステップ進めて描画する (一時停止中でも進む。コマ送り用)
+     */
+    pub fn step(&mut self) {
+        self.scene.borrow_mut().step_frame();
+        dispatch_events(&self.scene);
+    }
+
+    /**
+     * do_frame / start() のループでシミュレーションを進めないようにする (描画は続ける)
+     */
+    pub fn pause(&mut self) {
+        self.scene.borrow_mut().paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.scene.borrow_mut().paused = false;
+    }
+
+    /**
+     * pause() で止めているか、pause_when_hidden でページが非表示の間 true
+     */
+    pub fn is_paused(&self) -> bool {
+        let scene = self.scene.borrow();
+        scene.paused || scene.hidden
+    }
+
+    pub fn apply_force(&mut self, x: f64, y: f64, strength: f64, radius: f64) {
+        self.scene.borrow_mut().apply_force(x, y, strength, radius);
+    }
+
+    /**
+     * 全ディスクの速度に (dx, dy) をすぐに加える (キー操作で台を傾けるなど)
+     * 一時停止中は再開後の最初のステップの前にまとめて加える。max_speed を設定していればその速さまでに抑える
+     */
+    pub fn apply_impulse(&mut self, dx: f64, dy: f64) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .apply_impulse(dx, dy)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * (x, y) の周囲 radius 以内のディスクを、距離に反比例する強さ strength で外向きに弾く (爆発など)
+     * 一時停止中・max_speed の扱いは apply_impulse と同じ
+     */
+    pub fn apply_impulse_at(
+        &mut self,
+        x: f64,
+        y: f64,
+        strength: f64,
+        radius: f64,
+    ) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .apply_impulse_at(x, y, strength, radius)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * canvas の大きさを変える (ページのレイアウトが変わったときなど、フレームの間に呼ぶ)
+     * 新しい範囲からはみ出したディスクは内側に戻す
+     */
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), JsValue> {
+        self.scene.borrow_mut().resize(width, height)
+    }
+
+    /**
+     * canvas 上の (x, y) にある一番手前のディスクの番号 (なければ undefined)
+     */
+    pub fn pick(&self, x: f64, y: f64) -> Option<u32> {
+        self.scene.borrow().sim.pick(x, y)
+    }
+
+    /**
+     * canvas 上の (x, y) を含む一番手前のディスクの番号 (なければ -1)。マウスオーバーの判定など
+     */
+    pub fn hit_test(&self, x: f64, y: f64) -> i32 {
+        self.scene
+            .borrow()
+            .sim
+            .pick(x, y)
+            .map_or(-1, |index| index as i32)
+    }
+
+    /**
+     * canvas 上の (x, y) を含むすべてのディスクの番号 (手前に見える順)
+     */
+    pub fn hit_test_all(&self, x: f64, y: f64) -> js_sys::Int32Array {
+        let indices: Vec<i32> = self
+            .scene
+            .borrow()
+            .sim
+            .pick_all(x, y)
+            .into_iter()
+            .map(|index| index as i32)
+            .collect();
+        js_sys::Int32Array::from(indices.as_slice())
+    }
+
+    /**
+     * canvas 上の (x, y) にある一番手前のディスクを掴む (掴んだ番号、なければ -1)
+     * 掴んでいる間は drag_to の位置に留まり、壁で反射せず、他のディスクは動かない壁のように跳ね返る
+     */
+    pub fn grab(&mut self, x: f64, y: f64) -> i32 {
+        self.scene
+            .borrow_mut()
+            .grab(x, y)
+            .map_or(-1, |index| index as i32)
+    }
+
+    /**
+     * 掴んでいるディスクをポインタの位置 (x, y) へ動かす (pointermove などから呼ぶ)
+     */
+    pub fn drag_to(&mut self, x: f64, y: f64) {
+        self.scene.borrow_mut().drag_to(x, y);
+    }
+
+    /**
+     * 掴んでいるディスクを放し、直前のポインタの速度で投げる (canvas の外で放したら内側に戻す)
+     */
+    pub fn release(&mut self) {
+        self.scene.borrow_mut().release_disk();
+    }
+
+    /**
+     * 座標 (x, y)・速度 (vx, vy) のディスクを1つ足す (色はランダム)
+     */
+    pub fn add_disk(&mut self, x: f64, y: f64, vx: f64, vy: f64) {
+        let mut scene = self.scene.borrow_mut();
+        scene.suspend_adaptive();
+        scene.add_disk(x, y, vx, vy);
+    }
+
+    /**
+     * canvas の座標 (x, y) にランダムな向き・色のディスクを1つ足す (クリックした位置など)
+     * speed を省略すると初期化時と同じ範囲から選ぶ。画面外の座標は内側に寄せる
+     */
+    pub fn spawn_at(&mut self, x: f64, y: f64, speed: Option<f64>) {
+        let mut scene = self.scene.borrow_mut();
+        scene.suspend_adaptive();
+        scene.spawn_at(x, y, speed);
+    }
+
+    /**
+     * 画面全体のランダムな位置に、初期化時と同じ速さの範囲のディスクを n 個足す
+     */
+    pub fn add_random_disks(&mut self, n: u32) {
+        let mut scene = self.scene.borrow_mut();
+        scene.suspend_adaptive();
+        scene.add_random_disks(n);
+    }
+
+    /**
+     * 位置はそのままに、全ディスクの速度を初期化時と同じ決め方で選び直す (箱を揺するような操作に)
+     */
+    pub fn randomize_velocities(&mut self) {
+        self.scene.borrow_mut().randomize_velocities();
+    }
+
+    /**
+     * 全ディスクの速度に factor を掛ける (温度を上げ下げする。運動エネルギーは factor² 倍になる)
+     */
+    pub fn scale_velocities(&mut self, factor: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().scale_velocities(factor)
+    }
+
+    /**
+     * index 番目のディスクを取り除く (後ろのディスクの番号は1つずつ詰まる)。範囲外なら false
+     */
+    pub fn remove_disk(&mut self, index: usize) -> bool {
+        let mut scene = self.scene.borrow_mut();
+        scene.suspend_adaptive();
+        scene.remove_disk(index)
+    }
+
+    /**
+     * 今のディスクの数 (adaptive で増減した後の数)
+     */
+    pub fn disk_count(&self) -> u32 {
+        self.scene.borrow().sim.disk_count()
+    }
+
+    /**
+     * canvas の論理サイズ (CSS px)
+     */
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> f64 {
+        self.scene.borrow().sim.width()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> f64 {
+        self.scene.borrow().sim.height()
+    }
+
+    /**
+     * ディスクの数 (disk_count と同じ)
+     */
+    #[wasm_bindgen(getter)]
+    pub fn disk_num(&self) -> u32 {
+        self.disk_count()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn disk_size(&self) -> f64 {
+        self.scene.borrow().sim.disk_size()
+    }
+
+    /**
+     * ディスクの数を変える (増やす分はランダムな位置・速度・色で足し、減らす分は番号の大きい方から取り除く)
+     * 0 はエラー
+     */
+    pub fn set_disk_num(&mut self, disk_num: u32) -> Result<(), JsValue> {
+        let mut scene = self.scene.borrow_mut();
+        scene.suspend_adaptive();
+        scene
+            .set_disk_num(disk_num)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 壁で反射するときの反発係数を変える (0.0 - 1.0)
+     */
+    pub fn set_wall_restitution(&mut self, restitution: f64) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .sim
+            .set_wall_restitution(restitution)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * ディスク同士の衝突の反発係数を変える (0.0 - 1.0)
+     */
+    pub fn set_collision_restitution(&mut self, restitution: f64) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .sim
+            .set_collision_restitution(restitution)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 非推奨: set_collision_restitution の旧名 (そちらを呼ぶ)
+     */
+    pub fn set_restitution(&mut self, restitution: f64) -> Result<(), JsValue> {
+        self.set_collision_restitution(restitution)
+    }
+
+    /**
+     * 衝突したディスクを跳ね返すか ("bounce")、合体するか ("merge")。options.on_collision と同じ
+     */
+    pub fn set_collision_response(&mut self, mode: &str) -> Result<(), JsValue> {
+        let response = CollisionResponse::parse(mode).map_err(|e| JsValue::from(e.as_str()))?;
+        self.scene.borrow_mut().sim.set_collision_response(response);
+        Ok(())
+    }
+
+    /**
+     * 合体でディスクの数を min_disks より減らさず、合体後の半径が max_radius を超える組は合体しない (undefined で制限なし)
+     */
+    pub fn set_merge_limits(
+        &mut self,
+        min_disks: u32,
+        max_radius: Option<f64>,
+    ) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .sim
+            .set_merge_limits(min_disks, max_radius)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * groups の kind 番目のグループに属するディスクの数 (groups を指定しなければ全ディスクが 0)
+     */
+    pub fn count_of_kind(&self, kind: u8) -> u32 {
+        self.scene.borrow().sim.count_of_kind(kind)
+    }
+
+    /**
+     * groups ごとの今のディスクの数 [count0, count1, ...]
+     */
+    pub fn group_counts(&self) -> JsValue {
+        utils::to_js(&self.scene.borrow().group_counts())
+    }
+
+    /**
+     * group 番目のグループと同じ大きさ・質量・色 (palette を指定したグループはそこから選ぶ) のディスクを足す
+     * 範囲外のグループならエラーにして何も足さない
+     */
+    pub fn add_disk_to_group(
+        &mut self,
+        group: usize,
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+    ) -> Result<(), JsValue> {
+        let mut scene = self.scene.borrow_mut();
+        scene.suspend_adaptive();
+        scene
+            .add_disk_to_group(group, x, y, vx, vy)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * requestAnimationFrame のタイムスタンプを渡すと固定刻みで進めて補間して描く (省略すると60fpsの1フレーム分)
+     */
+    pub fn do_frame(&mut self, timestamp: Option<f64>) {
+        self.scene.borrow_mut().do_frame(timestamp);
+        dispatch_events(&self.scene);
+    }
+
+    pub fn do_frame_dt(&mut self, dt_ms: f64) {
+        self.scene.borrow_mut().do_frame_dt(dt_ms);
+        dispatch_events(&self.scene);
+    }
+
+    /**
+     * 壁やディスク同士の衝突をフレームごとにまとめて受け取るコールバックを登録する
+     * callback([{type: "wall" | "collision", indices, x, y, speed}, ...]) の形で呼ばれる
+     * 何も起きなかったフレームでは呼ばれない
+     */
+    pub fn on_event(&mut self, callback: js_sys::Function) {
+        self.scene.borrow_mut().set_event_callback(Some(callback));
+    }
+
+    /**
+     * on_event で受け取るイベントの種類を選ぶ (既定はどちらも true)
+     */
+    pub fn set_event_mask(&mut self, wall: bool, collision: bool) {
+        self.scene
+            .borrow_mut()
+            .set_event_mask(EventMask { wall, collision });
+    }
+
+    /**
+     * on_event で登録したコールバックを解除する
+     */
+    pub fn clear_event_callback(&mut self) {
+        self.scene.borrow_mut().set_event_callback(None);
+    }
+
+    /**
+     * 壁に当たったディスクをフレームごとにまとめて受け取るコールバックを登録する
+     * callback([{type: "wall", indices: [i], x, y, speed}, ...]) の形で呼ばれる (on_event とは別に呼ばれる)
+     */
+    pub fn set_on_wall_bounce(&mut self, callback: js_sys::Function) {
+        self.scene.borrow_mut().set_wall_callback(Some(callback));
+    }
+
+    pub fn clear_on_wall_bounce(&mut self) {
+        self.scene.borrow_mut().set_wall_callback(None);
+    }
+
+    /**
+     * ディスク同士の衝突をフレームごとにまとめて受け取るコールバックを登録する
+     * callback([{type: "collision", indices: [i, j], x, y, speed}, ...]) の形で呼ばれる
+     */
+    pub fn set_on_collision(&mut self, callback: js_sys::Function) {
+        self.scene
+            .borrow_mut()
+            .set_collision_callback(Some(callback));
+    }
+
+    pub fn clear_on_collision(&mut self) {
+        self.scene.borrow_mut().set_collision_callback(None);
+    }
+
+    /**
+     * 1フレームにコールバック・canvas へ渡すイベントの上限 (既定 1000。超えたら古いものから捨てる)
+     */
+    pub fn set_max_events_per_frame(&mut self, max: u32) {
+        self.scene.borrow_mut().pending_events.set_max(max);
+    }
+
+    /**
+     * WebGL のコンテキストが失われているか (この間の do_frame は何もしない)
+     */
+    pub fn is_context_lost(&self) -> bool {
+        self.scene.borrow().context_lost
+    }
+
+    /**
+     * コンテキストの消失・復元時に callback("lost" | "restored") を呼ぶ
+     */
+    pub fn on_context_change(&mut self, callback: js_sys::Function) {
+        self.scene.borrow_mut().context_callback = Some(callback);
+    }
+
+    pub fn clear_context_callback(&mut self) {
+        self.scene.borrow_mut().context_callback = None;
+    }
+
+    /**
+     * requestAnimationFrame のループを Rust 側で開始する (実行中なら何もしない)
+     * JS で rAF を回して do_frame を呼ぶ代わりに使う
+     */
+    pub fn start(&mut self) -> Result<(), JsValue> {
+        if self.animation.is_some() {
+            return Ok(());
+        }
+        {
+            let mut scene = self.scene.borrow_mut();
+            scene.fixed_step.reset();
+            scene.frame_limiter = FrameLimiter::new(scene.frame_limiter.target_fps());
+        }
+        let scene = self.scene.clone();
+        let animation = AnimationLoop::start(move |timestamp| {
+            scene.borrow_mut().animation_frame(timestamp);
+            dispatch_events(&scene);
+        })
+        .map_err(|e| JsValue::from(e.as_str()))?;
+        self.animation = Some(animation);
+        Ok(())
+    }
+
+    /**
+     * start() で開始したループを止める (予約済みのフレームも取り消す)
+     */
+    pub fn stop(&mut self) {
+        if let Some(mut animation) = self.animation.take() {
+            animation.stop();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.animation.is_some()
+    }
+
+    /**
+     * init_gl と同じオプションでディスクと色を作り直す (seed を指定していれば init_gl 直後と同じ状態になる)
+     * シェーダ・バッファは作り直さない。start() のループは止め、一時停止・引力点・力・記録は初期状態に戻す
+     * 次の do_frame で新しいディスクを描く (前の残像は消す)
+     * コールバックとイベントの監視はそのまま残る
+     */
+    pub fn reset(&mut self) -> Result<(), JsValue> {
+        let options = self.scene.borrow().options.clone();
+        self.reset_to(options)
+    }
+
+    /**
+     * reset と同じだが、options に書いたキー (disk_num・disk_size など) を置き換えて作り直す
+     * 書かなかったキーは前のオプションのまま。canvas_id・render_mode・シェーダ・context_attributes
+     * などコンテキストを作るときにだけ使うものは変えられない。エラーの場合は何も変えない (ループも止めない)
+     */
+    pub fn reset_with(&mut self, options: JsValue) -> Result<(), JsValue> {
+        let overrides: serde_json::Value =
+            utils::from_js(&options).map_err(|e| JsValue::from(e.as_str()))?;
+        let options = self
+            .scene
+            .borrow()
+            .options
+            .merged(&overrides)
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        self.reset_to(options)
+    }
+
+    /**
+     * options に書いたキーだけを今の設定から変える (reset_with と違い、ディスクの位置・速度・色はそのまま)
+     * canvas_id・context_attributes などは変えられず、seed・groups など最初のディスクを作るときにだけ使うキーは
+     * reset_with で変える。先にすべて確かめるので、エラーの場合は何も変えない (ループも止めない)
+     */
+    pub fn update_options(&mut self, options: JsValue) -> Result<(), JsValue> {
+        let partial: serde_json::Value =
+            utils::from_js(&options).map_err(|e| JsValue::from(e.as_str()))?;
+        self.scene
+            .borrow_mut()
+            .update_options(&partial)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 今の設定 (省略したキーは既定値で埋め、set_gravity などで変えた値も反映する)
+     * そのまま update_options や reset_with に渡せる
+     */
+    pub fn current_options(&self) -> JsValue {
+        utils::to_js(&self.scene.borrow().current_options())
+    }
+
+    fn reset_to(&mut self, options: Options) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .reset(options)
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        // ループのコールバックは同期的に割り込まないので、作り直した後に止めても前の状態のフレームは描かれない
+        self.stop();
+        Ok(())
+    }
+
+    /**
+     * start() のループで描くフレームを1秒あたり target_fps 回までにする (0 で間引かない)
+     * 間引いたフレームでもシミュレーションは進まず、次に描くフレームでその分まで進める
+     * stats() の fps_avg は描いたフレーム、callback_fps は requestAnimationFrame の呼び出しの回数 / 秒
+     */
+    pub fn set_target_fps(&mut self, target_fps: u32) {
+        self.scene.borrow_mut().frame_limiter = FrameLimiter::new(target_fps);
+    }
+
+    /**
+     * Screen をハンドルに移してループを開始する
+     * JS 側の screen は使えなくなり、handle.stop() で止めると Screen が返ってくる
+     * ハンドルを free するとループを止めて Screen も破棄する
+     */
+    pub fn run(mut self) -> Result<AnimationHandle, JsValue> {
+        self.start()?;
+        Ok(AnimationHandle { screen: self })
+    }
+
+    /**
+     * ループを止め、GLリソース (バッファ・シェーダ・プログラム・テクスチャ) を解放する
+     * 2回呼んでも安全で、以降の do_frame は何もしない
+     */
+    pub fn dispose(&mut self) {
+        self.stop();
+        self.scene.borrow_mut().release();
+    }
+
+    pub fn is_disposed(&self) -> bool {
+        self.scene.borrow().released
+    }
+
+    /**
+     * dispose した上で Screen 自体も破棄する
+     * 同じ canvas に対してもう一度 init_gl を呼べば、新しい Screen として使える
+     */
+    pub fn destroy(mut self) {
+        self.dispose();
+    }
+
+    pub fn stats(&self) -> JsValue {
+        self.scene.borrow().stats()
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.scene.borrow_mut().reset_stats();
+    }
+
+    pub fn set_color_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_color_mode(mode)
+    }
+
+    /**
+     * パレットを変え、今あるディスクの色もすぐに選び直す ("random" | "viridis" | "warm" | "cool" | "pastel" | "mono")
+     * 色モードは変えない ("speed" モードなら色の並びが変わる)
+     */
+    pub fn set_palette(&mut self, palette: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_palette(palette)
+    }
+
+    /**
+     * 全ディスクの色 [r0, g0, b0, r1, ...] (0.0 - 1.0、ディスクの数 * 3 個) を指定し、"random" モードにする
+     * 長さや値の範囲が合わなければエラーにして何も変えない
+     */
+    pub fn set_colors(&mut self, colors: &js_sys::Float32Array) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_colors(colors.to_vec())
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * index 番目のディスクの色を変え、"random" モードにする (他のディスクの色はそのまま)
+     */
+    pub fn set_disk_color(&mut self, index: usize, r: f32, g: f32, b: f32) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_disk_color(index, [r, g, b], None)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * set_disk_color と同じだが、ディスクの不透明度 a (0.0 - 1.0) も変える
+     * 1.0 未満のディスクがあると、重なった部分が透けて見えるようブレンドして描く
+     */
+    pub fn set_disk_color_rgba(
+        &mut self,
+        index: usize,
+        r: f32,
+        g: f32,
+        b: f32,
+        a: f32,
+    ) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_disk_color(index, [r, g, b], Some(a))
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * index 番目のディスクを描く層を変える (既定は 0。層が大きいディスクほど手前に描く)
+     * 同じ層のディスクは今までどおり番号の大きい方が上になる。範囲外の index はエラーにする
+     * render_mode: "points" のみ対応し、ブレンドして描く場合 (opacity・antialias・additive・テクスチャなど) は層を無視する
+     * trail とは併用できる (深度は毎フレーム消す)
+     */
+    pub fn set_disk_layer(&mut self, index: usize, layer: u8) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_disk_layer(index, layer)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 背景色を変える (各成分 0.0 - 1.0)
+     * a を 1.0 未満にするには、init_gl の background で半透明の色を指定して透過できる canvas にしておく必要がある
+     */
+    pub fn set_background(&mut self, r: f32, g: f32, b: f32, a: f32) -> Result<(), JsValue> {
+        let rgba = [r, g, b, a];
+        color::validate_rgba(rgba).map_err(|e| JsValue::from(e.as_str()))?;
+        let mut scene = self.scene.borrow_mut();
+        if a < 1. && !scene.transparent {
+            return Err(JsValue::from(
+                "the canvas is opaque; pass a translucent `background` to init_gl to allow alpha below 1",
+            ));
+        }
+        scene.background = rgba;
+        Ok(())
+    }
+
+    /**
+     * "speed" モードで青 (min 以下) から赤 (max 以上) になる速さの範囲を変える
+     */
+    pub fn set_speed_color_range(&mut self, min: f64, max: f64) -> Result<(), JsValue> {
+        color::validate_speed_range(min, max).map_err(|e| JsValue::from(e.as_str()))?;
+        let mut scene = self.scene.borrow_mut();
+        scene.min_velocity = min;
+        scene.max_velocity = max;
+        Ok(())
+    }
+
+    /**
+     * "cycle" モードで色相を回す速さ (度 / 秒、負で逆回り)
+     */
+    pub fn set_cycle_speed(&mut self, speed: f64) -> Result<(), JsValue> {
+        if !speed.is_finite() {
+            return Err(JsValue::from(
+                format!("cycle_speed must be a finite number: {}", speed).as_str(),
+            ));
+        }
+        self.scene.borrow_mut().cycle_speed = speed;
+        Ok(())
+    }
+
+    pub fn set_trail(&mut self, factor: f64) {
+        self.scene.borrow_mut().set_trail(factor);
+    }
+
+    pub fn set_opacity(&mut self, opacity: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_opacity(opacity)
+    }
+
+    pub fn set_blend_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_blend_mode(mode)
+    }
+
+    /**
+     * ログレベルを変える ("off" | "error" | "warn" | "info" | "debug" | "trace")
+     * info 以上にすると描画のたびに GL のエラーを調べて出力する
+     */
+    pub fn set_log_level(&mut self, level: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_log_level(level)
+    }
+
+    /**
+     * ディスクの形を変える ("circle" | "square" | "ring" | "glow"。組み込みのシェーダのみ)
+     */
+    pub fn set_shape(&mut self, shape: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_shape(shape)
+    }
+
+    pub fn set_boundary_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_boundary_mode(mode)
+    }
+
+    /**
+     * 動き方を切り替える ("ballistic" | "boids")。ディスクの位置は置き直さない
+     */
+    pub fn set_behavior(&mut self, behavior: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_behavior(behavior)
+    }
+
+    /**
+     * boids モードの {separation, alignment, cohesion, perception, max_speed} を変える (省略した値は既定値)
+     */
+    pub fn set_boids(&mut self, params: JsValue) -> Result<(), JsValue> {
+        let params: BoidsParams = utils::from_js(&params)
+            .map_err(|e| JsValue::from(format!("invalid boids: {}", e).as_str()))?;
+        self.scene.borrow_mut().set_boids(params)
+    }
+
+    /**
+     * 領域の形を切り替える ("rect" | "circle")。"circle" は canvas に内接する最大の円になる
+     */
+    pub fn set_arena(&mut self, mode: &str) -> Result<(), JsValue> {
+        let arena = Arena::parse(mode, None, None).map_err(|e| JsValue::from(e.as_str()))?;
+        self.scene.borrow_mut().set_arena(arena)
+    }
+
+    /**
+     * 中心 (x, y)・半径 radius の円の内側にディスクを閉じ込める
+     */
+    pub fn set_arena_circle(&mut self, x: f64, y: f64, radius: f64) -> Result<(), JsValue> {
+        let arena = Arena::parse("circle", Some([x, y]), Some(radius))
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        self.scene.borrow_mut().set_arena(arena)
+    }
+
+    pub fn set_show_arena(&mut self, show: bool) {
+        self.scene.borrow_mut().set_show_arena(show);
+    }
+
+    /**
+     * ディスクの大きさ (描画・壁での反射・衝突の半径) を変える
+     * 大きくなって壁に重なったディスクは内側に戻す。0 以下・有限でない値はエラー
+     */
+    pub fn set_disk_size(&mut self, disk_size: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_disk_size(disk_size)
+    }
+
+    /**
+     * 移動の速さの倍率 (1.0 で元の速さ)
+     */
+    pub fn set_speed_scale(&mut self, speed_scale: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_speed_scale(speed_scale)
+    }
+
+    /**
+     * シミュレーションの時間の倍率 (-16.0 - 16.0、1.0 で等速、0.1 でスローモーション、負で逆再生)
+     * 1 を超える倍率は1フレームを分割して進め、負の倍率は速度を反転して進める (衝突は完全には元に戻らない)
+     */
+    pub fn set_time_scale(&mut self, scale: f64) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_time_scale(scale)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn get_time_scale(&self) -> f64 {
+        self.scene.borrow().time_scale
+    }
+
+    /**
+     * 反射する壁を canvas の端から内側に寄せる幅 (canvas の短辺の半分未満)
+     */
+    pub fn set_bounds_padding(&mut self, padding: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_bounds_padding(padding)
+    }
+
+    /**
+     * 各ステップの最後に速さを max_speed までに抑える (undefined で制限しない)
+     */
+    pub fn set_max_speed(&mut self, max_speed: Option<f64>) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_max_speed(max_speed)
+    }
+
+    /**
+     * 毎フレーム縦方向の速度へ加える値 (px / フレーム²、正で下向き。0 で重力なし)
+     */
+    pub fn set_gravity(&mut self, gravity: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_gravity(gravity)
+    }
+
+    /**
+     * 毎フレーム全ディスクの速度へ加える一定の力 (px / フレーム²、風など)。重力と足し合わせて働き、(0, 0) で力なし
+     */
+    pub fn set_force(&mut self, fx: f64, fy: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_force(fx, fy)
+    }
+
+    /**
+     * 毎フレーム速度に (1 - drag) を掛けて減速させる (0.0 - 1.0、0.0 で減速しない)
+     */
+    pub fn set_drag(&mut self, drag: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_drag(drag)
+    }
+
+    /**
+     * 毎フレーム各ディスクの速度に、標準偏差 jitter の正規分布に従う値を加える (0.0 で加えない)
+     * drag と組み合わせると、速度が揺らぎながら0に引き戻されて拡散していく
+     */
+    pub fn set_jitter(&mut self, jitter: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_jitter(jitter)
+    }
+
+    /**
+     * 以降のステップで (x, y) (canvas の論理座標) の周囲のディスクを引き寄せる (pointer_force が負なら遠ざける)
+     * mousemove などから呼ぶ。clear_pointer を呼ぶまで力を加え続ける
+     */
+    pub fn set_pointer(&mut self, x: f64, y: f64) {
+        self.scene.borrow_mut().set_pointer(x, y);
+    }
+
+    /**
+     * 指ごとの位置 [x0, y0, x1, y1, ...] (canvas の論理座標) を置き換え、それぞれを set_pointer と同じ力で引き寄せる点にする
+     * touchmove などから呼ぶ。空の配列で全て外す
+     */
+    pub fn set_touch_points(&mut self, points: &js_sys::Float32Array) -> Result<(), JsValue> {
+        let points: Vec<f64> = points.to_vec().into_iter().map(f64::from).collect();
+        self.scene
+            .borrow_mut()
+            .set_touch_points(&points)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * (center_x, center_y) を canvas の中央に、scale 倍に拡大して表示する (描画だけに掛かり、物理は変えない)
+     * ディスクの見た目の大きさも scale 倍になる。scale は 0.001 - 1000
+     */
+    pub fn set_view(&mut self, center_x: f64, center_y: f64, scale: f64) -> Result<(), JsValue> {
+        let view = View::new(center_x, center_y, scale).map_err(|e| JsValue::from(e.as_str()))?;
+        self.scene.borrow_mut().set_view(Some(view));
+        Ok(())
+    }
+
+    /**
+     * 視点を戻し、領域全体を等倍で表示する
+     */
+    pub fn reset_view(&mut self) {
+        self.scene.borrow_mut().set_view(None);
+    }
+
+    /**
+     * canvas_id の canvas にも同じシミュレーションを描き、detach_view などに渡す番号を返す
+     * options は {width, height, center: [x, y], scale, background, dpr} (すべて省略できる)。
+     * 大きさ・背景を省略するとメインの canvas と同じで、center・scale を省略すると領域全体が収まるように縮める
+     * そのビューのコンテキストが消失しても、メインの canvas と他のビューは描き続ける
+     */
+    pub fn attach_view(&mut self, canvas_id: &str, options: JsValue) -> Result<u32, JsValue> {
+        let options: ViewOptions = if options.is_undefined() || options.is_null() {
+            ViewOptions::default()
+        } else {
+            utils::from_js(&options)
+                .map_err(|e| JsValue::from(format!("invalid view options: {}", e).as_str()))?
+        };
+        self.scene
+            .borrow_mut()
+            .attach_view(canvas_id, &options)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * attach_view で足したビューの視点を変える (set_view と同じく、scale は 0.001 - 1000)
+     */
+    pub fn set_attached_view(
+        &mut self,
+        id: u32,
+        center_x: f64,
+        center_y: f64,
+        scale: f64,
+    ) -> Result<(), JsValue> {
+        let view = View::new(center_x, center_y, scale).map_err(|e| JsValue::from(e.as_str()))?;
+        self.scene
+            .borrow_mut()
+            .set_attached_view(id, Some(view))
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * attach_view で足したビューを、領域全体が収まる視点に戻す
+     */
+    pub fn fit_attached_view(&mut self, id: u32) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_attached_view(id, None)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * attach_view で足したビューを外し、そのコンテキストの GL リソースを消す (無い番号なら false)
+     */
+    pub fn detach_view(&mut self, id: u32) -> bool {
+        self.scene.borrow_mut().detach_view(id)
+    }
+
+    /**
+     * attach_view で足したビューのコンテキストが消失中か (無い番号なら false)
+     */
+    pub fn is_view_context_lost(&self, id: u32) -> bool {
+        self.scene
+            .borrow()
+            .views
+            .iter()
+            .any(|(view_id, view)| *view_id == id && view.is_context_lost())
+    }
+
+    /**
+     * canvas 上の座標 (CSS px、クリックした位置など) を今の視点でのワールド座標 [x, y] にする
+     * spawn_at・set_pointer・add_attractor にはこちらで変換した座標を渡す
+     */
+    pub fn screen_to_world(&self, x: f64, y: f64) -> JsValue {
+        let (x, y) = self.scene.borrow().screen_to_world(x, y);
+        utils::to_js(&[x, y])
+    }
+
+    pub fn clear_pointer(&mut self) {
+        self.scene.borrow_mut().clear_pointer();
+    }
+
+    /**
+     * 以降のフレームでディスクを (x, y) へ引き寄せる引力点を置き、remove_attractor に渡す番号を返す
+     * 加速度は strength / 距離² (px / フレーム²) で、strength が負なら遠ざける。重力・ドラッグとは足し合わせて働く
+     */
+    pub fn add_attractor(&mut self, x: f64, y: f64, strength: f64) -> Result<u32, JsValue> {
+        self.scene
+            .borrow_mut()
+            .add_attractor(x, y, strength)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * add_attractor の番号の引力点を取り除く (なければ false)
+     */
+    pub fn remove_attractor(&mut self, id: u32) -> bool {
+        self.scene.borrow_mut().remove_attractor(id)
+    }
+
+    pub fn clear_attractors(&mut self) {
+        self.scene.borrow_mut().clear_attractors();
+    }
+
+    /**
+     * 左上 (x, y)・幅 width・高さ height の矩形の障害物を置き、remove_obstacle に渡す番号を返す
+     * ディスクは食い込んだ分だけ一番近い辺 (角では角からの放射方向) の外へ押し出され、wall_restitution で跳ね返る
+     */
+    pub fn add_obstacle_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<u32, JsValue> {
+        self.scene
+            .borrow_mut()
+            .add_obstacle(ObstacleShape::Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 中心 (x, y)・半径 radius の円の障害物を置き、remove_obstacle に渡す番号を返す
+     */
+    pub fn add_obstacle_circle(&mut self, x: f64, y: f64, radius: f64) -> Result<u32, JsValue> {
+        self.scene
+            .borrow_mut()
+            .add_obstacle(ObstacleShape::Circle { x, y, radius })
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * add_obstacle_rect / add_obstacle_circle の番号の障害物を取り除く (なければ false)
+     */
+    pub fn remove_obstacle(&mut self, id: u32) -> bool {
+        self.scene.borrow_mut().remove_obstacle(id)
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.scene.borrow_mut().clear_obstacles();
+    }
+
+    /**
+     * 障害物の外形を線で表示するかどうか
+     */
+    pub fn set_show_obstacles(&mut self, show: bool) {
+        self.scene.borrow_mut().set_show_obstacles(show);
+    }
+
+    /**
+     * 引力点の位置を大きな点で表示するかどうか (引き寄せる点は黄、遠ざける点は赤)
+     */
+    pub fn set_show_attractors(&mut self, show: bool) {
+        self.scene.borrow_mut().set_show_attractors(show);
+    }
+
+    /**
+     * ポインタの力の強さ (px / フレーム²、負で遠ざける) と届く半径 (px) を変える
+     */
+    pub fn set_pointer_force(&mut self, force: f64, radius: f64) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .sim
+            .set_pointer_force(force, radius)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn load_texture(&mut self, url: &str) -> Result<(), JsValue> {
+        self.scene.borrow_mut().load_texture(url)
+    }
+
+    /**
+     * 読み込み済みの <img> をディスクに貼る ("points" モードのみ。透過部分はブレンドで抜ける)
+     */
+    pub fn set_texture(&mut self, image: &web_sys::HtmlImageElement) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_texture(TextureSource::Image(image.clone()))
+    }
+
+    /**
+     * createImageBitmap で作った画像をディスクに貼る (Worker でも使える)
+     */
+    pub fn set_texture_bitmap(&mut self, bitmap: &web_sys::ImageBitmap) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .set_texture(TextureSource::Bitmap(bitmap.clone()))
+    }
+
+    /**
+     * 貼った画像をやめて通常の円で描く
+     */
+    pub fn clear_texture(&mut self) {
+        self.scene.borrow_mut().clear_texture();
+    }
+
+    pub fn overlay_enabled(&mut self, on: bool) {
+        self.scene.borrow_mut().overlay_enabled(on);
+    }
+
+    /**
+     * 全ディスクの位置・速度 [{x, y, vx, vy}, ...] (描画と同じ順番)
+     */
+    pub fn get_disks(&self) -> JsValue {
+        let scene = self.scene.borrow();
+        let disks: Vec<DiskInfo> = scene.sim.disks().iter().map(|d| d.into()).collect();
+        utils::to_js(&disks)
+    }
+
+    /**
+     * index 番目のディスクの {x, y, vx, vy} (範囲外なら undefined)
+     */
+    pub fn get_disk(&self, index: usize) -> JsValue {
+        match self.scene.borrow().sim.disks().get(index) {
+            Some(disk) => utils::to_js(&DiskInfo::from(disk)),
+            None => JsValue::UNDEFINED,
+        }
+    }
+
+    /**
+     * index 番目のディスクを読むハンドル (範囲外なら undefined)。ディスクが減るか置き換わると無効になる
+     */
+    pub fn disk(&self, index: usize) -> Option<DiskRef> {
+        let scene = self.scene.borrow();
+        (index < scene.sim.disks().len()).then(|| DiskRef {
+            scene: Rc::downgrade(&self.scene),
+            index,
+            generation: scene.tracker.generation(),
+        })
+    }
+
+    /**
+     * index 番目のディスクを追跡する (削除・合体で番号が変わっても同じディスクを追う)。範囲外なら false
+     */
+    pub fn track(&mut self, index: usize) -> bool {
+        let mut scene = self.scene.borrow_mut();
+        let count = scene.sim.disks().len();
+        scene.tracker.track(index, count)
+    }
+
+    pub fn untrack(&mut self) {
+        self.scene.borrow_mut().tracker.untrack();
+    }
+
+    /**
+     * 追跡中のディスクの今の番号 (追跡していないか、そのディスクが消えた場合は undefined)
+     */
+    pub fn tracked_index(&self) -> Option<usize> {
+        self.scene.borrow().tracker.tracked()
+    }
+
+    /**
+     * 追跡中のディスクの [x, y] (get_positions と同じく画面に見えている位置)
+     * 追跡していないか、そのディスクが消えた場合は空の配列
+     */
+    pub fn tracked_position(&self) -> js_sys::Float32Array {
+        let scene = self.scene.borrow();
+        let index = match scene.tracker.tracked() {
+            Some(index) => index,
+            None => return js_sys::Float32Array::new_with_length(0),
+        };
+        let position = if scene.positions.len() == scene.sim.disks().len() * 2 {
+            [scene.positions[index * 2], scene.positions[index * 2 + 1]]
+        } else {
+            let disk = &scene.sim.disks()[index];
+            [disk.x as f32, disk.y as f32]
+        };
+        js_sys::Float32Array::from(&position[..])
+    }
+
+    /**
+     * 直近のフレームで描いた座標 [x0, y0, x1, y1, ...] のコピー (補間・再生中の座標を含め、画面に見えている位置)
+     * まだ描いていない場合やディスクの数が変わった直後は、シミュレーションの現在の座標を返す
+     */
+    pub fn get_positions(&self) -> js_sys::Float32Array {
+        let scene = self.scene.borrow();
+        if scene.positions.len() == scene.sim.disks().len() * 2 {
+            js_sys::Float32Array::from(scene.positions.as_slice())
+        } else {
+            scene.sim.positions()
+        }
+    }
+
+    /**
+     * 全ディスクの位置・速度 ({disk_count, disks: [{x, y, cos, sin, kind, mass, size}, ...]})
+     * restore に渡すとその時点の状態に戻せる
+     */
+    pub fn snapshot(&self) -> JsValue {
+        utils::to_js(&self.scene.borrow().sim.snapshot())
+    }
+
+    /**
+     * snapshot で保存した状態に戻す。ディスクの数が違ってもよい
+     * 形式が不正な場合や disk_count がディスクの数と合わない場合はエラーにして何も変えない
+     */
+    pub fn restore(&mut self, state: JsValue) -> Result<(), JsValue> {
+        let state: SimulationState = utils::from_js(&state)
+            .map_err(|e| JsValue::from(format!("invalid state: {}", e).as_str()))?;
+        self.scene.borrow_mut().restore(&state)
+    }
+
+    /**
+     * 大きさ・物理の設定・全ディスクの状態・色を、版つきの JSON にする (localStorage への保存など)
+     */
+    pub fn export_state(&self) -> String {
+        self.scene.borrow().export_state()
+    }
+
+    /**
+     * export_state の JSON に戻す。ディスクの数や canvas の大きさが違ってもよい
+     * 形式・版・値が不正ならエラーにして何も変えない
+     */
+    pub fn import_state(&mut self, json: &str) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .import_state(json)
+            .map_err(|e| JsValue::from(e.as_str()))
     }
-}
 
-fn log(s: &String) {
-    log_1(&JsValue::from(s));
-}
+    /**
+     * 各ディスクの中心から速度ベクトルの向きに線分を描く (物理パラメータの調整用)
+     */
+    pub fn set_debug_overlay(&mut self, enabled: bool) {
+        self.scene.borrow_mut().set_debug_overlay(enabled);
+    }
 
-#[wasm_bindgen]
-pub fn output_log(s: &str) {
-    log(&format!("Hello {}", s));
-}
+    /**
+     * set_debug_overlay と同じ (Options.show_velocity に合わせた名前)
+     */
+    pub fn set_show_velocity(&mut self, show: bool) {
+        self.set_debug_overlay(show);
+    }
 
-#[derive(Clone, Copy, Debug)]
-pub struct Disk {
-    x: f64,   // x-coordinate
-    y: f64,   // y-coordinate
-    cos: f64, // moving velocity-cos
-    sin: f64, // moving velocity-sin
-}
+    /**
+     * 速度ベクトルの線分の長さの倍率 (既定 10。速度は1フレームあたりの移動量)
+     */
+    pub fn set_debug_vector_scale(&mut self, scale: f64) -> Result<(), JsValue> {
+        self.scene.borrow_mut().set_debug_vector_scale(scale)
+    }
+
+    pub fn start_recording(
+        &mut self,
+        max_frames: u32,
+        byte_budget: Option<u32>,
+    ) -> Result<(), JsValue> {
+        self.scene
+            .borrow_mut()
+            .start_recording(max_frames, byte_budget)
+    }
+
+    pub fn stop_recording(&mut self) -> JsValue {
+        self.scene.borrow_mut().stop_recording()
+    }
+
+    pub fn play_recording(&mut self, data: JsValue, looping: bool) -> Result<(), JsValue> {
+        self.scene.borrow_mut().play_recording(data, looping)
+    }
+
+    pub fn stop_playback(&mut self) {
+        self.scene.borrow_mut().stop_playback();
+    }
+
+    /**
+     * 現在の状態・乱数の種と、以降の操作 (spawn_at・add_disk・remove_disk・apply_force・set_pointer・resize など)
+     * とステップの時間を記録する。座標を記録する start_recording と違い、replay で物理演算ごと再現できる
+     */
+    pub fn start_input_recording(&mut self) {
+        self.scene.borrow_mut().start_input_recording();
+    }
+
+    /**
+     * 操作の記録を終了して返す ({version, seed, initial, ticks, inputs: [{tick, input}, ...]}、記録していなければ null)
+     */
+    pub fn stop_input_recording(&mut self) -> JsValue {
+        match self.scene.borrow_mut().stop_input_recording() {
+            Some(recording) => utils::to_js(&recording),
+            None => JsValue::NULL,
+        }
+    }
+
+    /**
+     * stop_input_recording の記録を、記録開始時の状態から再生する (以降のフレームで記録と同じステップを進める)
+     * 最後のステップまで進むと一時停止し、export_state が記録終了時と同じになる
+     */
+    pub fn replay(&mut self, recording: JsValue) -> Result<(), JsValue> {
+        let recording: InputRecording = utils::from_js(&recording)
+            .map_err(|e| JsValue::from(format!("invalid recording: {}", e).as_str()))?;
+        self.scene
+            .borrow_mut()
+            .replay(recording)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.scene.borrow().replay.is_some()
+    }
+
+    /**
+     * 運動エネルギー ½ * mass * v² の総和 (mass は衝突に使う質量。mass_from_radius なら半径²、指定がなければ 1.0)
+     */
+    pub fn total_kinetic_energy(&self) -> f64 {
+        self.scene.borrow().sim.total_kinetic_energy()
+    }
+
+    /**
+     * 速さを [0, max_speed) の bins 個の区間に分けた、区間ごとのディスクの数 (max_speed 以上は最後の区間)
+     * 毎フレームのグラフ更新に使えるよう、型付き配列で返す
+     */
+    pub fn speed_histogram(
+        &self,
+        bins: u32,
+        max_speed: f64,
+    ) -> Result<js_sys::Uint32Array, JsValue> {
+        let counts = self
+            .scene
+            .borrow()
+            .sim
+            .speed_histogram(bins, max_speed)
+            .map_err(|e| JsValue::from(e.as_str()))?;
+        Ok(js_sys::Uint32Array::from(counts.as_slice()))
+    }
+
+    /**
+     * 運動エネルギーの総和 (デバッグビルドのみ)
+     */
+    #[cfg(debug_assertions)]
+    pub fn total_energy(&self) -> f64 {
+        self.scene.borrow().sim.total_energy()
+    }
+
+    /**
+     * 運動量の総和 [px, py] (デバッグビルドのみ)
+     */
+    #[cfg(debug_assertions)]
+    pub fn total_momentum(&self) -> Vec<f64> {
+        let (px, py) = self.scene.borrow().sim.total_momentum();
+        vec![px, py]
+    }
+
+    /**
+     * check_invariants で最後に記録した、不変条件に反したティック (無ければ undefined)
+     * {tick, energy_before, energy_after, energy_delta, momentum_before, momentum_after, momentum_delta,
+     *  driven, conserving, collisions: [[i, j], ...]}。seed を指定すれば同じティックを再現できる
+     */
+    pub fn last_invariant_report(&self) -> JsValue {
+        match self.scene.borrow().sim.last_invariant_report() {
+            Some(report) => utils::to_js(report),
+            None => JsValue::UNDEFINED,
+        }
+    }
+
+    pub fn positions_ptr(&self) -> *const f32 {
+        self.scene.borrow().positions_ptr()
+    }
+
+    pub fn positions_len(&self) -> usize {
+        self.scene.borrow().positions_len()
+    }
+
+    /**
+     * positions_ptr から作ったビューが使えなくなるたびに増える番号
+     * ディスクの数が変わった後 (add_disk・remove_disk・import_state・ディスク数の変更など) や、座標バッファの確保し直し・メモリの伸長の後に変わる
+     * JS からは読む前に番号を確かめ、変わっていればビューを作り直す:
+     * `if (screen.positions_generation() !== generation) { view = screen.positions_view(); generation = screen.positions_generation(); }`
+     * その後は `for (let i = 0; i < view.length; i += 2) ctx.fillText(i / 2, view[i], view[i + 1]);` のようにコピーせずに読める
+     */
+    pub fn positions_generation(&self) -> u32 {
+        self.scene.borrow_mut().positions_generation()
+    }
+
+    /**
+     * 直近の描画で使った座標 (x, y が交互に並ぶ) をコピーせずに参照する Float32Array
+     * positions_generation が変わるまでの間だけ有効 (それ以降は古い内容か空の配列になる)
+     */
+    pub fn positions_view(&self) -> js_sys::Float32Array {
+        let mut scene = self.scene.borrow_mut();
+        // ビューを作る時点の場所を記録し、以降の変化を positions_generation で検出する
+        scene.positions_generation();
+        let memory: js_sys::WebAssembly::Memory = wasm_bindgen::memory().unchecked_into();
+        js_sys::Float32Array::new_with_byte_offset_and_length(
+            &memory.buffer(),
+            scene.positions_ptr() as u32,
+            scene.positions_len() as u32,
+        )
+    }
+
+    pub fn backend(&self) -> String {
+        self.scene.borrow().backend()
+    }
+
+    /**
+     * ブラウザが実際に与えたコンテキスト属性 ({antialias, alpha, powerPreference, ...})
+     * options.context_attributes で要求した値がそのまま使われるとは限らないので、ここで確かめる
+     */
+    pub fn context_attributes(&self) -> JsValue {
+        self.scene.borrow().gl.context_attributes()
+    }
+
+    /**
+     * 描画バッファの倍率 (devicePixelRatio、または options.dpr)。API の座標はこの倍率によらず CSS の px
+     */
+    pub fn pixel_ratio(&self) -> f64 {
+        self.scene.borrow().pixel_ratio
+    }
+
+    /**
+     * 組み込みのフラグメントシェーダのソース (options.fragment_shader で差し替えるときの出発点に)
+     * WebGL2 では `#version 300 es` 版を返す
+     */
+    pub fn default_fragment_shader(&self) -> String {
+        self.scene.borrow().default_fragment_shader()
+    }
+
+    /**
+     * 点の描画に使うフラグメントシェーダを実行中に差し替える (今の頂点シェーダとリンクする)
+     * コンパイル・リンクに失敗したら情報ログを含むエラーを返し、今のシェーダのまま描き続ける
+     */
+    pub fn set_fragment_shader(&mut self, source: &str) -> Result<(), JsValue> {
+        let mut scene = self.scene.borrow_mut();
+        let sources = dom_utils::ShaderSources {
+            fragment: Some(source.to_string()),
+            ..scene.shader_sources.clone()
+        };
+        scene
+            .set_shader_sources(sources)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 点の描画に使う頂点シェーダを実行中に差し替える (今のフラグメントシェーダとリンクする)
+     */
+    pub fn set_vertex_shader(&mut self, source: &str) -> Result<(), JsValue> {
+        let mut scene = self.scene.borrow_mut();
+        let sources = dom_utils::ShaderSources {
+            vertex: Some(source.to_string()),
+            ..scene.shader_sources.clone()
+        };
+        scene
+            .set_shader_sources(sources)
+            .map_err(|e| JsValue::from(e.as_str()))
+    }
+
+    /**
+     * 取得できた WebGL のバージョン ("webgl2" を先に試し、使えなければ 1 にフォールバックする)
+     */
+    pub fn gl_version(&self) -> u32 {
+        self.scene.borrow().gl.version()
+    }
+
+    /**
+     * "points" モードで描けるディスクの直径の上限 (描画バッファの px)
+     * disk_size * devicePixelRatio がこれを超える場合は render_mode: "quads" を使う
+     */
+    pub fn max_point_size(&self) -> f64 {
+        self.scene.borrow().max_point_size()
+    }
+
+    /**
+     * 現在の状態を描き直して PNG の data URL を返す (preserveDrawingBuffer は不要)
+     * 記録の再生中は、描き直した分だけ1フレーム進む
+     * trail か options.preserve_drawing_buffer を指定していれば描き直さず、直前に描いたフレームをそのまま返す
+     */
+    pub fn capture_png(&self) -> Result<String, JsValue> {
+        self.scene.borrow_mut().capture_png()
+    }
 
-impl Disk {
-    fn new(x: f64, y: f64, cos: f64, sin: f64) -> Self {
-        Self { x, y, cos, sin }
+    /**
+     * 現在の状態を描き直して RGBA の画素 (左上から行ごと、幅・高さは描画バッファの px) を返す
+     * 描画バッファの大きさは width * devicePixelRatio x height * devicePixelRatio
+     */
+    pub fn capture_rgba(&self) -> Result<js_sys::Uint8Array, JsValue> {
+        self.scene.borrow_mut().capture_rgba()
     }
 }
 
 /**
- * ディスクのベクタを初期化する
+ * Screen::run で開始したループを持つハンドル
  */
-fn init_disks(disk_num: u32, bound_x: u32, bound_y: u32) -> Vec<Box<Disk>> {
-    let mut disks_buffer: Vec<Box<Disk>> = Vec::with_capacity(disk_num as usize);
+#[derive(Debug)]
+#[wasm_bindgen]
+pub struct AnimationHandle {
+    screen: Screen,
+}
+
+#[wasm_bindgen]
+impl AnimationHandle {
+    /**
+     * ループを止めて Screen を返す (再開するには screen.start() か screen.run())
+     */
+    pub fn stop(mut self) -> Screen {
+        self.screen.stop();
+        self.screen
+    }
 
-    let mut rand = rand::thread_rng();
-    for i in 0..disk_num {
-        let random = rand.gen_range(0., 1.);
-        let velocity = 1. + 3. * random;
-        let angle = std::f64::consts::PI * (0.1 * (i as f64) * random);
-        let disk = Box::new(Disk::new(
-            (bound_x as f64) / 2.,
-            (bound_y as f64) / 2.,
-            velocity * angle.cos(),
-            velocity * angle.sin(),
-        ));
-        disks_buffer.push(disk);
+    pub fn is_running(&self) -> bool {
+        self.screen.is_running()
     }
-    disks_buffer
 }
 
+/**
+ * Screen::disk で取った1つのディスクへのハンドル (読むたびに Screen の今の値を返す)
+ * ディスクが減るか置き換わる (remove_disk・reset・合体・emitter の寿命など) と無効になり、
+ * 別のディスクの値を返す代わりにエラーになる。取り直すには screen.disk(index) を呼ぶ
+ */
 #[derive(Debug)]
 #[wasm_bindgen]
-pub struct Screen {
-    gl: WebGlRenderingContext,
-    uniform_point_size: WebGlUniformLocation,
-    buffer_coords: WebGlBuffer,
-
-    attrib_coords: i32,
-    attrib_color: i32,
-    width: u32,
-    height: u32,
-    disk_num: u32,
-    disk_size: f64,
+pub struct DiskRef {
+    scene: std::rc::Weak<RefCell<Scene>>,
+    index: usize,
+    generation: u64,
+}
 
-    disks: Vec<Box<Disk>>,
+impl DiskRef {
+    fn read<T>(&self, f: impl FnOnce(&Scene, &Disk) -> T) -> Result<T, JsValue> {
+        let scene = self
+            .scene
+            .upgrade()
+            .ok_or_else(|| JsValue::from("the screen has been dropped"))?;
+        let scene = scene.borrow();
+        if scene.released {
+            return Err(JsValue::from("the screen has been destroyed"));
+        }
+        let disk = scene
+            .sim
+            .disks()
+            .get(self.index)
+            .filter(|_| scene.tracker.generation() == self.generation)
+            .ok_or_else(|| {
+                JsValue::from(
+                    format!(
+                        "disk {} is stale (disks were removed or replaced); call screen.disk() again",
+                        self.index
+                    )
+                    .as_str(),
+                )
+            })?;
+        Ok(f(&scene, disk))
+    }
 }
 
 #[wasm_bindgen]
-impl Screen {
-    /**
-     * 1イテレーションごとの座標計算
-     */
-    fn on_animation_frame(&mut self) -> () {
-        let size = self.disk_size as f64;
-        let width = self.width as f64;
-        let height = self.height as f64;
-        for disk in self.disks.iter_mut() {
-            disk.x += disk.cos;
-            disk.y += disk.sin;
-            if disk.x - size < 0. {
-                disk.x = size - (disk.x - size);
-                disk.cos = disk.cos.abs();
-            } else if disk.x + size > width {
-                disk.x = width - (disk.x + size - width) - size;
-                disk.cos = -disk.cos.abs();
-            }
-            if disk.y - size < 0. {
-                disk.y = size - (disk.y - size);
-                disk.sin = disk.sin.abs();
-            } else if disk.y + size > height {
-                disk.y = height - (disk.y + size - height) - size;
-                disk.sin = -disk.sin.abs();
-            }
-        }
+impl DiskRef {
+    pub fn index(&self) -> usize {
+        self.index
     }
 
     /**
-     * 各アニメーションフレームごとの処理
+     * まだ同じディスクを指しているか (false なら getter はエラーになる)
      */
-    pub fn do_frame(&mut self) -> () {
-        self.on_animation_frame();
-        self.draw();
+    pub fn is_valid(&self) -> bool {
+        self.read(|_, _| ()).is_ok()
+    }
+
+    pub fn x(&self) -> Result<f64, JsValue> {
+        self.read(|_, disk| disk.x)
+    }
+
+    pub fn y(&self) -> Result<f64, JsValue> {
+        self.read(|_, disk| disk.y)
+    }
+
+    pub fn vx(&self) -> Result<f64, JsValue> {
+        self.read(|_, disk| disk.cos)
+    }
+
+    pub fn vy(&self) -> Result<f64, JsValue> {
+        self.read(|_, disk| disk.sin)
+    }
+
+    pub fn radius(&self) -> Result<f64, JsValue> {
+        self.read(|scene, disk| disk.size_or(scene.sim.disk_size()))
     }
 
     /**
-     * レンダリング処理
+     * 描いている色 [r, g, b, a] (0.0〜1.0)
      */
-    fn draw(&self) -> () {
-        self.gl.clear_color(0., 0., 0., 1.);
-        self.gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+    pub fn color(&self) -> Result<Vec<f32>, JsValue> {
+        self.read(|scene, _| {
+            scene
+                .colors
+                .get(self.index * 4..self.index * 4 + 4)
+                .map_or_else(|| vec![0., 0., 0., 1.], <[f32]>::to_vec)
+        })
+    }
+}
 
-        self.gl.bind_buffer(
-            WebGlRenderingContext::ARRAY_BUFFER,
-            Some(&self.buffer_coords),
-        );
-        let buff_vec = self
-            .disks
-            .iter()
-            .flat_map(|d| {
-                let cloned = d.as_ref().clone();
-                vec![cloned.x as f32, cloned.y as f32]
-            })
-            .collect::<Vec<f32>>();
-        unsafe {
-            self.gl.buffer_data_with_array_buffer_view(
-                WebGlRenderingContext::ARRAY_BUFFER,
-                &js_sys::Float32Array::view(buff_vec.as_slice()), //
-                WebGlRenderingContext::STREAM_DRAW,
-            )
+impl Drop for Screen {
+    fn drop(&mut self) {
+        self.stop();
+        let mut scene = self.scene.borrow_mut();
+        if !scene.released {
+            warn!("Screen was dropped without destroy(); releasing GL resources");
+            scene.release();
         }
-        self.gl.vertex_attrib_pointer_with_f64(
-            self.attrib_coords as u32,
-            2,
-            WebGlRenderingContext::FLOAT,
-            false,
-            0,
-            0.,
-        );
-        self.gl
-            .enable_vertex_attrib_array(self.attrib_coords as u32);
+    }
+}
 
-        self.gl.enable_vertex_attrib_array(self.attrib_color as u32);
-        self.gl
-            .vertex_attrib3f(self.attrib_color as u32, 1., 0., 0.);
+impl Screen {
+    /**
+     * Rust側から統計情報を直接参照する
+     */
+    pub fn frame_stats(&self) -> stats::FrameStats {
+        self.scene.borrow().frame_stats()
+    }
+}
 
-        self.gl
-            .uniform1f(Some(&self.uniform_point_size), self.disk_size as f32);
+/**
+ * WebGLContextの初期化処理 (オプションをオブジェクトで渡す)
+ */
+#[wasm_bindgen]
+pub fn init_gl(option_input: JsValue) -> Result<Screen, JsValue> {
+    create_screen(parse_options(&option_input)?, ScreenTarget::CanvasId)
+}
 
-        self.gl
-            .draw_arrays(WebGlRenderingContext::POINTS, 0, self.disk_num as i32);
-    }
+/**
+ * ScreenOptions で組み立てたオプションで初期化する
+ */
+#[wasm_bindgen]
+pub fn init_gl_with_options(options: &ScreenOptions) -> Result<Screen, JsValue> {
+    create_screen(options.options().clone(), ScreenTarget::CanvasId)
+}
+
+fn parse_options(option_input: &JsValue) -> Result<Options, JsValue> {
+    utils::from_js(option_input)
+        .map_err(|e| JsValue::from(format!("invalid options: {}", e).as_str()))
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Options {
-    pub canvas_id: String,
-    pub disk_num: Option<u32>,
-    pub width: Option<u32>,
-    pub height: Option<u32>,
-    pub disk_size: Option<f64>,
-    pub collision: Option<bool>,
+/**
+ * Web Worker 内で OffscreenCanvas を使って初期化する
+ * canvas は canvas.transferControlToOffscreen() で Worker に渡したもの (options.canvas_id は使わない)
+ */
+#[wasm_bindgen]
+pub fn init_gl_offscreen(
+    canvas: web_sys::OffscreenCanvas,
+    option_input: JsValue,
+) -> Result<Screen, JsValue> {
+    create_screen(
+        parse_options(&option_input)?,
+        ScreenTarget::Offscreen(canvas),
+    )
 }
 
 /**
- * WebGLContextの初期化処理
+ * WebGLContextの初期化処理 (基本的なオプションを引数で渡す。その他は既定値)
  */
 #[wasm_bindgen]
-pub fn init_gl(option_input: JsValue) -> Screen {
-    let options: Options = option_input.into_serde().unwrap();
-    let canvas_id = options.canvas_id;
+pub fn init_gl_with(
+    canvas_id: &str,
+    disk_num: u32,
+    width: u32,
+    height: u32,
+    disk_size: f64,
+) -> Result<Screen, JsValue> {
+    create_screen(
+        Options {
+            canvas_id: canvas_id.to_string(),
+            disk_num: Some(disk_num),
+            width: Some(width),
+            height: Some(height),
+            disk_size: Some(disk_size),
+            ..Options::default()
+        },
+        ScreenTarget::CanvasId,
+    )
+}
+
+/**
+ * create_screen で描画する先
+ */
+enum ScreenTarget {
+    // options.canvas_id の canvas 要素
+    CanvasId,
+    // Worker に転送された OffscreenCanvas
+    Offscreen(web_sys::OffscreenCanvas),
+    // 呼び出し側が取得済みのコンテキスト (その canvas に描く)
+    Context(GlContext),
+}
+
+/**
+ * perf_marks を指定したときだけ作る (指定しなければ Performance API は呼ばない)
+ */
+fn perf_marks(options: &Options) -> Option<PerfMarks> {
+    if !options.perf_marks.unwrap_or(false) {
+        return None;
+    }
+    let perf_marks = PerfMarks::new(&options.canvas_id);
+    if perf_marks.is_none() {
+        warn!("perf_marks has no effect: the Performance API is not available");
+    }
+    perf_marks
+}
+
+fn create_screen(options: Options, target: ScreenTarget) -> Result<Screen, JsValue> {
+    utils::set_panic_hook();
+    options.validate().map_err(|e| JsValue::from(e.as_str()))?;
+    // debug を指定しなければ console の出力レベルは変えず、GL エラーも調べない
+    let log_level = match options.debug.as_deref() {
+        Some(level) => {
+            let level = logger::parse_level(level).map_err(|e| JsValue::from(e.as_str()))?;
+            logger::set_level(level);
+            level
+        }
+        None => Level::Warn,
+    };
+    let canvas_id = options.canvas_id.clone();
     let width = options.width.unwrap_or(500);
     let height = options.height.unwrap_or(500);
-    let disk_num = options.disk_num.unwrap_or(100);
-    let disk_size = options.disk_size.unwrap_or(32.);
-
-    let context = dom_utils::get_webgl_context_by_id(canvas_id.as_str(), width, height).unwrap();
-    let program = dom_utils::create_program(&context).unwrap();
-    context.use_program(Some(&program));
+    let render_mode = RenderMode::parse(options.render_mode.as_deref().unwrap_or("points"))
+        .map_err(|e| JsValue::from(e.as_str()))?;
 
-    let disks = init_disks(disk_num, width, height);
-    let attrib_coords = context.get_attrib_location(&program, "a_coords");
-    let buffer_coords = context.create_buffer().unwrap();
-    let attrib_color = context.get_attrib_location(&program, "a_color");
-    let buffer_color = context.create_buffer().unwrap();
-    let uniform_height = context.get_uniform_location(&program, "u_height").unwrap();
-    let uniform_width = context.get_uniform_location(&program, "u_width").unwrap();
-    let uniform_point_size = context
-        .get_uniform_location(&program, "u_pointsize")
-        .unwrap();
-    context.uniform1f(Some(&uniform_height), width as f32);
-    context.uniform1f(Some(&uniform_width), height as f32);
-
-    // ランダム生成した浮動小数点値を1diskあたりに3値(rgb)割り当てる
-    let mut random = rand::thread_rng();
-    let color_buffer_array = (0..(disk_num * 3))
-        .into_iter()
-        .map(|_| random.gen_range(0., 1.) as f32)
-        .collect::<Vec<f32>>();
-    context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&buffer_color));
-    unsafe {
-        context.buffer_data_with_array_buffer_view(
-            WebGlRenderingContext::ARRAY_BUFFER,
-            &js_sys::Float32Array::view(color_buffer_array.as_slice()), //
-            WebGlRenderingContext::STREAM_DRAW,
-        )
+    let background = match options.background.as_ref() {
+        Some(background) => background
+            .to_rgba()
+            .map_err(|e| JsValue::from(e.as_str()))?,
+        None => BACKGROUND,
+    };
+    let transparent = background[3] < 1.;
+    let requested = options.context_attributes.clone().unwrap_or_default();
+    for key in requested.unknown.keys() {
+        warn!("ignoring unknown context attribute: {}", key);
     }
-    context.vertex_attrib_pointer_with_f64(
-        attrib_color as u32,
-        3,
-        WebGlRenderingContext::FLOAT,
-        false,
-        0,
-        0.,
+    let context_attributes = dom_utils::ContextAttributes {
+        // trail は前のフレームに重ねて描くので、指定によらず保持する
+        preserve_drawing_buffer: options.trail.is_some()
+            || options.preserve_drawing_buffer.unwrap_or(false)
+            || requested.preserve_drawing_buffer.unwrap_or(false),
+        alpha: requested.alpha.unwrap_or(transparent),
+        antialias: requested.antialias,
+        premultiplied_alpha: requested.premultiplied_alpha,
+        power_preference: requested.power_preference,
+        desynchronized: requested.desynchronized,
+    };
+    let pixel_ratio = match options.dpr {
+        Some(dpr) => dpr,
+        None if options.use_device_pixel_ratio.unwrap_or(true) => dom_utils::device_pixel_ratio(),
+        None => 1.,
+    };
+    // OffscreenCanvas も EventTarget なので Worker 内で addEventListener できる
+    let canvas_target: Option<web_sys::EventTarget> = match &target {
+        ScreenTarget::CanvasId => dom_utils::canvas(&canvas_id).map(Into::into),
+        ScreenTarget::Offscreen(canvas) => Some(canvas.clone().into()),
+        ScreenTarget::Context(context) => context
+            .canvas()
+            .and_then(|canvas| canvas.dyn_into::<web_sys::EventTarget>().ok()),
+    };
+    let emit_events = options.emit_events.unwrap_or(false);
+    let collision_target = canvas_target.clone().filter(|_| emit_events);
+    let context = match target {
+        ScreenTarget::Offscreen(canvas) => dom_utils::get_webgl_context_offscreen(
+            &canvas,
+            width,
+            height,
+            pixel_ratio,
+            &context_attributes,
+        ),
+        ScreenTarget::CanvasId => dom_utils::get_webgl_context_by_id(
+            canvas_id.as_str(),
+            width,
+            height,
+            pixel_ratio,
+            &context_attributes,
+        ),
+        ScreenTarget::Context(context) => {
+            dom_utils::adopt_webgl_context(context, width, height, pixel_ratio)
+        }
+    }
+    .map_err(|e| JsValue::from(e.as_str()))?;
+    tagged!(
+        Level::Debug,
+        Subsystem::Init,
+        "webgl backend: {}",
+        context.backend()
     );
+    let world = World::build(&options, width, height).map_err(|e| JsValue::from(e.as_str()))?;
+    let largest_size = world.largest_size();
+    if render_mode == RenderMode::Points && largest_size * pixel_ratio > context.max_point_size() {
+        tagged!(
+            Level::Warn,
+            Subsystem::Init,
+            "disk_size {} exceeds the point size limit {}; use render_mode: \"quads\"",
+            largest_size,
+            context.max_point_size() / pixel_ratio
+        );
+    }
+    if render_mode == RenderMode::Instanced {
+        // points との比較のため、points で描ける大きさの上限を出しておく
+        tagged!(
+            Level::Info,
+            Subsystem::Init,
+            "instanced renderer; the point size limit is {} (disk_size up to {})",
+            context.max_point_size(),
+            context.max_point_size() / pixel_ratio
+        );
+    }
+    let antialias = options.antialias.unwrap_or(false);
+    let blend_mode = BlendMode::parse(options.blend_mode.as_deref().unwrap_or("normal"))
+        .map_err(|e| JsValue::from(e.as_str()))?;
+    let shape = Shape::parse(
+        options.shape.as_deref().unwrap_or("circle"),
+        options.ring_thickness,
+    )
+    .map_err(|e| JsValue::from(e.as_str()))?;
+    let shader_sources = dom_utils::ShaderSources {
+        vertex: options.vertex_shader.clone(),
+        fragment: options.fragment_shader.clone(),
+        antialias,
+    };
+    let points = create_point_program(&context, &shader_sources, width as f64, height as f64)
+        .map_err(|e| JsValue::from(e.as_str()))?;
+
+    let buffer_vertices = DynamicBuffer::new(&context).map_err(|e| JsValue::from(e.as_str()))?;
+
+    let trail_pass = TrailPass::new(&context).map_err(|e| JsValue::from(e.as_str()))?;
+    let circles = match render_mode {
+        RenderMode::Points => None,
+        RenderMode::Circles | RenderMode::Instanced => Some(
+            CirclePass::new(
+                &context,
+                width,
+                height,
+                antialias,
+                render_mode == RenderMode::Instanced,
+            )
+            .map_err(|e| JsValue::from(e.as_str()))?,
+        ),
+    };
 
-    Screen {
+    let disk_num = world.sim.disk_count();
+    let adaptive = options
+        .adaptive_quality(disk_num)
+        .map_err(|e| JsValue::from(e.as_str()))?;
+    let flash = options
+        .bounce_flash()
+        .map_err(|e| JsValue::from(e.as_str()))?;
+    let mut scene = Scene {
         gl: context,
-        disks,
-        disk_size,
-        disk_num,
-        width,
-        height,
-        uniform_point_size,
-        attrib_coords,
-        buffer_coords,
-        attrib_color,
+        program: points.program,
+        uniform_point_size: points.uniform_point_size,
+        attrib_coords: points.attrib_coords,
+        buffer_vertices,
+        attrib_color: points.attrib_color,
+        attrib_scale: points.attrib_scale,
+        color_mode: world.color_mode,
+        min_velocity: world.min_velocity,
+        max_velocity: world.max_velocity,
+        cycle_speed: options.cycle_speed.unwrap_or(DEFAULT_CYCLE_SPEED),
+        cycle_phase: 0.,
+        velocities: world.velocities,
+        rng: world.rng,
+        random_colors: world.random_colors,
+        disk_alphas: Vec::with_capacity(disk_num as usize),
+        disk_alpha: options.disk_alpha.unwrap_or(1.) as f32,
+        translucent: false,
+        disk_layers: Vec::new(),
+        disk_hues: DiskHues::default(),
+        flash,
+        tracker: DiskTracker::default(),
+        static_palette: world.static_palette,
+        palette: world.palette,
+        groups: world.groups,
+        mass_from_radius: world.mass_from_radius,
+        colors: Vec::new(),
+        colors_dirty: true,
+        positions: Vec::with_capacity(disk_num as usize * 2),
+        positions_generation: 0,
+        positions_location: (0, 0, 0),
+        scales: Vec::with_capacity(disk_num as usize),
+        vertices: Vec::with_capacity(disk_num as usize * VERTEX_FLOATS),
+        circles,
+        sprite: None,
+        overlay: None,
+        overlay_on: false,
+        velocity: None,
+        velocity_on: false,
+        velocity_scale: options.velocity_scale.unwrap_or(10.),
+        markers: None,
+        markers_on: false,
+        outline: None,
+        outline_on: false,
+        obstacle_outline: None,
+        obstacles_on: false,
+        background,
+        view: View::default(),
+        transparent,
+        trail: 0.,
+        preserve_drawing_buffer: context_attributes.preserve_drawing_buffer,
+        trail_pass,
+        needs_clear: true,
+        antialias,
+        opacity: 1.,
+        blend_mode,
+        shape,
+        uniform_alpha: points.uniform_alpha,
+        uniform_glow: points.uniform_glow,
+        draw_uniforms: points.uniforms,
+        sim: world.sim,
+        timer: stats::FrameTimer::default(),
+        collect_stats: options.collect_stats.unwrap_or(true),
+        perf_marks: perf_marks(&options),
+        log_level,
+        paused: false,
+        hidden: false,
+        auto_resize: options.auto_resize.unwrap_or(false),
+        pending_resize: None,
+        queued_impulses: Vec::new(),
+        resize_watcher: None,
+        fixed_step: FixedStep::default(),
+        frame_limiter: FrameLimiter::new(options.target_fps.unwrap_or(0)),
+        adaptive,
+        previous_positions: Vec::new(),
+        interpolation: None,
+        released: false,
+        pixel_ratio,
+        recorder: None,
+        stopped_recording: None,
+        playback: None,
+        tick: 0,
+        input_recorder: None,
+        replay: None,
+        emitter: world.emitter,
+        time_scale: 1.,
+        event_callback: None,
+        wall_callback: None,
+        collision_callback: None,
+        collision_target,
+        event_mask: EventMask::ALL,
+        pending_events: EventQueue::new(
+            options
+                .max_events_per_frame
+                .unwrap_or(DEFAULT_MAX_EVENTS_PER_FRAME),
+        ),
+        shader_sources,
+        texture: None,
+        context_lost: false,
+        context_callback: None,
+        context_listeners: Vec::new(),
+        views: Vec::new(),
+        next_view_id: 0,
+        options: options.clone(),
+    };
+    scene.fill_base_colors();
+    scene.apply_event_mask();
+    scene.set_trail(options.trail.unwrap_or(0.));
+    scene.set_opacity(options.opacity.unwrap_or(1.))?;
+    scene.set_show_attractors(options.show_attractors.unwrap_or(false));
+    scene.set_debug_overlay(options.show_velocity.unwrap_or(false));
+    scene.set_show_arena(options.show_arena.unwrap_or(false));
+    scene.set_show_obstacles(options.show_obstacles.unwrap_or(false));
+    if let Some(url) = options.texture_url.as_deref() {
+        scene.load_texture(url)?;
+    }
+    let scene = Rc::new(RefCell::new(scene));
+    let canvas_element = canvas_target
+        .clone()
+        .and_then(|target| target.dyn_into::<web_sys::HtmlCanvasElement>().ok());
+    if let Some(target) = canvas_target {
+        let mut listeners = watch_context(&scene, &target)?;
+        if options.touch_input.unwrap_or(false) {
+            match target.clone().dyn_into::<web_sys::HtmlCanvasElement>() {
+                Ok(canvas) => listeners.extend(watch_touches(&scene, &canvas)?),
+                Err(_) => warn!("touch_input is not supported on an OffscreenCanvas"),
+            }
+        }
+        if options.spawn_on_click.unwrap_or(false) {
+            match target.dyn_into::<web_sys::HtmlCanvasElement>() {
+                Ok(canvas) => listeners.push(spawn_on_click(&scene, &canvas)?),
+                Err(_) => warn!("spawn_on_click is not supported on an OffscreenCanvas"),
+            }
+        }
+        scene.borrow_mut().context_listeners = listeners;
+    }
+    if options.pause_when_hidden.unwrap_or(false) {
+        match dom_utils::document() {
+            Some(document) => {
+                let listener = watch_visibility(&scene, &document)?;
+                scene.borrow_mut().context_listeners.push(listener);
+            }
+            None => warn!("pause_when_hidden needs a document (not available in a worker)"),
+        }
+    }
+    if options.auto_resize.unwrap_or(false) {
+        match canvas_element {
+            Some(canvas) => {
+                let watcher = watch_canvas_size(&scene, &canvas)?;
+                scene.borrow_mut().resize_watcher = Some(watcher);
+            }
+            None => {
+                warn!("auto_resize needs a <canvas> element (not available for an OffscreenCanvas)")
+            }
+        }
+    }
+    Ok(Screen {
+        scene,
+        animation: None,
+    })
+}
+
+/**
+ * コンテキストの消失・復元を監視する
+ * webglcontextlost で既定の動作を止めておかないと、ブラウザは復元しない
+ */
+fn watch_context(
+    scene: &Rc<RefCell<Scene>>,
+    target: &web_sys::EventTarget,
+) -> Result<Vec<dom_utils::EventListener>, JsValue> {
+    let weak = Rc::downgrade(scene);
+    let lost = dom_utils::EventListener::new(target, "webglcontextlost", move |event| {
+        event.prevent_default();
+        if let Some(scene) = weak.upgrade() {
+            warn!("webgl context lost");
+            scene.borrow_mut().context_lost = true;
+            notify_context_change(&scene, "lost");
+        }
+    })?;
+    let weak = Rc::downgrade(scene);
+    let restored = dom_utils::EventListener::new(target, "webglcontextrestored", move |_| {
+        if let Some(scene) = weak.upgrade() {
+            let result = scene.borrow_mut().restore_context();
+            match result {
+                Ok(()) => {
+                    info!("webgl context restored");
+                    notify_context_change(&scene, "restored");
+                }
+                Err(e) => error!("failed to restore webgl context: {}", e),
+            }
+        }
+    })?;
+    Ok(vec![lost, restored])
+}
+
+/**
+ * document の visibilitychange を監視し、非表示の間はフレームを止める
+ * リスナーは dispose で外す
+ */
+fn watch_visibility(
+    scene: &Rc<RefCell<Scene>>,
+    document: &web_sys::Document,
+) -> Result<dom_utils::EventListener, JsValue> {
+    scene.borrow_mut().set_hidden(document.hidden());
+    let weak = Rc::downgrade(scene);
+    let element = document.clone();
+    dom_utils::EventListener::new(document, "visibilitychange", move |_| {
+        if let Some(scene) = weak.upgrade() {
+            scene.borrow_mut().set_hidden(element.hidden());
+        }
+    })
+}
+
+/**
+ * canvas の CSS の大きさを監視し、変わったら次のフレームで描画バッファ・viewport・ディスクの範囲を合わせる
+ * 大きさは CSS (width: 100% など) で決めること。指定がないと描画バッファに合わせて広がってしまう
+ * 監視は dispose で止める
+ */
+fn watch_canvas_size(
+    scene: &Rc<RefCell<Scene>>,
+    canvas: &web_sys::HtmlCanvasElement,
+) -> Result<dom_utils::ResizeWatcher, JsValue> {
+    dom_utils::release_css_size(&scene.borrow().gl);
+    let weak = Rc::downgrade(scene);
+    dom_utils::ResizeWatcher::new(canvas, move |width, height| {
+        let width = width.round() as u32;
+        let height = height.round() as u32;
+        // display: none などで大きさがなくなった間は今の大きさのままにする
+        if width == 0 || height == 0 {
+            return;
+        }
+        if let Some(scene) = weak.upgrade() {
+            scene.borrow_mut().pending_resize = Some((width, height));
+        }
+    })
+}
+
+/**
+ * canvas をクリックした位置にディスクを足す
+ */
+fn spawn_on_click(
+    scene: &Rc<RefCell<Scene>>,
+    canvas: &web_sys::HtmlCanvasElement,
+) -> Result<dom_utils::EventListener, JsValue> {
+    let weak = Rc::downgrade(scene);
+    let element = canvas.clone();
+    dom_utils::EventListener::new(canvas, "click", move |event| {
+        let scene = match weak.upgrade() {
+            Some(scene) => scene,
+            None => return,
+        };
+        let event = match event.dyn_into::<web_sys::MouseEvent>() {
+            Ok(event) => event,
+            Err(_) => return,
+        };
+        let mut scene = scene.borrow_mut();
+        let (width, height) = (scene.sim.width(), scene.sim.height());
+        if let Some((x, y)) = dom_utils::client_to_canvas(&element, &event, width, height) {
+            let (x, y) = scene.screen_to_world(x, y);
+            scene.suspend_adaptive();
+            scene.spawn_at(x, y, None);
+        }
+    })
+}
+
+/**
+ * canvas のタッチを監視し、触れている指ごとに set_touch_points の点を置く
+ * 毎回 event.touches() (まだ触れている指) から作り直すので、離した指の点は残らない
+ * ページがスクロールしないよう既定の動作は止める
+ */
+fn watch_touches(
+    scene: &Rc<RefCell<Scene>>,
+    canvas: &web_sys::HtmlCanvasElement,
+) -> Result<Vec<dom_utils::EventListener>, JsValue> {
+    let _ = canvas.style().set_property("touch-action", "none");
+    let mut listeners = Vec::new();
+    for name in ["touchstart", "touchmove", "touchend", "touchcancel"] {
+        let weak = Rc::downgrade(scene);
+        let element = canvas.clone();
+        let mut points = Vec::new();
+        listeners.push(dom_utils::EventListener::new(canvas, name, move |event| {
+            event.prevent_default();
+            let scene = match weak.upgrade() {
+                Some(scene) => scene,
+                None => return,
+            };
+            let event = match event.dyn_into::<web_sys::TouchEvent>() {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            let mut scene = scene.borrow_mut();
+            let (width, height) = (scene.sim.width(), scene.sim.height());
+            let touches = event.touches();
+            points.clear();
+            for index in 0..touches.length() {
+                let touch = match touches.get(index) {
+                    Some(touch) => touch,
+                    None => continue,
+                };
+                if let Some((x, y)) = dom_utils::client_point_to_canvas(
+                    &element,
+                    touch.client_x() as f64,
+                    touch.client_y() as f64,
+                    width,
+                    height,
+                ) {
+                    let (x, y) = scene.screen_to_world(x, y);
+                    points.extend([x, y]);
+                }
+            }
+            if let Err(e) = scene.set_touch_points(&points) {
+                error!("failed to set touch points: {}", e);
+            }
+        })?);
+    }
+    Ok(listeners)
+}
+
+fn notify_context_change(scene: &RefCell<Scene>, state: &str) {
+    let callback = scene.borrow().context_callback.clone();
+    if let Some(callback) = callback {
+        if let Err(e) = callback.call1(&JsValue::NULL, &JsValue::from(state)) {
+            error!("context callback threw: {:?}", e);
+        }
     }
 }
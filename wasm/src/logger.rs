@@ -0,0 +1,266 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use wasm_bindgen::prelude::*;
+use web_sys::console;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug = 0,
+    Info = 1,
+    Warn = 2,
+    Error = 3,
+    Off = 4,
+}
+
+impl Level {
+    pub fn parse(level: &str) -> Option<Level> {
+        match level.to_ascii_lowercase().as_str() {
+            "debug" | "trace" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            "off" => Some(Level::Off),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Options.debug / Screen::set_log_level の値を読む ("trace" は "debug" と同じ)
+ */
+pub fn parse_level(level: &str) -> Result<Level, String> {
+    Level::parse(level).ok_or_else(|| {
+        format!(
+            "unknown log level: {} (expected \"off\", \"error\", \"warn\", \"info\", \"debug\" or \"trace\")",
+            level
+        )
+    })
+}
+
+/**
+ * ログを出した処理の区分。メッセージの先頭に [init] などの形で付ける
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    Init,
+    Physics,
+    Render,
+}
+
+impl Subsystem {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Subsystem::Init => "init",
+            Subsystem::Physics => "physics",
+            Subsystem::Render => "render",
+        }
+    }
+}
+
+// 同じ種類のメッセージを出す間隔の下限 (ms)
+pub const THROTTLE_MS: f64 = 1000.;
+// これを超えたら間隔を過ぎたメッセージの記録を捨てる
+const THROTTLE_ENTRIES: usize = 256;
+
+/**
+ * 毎フレーム出るような同じ種類のメッセージを THROTTLE_MS に1回までに抑える
+ * 区分と書式の文字列で区別するので、埋め込んだ値 (ディスクの数など) が毎回違っても同じメッセージとして数える
+ */
+#[derive(Debug, Default)]
+pub struct LogThrottle {
+    // (区分, 書式) -> (最後に出した時刻, それ以降に抑えた回数)
+    entries: HashMap<(Subsystem, &'static str), (f64, u32)>,
+}
+
+impl LogThrottle {
+    /**
+     * now_ms に subsystem の template の書式のメッセージを出してよければ、前回出してから抑えた回数を返す (出さない場合は None)
+     */
+    pub fn check(
+        &mut self,
+        subsystem: Subsystem,
+        template: &'static str,
+        now_ms: f64,
+    ) -> Option<u32> {
+        let key = (subsystem, template);
+        if let Some((last, suppressed)) = self.entries.get_mut(&key) {
+            if now_ms - *last < THROTTLE_MS {
+                *suppressed += 1;
+                return None;
+            }
+            let count = *suppressed;
+            *last = now_ms;
+            *suppressed = 0;
+            return Some(count);
+        }
+        if self.entries.len() >= THROTTLE_ENTRIES {
+            self.entries
+                .retain(|_, (last, _)| now_ms - *last < THROTTLE_MS);
+        }
+        self.entries.insert(key, (now_ms, 0));
+        Some(0)
+    }
+}
+
+thread_local! {
+    static THROTTLE: RefCell<LogThrottle> = RefCell::new(LogThrottle::default());
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/**
+ * 指定したレベルのログが出力対象かどうか
+ */
+pub fn enabled(level: Level) -> bool {
+    ENABLED.load(Ordering::Relaxed)
+        && level != Level::Off
+        && level as u8 >= LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn log(level: Level, message: &str) {
+    if !enabled(level) {
+        return;
+    }
+    let message = JsValue::from(message);
+    match level {
+        Level::Debug => console::debug_1(&message),
+        Level::Info => console::info_1(&message),
+        Level::Warn => console::warn_1(&message),
+        Level::Error => console::error_1(&message),
+        Level::Off => {}
+    }
+}
+
+/**
+ * 区分のタグを付けて出力する
+ */
+pub fn log_tagged(level: Level, subsystem: Subsystem, message: &str) {
+    if !enabled(level) {
+        return;
+    }
+    log(level, &format!("[{}] {}", subsystem.tag(), message));
+}
+
+/**
+ * log_tagged と同じだが、同じ書式 template のメッセージは THROTTLE_MS に1回までにし、抑えた回数を添える
+ * message は template に値を埋め込んだもの
+ */
+pub fn log_throttled(
+    level: Level,
+    subsystem: Subsystem,
+    template: &'static str,
+    message: &str,
+    now_ms: f64,
+) {
+    if !enabled(level) {
+        return;
+    }
+    let message = format!("[{}] {}", subsystem.tag(), message);
+    match THROTTLE.with(|throttle| throttle.borrow_mut().check(subsystem, template, now_ms)) {
+        Some(0) => log(level, &message),
+        Some(suppressed) => log(
+            level,
+            &format!("{} ({} similar messages suppressed)", message, suppressed),
+        ),
+        None => {}
+    }
+}
+
+// レベルが無効な場合は `format!` 自体を評価しない
+macro_rules! debug {
+    ( $( $t:tt )* ) => {
+        if $crate::logger::enabled($crate::logger::Level::Debug) {
+            $crate::logger::log($crate::logger::Level::Debug, &format!( $( $t )* ));
+        }
+    }
+}
+
+macro_rules! info {
+    ( $( $t:tt )* ) => {
+        if $crate::logger::enabled($crate::logger::Level::Info) {
+            $crate::logger::log($crate::logger::Level::Info, &format!( $( $t )* ));
+        }
+    }
+}
+
+macro_rules! warn {
+    ( $( $t:tt )* ) => {
+        if $crate::logger::enabled($crate::logger::Level::Warn) {
+            $crate::logger::log($crate::logger::Level::Warn, &format!( $( $t )* ));
+        }
+    }
+}
+
+macro_rules! error {
+    ( $( $t:tt )* ) => {
+        if $crate::logger::enabled($crate::logger::Level::Error) {
+            $crate::logger::log($crate::logger::Level::Error, &format!( $( $t )* ));
+        }
+    }
+}
+
+/**
+ * 区分のタグを付けて出力する。`tagged!(Level::Info, Subsystem::Init, "...", ...)`
+ */
+macro_rules! tagged {
+    ( $level:expr, $subsystem:expr, $( $t:tt )* ) => {
+        if $crate::logger::enabled($level) {
+            $crate::logger::log_tagged($level, $subsystem, &format!( $( $t )* ));
+        }
+    }
+}
+
+/**
+ * 毎フレーム呼ばれる処理から使う。`throttled!(Level::Warn, Subsystem::Render, "...", ...)`
+ */
+macro_rules! throttled {
+    ( $level:expr, $subsystem:expr, $template:literal $( $t:tt )* ) => {
+        if $crate::logger::enabled($level) {
+            $crate::logger::log_throttled(
+                $level,
+                $subsystem,
+                $template,
+                &format!( $template $( $t )* ),
+                $crate::dom_utils::now(),
+            );
+        }
+    }
+}
+
+/**
+ * ログレベルの変更 ("debug" | "trace" | "info" | "warn" | "error" | "off")
+ */
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) {
+    match Level::parse(level) {
+        Some(level) => set_level(level),
+        None => warn!("unknown log level: {}", level),
+    }
+}
+
+/**
+ * ログ出力全体の有効・無効を切り替える (本番環境での抑制用)
+ */
+#[wasm_bindgen]
+pub fn set_log_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/**
+ * レベルを指定してログを出力する ("debug" | "info" | "log" | "warn" | "error")
+ * 不明なレベルは "info" として扱う
+ */
+#[wasm_bindgen]
+pub fn log_message(level: &str, msg: &str) {
+    let level = match level {
+        "log" => Level::Info,
+        _ => Level::parse(level).unwrap_or(Level::Info),
+    };
+    log(level, msg);
+}
@@ -0,0 +1,96 @@
+use crate::simulation::Disk;
+use serde::{Deserialize, Serialize};
+
+// 1ステップの中で合体を繰り返す回数の上限 (合体したディスクがさらに別のディスクに重なる場合)
+pub const MAX_MERGE_ROUNDS: usize = 8;
+
+/**
+ * ディスク同士が衝突したときの振る舞い (Options.on_collision)
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionResponse {
+    // 跳ね返る
+    #[default]
+    Bounce,
+    // 面積と運動量を保ったまま1つのディスクになる
+    Merge,
+}
+
+impl CollisionResponse {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollisionResponse::Bounce => "bounce",
+            CollisionResponse::Merge => "merge",
+        }
+    }
+
+    /**
+     * "bounce" | "merge"
+     */
+    pub fn parse(mode: &str) -> Result<CollisionResponse, String> {
+        match mode {
+            "bounce" => Ok(CollisionResponse::Bounce),
+            "merge" => Ok(CollisionResponse::Merge),
+            _ => Err(format!(
+                "unknown on_collision: {} (expected \"bounce\" or \"merge\")",
+                mode
+            )),
+        }
+    }
+}
+
+/**
+ * 1回の合体。survivor 番目のディスクが合体後のディスクになり、removed 番目のディスクが取り除かれた
+ * 番号は合体した時点のもの (直前までの合体で取り除いた分を詰めた後) なので、記録された順に
+ * 「survivor の色を removed の色と weight の割合で混ぜ、removed を取り除く」と色の配列も揃う
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Merge {
+    pub survivor: usize,
+    pub removed: usize,
+    // removed 側の面積の割合 (色を混ぜる割合)
+    pub weight: f32,
+}
+
+/**
+ * a と b を合体したディスク
+ * 半径は面積を保つ sqrt(ra² + rb²)、質量は和、位置と速度は質量で重み付けした平均 (運動量を保つ)
+ * グループなどの他の値は a のものを引き継ぐ。size は固有の大きさがない場合の半径
+ */
+pub fn merge_pair(a: &Disk, b: &Disk, size: f64) -> Disk {
+    let (ra, rb) = (a.size_or(size), b.size_or(size));
+    let mass = a.mass + b.mass;
+    let (wa, wb) = (a.mass / mass, b.mass / mass);
+    Disk {
+        x: a.x * wa + b.x * wb,
+        y: a.y * wa + b.y * wb,
+        cos: a.cos * wa + b.cos * wb,
+        sin: a.sin * wa + b.sin * wb,
+        mass,
+        size: Some(ra.hypot(rb)),
+        ..*a
+    }
+}
+
+/**
+ * a に b を合体するときに b の色を混ぜる割合 (面積の比)
+ */
+pub fn merge_weight(a: &Disk, b: &Disk, size: f64) -> f32 {
+    let (ra, rb) = (a.size_or(size), b.size_or(size));
+    (rb * rb / (ra * ra + rb * rb)) as f32
+}
+
+/**
+ * rgb の survivor 番目の色を removed 番目の色と weight の割合で混ぜ、removed 番目の色を取り除く (merge の記録を色に反映する)
+ */
+pub fn apply_merge_colors(colors: &mut Vec<f32>, merge: &Merge) {
+    let (s, r) = (merge.survivor * 3, merge.removed * 3);
+    if colors.len() < r + 3 || colors.len() < s + 3 {
+        return;
+    }
+    for channel in 0..3 {
+        colors[s + channel] += (colors[r + channel] - colors[s + channel]) * merge.weight;
+    }
+    colors.drain(r..r + 3);
+}
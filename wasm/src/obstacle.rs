@@ -0,0 +1,249 @@
+use crate::collision::Restitution;
+use crate::simulation::Disk;
+use serde::{Deserialize, Serialize};
+
+// 重なった障害物の間で押し出しを繰り返す回数の上限 (push_disk で置いたディスク用)
+const EJECT_PASSES: usize = 8;
+
+/**
+ * 障害物の形 (座標はワールド座標)
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum ObstacleShape {
+    // 左上 (x, y) と幅・高さ
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    Circle {
+        x: f64,
+        y: f64,
+        radius: f64,
+    },
+}
+
+/**
+ * add_obstacle_rect / add_obstacle_circle で置いた、ディスクが跳ね返る動かない形
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub id: u32,
+    #[serde(flatten)]
+    pub shape: ObstacleShape,
+}
+
+impl ObstacleShape {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            ObstacleShape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if !(x.is_finite() && y.is_finite()) {
+                    return Err(format!("obstacle position must be finite: ({}, {})", x, y));
+                }
+                if !(width.is_finite() && width > 0. && height.is_finite() && height > 0.) {
+                    return Err(format!(
+                        "obstacle size must be positive numbers: {} x {}",
+                        width, height
+                    ));
+                }
+            }
+            ObstacleShape::Circle { x, y, radius } => {
+                if !(x.is_finite() && y.is_finite()) {
+                    return Err(format!("obstacle position must be finite: ({}, {})", x, y));
+                }
+                if !(radius.is_finite() && radius > 0.) {
+                    return Err(format!(
+                        "obstacle radius must be a positive number: {}",
+                        radius
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * 中心 (x, y)・半径 size の円がこの形に食い込んでいれば、外向きの法線と食い込んだ深さを返す
+     * 矩形の外にある中心は一番近い点からの向き (角では角からの放射方向)、内側にある中心は一番近い辺の向きに押し出す
+     */
+    pub fn penetration(&self, x: f64, y: f64, size: f64) -> Option<(f64, f64, f64)> {
+        match *self {
+            ObstacleShape::Rect {
+                x: left,
+                y: top,
+                width,
+                height,
+            } => {
+                let (right, bottom) = (left + width, top + height);
+                let dx = x - x.clamp(left, right);
+                let dy = y - y.clamp(top, bottom);
+                if dx != 0. || dy != 0. {
+                    let distance = dx.hypot(dy);
+                    if distance >= size {
+                        return None;
+                    }
+                    return Some((dx / distance, dy / distance, size - distance));
+                }
+                // 辺の順 (左・右・上・下) に比べ、同じ距離なら先の辺へ出す
+                let faces = [
+                    (-1., 0., x - left),
+                    (1., 0., right - x),
+                    (0., -1., y - top),
+                    (0., 1., bottom - y),
+                ];
+                let (nx, ny, depth) = faces
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| a.2.total_cmp(&b.2))
+                    .unwrap_or(faces[0]);
+                Some((nx, ny, depth + size))
+            }
+            ObstacleShape::Circle {
+                x: cx,
+                y: cy,
+                radius,
+            } => {
+                let (dx, dy) = (x - cx, y - cy);
+                let distance = dx.hypot(dy);
+                let limit = radius + size;
+                if distance >= limit || distance.is_nan() {
+                    return None;
+                }
+                // ちょうど中心にあるディスクは方向が定まらないので上へ出す
+                if distance <= f64::EPSILON {
+                    return Some((0., -1., limit));
+                }
+                Some((dx / distance, dy / distance, limit - distance))
+            }
+        }
+    }
+
+    /**
+     * 中心 (x, y)・半径 size の円が食い込んでいれば、この形の外へ出る位置の候補 (食い込んでいなければ空)
+     * 一番浅く出る位置に加え、矩形では4つの辺それぞれのすぐ外も候補にする
+     */
+    pub fn exits(&self, x: f64, y: f64, size: f64) -> Vec<(f64, f64)> {
+        let (nx, ny, depth) = match self.penetration(x, y, size) {
+            Some(penetration) => penetration,
+            None => return Vec::new(),
+        };
+        let mut exits = vec![(x + nx * depth, y + ny * depth)];
+        if let ObstacleShape::Rect {
+            x: left,
+            y: top,
+            width,
+            height,
+        } = *self
+        {
+            exits.extend([
+                (left - size, y),
+                (left + width + size, y),
+                (x, top - size),
+                (x, top + height + size),
+            ]);
+        }
+        exits
+    }
+
+    /**
+     * 外形を LINE_LOOP で描くための頂点 [x0, y0, x1, y1, ...] を out に足し、頂点の数を返す (円は segments 個)
+     */
+    pub fn outline(&self, segments: usize, out: &mut Vec<f64>) -> usize {
+        match *self {
+            ObstacleShape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                out.extend([x, y, x + width, y, x + width, y + height, x, y + height]);
+                4
+            }
+            ObstacleShape::Circle { x, y, radius } => {
+                for i in 0..segments {
+                    let angle = std::f64::consts::PI * 2. * i as f64 / segments as f64;
+                    out.extend([x + radius * angle.cos(), y + radius * angle.sin()]);
+                }
+                segments
+            }
+        }
+    }
+}
+
+/**
+ * 障害物に食い込んだディスクを法線の向きに押し出し、近づく向きの速度の成分を restitution で反射する
+ * ディスクの半径は size (ディスク固有の大きさがあればそれ)。押し出したディスクごとに on_hit を呼ぶ
+ */
+pub fn bounce_off_obstacles(
+    disks: &mut [Disk],
+    obstacles: &[Obstacle],
+    size: f64,
+    restitution: Restitution,
+    mut on_hit: impl FnMut(usize, &Disk),
+) {
+    for (index, disk) in disks.iter_mut().enumerate() {
+        let radius = disk.size_or(size);
+        let mut hit = false;
+        for obstacle in obstacles {
+            let (nx, ny, depth) = match obstacle.shape.penetration(disk.x, disk.y, radius) {
+                Some(penetration) => penetration,
+                None => continue,
+            };
+            disk.x += nx * depth;
+            disk.y += ny * depth;
+            let normal_speed = disk.cos * nx + disk.sin * ny;
+            if normal_speed < 0. {
+                let reflected = -normal_speed * restitution.at(-normal_speed);
+                disk.cos += (reflected - normal_speed) * nx;
+                disk.sin += (reflected - normal_speed) * ny;
+            }
+            hit = true;
+        }
+        if hit {
+            on_hit(index, disk);
+        }
+    }
+}
+
+/**
+ * 障害物の中に置かれたディスクを、どの障害物にも重ならない一番近い位置へ出す (速度は変えない)
+ * 候補は重なった障害物の各辺 (円なら放射方向) のすぐ外で、どれも他の障害物に重なれば一番近い候補から探し直す
+ * 動かしたかどうかを返す
+ */
+pub fn eject_from_obstacles(disk: &mut Disk, obstacles: &[Obstacle], size: f64) -> bool {
+    let radius = disk.size_or(size);
+    let free = |x: f64, y: f64| {
+        obstacles
+            .iter()
+            .all(|o| o.shape.penetration(x, y, radius).is_none())
+    };
+    let origin = (disk.x, disk.y);
+    let distance = |&(x, y): &(f64, f64)| (x - origin.0).hypot(y - origin.1);
+    let mut position = origin;
+    for _ in 0..EJECT_PASSES {
+        if free(position.0, position.1) {
+            break;
+        }
+        let mut candidates: Vec<(f64, f64)> = obstacles
+            .iter()
+            .flat_map(|o| o.shape.exits(position.0, position.1, radius))
+            .collect();
+        candidates.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        match candidates.iter().find(|&&(x, y)| free(x, y)) {
+            Some(&exit) => position = exit,
+            None => match candidates.first() {
+                Some(&nearest) => position = nearest,
+                None => break,
+            },
+        }
+    }
+    (disk.x, disk.y) = position;
+    position != origin
+}
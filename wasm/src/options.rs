@@ -0,0 +1,1208 @@
+use crate::adaptive::{AdaptiveQuality, DEFAULT_ADAPTIVE_FPS};
+use crate::boids::{Behavior, BoidsParams};
+use crate::circles::RenderMode;
+use crate::color::{
+    self, ColorMode, ColorSpec, Palette, DEFAULT_CYCLE_SPEED, DEFAULT_MAX_VELOCITY,
+};
+use crate::dom_utils::{BlendMode, Shape};
+use crate::events::DEFAULT_MAX_EVENTS_PER_FRAME;
+use crate::flash::{BounceFlash, DEFAULT_FLASH_COLOR};
+use crate::logger;
+use crate::merge::CollisionResponse;
+use crate::simulation::{
+    Arena, Boundary, GroupSpec, SpawnPattern, VelocityDistribution, DEFAULT_POINTER_FORCE,
+    DEFAULT_POINTER_RADIUS, DEFAULT_SPEED_RANGE, FULL_ANGLE_RANGE,
+};
+use crate::utils;
+use crate::view::View;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/**
+ * init_gl に渡すオプション (JS のオブジェクト、または ScreenOptions で組み立てる)
+ * 知らないフィールド名 (diskNum など) はエラーにする
+ */
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Options {
+    // init_gl_offscreen では使わないので省略できる
+    #[serde(default)]
+    pub canvas_id: String,
+    pub disk_num: Option<u32>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub disk_size: Option<f64>,
+    pub collision: Option<bool>,
+    // collision: true で衝突したディスクを "bounce" (既定) 跳ね返すか、"merge" 面積と運動量を保って1つに合体するか
+    // 合体したディスクの色は2つの色を面積の比で混ぜたもの。数は min_disks まで減り、max_radius を超える組は合体しない
+    pub on_collision: Option<String>,
+    // on_collision: "merge" で合体後の半径の上限 (既定は制限なし)
+    pub max_radius: Option<f64>,
+    pub color_mode: Option<String>,
+    // "static" モードで順番に割り当てる色 (rgb 0.0 - 1.0)
+    pub static_palette: Option<Vec<[f32; 3]>>,
+    // ディスクの色を選ぶパレット "random" (既定。rgb を一様に選ぶ) | "viridis" | "warm" | "cool" | "pastel" | "mono"
+    // 後から足したディスクにも使い、"speed" モードでは遅い側から速い側への色の並びになる
+    pub palette: Option<String>,
+    // パレットの色 ["#rrggbb", ...] (指定すると palette より優先する。"speed" モードでは間を補間する)
+    pub palette_colors: Option<Vec<String>>,
+    // "speed" モードで最も遅い色 (青) になる速さ (既定 0.0)。これ以下の速さはすべて青になる
+    pub min_velocity: Option<f64>,
+    // "speed" モードで最も速い色 (赤) になる速さ (既定 4.0)。これ以上の速さはすべて赤になる
+    pub max_velocity: Option<f64>,
+    // "cycle" モードで色相を回す速さ (度 / 秒、既定 30。負で逆回り)。一時停止中は回らない
+    pub cycle_speed: Option<f64>,
+    // 背景色 ([r, g, b, a] (0.0 - 1.0) か "#rrggbb" などの文字列、既定は不透明の黒)
+    // a が 1.0 未満ならページの背景が透ける
+    pub background: Option<ColorSpec>,
+    // "points" (既定) | "circles" ("quads" とも書ける。gl_PointSize の上限を超える大きさも描ける)
+    // | "instanced" ("circles" と同じ見た目をインスタンス描画で描く)
+    #[serde(alias = "renderer")]
+    pub render_mode: Option<String>,
+    // "bounce" (既定) | "wrap"
+    pub boundary: Option<String>,
+    // "ballistic" (既定) | "boids" (近くのディスクと分離・整列・結合しながら群れで動き、壁の手前で向きを変える)
+    pub behavior: Option<String>,
+    // behavior: "boids" の {separation, alignment, cohesion, perception, max_speed} (省略した値は既定値)
+    pub boids: Option<BoidsParams>,
+    // ディスクを閉じ込める領域の形 "rect" (既定) | "circle" (円の内側で反射する。boundary は使わない)
+    pub arena: Option<String>,
+    // arena: "circle" の円の中心 [x, y] と半径 (既定は canvas に内接する最大の円)
+    pub arena_center: Option<[f64; 2]>,
+    pub arena_radius: Option<f64>,
+    // arena: "circle" の円の縁を線で表示する (既定 false)
+    pub show_arena: Option<bool>,
+    // Screen::add_obstacle_rect / add_obstacle_circle で置いた障害物の外形を線で表示する (既定 false)
+    pub show_obstacles: Option<bool>,
+    // 初期位置 "uniform" (既定。"random" も可) | "center" | "grid" | "ring" | "orbit" (placement とも書ける)
+    // いずれも disk_size の分だけ壁から離して置く
+    #[serde(alias = "placement")]
+    pub spawn: Option<String>,
+    // spawn: "ring" / "orbit" の円の半径 (既定は短辺の 1/4。壁にめり込む大きさは縮める)
+    pub ring_radius: Option<f64>,
+    // spawn で決めた位置の代わりに、互いに重ならず壁にもめり込まない位置へランダムに置く (既定 false)
+    // disk_num 個が収まらなければ init_gl がエラーになり、置ける数の上限を示す
+    pub no_initial_overlap: Option<bool>,
+    // 初期速度の範囲 [最小, 最大] (既定 [1.0, 4.0])
+    pub speed_range: Option<[f64; 2]>,
+    // 初期速度の範囲を個別に指定する (speed_range とは同時に使えない)
+    // max_speed を指定すると、以後も各ステップで速さを max_speed までに抑える
+    #[serde(alias = "speed_min")]
+    pub min_speed: Option<f64>,
+    #[serde(alias = "speed_max")]
+    pub max_speed: Option<f64>,
+    // 初期速度の向きの範囲 (ラジアン、既定 [0, 2π] で全方向)。速さとは別の乱数で選ぶ
+    #[serde(alias = "angle_min")]
+    pub min_angle: Option<f64>,
+    #[serde(alias = "angle_max")]
+    pub max_angle: Option<f64>,
+    // 初期速度の決め方 "uniform" (既定) | "legacy" (最初の版の決め方。速さ・向きの範囲は使わない)
+    // add_random_disks・randomize_velocities も同じ決め方で選ぶ
+    pub velocity_distribution: Option<String>,
+    // 残像の強さ (0.0 - 1.0)。背景色を不透明度 1.0 - trail で塗り重ねる
+    // 指定すると描画バッファを保持するコンテキストを作る
+    pub trail: Option<f64>,
+    // 描画バッファを保持するコンテキストを作る (既定 false、trail を指定した場合は常に true)
+    // 有効にすると capture_png / capture_rgba が描き直さずに、表示中のフレームをそのまま読み出す
+    pub preserve_drawing_buffer: Option<bool>,
+    // 指定するとディスクの初期状態と色を再現可能な乱数で生成する
+    pub seed: Option<u64>,
+    // 組み込みのシェーダの代わりに使うソース ("points" モードのみ)
+    // a_coords, a_color, u_width, u_height, u_pointsize を使っている必要がある
+    // u_view_offset (vec2)・u_view_scale (float) を宣言すると Screen::set_view の視点に追従する (組み込みのものを参照)
+    // a_color はディスクごとの rgba (vec4)。vec3 で宣言した場合は不透明度を使わない
+    pub vertex_shader: Option<String>,
+    // フラグメントシェーダは varying vec3 v_color (ディスクの色)、varying float v_alpha (ディスクの不透明度) と gl_PointCoord (点の中の 0.0 - 1.0 の座標) を使える
+    // 組み込みのものは Screen::default_fragment_shader で取得できる。コンパイルに失敗すると init_gl がエラーになる
+    pub fragment_shader: Option<String>,
+    // ディスクに貼る画像の URL ("points" モードのみ)
+    pub texture_url: Option<String>,
+    // ディスクの縁のおよそ1ピクセルを smoothstep でぼかす (既定 false。有効にすると描画時にブレンドする)
+    // false なら縁で discard するので、塗られるピクセルは正確に円の内側だけになる。"smooth_edges" とも書ける
+    // fragment_shader を指定した場合は、そのシェーダが出力するアルファでブレンドする
+    #[serde(alias = "smooth_edges")]
+    pub antialias: Option<bool>,
+    // 衝突のたびに canvas へ "disk-collision" の CustomEvent を送る (既定 false)
+    // detail は {type: "collision", indices: [i, j], x, y, speed}。衝突の多い場面で DOM イベントを作り続けないよう既定では送らない
+    // 受け取るには {emit_events: true} を渡してから canvas.addEventListener("disk-collision", ...) する
+    // (コールバックだけでよければ set_on_collision を使う)
+    pub emit_events: Option<bool>,
+    // 1フレームにコールバック・canvas へ渡すイベントの上限 (既定 1000。超えたら古いものから捨てる)
+    pub max_events_per_frame: Option<u32>,
+    // フレームごとの処理時間・FPS を計測して stats() に含める (既定 true)
+    pub collect_stats: Option<bool>,
+    // 物理演算 (physics)・頂点バッファの転送 (buffer-upload)・描画 (draw-call) の区間を performance.mark / measure で囲む
+    // 名前は "<canvas_id>:physics" など (既定 false。false なら Performance API を呼ばない)
+    pub perf_marks: Option<bool>,
+    // start() のループで描くフレームを1秒あたりこの回数までにする (既定・0 で間引かない)
+    pub target_fps: Option<u32>,
+    // フレームの間隔と処理時間を2秒ごとに見直し、目標の FPS を下回ればディスクを後ろ (最後に足したもの) から減らし、
+    // 余裕があれば少しずつ戻す (既定 false。emitter とは同時に使えない)。今の数は Screen::disk_count で取れる
+    // add_disk・set_disk_num などで手動で数を変えると、しばらく見直しを止める
+    pub adaptive: Option<bool>,
+    // adaptive で保つ FPS (既定は target_fps、それも無ければ 60)
+    pub adaptive_target_fps: Option<u32>,
+    // adaptive で減らすとき・on_collision: "merge" で合体するときの下限 (既定 1)
+    pub min_disks: Option<u32>,
+    // canvas をクリックした位置にディスクを足す (既定 false。OffscreenCanvas では使えない)
+    pub spawn_on_click: Option<bool>,
+    // canvas のタッチを監視し、触れている指ごとに pointer_force で引き寄せる (既定 false。ページのスクロールは止める)
+    pub touch_input: Option<bool>,
+    // ページが非表示の間はシミュレーションと描画を止め、表示に戻ったら溜まった時間を捨てて再開する (既定 false)
+    // document の visibilitychange を監視する (Worker では使えない)
+    pub pause_when_hidden: Option<bool>,
+    // canvas の CSS の大きさ (width: 100% など) が変わったら、その大きさ * devicePixelRatio に描画バッファを合わせる
+    // (既定 false。大きさは CSS で決めること。width / height は最初の大きさとしてだけ使う)
+    pub auto_resize: Option<bool>,
+    // ログレベル "off" | "error" | "warn" | "info" | "debug" | "trace" (省略すると console の出力レベルは変えない)
+    // "info" 以上にすると描画のたびに GL のエラーを調べる。同じメッセージが毎フレーム出る場合は1秒に1回までにまとめる
+    pub debug: Option<String>,
+    // 最初にディスクを作らず、emit_origin から emit_rate の割合でディスクを出し、lifetime を過ぎたら消す (既定 false)
+    // 有効にすると disk_num・groups は使わない
+    pub emitter: Option<bool>,
+    // emitter モードで1秒あたりに出すディスクの数 (既定 30)
+    pub emit_rate: Option<f64>,
+    // emitter モードのディスクの寿命 (秒、既定 5)
+    pub lifetime: Option<f64>,
+    // emitter モードで同時に存在するディスクの上限 (既定 500。上限の間は出さない)
+    // adaptive では増やすときの上限 (既定は最初のディスクの数)
+    pub max_disks: Option<u32>,
+    // emitter モードでディスクを出す位置 [x, y] (既定は canvas の中央)
+    pub emit_origin: Option<[f64; 2]>,
+    // add_attractor で置いた引力点の位置を大きな点で表示する (既定 false)
+    pub show_attractors: Option<bool>,
+    // 各ディスクの中心から速度ベクトルの向きに線分を描く (既定 false。視点の移動・拡大にも追従する)
+    pub show_velocity: Option<bool>,
+    // 速度ベクトルの線分の長さの倍率 (既定 10。速度は1フレームあたりの移動量)
+    pub velocity_scale: Option<f64>,
+    // ディスクの不透明度 (0.0 - 1.0、既定 1.0)
+    pub opacity: Option<f64>,
+    // 壁・他のディスクに当たったディスクを flash_color に光らせ、この秒数をかけて元の色へ戻す (既定・0 で光らせない)
+    pub bounce_flash: Option<f64>,
+    // bounce_flash で光らせる色 ([r, g, b, a] か CSS の色。アルファは使わない。既定は白)
+    pub flash_color: Option<ColorSpec>,
+    // ディスクごとの不透明度の初期値 (0.0 - 1.0、既定 1.0)。Screen::set_disk_color_rgba で個別に変えられる
+    // opacity は全体に掛かり、こちらはディスクごとに重なった部分をブレンドして描く
+    pub disk_alpha: Option<f64>,
+    // "normal" (既定) | "additive" (重なった部分が明るくなり、組み込みのシェーダでは中心から縁へ暗くして柔らかく光らせる)
+    pub blend_mode: Option<String>,
+    // 組み込みのシェーダで描くディスクの形 "circle" (既定) | "square" | "ring" | "glow" (中心から縁へ透ける。ブレンドして描く)
+    pub shape: Option<String>,
+    // shape: "ring" の輪の太さ (半径に対する割合、0.0 より大きく 1.0 以下。既定 0.3)
+    pub ring_thickness: Option<f64>,
+    // 高解像度ディスプレイで devicePixelRatio 倍の描画バッファを使う (既定 true)
+    // Worker には devicePixelRatio が無いので、init_gl_offscreen では常に 1 倍になる
+    pub use_device_pixel_ratio: Option<bool>,
+    // 描画バッファの倍率を直接指定する (use_device_pixel_ratio より優先。弱い GPU で 1.0 に固定するなど)
+    // 座標は倍率によらず CSS の px のまま
+    pub dpr: Option<f64>,
+    // 大きさ・質量・色の違うディスクのグループ [{count, size, size_range, mass, color, palette, speed_range, collides}, ...]
+    // 指定した場合 disk_num は使わず、count の合計がディスクの数になる (Screen::group_counts で今の数を取れる)
+    pub groups: Option<Vec<GroupSpec>>,
+    // ディスクごとの大きさを [最小, 最大] から一様に選ぶ (size / size_range を指定していない groups にも適用する)
+    // 省略すると全ディスクが disk_size になる
+    pub disk_size_range: Option<[f64; 2]>,
+    // mass を省略したディスクの質量を 半径² にする
+    // 既定は大きさを指定したディスクがあれば (groups の size / size_range、disk_size_range) true、なければ false (全ディスク 1.0)
+    pub mass_from_radius: Option<bool>,
+    // 毎フレーム縦方向の速度へ加える値 (px / フレーム²、正で下向き。既定 0.0 で重力なし)
+    pub gravity: Option<f64>,
+    // 毎フレーム全ディスクの速度へ加える一定の力 [fx, fy] (px / フレーム²、風など。既定 [0, 0] で力なし)
+    // gravity と足し合わせて働く。壁に押し付けられたディスクは rest_speed の既定値でこの力も考慮して壁に沿って滑る
+    pub force: Option<(f64, f64)>,
+    // 毎フレーム速度に (1 - drag) を掛けて減速させる (0.0 - 1.0、既定 0.0 で減速しない)
+    pub drag: Option<f64>,
+    // 毎フレーム速度に加えるランダムな値 (正規分布) の標準偏差 (既定 0.0 で加えない。drag より先に加える)
+    pub jitter: Option<f64>,
+    // 壁で反射するときの反発係数 (0.0 - 1.0、既定 1.0 で速さを保つ)
+    pub wall_restitution: Option<f64>,
+    // ディスク同士の衝突の反発係数 (0.0 - 1.0、既定 1.0 で弾性衝突。以前の名前 restitution でも指定できる)
+    #[serde(alias = "restitution")]
+    pub collision_restitution: Option<f64>,
+    // これ未満の速さでぶつかったら跳ね返さずに止める (反発係数が 1.0 未満のときのみ)
+    // 既定は重力と force で1フレームに増える速さ (軸ごとの大きい方) の2倍で、床や壁に押し付けられたディスクが揺れ続けずに止まる
+    pub rest_speed: Option<f64>,
+    // Screen::set_pointer の位置へディスクを引き寄せる加速度 (px / フレーム²、負で遠ざける。既定 0.5)
+    pub pointer_force: Option<f64>,
+    // ポインタの力が届く半径 (px、既定 100)。中心から離れるほど弱まり、この距離で 0 になる
+    pub pointer_radius: Option<f64>,
+    // 座標・速度が NaN / 無限大になったディスクを中央に戻さず panic する (既定 false。開発用)
+    pub strict: Option<bool>,
+    // ステップごとに運動エネルギー・運動量を比べ、不変条件に反したら警告する (既定 false。開発用)
+    // 記録は Screen::last_invariant_report で取り出せる
+    pub check_invariants: Option<bool>,
+    // getContext に渡すコンテキスト属性 {antialias, alpha, premultipliedAlpha, preserveDrawingBuffer, powerPreference, desynchronized}
+    // 省略した属性はブラウザの既定 (alpha・preserveDrawingBuffer は background・trail から決める)
+    // 実際に得られた属性は Screen::context_attributes で確かめられる
+    pub context_attributes: Option<ContextAttributeOptions>,
+}
+
+/**
+ * Options.context_attributes (キーは getContext と同じ camelCase)
+ * 知らないキーはエラーにせず、init_gl で警告を出して無視する
+ */
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextAttributeOptions {
+    pub antialias: Option<bool>,
+    pub alpha: Option<bool>,
+    pub premultiplied_alpha: Option<bool>,
+    pub preserve_drawing_buffer: Option<bool>,
+    // "default" | "high-performance" | "low-power"
+    pub power_preference: Option<String>,
+    pub desynchronized: Option<bool>,
+    #[serde(flatten)]
+    pub unknown: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+// powerPreference に指定できる値
+const POWER_PREFERENCES: [&str; 3] = ["default", "high-performance", "low-power"];
+
+impl ContextAttributeOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.power_preference.as_deref() {
+            Some(preference) if !POWER_PREFERENCES.contains(&preference) => Err(format!(
+                "unknown powerPreference: {} (expected \"default\", \"high-performance\" or \"low-power\")",
+                preference
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+// init_gl でコンテキスト・シェーダ・イベントの監視を作るときにだけ使うキー (Screen を作った後は変えられない)
+pub const IMMUTABLE_OPTIONS: [&str; 15] = [
+    "canvas_id",
+    "render_mode",
+    "vertex_shader",
+    "fragment_shader",
+    "texture_url",
+    "antialias",
+    "context_attributes",
+    "preserve_drawing_buffer",
+    "emit_events",
+    "spawn_on_click",
+    "touch_input",
+    "pause_when_hidden",
+    "auto_resize",
+    "use_device_pixel_ratio",
+    "dpr",
+];
+
+// 最初のディスクを作るときにだけ使うキー (Screen::update_options では変えられず、reset_with で作り直す)
+pub const RESET_ONLY_OPTIONS: [&str; 15] = [
+    "seed",
+    "spawn",
+    "ring_radius",
+    "no_initial_overlap",
+    "groups",
+    "disk_size_range",
+    "mass_from_radius",
+    "disk_alpha",
+    "emitter",
+    "emit_rate",
+    "lifetime",
+    "max_disks",
+    "emit_origin",
+    "adaptive",
+    "adaptive_target_fps",
+];
+
+fn check_positive(name: &str, value: Option<f64>) -> Result<(), String> {
+    match value {
+        Some(value) if !(value.is_finite() && value > 0.) => {
+            Err(format!("{} must be a positive number: {}", name, value))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_unit(name: &str, value: Option<f64>) -> Result<(), String> {
+    match value {
+        Some(value) if !(0. ..=1.).contains(&value) => {
+            Err(format!("{} must be in [0, 1]: {}", name, value))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Options {
+    /**
+     * arena・arena_center・arena_radius から決まる領域の形
+     */
+    pub fn arena(&self) -> Result<Arena, String> {
+        Arena::parse(
+            self.arena.as_deref().unwrap_or("rect"),
+            self.arena_center,
+            self.arena_radius,
+        )
+    }
+
+    /**
+     * palette・palette_colors から決まるディスクの色の選び方
+     */
+    pub fn palette(&self) -> Result<Palette, String> {
+        match self.palette_colors.as_deref() {
+            Some(colors) => Palette::custom(colors),
+            None => Palette::parse(self.palette.as_deref().unwrap_or("random")),
+        }
+    }
+
+    /**
+     * bounce_flash を指定していれば、ディスクを光らせる設定 (0 なら None)
+     */
+    pub fn bounce_flash(&self) -> Result<Option<BounceFlash>, String> {
+        let duration = match self.bounce_flash {
+            Some(duration) if duration != 0. => duration,
+            _ => return Ok(None),
+        };
+        let highlight = match self.flash_color.as_ref() {
+            Some(color) => {
+                let [r, g, b, _] = color.to_rgba()?;
+                [r, g, b]
+            }
+            None => DEFAULT_FLASH_COLOR,
+        };
+        BounceFlash::new(duration, highlight).map(Some)
+    }
+
+    /**
+     * 初期速度の範囲 (speed_range、または min_speed / max_speed。省略した側は既定値)
+     */
+    pub fn speed_range(&self) -> [f64; 2] {
+        self.speed_range.unwrap_or([
+            self.min_speed.unwrap_or(DEFAULT_SPEED_RANGE[0]),
+            self.max_speed.unwrap_or(DEFAULT_SPEED_RANGE[1]),
+        ])
+    }
+
+    /**
+     * 初期速度の決め方 (velocity_distribution と速さ・向きの範囲)
+     */
+    pub fn velocities(&self) -> Result<VelocityDistribution, String> {
+        VelocityDistribution::parse(
+            self.velocity_distribution.as_deref().unwrap_or("uniform"),
+            self.speed_range(),
+            [
+                self.min_angle.unwrap_or(FULL_ANGLE_RANGE[0]),
+                self.max_angle.unwrap_or(FULL_ANGLE_RANGE[1]),
+            ],
+        )
+    }
+
+    /**
+     * 値の範囲・文字列のモード名・組み合わせを確認する
+     * init_gl (JS のオブジェクト) と ScreenOptions のどちらもここを通るので、同じ値は同じように扱われる
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err(String::from("width and height must be at least 1"));
+        }
+        check_positive("disk_size", self.disk_size)?;
+        check_positive("max_velocity", self.max_velocity)?;
+        check_positive("velocity_scale", self.velocity_scale)?;
+        if let Some(background) = self.background.as_ref() {
+            background.to_rgba()?;
+        }
+        Shape::parse(
+            self.shape.as_deref().unwrap_or("circle"),
+            self.ring_thickness,
+        )?;
+        if let Some(level) = self.debug.as_deref() {
+            logger::parse_level(level)?;
+        }
+        if let Some(attributes) = self.context_attributes.as_ref() {
+            attributes.validate()?;
+        }
+        color::validate_speed_range(
+            self.min_velocity.unwrap_or(0.),
+            self.max_velocity.unwrap_or(DEFAULT_MAX_VELOCITY),
+        )?;
+        if let Some(speed) = self.cycle_speed {
+            if !speed.is_finite() {
+                return Err(format!("cycle_speed must be a finite number: {}", speed));
+            }
+        }
+        check_positive("ring_radius", self.ring_radius)?;
+        check_positive("dpr", self.dpr)?;
+        check_unit("trail", self.trail)?;
+        check_unit("opacity", self.opacity)?;
+        if let Some(duration) = self.bounce_flash {
+            if !(duration.is_finite() && duration >= 0.) {
+                return Err(format!(
+                    "bounce_flash must be a non-negative number of seconds: {}",
+                    duration
+                ));
+            }
+        }
+        if let Some(color) = self.flash_color.as_ref() {
+            color.to_rgba()?;
+        }
+        check_unit("disk_alpha", self.disk_alpha)?;
+        if let Some(gravity) = self.gravity {
+            if !gravity.is_finite() {
+                return Err(format!("gravity must be a finite number: {}", gravity));
+            }
+        }
+        if let Some((fx, fy)) = self.force {
+            if !(fx.is_finite() && fy.is_finite()) {
+                return Err(format!("force must be finite numbers: ({}, {})", fx, fy));
+            }
+        }
+        check_unit("drag", self.drag)?;
+        if let Some(jitter) = self.jitter {
+            if !(jitter.is_finite() && jitter >= 0.) {
+                return Err(format!("jitter must be a non-negative number: {}", jitter));
+            }
+        }
+        CollisionResponse::parse(self.on_collision.as_deref().unwrap_or("bounce"))?;
+        check_positive("max_radius", self.max_radius)?;
+        check_positive("emit_rate", self.emit_rate)?;
+        check_positive("lifetime", self.lifetime)?;
+        if self.max_disks == Some(0) {
+            return Err(String::from("max_disks must be at least 1"));
+        }
+        if self.adaptive.unwrap_or(false) && self.emitter.unwrap_or(false) {
+            return Err(String::from("`adaptive` cannot be combined with `emitter`"));
+        }
+        if self.adaptive_target_fps == Some(0) {
+            return Err(String::from("adaptive_target_fps must be at least 1"));
+        }
+        if self.min_disks == Some(0) {
+            return Err(String::from("min_disks must be at least 1"));
+        }
+        if let (Some(min), Some(max)) = (self.min_disks, self.max_disks) {
+            if min > max {
+                return Err(format!(
+                    "min_disks must not exceed max_disks: {} > {}",
+                    min, max
+                ));
+            }
+        }
+        if let Some([x, y]) = self.emit_origin {
+            if !(x.is_finite() && y.is_finite()) {
+                return Err(format!("emit_origin must be finite: ({}, {})", x, y));
+            }
+        }
+        if let Some(force) = self.pointer_force {
+            if !force.is_finite() {
+                return Err(format!("pointer_force must be a finite number: {}", force));
+            }
+        }
+        check_positive("pointer_radius", self.pointer_radius)?;
+        check_unit("wall_restitution", self.wall_restitution)?;
+        check_unit("collision_restitution", self.collision_restitution)?;
+        if let Some(rest_speed) = self.rest_speed {
+            if !(rest_speed.is_finite() && rest_speed >= 0.) {
+                return Err(format!(
+                    "rest_speed must be a non-negative number: {}",
+                    rest_speed
+                ));
+            }
+        }
+        let color_mode = ColorMode::parse(self.color_mode.as_deref().unwrap_or("random"))?;
+        self.palette()?;
+        if let Some(palette) = self.palette.as_deref() {
+            Palette::parse(palette)?;
+        }
+        if color_mode == ColorMode::Static && self.static_palette.as_ref().is_none_or(Vec::is_empty)
+        {
+            return Err(String::from("static color mode requires `static_palette`"));
+        }
+        RenderMode::parse(self.render_mode.as_deref().unwrap_or("points"))?;
+        Boundary::parse(self.boundary.as_deref().unwrap_or("bounce"))?;
+        Behavior::parse(self.behavior.as_deref().unwrap_or("ballistic"))?;
+        if let Some(boids) = self.boids.as_ref() {
+            boids.validate()?;
+        }
+        self.arena()?;
+        SpawnPattern::parse(self.spawn.as_deref().unwrap_or("uniform"))?;
+        BlendMode::parse(self.blend_mode.as_deref().unwrap_or("normal"))?;
+        if self.speed_range.is_some() && (self.min_speed.is_some() || self.max_speed.is_some()) {
+            return Err(String::from(
+                "`speed_range` cannot be combined with `min_speed` / `max_speed`",
+            ));
+        }
+        let speed_range = self.speed_range();
+        if !(speed_range[0] >= 0. && speed_range[0] <= speed_range[1]) {
+            return Err(String::from(
+                "speed range must be [min, max] with 0 <= min <= max",
+            ));
+        }
+        self.velocities()?;
+        if let Some(groups) = self.groups.as_ref() {
+            GroupSpec::validate(groups)?;
+        }
+        if let Some([min, max]) = self.disk_size_range {
+            if !(min.is_finite() && max.is_finite() && min > 0. && min <= max) {
+                return Err(format!(
+                    "disk_size_range must be [min, max] with 0 < min <= max: [{}, {}]",
+                    min, max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * adaptive を有効にしていれば、disk_num 個から始めるときのディスクの数の決め方
+     */
+    pub fn adaptive_quality(&self, disk_num: u32) -> Result<Option<AdaptiveQuality>, String> {
+        if !self.adaptive.unwrap_or(false) {
+            return Ok(None);
+        }
+        let target_fps = self
+            .adaptive_target_fps
+            .or(self.target_fps.filter(|&fps| fps > 0))
+            .unwrap_or(DEFAULT_ADAPTIVE_FPS);
+        let min_disks = self.min_disks.unwrap_or(1);
+        let max_disks = self.max_disks.unwrap_or(disk_num).max(min_disks);
+        AdaptiveQuality::new(target_fps, min_disks, max_disks).map(Some)
+    }
+
+    /**
+     * overrides (JSON のオブジェクト) に書いたキーだけを置き換えたオプション (Screen::reset_with で使う)
+     * 別名 (renderer など) でも書け、null にしたキーは省略した場合と同じになる。結果は validate を通す
+     */
+    pub fn merged(&self, overrides: &serde_json::Value) -> Result<Options, String> {
+        let overrides = overrides
+            .as_object()
+            .ok_or_else(|| String::from("options must be an object"))?;
+        let defaults = to_map(&Options::default())?;
+        let mut merged = to_map(self)?;
+        // 別名で書いたキーが元の名前の null と重複しないよう、省略されたキーは除いておく
+        merged.retain(|_, value| !value.is_null());
+        for (key, value) in overrides {
+            // 1つずつ読んで知らないキー・型の違う値をエラーにし、別名は元の名前に直す
+            let mut entry = serde_json::Map::new();
+            entry.insert(key.clone(), value.clone());
+            let single: Options = serde_json::from_value(serde_json::Value::Object(entry))
+                .map_err(|e| e.to_string())?;
+            let name = to_map(&single)?
+                .into_iter()
+                .find(|(name, value)| defaults.get(name) != Some(value))
+                .map_or_else(|| key.clone(), |(name, _)| name);
+            merged.insert(name, value.clone());
+        }
+        let options: Options =
+            serde_json::from_value(serde_json::Value::Object(merged)).map_err(|e| e.to_string())?;
+        options.validate()?;
+        Ok(options)
+    }
+
+    /**
+     * コンテキスト・シェーダ・イベントの監視を作るときにだけ使うオプションが next で変わっていないか
+     * これらは Screen::reset_with では変えられない (新しい Screen を作ること)
+     */
+    pub fn check_reset(&self, next: &Options) -> Result<(), String> {
+        let changed = self.changed_keys(next)?;
+        match changed
+            .iter()
+            .find(|key| IMMUTABLE_OPTIONS.contains(&key.as_str()))
+        {
+            Some(name) => Err(format!(
+                "{} cannot be changed by reset_with; create a new screen instead",
+                name
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /**
+     * Screen::update_options で next に変えたときに値が変わるキー (既定値で埋めてから比べる)
+     * Screen を作った後は変えられないキーや、ディスクを作り直さないと反映されないキーが変わる場合はエラーにする
+     */
+    pub fn check_update(&self, next: &Options) -> Result<Vec<String>, String> {
+        let changed = self.changed_keys(next)?;
+        let matching = |names: &[&str]| {
+            changed
+                .iter()
+                .filter(|key| names.contains(&key.as_str()))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let immutable = matching(&IMMUTABLE_OPTIONS);
+        if !immutable.is_empty() {
+            return Err(format!(
+                "{} cannot be changed after the screen is created (immutable options: {})",
+                immutable.join(", "),
+                IMMUTABLE_OPTIONS.join(", ")
+            ));
+        }
+        let reset_only = matching(&RESET_ONLY_OPTIONS);
+        if !reset_only.is_empty() {
+            return Err(format!(
+                "{} only take effect when the disks are created; use reset_with instead",
+                reset_only.join(", ")
+            ));
+        }
+        Ok(changed)
+    }
+
+    /**
+     * keys に挙げたキーだけを取り出したオブジェクト (merged にそのまま渡せる。省略したキーは null)
+     */
+    pub fn subset(&self, keys: &[String]) -> Result<serde_json::Value, String> {
+        let map = to_map(self)?;
+        Ok(serde_json::Value::Object(
+            keys.iter()
+                .map(|key| {
+                    let value = map.get(key).cloned().unwrap_or(serde_json::Value::Null);
+                    (key.clone(), value)
+                })
+                .collect(),
+        ))
+    }
+
+    /**
+     * 省略したキーを既定値で埋めたオプション (Screen::current_options の元になる)
+     * 省略した場合と意味が変わるキー (trail・max_speed・debug・emitter の設定など) は埋めない
+     */
+    pub fn with_defaults(&self) -> Options {
+        let mut options = self.clone();
+        options.disk_num.get_or_insert(100);
+        options.width.get_or_insert(500);
+        options.height.get_or_insert(500);
+        options.disk_size.get_or_insert(32.);
+        options.collision.get_or_insert(false);
+        options.on_collision.get_or_insert_with(|| "bounce".into());
+        options.color_mode.get_or_insert_with(|| "random".into());
+        if options.palette_colors.is_none() {
+            options.palette.get_or_insert_with(|| "random".into());
+        }
+        options.min_velocity.get_or_insert(0.);
+        options.max_velocity.get_or_insert(DEFAULT_MAX_VELOCITY);
+        options.cycle_speed.get_or_insert(DEFAULT_CYCLE_SPEED);
+        options
+            .background
+            .get_or_insert(ColorSpec::Rgba([0., 0., 0., 1.]));
+        options.render_mode.get_or_insert_with(|| "points".into());
+        options.boundary.get_or_insert_with(|| "bounce".into());
+        options.behavior.get_or_insert_with(|| "ballistic".into());
+        options.boids.get_or_insert_with(BoidsParams::default);
+        options.arena.get_or_insert_with(|| "rect".into());
+        options.show_arena.get_or_insert(false);
+        options.show_obstacles.get_or_insert(false);
+        options.spawn.get_or_insert_with(|| "uniform".into());
+        options.no_initial_overlap.get_or_insert(false);
+        options
+            .velocity_distribution
+            .get_or_insert_with(|| "uniform".into());
+        options.preserve_drawing_buffer.get_or_insert(false);
+        options.antialias.get_or_insert(false);
+        options.emit_events.get_or_insert(false);
+        options
+            .max_events_per_frame
+            .get_or_insert(DEFAULT_MAX_EVENTS_PER_FRAME);
+        options.collect_stats.get_or_insert(true);
+        options.perf_marks.get_or_insert(false);
+        options.target_fps.get_or_insert(0);
+        options.adaptive.get_or_insert(false);
+        options.min_disks.get_or_insert(1);
+        options.spawn_on_click.get_or_insert(false);
+        options.touch_input.get_or_insert(false);
+        options.pause_when_hidden.get_or_insert(false);
+        options.auto_resize.get_or_insert(false);
+        options.emitter.get_or_insert(false);
+        options.show_attractors.get_or_insert(false);
+        options.show_velocity.get_or_insert(false);
+        options.velocity_scale.get_or_insert(10.);
+        options.opacity.get_or_insert(1.);
+        options.bounce_flash.get_or_insert(0.);
+        options.disk_alpha.get_or_insert(1.);
+        options.blend_mode.get_or_insert_with(|| "normal".into());
+        options.shape.get_or_insert_with(|| "circle".into());
+        options.use_device_pixel_ratio.get_or_insert(true);
+        options.gravity.get_or_insert(0.);
+        options.force.get_or_insert((0., 0.));
+        options.drag.get_or_insert(0.);
+        options.jitter.get_or_insert(0.);
+        options.wall_restitution.get_or_insert(1.);
+        options.collision_restitution.get_or_insert(1.);
+        options.pointer_force.get_or_insert(DEFAULT_POINTER_FORCE);
+        options.pointer_radius.get_or_insert(DEFAULT_POINTER_RADIUS);
+        options.strict.get_or_insert(false);
+        options.check_invariants.get_or_insert(false);
+        options
+    }
+
+    // 既定値で埋めてから比べて、値の違うキー
+    fn changed_keys(&self, next: &Options) -> Result<Vec<String>, String> {
+        let before = to_map(&self.with_defaults())?;
+        let after = to_map(&next.with_defaults())?;
+        Ok(after
+            .into_iter()
+            .filter(|(key, value)| before.get(key) != Some(value))
+            .map(|(key, _)| key)
+            .collect())
+    }
+}
+
+fn to_map(options: &Options) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    match serde_json::to_value(options) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        _ => Err(String::from("options are not serializable")),
+    }
+}
+
+/**
+ * Screen::attach_view に渡すオプション (すべて省略できる)
+ */
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ViewOptions {
+    // canvas の論理サイズ (CSS ピクセル)。省略するとメインの canvas と同じ
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // 表示の中心 (ワールド座標) と拡大率。どちらも省略すると領域全体が収まるように合わせる (resize に追従する)
+    pub center: Option<[f64; 2]>,
+    pub scale: Option<f64>,
+    // 省略するとメインの背景色 (半透明にすると canvas を透過できるコンテキストを作る)
+    pub background: Option<ColorSpec>,
+    // 省略するとメインの canvas と同じ
+    pub dpr: Option<f64>,
+}
+
+impl ViewOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err(String::from("width and height must be at least 1"));
+        }
+        self.view()?;
+        if let Some(background) = self.background.as_ref() {
+            background.to_rgba()?;
+        }
+        check_positive("dpr", self.dpr)
+    }
+
+    /**
+     * center・scale から決まる視点 (どちらも省略すれば None で、領域全体に合わせる)
+     * scale を省略すると 1。scale を指定するときは center も指定する
+     */
+    pub fn view(&self) -> Result<Option<View>, String> {
+        match (self.center, self.scale) {
+            (None, None) => Ok(None),
+            (Some([x, y]), scale) => View::new(x, y, scale.unwrap_or(1.)).map(Some),
+            (None, Some(scale)) => Err(format!("scale needs a center to zoom around: {}", scale)),
+        }
+    }
+}
+
+/**
+ * JS からオプションを1つずつ設定して組み立てる (init_gl_with_options に渡す)
+ * 各 setter は値を確認し、不正な値ならエラーにして何も変えない
+ */
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct ScreenOptions {
+    options: Options,
+}
+
+#[wasm_bindgen]
+impl ScreenOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas_id: &str) -> ScreenOptions {
+        ScreenOptions {
+            options: Options {
+                canvas_id: canvas_id.to_string(),
+                ..Options::default()
+            },
+        }
+    }
+
+    pub fn set_disk_num(&mut self, disk_num: u32) {
+        self.options.disk_num = Some(disk_num);
+    }
+
+    pub fn set_width(&mut self, width: u32) -> Result<(), JsValue> {
+        self.update(|o| o.width = Some(width))
+    }
+
+    pub fn set_height(&mut self, height: u32) -> Result<(), JsValue> {
+        self.update(|o| o.height = Some(height))
+    }
+
+    pub fn set_disk_size(&mut self, disk_size: f64) -> Result<(), JsValue> {
+        self.update(|o| o.disk_size = Some(disk_size))
+    }
+
+    pub fn set_collision(&mut self, collision: bool) {
+        self.options.collision = Some(collision);
+    }
+
+    pub fn set_on_collision(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.update(|o| o.on_collision = Some(mode.to_string()))
+    }
+
+    pub fn set_max_radius(&mut self, max_radius: f64) -> Result<(), JsValue> {
+        self.update(|o| o.max_radius = Some(max_radius))
+    }
+
+    /**
+     * "static" の場合は先に set_static_palette を呼ぶ
+     */
+    pub fn set_color_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.update(|o| o.color_mode = Some(mode.to_string()))
+    }
+
+    /**
+     * "random" | "viridis" | "warm" | "cool" | "pastel" | "mono"
+     */
+    pub fn set_palette(&mut self, palette: &str) -> Result<(), JsValue> {
+        self.update(|o| o.palette = Some(palette.to_string()))
+    }
+
+    /**
+     * ["#rrggbb", ...]
+     */
+    pub fn set_palette_colors(&mut self, colors: Vec<String>) -> Result<(), JsValue> {
+        self.update(|o| o.palette_colors = Some(colors))
+    }
+
+    /**
+     * [[r, g, b], ...] (0.0 - 1.0)
+     */
+    pub fn set_static_palette(&mut self, palette: JsValue) -> Result<(), JsValue> {
+        let palette = utils::from_js(&palette)
+            .map_err(|e| JsValue::from(format!("invalid static_palette: {}", e).as_str()))?;
+        self.update(|o| o.static_palette = Some(palette))
+    }
+
+    /**
+     * [r, g, b, a] (0.0 - 1.0) か "#rrggbb" などの文字列
+     */
+    pub fn set_background(&mut self, background: JsValue) -> Result<(), JsValue> {
+        let background = utils::from_js(&background)
+            .map_err(|e| JsValue::from(format!("invalid background: {}", e).as_str()))?;
+        self.update(|o| o.background = Some(background))
+    }
+
+    /**
+     * {antialias, alpha, premultipliedAlpha, preserveDrawingBuffer, powerPreference, desynchronized}
+     */
+    pub fn set_context_attributes(&mut self, attributes: JsValue) -> Result<(), JsValue> {
+        let attributes = utils::from_js(&attributes)
+            .map_err(|e| JsValue::from(format!("invalid context_attributes: {}", e).as_str()))?;
+        self.update(|o| o.context_attributes = Some(attributes))
+    }
+
+    pub fn set_min_velocity(&mut self, min_velocity: f64) -> Result<(), JsValue> {
+        self.update(|o| o.min_velocity = Some(min_velocity))
+    }
+
+    pub fn set_max_velocity(&mut self, max_velocity: f64) -> Result<(), JsValue> {
+        self.update(|o| o.max_velocity = Some(max_velocity))
+    }
+
+    pub fn set_cycle_speed(&mut self, speed: f64) -> Result<(), JsValue> {
+        self.update(|o| o.cycle_speed = Some(speed))
+    }
+
+    pub fn set_render_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.update(|o| o.render_mode = Some(mode.to_string()))
+    }
+
+    pub fn set_boundary(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.update(|o| o.boundary = Some(mode.to_string()))
+    }
+
+    pub fn set_behavior(&mut self, behavior: &str) -> Result<(), JsValue> {
+        self.update(|o| o.behavior = Some(behavior.to_string()))
+    }
+
+    /**
+     * {separation, alignment, cohesion, perception, max_speed}
+     */
+    pub fn set_boids(&mut self, boids: JsValue) -> Result<(), JsValue> {
+        let boids = utils::from_js(&boids)
+            .map_err(|e| JsValue::from(format!("invalid boids: {}", e).as_str()))?;
+        self.update(|o| o.boids = Some(boids))
+    }
+
+    /**
+     * "circle" の円の中心・半径を指定する場合は、先にこちらを呼ぶ
+     */
+    pub fn set_arena(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.update(|o| o.arena = Some(mode.to_string()))
+    }
+
+    pub fn set_arena_center(&mut self, x: f64, y: f64) -> Result<(), JsValue> {
+        self.update(|o| o.arena_center = Some([x, y]))
+    }
+
+    pub fn set_arena_radius(&mut self, radius: f64) -> Result<(), JsValue> {
+        self.update(|o| o.arena_radius = Some(radius))
+    }
+
+    pub fn set_show_arena(&mut self, show: bool) {
+        self.options.show_arena = Some(show);
+    }
+
+    pub fn set_show_obstacles(&mut self, show: bool) {
+        self.options.show_obstacles = Some(show);
+    }
+
+    pub fn set_spawn(&mut self, spawn: &str) -> Result<(), JsValue> {
+        self.update(|o| o.spawn = Some(spawn.to_string()))
+    }
+
+    pub fn set_no_initial_overlap(&mut self, enabled: bool) {
+        self.options.no_initial_overlap = Some(enabled);
+    }
+
+    pub fn set_ring_radius(&mut self, radius: f64) -> Result<(), JsValue> {
+        self.update(|o| o.ring_radius = Some(radius))
+    }
+
+    pub fn set_speed_range(&mut self, min: f64, max: f64) -> Result<(), JsValue> {
+        self.update(|o| o.speed_range = Some([min, max]))
+    }
+
+    pub fn set_min_speed(&mut self, min_speed: f64) -> Result<(), JsValue> {
+        self.update(|o| o.min_speed = Some(min_speed))
+    }
+
+    pub fn set_max_speed(&mut self, max_speed: f64) -> Result<(), JsValue> {
+        self.update(|o| o.max_speed = Some(max_speed))
+    }
+
+    /**
+     * 初期速度の向きの範囲 (ラジアン)
+     */
+    pub fn set_angle_range(&mut self, min: f64, max: f64) -> Result<(), JsValue> {
+        self.update(|o| {
+            o.min_angle = Some(min);
+            o.max_angle = Some(max);
+        })
+    }
+
+    pub fn set_velocity_distribution(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.update(|o| o.velocity_distribution = Some(mode.to_string()))
+    }
+
+    pub fn set_trail(&mut self, trail: f64) -> Result<(), JsValue> {
+        self.update(|o| o.trail = Some(trail))
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.options.seed = Some(seed);
+    }
+
+    pub fn set_vertex_shader(&mut self, source: &str) {
+        self.options.vertex_shader = Some(source.to_string());
+    }
+
+    pub fn set_fragment_shader(&mut self, source: &str) {
+        self.options.fragment_shader = Some(source.to_string());
+    }
+
+    pub fn set_texture_url(&mut self, url: &str) {
+        self.options.texture_url = Some(url.to_string());
+    }
+
+    pub fn set_antialias(&mut self, antialias: bool) {
+        self.options.antialias = Some(antialias);
+    }
+
+    pub fn set_emit_events(&mut self, emit_events: bool) {
+        self.options.emit_events = Some(emit_events);
+    }
+
+    pub fn set_max_events_per_frame(&mut self, max: u32) {
+        self.options.max_events_per_frame = Some(max);
+    }
+
+    pub fn set_spawn_on_click(&mut self, spawn_on_click: bool) {
+        self.options.spawn_on_click = Some(spawn_on_click);
+    }
+
+    pub fn set_touch_input(&mut self, touch_input: bool) {
+        self.options.touch_input = Some(touch_input);
+    }
+
+    pub fn set_pause_when_hidden(&mut self, pause_when_hidden: bool) {
+        self.options.pause_when_hidden = Some(pause_when_hidden);
+    }
+
+    pub fn set_auto_resize(&mut self, auto_resize: bool) {
+        self.options.auto_resize = Some(auto_resize);
+    }
+
+    pub fn set_debug(&mut self, level: &str) -> Result<(), JsValue> {
+        self.update(|o| o.debug = Some(level.to_string()))
+    }
+
+    pub fn set_show_attractors(&mut self, show: bool) {
+        self.options.show_attractors = Some(show);
+    }
+
+    pub fn set_show_velocity(&mut self, show: bool) {
+        self.options.show_velocity = Some(show);
+    }
+
+    pub fn set_velocity_scale(&mut self, scale: f64) -> Result<(), JsValue> {
+        self.update(|o| o.velocity_scale = Some(scale))
+    }
+
+    pub fn set_emitter(&mut self, emitter: bool) -> Result<(), JsValue> {
+        self.update(|o| o.emitter = Some(emitter))
+    }
+
+    pub fn set_emit_rate(&mut self, rate: f64) -> Result<(), JsValue> {
+        self.update(|o| o.emit_rate = Some(rate))
+    }
+
+    pub fn set_lifetime(&mut self, lifetime: f64) -> Result<(), JsValue> {
+        self.update(|o| o.lifetime = Some(lifetime))
+    }
+
+    pub fn set_max_disks(&mut self, max_disks: u32) -> Result<(), JsValue> {
+        self.update(|o| o.max_disks = Some(max_disks))
+    }
+
+    pub fn set_emit_origin(&mut self, x: f64, y: f64) -> Result<(), JsValue> {
+        self.update(|o| o.emit_origin = Some([x, y]))
+    }
+
+    pub fn set_preserve_drawing_buffer(&mut self, preserve: bool) {
+        self.options.preserve_drawing_buffer = Some(preserve);
+    }
+
+    pub fn set_collect_stats(&mut self, collect_stats: bool) {
+        self.options.collect_stats = Some(collect_stats);
+    }
+
+    pub fn set_perf_marks(&mut self, perf_marks: bool) {
+        self.options.perf_marks = Some(perf_marks);
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: u32) {
+        self.options.target_fps = Some(target_fps);
+    }
+
+    pub fn set_adaptive(&mut self, adaptive: bool) -> Result<(), JsValue> {
+        self.update(|o| o.adaptive = Some(adaptive))
+    }
+
+    pub fn set_adaptive_target_fps(&mut self, target_fps: u32) -> Result<(), JsValue> {
+        self.update(|o| o.adaptive_target_fps = Some(target_fps))
+    }
+
+    pub fn set_min_disks(&mut self, min_disks: u32) -> Result<(), JsValue> {
+        self.update(|o| o.min_disks = Some(min_disks))
+    }
+
+    pub fn set_opacity(&mut self, opacity: f64) -> Result<(), JsValue> {
+        self.update(|o| o.opacity = Some(opacity))
+    }
+
+    pub fn set_bounce_flash(&mut self, duration: f64) -> Result<(), JsValue> {
+        self.update(|o| o.bounce_flash = Some(duration))
+    }
+
+    pub fn set_flash_color(&mut self, r: f32, g: f32, b: f32) -> Result<(), JsValue> {
+        self.update(|o| o.flash_color = Some(ColorSpec::Rgba([r, g, b, 1.])))
+    }
+
+    pub fn set_disk_alpha(&mut self, alpha: f64) -> Result<(), JsValue> {
+        self.update(|o| o.disk_alpha = Some(alpha))
+    }
+
+    pub fn set_blend_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.update(|o| o.blend_mode = Some(mode.to_string()))
+    }
+
+    pub fn set_shape(&mut self, shape: &str) -> Result<(), JsValue> {
+        self.update(|o| o.shape = Some(shape.to_string()))
+    }
+
+    pub fn set_ring_thickness(&mut self, thickness: f64) -> Result<(), JsValue> {
+        self.update(|o| o.ring_thickness = Some(thickness))
+    }
+
+    pub fn set_use_device_pixel_ratio(&mut self, enabled: bool) {
+        self.options.use_device_pixel_ratio = Some(enabled);
+    }
+
+    pub fn set_dpr(&mut self, dpr: f64) -> Result<(), JsValue> {
+        self.update(|o| o.dpr = Some(dpr))
+    }
+
+    /**
+     * [{count, size, mass, color}, ...]
+     */
+    pub fn set_groups(&mut self, groups: JsValue) -> Result<(), JsValue> {
+        let groups = utils::from_js(&groups)
+            .map_err(|e| JsValue::from(format!("invalid groups: {}", e).as_str()))?;
+        self.update(|o| o.groups = Some(groups))
+    }
+
+    pub fn set_disk_size_range(&mut self, min: f64, max: f64) -> Result<(), JsValue> {
+        self.update(|o| o.disk_size_range = Some([min, max]))
+    }
+
+    pub fn set_mass_from_radius(&mut self, enabled: bool) {
+        self.options.mass_from_radius = Some(enabled);
+    }
+
+    pub fn set_gravity(&mut self, gravity: f64) -> Result<(), JsValue> {
+        self.update(|o| o.gravity = Some(gravity))
+    }
+
+    pub fn set_force(&mut self, fx: f64, fy: f64) -> Result<(), JsValue> {
+        self.update(|o| o.force = Some((fx, fy)))
+    }
+
+    pub fn set_drag(&mut self, drag: f64) -> Result<(), JsValue> {
+        self.update(|o| o.drag = Some(drag))
+    }
+
+    pub fn set_jitter(&mut self, jitter: f64) -> Result<(), JsValue> {
+        self.update(|o| o.jitter = Some(jitter))
+    }
+
+    pub fn set_pointer_force(&mut self, force: f64) -> Result<(), JsValue> {
+        self.update(|o| o.pointer_force = Some(force))
+    }
+
+    pub fn set_pointer_radius(&mut self, radius: f64) -> Result<(), JsValue> {
+        self.update(|o| o.pointer_radius = Some(radius))
+    }
+
+    pub fn set_wall_restitution(&mut self, restitution: f64) -> Result<(), JsValue> {
+        self.update(|o| o.wall_restitution = Some(restitution))
+    }
+
+    pub fn set_collision_restitution(&mut self, restitution: f64) -> Result<(), JsValue> {
+        self.update(|o| o.collision_restitution = Some(restitution))
+    }
+
+    pub fn set_rest_speed(&mut self, rest_speed: f64) -> Result<(), JsValue> {
+        self.update(|o| o.rest_speed = Some(rest_speed))
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.options.strict = Some(strict);
+    }
+
+    pub fn set_check_invariants(&mut self, check: bool) {
+        self.options.check_invariants = Some(check);
+    }
+}
+
+impl ScreenOptions {
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /**
+     * 変更後の値を validate し、問題なければ反映する
+     */
+    fn update(&mut self, change: impl FnOnce(&mut Options)) -> Result<(), JsValue> {
+        let mut options = self.options.clone();
+        change(&mut options);
+        options.validate().map_err(|e| JsValue::from(e.as_str()))?;
+        self.options = options;
+        Ok(())
+    }
+}
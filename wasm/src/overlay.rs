@@ -0,0 +1,375 @@
+use crate::dom_utils::{self, GlContext};
+use crate::obstacle::Obstacle;
+use crate::simulation::{Attractor, Circle};
+use crate::view::View;
+use std::collections::VecDeque;
+use web_sys::{WebGlBuffer, WebGlProgram, WebGlRenderingContext, WebGlUniformLocation};
+
+// 3x5 のビットマップフォント (各行の下位3ビットを左から使う)
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'F' => [0b111, 0b100, 0b111, 0b100, 0b100],
+        'P' => [0b111, 0b101, 0b111, 0b100, 0b100],
+        'S' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        'N' => [0b101, 0b111, 0b111, 0b111, 0b101],
+        _ => [0; 5],
+    }
+}
+
+// フォントの1ドットの大きさ (px)
+const DOT: f64 = 3.;
+const MARGIN: f64 = 8.;
+const LINE_HEIGHT: f64 = DOT * 7.;
+const COLOR: [f32; 4] = [1., 1., 1., 1.];
+// FPS を求めるのに使う直近のフレーム数
+const FPS_WINDOW: usize = 60;
+
+/**
+ * canvas 内に FPS とディスク数を描くデバッグ表示
+ * 文字はビットマップフォントの1ドットを1つの点として描く
+ */
+#[derive(Debug)]
+pub struct OverlayPass {
+    program: WebGlProgram,
+    buffer_dots: WebGlBuffer,
+    attrib_position: u32,
+    uniform_point_size: WebGlUniformLocation,
+    uniform_color: WebGlUniformLocation,
+    frame_times: VecDeque<f64>,
+    dots: Vec<f32>,
+}
+
+impl OverlayPass {
+    pub fn new(gl: &GlContext) -> Result<OverlayPass, String> {
+        let program = dom_utils::create_overlay_program(gl)?;
+        let uniform = |name: &str| dom_utils::uniform_location(gl, &program, name);
+        Ok(OverlayPass {
+            attrib_position: dom_utils::attrib_location(gl, &program, "a_position")?,
+            uniform_point_size: uniform("u_pointsize")?,
+            uniform_color: uniform("u_color")?,
+            buffer_dots: gl
+                .create_buffer()
+                .ok_or_else(|| String::from("failed to create buffer."))?,
+            program,
+            frame_times: VecDeque::with_capacity(FPS_WINDOW),
+            dots: Vec::new(),
+        })
+    }
+
+    pub fn delete(&self, gl: &GlContext) {
+        dom_utils::delete_program(gl, &self.program);
+        gl.delete_buffer(Some(&self.buffer_dots));
+    }
+
+    /**
+     * フレームの時刻 (ms) を記録する
+     */
+    pub fn record_frame(&mut self, now: f64) {
+        if self.frame_times.len() == FPS_WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(now);
+    }
+
+    /**
+     * 直近 FPS_WINDOW フレームの平均 FPS
+     */
+    pub fn fps(&self) -> f64 {
+        match (self.frame_times.front(), self.frame_times.back()) {
+            (Some(first), Some(last)) if last > first => {
+                (self.frame_times.len() - 1) as f64 * 1000. / (last - first)
+            }
+            _ => 0.,
+        }
+    }
+
+    // 左上を (x, y) として文字列のドットをクリップ座標で追加する
+    fn push_text(&mut self, text: &str, x: f64, y: f64, width: f64, height: f64) {
+        for (index, c) in text.chars().enumerate() {
+            let left = x + index as f64 * DOT * 4.;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (0b100 >> col) == 0 {
+                        continue;
+                    }
+                    let px = left + (col as f64 + 0.5) * DOT;
+                    let py = y + (row as f64 + 0.5) * DOT;
+                    self.dots.push((-1. + 2. * px / width) as f32);
+                    self.dots.push((1. - 2. * py / height) as f32);
+                }
+            }
+        }
+    }
+
+    /**
+     * 左上に FPS とディスク数を描く
+     * 使った attribute は無効に戻し、ディスクの描画の状態に影響しないようにする
+     */
+    pub fn draw(
+        &mut self,
+        gl: &GlContext,
+        disk_count: usize,
+        width: f64,
+        height: f64,
+        pixel_ratio: f64,
+    ) {
+        self.dots.clear();
+        let fps = format!("FPS {}", self.fps().round() as u32);
+        let disks = format!("N {}", disk_count);
+        self.push_text(&fps, MARGIN, MARGIN, width, height);
+        self.push_text(&disks, MARGIN, MARGIN + LINE_HEIGHT, width, height);
+
+        gl.use_program(Some(&self.program));
+        gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&self.buffer_dots));
+        gl.buffer_data_f32(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            &self.dots,
+            WebGlRenderingContext::STREAM_DRAW,
+        );
+        gl.vertex_attrib_pointer(
+            self.attrib_position,
+            2,
+            WebGlRenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        gl.enable_vertex_attrib_array(self.attrib_position);
+        gl.uniform1f(Some(&self.uniform_point_size), (DOT * pixel_ratio) as f32);
+        let [r, g, b, a] = COLOR;
+        gl.uniform4f(Some(&self.uniform_color), r, g, b, a);
+        gl.draw_arrays(
+            WebGlRenderingContext::POINTS,
+            0,
+            (self.dots.len() / 2) as i32,
+        );
+        gl.disable_vertex_attrib_array(self.attrib_position);
+    }
+}
+
+// 引力点の印の大きさ (CSS px) と色 (引き寄せる点は黄、遠ざける点は赤)
+const MARKER_SIZE: f64 = 12.;
+const ATTRACT_COLOR: [f32; 4] = [1., 0.85, 0.2, 1.];
+const REPEL_COLOR: [f32; 4] = [1., 0.3, 0.3, 1.];
+
+/**
+ * show_attractors で引力点の位置を大きな点で描くデバッグ表示
+ */
+#[derive(Debug)]
+pub struct MarkerPass {
+    program: WebGlProgram,
+    buffer_points: WebGlBuffer,
+    attrib_position: u32,
+    uniform_point_size: WebGlUniformLocation,
+    uniform_color: WebGlUniformLocation,
+    points: Vec<f32>,
+}
+
+impl MarkerPass {
+    pub fn new(gl: &GlContext) -> Result<MarkerPass, String> {
+        let program = dom_utils::create_overlay_program(gl)?;
+        let uniform = |name: &str| dom_utils::uniform_location(gl, &program, name);
+        Ok(MarkerPass {
+            attrib_position: dom_utils::attrib_location(gl, &program, "a_position")?,
+            uniform_point_size: uniform("u_pointsize")?,
+            uniform_color: uniform("u_color")?,
+            buffer_points: gl
+                .create_buffer()
+                .ok_or_else(|| String::from("failed to create buffer."))?,
+            program,
+            points: Vec::new(),
+        })
+    }
+
+    pub fn delete(&self, gl: &GlContext) {
+        dom_utils::delete_program(gl, &self.program);
+        gl.delete_buffer(Some(&self.buffer_points));
+    }
+
+    /**
+     * 引力点を描く (遠ざける点の後に引き寄せる点を描くので、重なったら引き寄せる点が手前になる)
+     */
+    pub fn draw(
+        &mut self,
+        gl: &GlContext,
+        attractors: &[Attractor],
+        view: &View,
+        width: f64,
+        height: f64,
+        pixel_ratio: f64,
+    ) {
+        if attractors.is_empty() {
+            return;
+        }
+        gl.use_program(Some(&self.program));
+        gl.bind_buffer(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            Some(&self.buffer_points),
+        );
+        gl.uniform1f(
+            Some(&self.uniform_point_size),
+            (MARKER_SIZE * pixel_ratio) as f32,
+        );
+        gl.enable_vertex_attrib_array(self.attrib_position);
+        for &(repel, color) in &[(true, REPEL_COLOR), (false, ATTRACT_COLOR)] {
+            self.points.clear();
+            for attractor in attractors.iter().filter(|a| (a.strength < 0.) == repel) {
+                self.points
+                    .extend(view.to_clip(attractor.x, attractor.y, width, height));
+            }
+            if self.points.is_empty() {
+                continue;
+            }
+            gl.buffer_data_f32(
+                WebGlRenderingContext::ARRAY_BUFFER,
+                &self.points,
+                WebGlRenderingContext::STREAM_DRAW,
+            );
+            gl.vertex_attrib_pointer(
+                self.attrib_position,
+                2,
+                WebGlRenderingContext::FLOAT,
+                false,
+                0,
+                0,
+            );
+            let [r, g, b, a] = color;
+            gl.uniform4f(Some(&self.uniform_color), r, g, b, a);
+            gl.draw_arrays(
+                WebGlRenderingContext::POINTS,
+                0,
+                (self.points.len() / 2) as i32,
+            );
+        }
+        gl.disable_vertex_attrib_array(self.attrib_position);
+    }
+}
+
+// 領域・障害物の円を近似する線分の数と、線の色
+const OUTLINE_SEGMENTS: usize = 128;
+const OUTLINE_COLOR: [f32; 4] = [1., 1., 1., 0.5];
+
+/**
+ * show_arena で circle の領域の縁を、show_obstacles で障害物の外形を LINE_LOOP で描くデバッグ表示
+ */
+#[derive(Debug)]
+pub struct OutlinePass {
+    program: WebGlProgram,
+    buffer_points: WebGlBuffer,
+    attrib_position: u32,
+    uniform_color: WebGlUniformLocation,
+    points: Vec<f32>,
+    // draw_obstacles で使う、ワールド座標の頂点と障害物ごとの頂点の数
+    corners: Vec<f64>,
+    loops: Vec<usize>,
+}
+
+impl OutlinePass {
+    pub fn new(gl: &GlContext) -> Result<OutlinePass, String> {
+        let program = dom_utils::create_overlay_program(gl)?;
+        let uniform_color = dom_utils::uniform_location(gl, &program, "u_color")?;
+        Ok(OutlinePass {
+            attrib_position: dom_utils::attrib_location(gl, &program, "a_position")?,
+            uniform_color,
+            buffer_points: gl
+                .create_buffer()
+                .ok_or_else(|| String::from("failed to create buffer."))?,
+            program,
+            points: Vec::with_capacity(OUTLINE_SEGMENTS * 2),
+            corners: Vec::new(),
+            loops: Vec::new(),
+        })
+    }
+
+    pub fn delete(&self, gl: &GlContext) {
+        dom_utils::delete_program(gl, &self.program);
+        gl.delete_buffer(Some(&self.buffer_points));
+    }
+
+    /**
+     * 円の縁を描く
+     */
+    pub fn draw(&mut self, gl: &GlContext, circle: &Circle, view: &View, width: f64, height: f64) {
+        self.points.clear();
+        for i in 0..OUTLINE_SEGMENTS {
+            let angle = std::f64::consts::PI * 2. * i as f64 / OUTLINE_SEGMENTS as f64;
+            let x = circle.x + circle.radius * angle.cos();
+            let y = circle.y + circle.radius * angle.sin();
+            self.points.extend(view.to_clip(x, y, width, height));
+        }
+        self.stroke(gl, &[OUTLINE_SEGMENTS]);
+    }
+
+    /**
+     * 障害物ごとに外形を描く (まとめて1回で送り、LINE_LOOP は障害物ごとに閉じる)
+     */
+    pub fn draw_obstacles(
+        &mut self,
+        gl: &GlContext,
+        obstacles: &[Obstacle],
+        view: &View,
+        width: f64,
+        height: f64,
+    ) {
+        if obstacles.is_empty() {
+            return;
+        }
+        self.points.clear();
+        self.corners.clear();
+        self.loops.clear();
+        for obstacle in obstacles {
+            let count = obstacle.shape.outline(OUTLINE_SEGMENTS, &mut self.corners);
+            self.loops.push(count);
+        }
+        for corner in self.corners.chunks_exact(2) {
+            self.points
+                .extend(view.to_clip(corner[0], corner[1], width, height));
+        }
+        let loops = std::mem::take(&mut self.loops);
+        self.stroke(gl, &loops);
+        self.loops = loops;
+    }
+
+    /**
+     * points を送り、先頭から順に counts 個ずつの頂点をそれぞれ閉じた線で描く
+     */
+    fn stroke(&self, gl: &GlContext, counts: &[usize]) {
+        gl.use_program(Some(&self.program));
+        gl.bind_buffer(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            Some(&self.buffer_points),
+        );
+        gl.buffer_data_f32(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            &self.points,
+            WebGlRenderingContext::STREAM_DRAW,
+        );
+        gl.enable_vertex_attrib_array(self.attrib_position);
+        gl.vertex_attrib_pointer(
+            self.attrib_position,
+            2,
+            WebGlRenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        let [r, g, b, a] = OUTLINE_COLOR;
+        gl.uniform4f(Some(&self.uniform_color), r, g, b, a);
+        let mut first = 0;
+        for &count in counts {
+            gl.draw_arrays(WebGlRenderingContext::LINE_LOOP, first, count as i32);
+            first += count as i32;
+        }
+        gl.disable_vertex_attrib_array(self.attrib_position);
+    }
+}
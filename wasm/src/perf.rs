@@ -0,0 +1,126 @@
+use crate::dom_utils;
+use wasm_bindgen::JsCast;
+use web_sys::Performance;
+
+// この数のフレームごとにマークと計測を消す (タイムラインのバッファが伸び続けないように)
+pub const PERF_CLEAR_FRAMES: u32 = 120;
+
+/**
+ * perf_marks で performance.measure を取る do_frame の区間
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PerfPhase {
+    // シミュレーションのステップ
+    Physics,
+    // 座標・色をまとめて頂点バッファへ転送するまで
+    BufferUpload,
+    // drawArrays とオーバーレイの描画
+    DrawCall,
+}
+
+impl PerfPhase {
+    pub const ALL: [PerfPhase; 3] = [
+        PerfPhase::Physics,
+        PerfPhase::BufferUpload,
+        PerfPhase::DrawCall,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PerfPhase::Physics => "physics",
+            PerfPhase::BufferUpload => "buffer-upload",
+            PerfPhase::DrawCall => "draw-call",
+        }
+    }
+}
+
+/**
+ * 区間の (開始のマーク, 終了のマーク, 計測) の名前 ("<canvas_id>:physics" など)
+ * canvas_id が空 (コンテキストを渡して作った Screen) なら "screen" を前に付ける
+ */
+pub fn perf_names(canvas_id: &str, phase: PerfPhase) -> (String, String, String) {
+    let prefix = if canvas_id.is_empty() {
+        "screen"
+    } else {
+        canvas_id
+    };
+    let measure = format!("{}:{}", prefix, phase.name());
+    (
+        format!("{}:start", measure),
+        format!("{}:end", measure),
+        measure,
+    )
+}
+
+/**
+ * do_frame の区間を performance.mark / measure で囲む (DevTools の Timings に出る)
+ * 作らなければ Performance API は一切呼ばない
+ */
+#[derive(Debug)]
+pub struct PerfMarks {
+    performance: Performance,
+    names: Vec<(String, String, String)>,
+    frames: u32,
+}
+
+impl PerfMarks {
+    /**
+     * Performance API が使えない環境では None
+     */
+    pub fn new(canvas_id: &str) -> Option<PerfMarks> {
+        let performance = dom_utils::global_property("performance")?
+            .dyn_into::<Performance>()
+            .ok()?;
+        Some(PerfMarks {
+            performance,
+            names: PerfPhase::ALL
+                .iter()
+                .map(|&phase| perf_names(canvas_id, phase))
+                .collect(),
+            frames: 0,
+        })
+    }
+
+    fn names(&self, phase: PerfPhase) -> &(String, String, String) {
+        &self.names[phase as usize]
+    }
+
+    pub fn begin(&self, phase: PerfPhase) {
+        let (start, _, _) = self.names(phase);
+        let _ = self.performance.mark(start);
+    }
+
+    pub fn end(&self, phase: PerfPhase) {
+        let (start, end, measure) = self.names(phase);
+        let _ = self.performance.mark(end);
+        let _ = self
+            .performance
+            .measure_with_start_mark_and_end_mark(measure, start, end);
+    }
+
+    /**
+     * フレームの終わりに呼ぶ。PERF_CLEAR_FRAMES フレームごとにこの Screen のマークと計測を消す
+     */
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        if self.frames < PERF_CLEAR_FRAMES {
+            return;
+        }
+        self.frames = 0;
+        self.clear();
+    }
+
+    pub fn clear(&self) {
+        for (start, end, measure) in self.names.iter() {
+            self.performance.clear_marks_with_mark_name(start);
+            self.performance.clear_marks_with_mark_name(end);
+            self.performance.clear_measures_with_measure_name(measure);
+        }
+    }
+}
+
+impl Drop for PerfMarks {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,402 @@
+use crate::obstacle::ObstacleShape;
+use serde::{Deserialize, Serialize};
+
+// 既定の記録サイズの上限 (座標データのみ、バイト)
+pub const DEFAULT_BYTE_BUDGET: usize = 64 * 1024 * 1024;
+
+/**
+ * 記録したシミュレーション
+ * frames[i] は i フレーム目の座標 (x0, y0, x1, y1, ...)
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Recording {
+    pub disk_count: u32,
+    pub frames: Vec<Vec<f32>>,
+}
+
+impl Recording {
+    /**
+     * すべてのフレームの長さが disk_count と一致しているか
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        let expected = self.disk_count as usize * 2;
+        match self.frames.iter().position(|f| f.len() != expected) {
+            Some(index) => Err(format!(
+                "frame {} has {} values (expected {})",
+                index,
+                self.frames[index].len(),
+                expected
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn frame_bytes(disk_count: u32) -> usize {
+    disk_count as usize * 2 * std::mem::size_of::<f32>()
+}
+
+/**
+ * フレームごとの座標を max_frames まで記録する
+ * 必要な容量が byte_budget を超える場合は、wasm のヒープを使い切る前にエラーにする
+ */
+#[derive(Debug)]
+pub struct Recorder {
+    max_frames: usize,
+    recording: Recording,
+}
+
+impl Recorder {
+    pub fn new(disk_count: u32, max_frames: u32, byte_budget: usize) -> Result<Recorder, String> {
+        let required = frame_bytes(disk_count).saturating_mul(max_frames as usize);
+        if required > byte_budget {
+            return Err(format!(
+                "recording {} frames of {} disks needs {} bytes, which exceeds the budget of {} bytes",
+                max_frames, disk_count, required, byte_budget
+            ));
+        }
+        let mut frames = Vec::new();
+        frames
+            .try_reserve_exact(max_frames as usize)
+            .map_err(|_| String::from("failed to allocate recording"))?;
+        Ok(Recorder {
+            max_frames: max_frames as usize,
+            recording: Recording { disk_count, frames },
+        })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.recording.frames.len() >= self.max_frames
+    }
+
+    /**
+     * 1フレーム分の座標を追加する (max_frames に達していれば何もしない)
+     */
+    pub fn record(&mut self, positions: &[f32]) -> Result<(), String> {
+        if self.is_full() {
+            return Ok(());
+        }
+        if positions.len() != self.recording.disk_count as usize * 2 {
+            return Err(String::from("disk count changed while recording"));
+        }
+        let mut frame = Vec::new();
+        frame
+            .try_reserve_exact(positions.len())
+            .map_err(|_| String::from("failed to allocate recording frame"))?;
+        frame.extend_from_slice(positions);
+        self.recording.frames.push(frame);
+        Ok(())
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+/**
+ * 記録中なら1フレーム分の座標を足す。失敗したら記録を止め、それまでの記録を stopped に移してエラーを返す
+ * 止めた後は何もしないので、同じエラーは1回しか返らない
+ */
+pub fn record_or_stop(
+    recorder: &mut Option<Recorder>,
+    stopped: &mut Option<Recording>,
+    positions: &[f32],
+) -> Result<(), String> {
+    let result = match recorder.as_mut() {
+        Some(active) => active.record(positions),
+        None => return Ok(()),
+    };
+    if result.is_err() {
+        *stopped = recorder.take().map(Recorder::finish);
+    }
+    result
+}
+
+/**
+ * 記録を1フレームずつ再生する
+ * looping が true なら末尾の次は先頭に戻り、false なら最後のフレームで止まる
+ */
+#[derive(Debug)]
+pub struct Playback {
+    recording: Recording,
+    looping: bool,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(recording: Recording, looping: bool) -> Result<Playback, String> {
+        recording.validate()?;
+        if recording.frames.is_empty() {
+            return Err(String::from("recording has no frames"));
+        }
+        Ok(Playback {
+            recording,
+            looping,
+            next: 0,
+        })
+    }
+
+    pub fn disk_count(&self) -> u32 {
+        self.recording.disk_count
+    }
+
+    /**
+     * 最後まで再生し終えたか (looping の場合は常に false)
+     */
+    pub fn finished(&self) -> bool {
+        !self.looping && self.next >= self.recording.frames.len()
+    }
+
+    pub fn next_frame(&mut self) -> &[f32] {
+        let len = self.recording.frames.len();
+        if self.looping && self.next >= len {
+            self.next = 0;
+        }
+        let index = self.next.min(len - 1);
+        self.next += 1;
+        &self.recording.frames[index]
+    }
+}
+
+// 操作の記録の形式の版
+pub const INPUT_RECORDING_VERSION: u32 = 1;
+
+/**
+ * 記録する外部からの操作 (シミュレーションの結果を変えるもの)
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Input {
+    // 以降のステップで進める時間 (変わったときだけ記録する)
+    Dt {
+        dt_ms: f64,
+    },
+    Spawn {
+        x: f64,
+        y: f64,
+        speed: Option<f64>,
+    },
+    AddDisk {
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+    },
+    AddDiskToGroup {
+        group: usize,
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+    },
+    AddRandomDisks {
+        count: u32,
+    },
+    RandomizeVelocities,
+    ScaleVelocities {
+        factor: f64,
+    },
+    TruncateDisks {
+        len: usize,
+    },
+    RemoveDisk {
+        index: usize,
+    },
+    Impulse {
+        dx: f64,
+        dy: f64,
+    },
+    Force {
+        x: f64,
+        y: f64,
+        strength: f64,
+        radius: f64,
+    },
+    Pointer {
+        x: f64,
+        y: f64,
+    },
+    ClearPointer,
+    Grab {
+        x: f64,
+        y: f64,
+    },
+    Drag {
+        x: f64,
+        y: f64,
+    },
+    Release,
+    TouchPoints {
+        points: Vec<f64>,
+    },
+    AddAttractor {
+        x: f64,
+        y: f64,
+        strength: f64,
+    },
+    RemoveAttractor {
+        id: u32,
+    },
+    ClearAttractors,
+    AddObstacle {
+        obstacle: ObstacleShape,
+    },
+    RemoveObstacle {
+        id: u32,
+    },
+    ClearObstacles,
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    TimeScale {
+        scale: f64,
+    },
+}
+
+/**
+ * tick ステップ目 (記録開始から数えて、そのステップを進める前) に行った操作
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimedInput {
+    pub tick: u64,
+    pub input: Input,
+}
+
+/**
+ * 記録開始時の状態・乱数の種と、それ以降の操作
+ * initial から seed の乱数で inputs を同じ tick に与えながら ticks ステップ進めると、同じ結果になる
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputRecording {
+    pub version: u32,
+    pub seed: u64,
+    pub initial: crate::state::SavedState,
+    pub ticks: u64,
+    pub inputs: Vec<TimedInput>,
+}
+
+impl InputRecording {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version != INPUT_RECORDING_VERSION {
+            return Err(format!(
+                "unsupported recording version {} (expected {})",
+                self.version, INPUT_RECORDING_VERSION
+            ));
+        }
+        self.initial.validate()?;
+        if self
+            .inputs
+            .windows(2)
+            .any(|pair| pair[0].tick > pair[1].tick)
+        {
+            return Err(String::from("inputs are not in tick order"));
+        }
+        match self.inputs.last() {
+            Some(last) if last.tick > self.ticks => Err(format!(
+                "input at tick {} is after the end of the recording ({})",
+                last.tick, self.ticks
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/**
+ * 操作を記録する
+ */
+#[derive(Debug)]
+pub struct InputRecorder {
+    recording: InputRecording,
+    last_dt: Option<f64>,
+}
+
+impl InputRecorder {
+    pub fn new(seed: u64, initial: crate::state::SavedState) -> InputRecorder {
+        InputRecorder {
+            recording: InputRecording {
+                version: INPUT_RECORDING_VERSION,
+                seed,
+                initial,
+                ticks: 0,
+                inputs: Vec::new(),
+            },
+            last_dt: None,
+        }
+    }
+
+    pub fn push(&mut self, tick: u64, input: Input) {
+        self.recording.inputs.push(TimedInput { tick, input });
+    }
+
+    /**
+     * tick ステップ目を dt_ms で進める (前のステップと違う時間なら Dt を記録する)
+     */
+    pub fn step(&mut self, tick: u64, dt_ms: f64) {
+        if self.last_dt != Some(dt_ms) {
+            self.last_dt = Some(dt_ms);
+            self.push(tick, Input::Dt { dt_ms });
+        }
+        self.recording.ticks = tick + 1;
+    }
+
+    pub fn finish(self) -> InputRecording {
+        self.recording
+    }
+}
+
+/**
+ * 記録した操作を tick の順に取り出す
+ */
+#[derive(Debug)]
+pub struct InputReplay {
+    recording: InputRecording,
+    next: usize,
+    dt_ms: f64,
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Result<InputReplay, String> {
+        recording.validate()?;
+        Ok(InputReplay {
+            recording,
+            next: 0,
+            dt_ms: crate::simulation::FRAME_MS,
+        })
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+
+    /**
+     * tick ステップ目の前に与える操作を1つずつ返す (Dt は取り出さずに dt_ms に反映する)
+     */
+    pub fn next_input(&mut self, tick: u64) -> Option<Input> {
+        while let Some(timed) = self.recording.inputs.get(self.next) {
+            if timed.tick > tick {
+                return None;
+            }
+            self.next += 1;
+            match timed.input {
+                Input::Dt { dt_ms } => self.dt_ms = dt_ms,
+                ref input => return Some(input.clone()),
+            }
+        }
+        None
+    }
+
+    /**
+     * 現在のステップを進める時間
+     */
+    pub fn dt_ms(&self) -> f64 {
+        self.dt_ms
+    }
+
+    /**
+     * tick ステップ目が記録の終わりか
+     */
+    pub fn finished(&self, tick: u64) -> bool {
+        tick >= self.recording.ticks
+    }
+}
@@ -0,0 +1,3051 @@
+use crate::boids::{steer_boids, Behavior, BoidsParams};
+use crate::collision::{Grid, Restitution};
+use crate::color::Palette;
+use crate::events::{EventMask, SimEvent};
+use crate::merge::{merge_pair, merge_weight, CollisionResponse, Merge, MAX_MERGE_ROUNDS};
+use crate::obstacle::{bounce_off_obstacles, eject_from_obstacles, Obstacle, ObstacleShape};
+use crate::stats;
+use crate::utils;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Disk {
+    pub x: f64,   // x-coordinate
+    pub y: f64,   // y-coordinate
+    pub cos: f64, // moving velocity-cos
+    pub sin: f64, // moving velocity-sin
+    // 所属するグループの番号 (groups を指定しなければ 0)
+    #[serde(default)]
+    pub kind: u8,
+    // 衝突の計算に使う質量
+    #[serde(default = "default_mass")]
+    pub mass: f64,
+    // ディスク固有の大きさ (None ならシミュレーションの disk_size)
+    #[serde(default)]
+    pub size: Option<f64>,
+    // true なら他のディスクと衝突せずにすり抜ける (壁では跳ね返る)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ghost: bool,
+}
+
+fn default_mass() -> f64 {
+    1.
+}
+
+impl Disk {
+    pub fn new(x: f64, y: f64, cos: f64, sin: f64) -> Self {
+        Self {
+            x,
+            y,
+            cos,
+            sin,
+            kind: 0,
+            mass: default_mass(),
+            size: None,
+            ghost: false,
+        }
+    }
+
+    /**
+     * 壁・衝突判定に使う半径 (固有の大きさがなければ default)
+     */
+    pub fn size_or(&self, default: f64) -> f64 {
+        self.size.unwrap_or(default)
+    }
+}
+
+/**
+ * 同じ大きさ・質量・色を持つディスクのまとまり
+ * size / mass を省略するとシミュレーションの disk_size / 1.0、color を省略するとランダムな色になる
+ * size の代わりに size_range ([最小, 最大]) を指定すると、ディスクごとに大きさを一様に選ぶ
+ * speed_range を指定すると初期速度の速さだけをその範囲から選び直す (向きはそのまま)
+ * palette は color の代わりにグループの色を選ぶパレット、collides: false なら他のディスクとすり抜ける
+ */
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupSpec {
+    pub count: u32,
+    #[serde(default)]
+    pub size: Option<f64>,
+    #[serde(default)]
+    pub size_range: Option<[f64; 2]>,
+    #[serde(default)]
+    pub mass: Option<f64>,
+    // rgb 0.0 - 1.0
+    #[serde(default)]
+    pub color: Option<[f32; 3]>,
+    #[serde(default)]
+    pub speed_range: Option<[f64; 2]>,
+    #[serde(default)]
+    pub palette: Option<String>,
+    #[serde(default)]
+    pub collides: Option<bool>,
+}
+
+// kind は u8 なので、それ以上のグループは区別できない
+pub const MAX_GROUPS: usize = u8::MAX as usize + 1;
+
+impl GroupSpec {
+    /**
+     * グループの数・大きさ・質量が使える値か
+     */
+    pub fn validate(groups: &[GroupSpec]) -> Result<(), String> {
+        if groups.len() > MAX_GROUPS {
+            return Err(format!(
+                "too many groups: {} (at most {})",
+                groups.len(),
+                MAX_GROUPS
+            ));
+        }
+        for (index, group) in groups.iter().enumerate() {
+            if let Some(size) = group.size {
+                if !(size.is_finite() && size > 0.) {
+                    return Err(format!(
+                        "group {}: size must be a positive number: {}",
+                        index, size
+                    ));
+                }
+            }
+            if let Some([min, max]) = group.size_range {
+                if group.size.is_some() {
+                    return Err(format!(
+                        "group {}: `size` cannot be combined with `size_range`",
+                        index
+                    ));
+                }
+                if !(min.is_finite() && max.is_finite() && min > 0. && min <= max) {
+                    return Err(format!(
+                        "group {}: size_range must be [min, max] with 0 < min <= max: [{}, {}]",
+                        index, min, max
+                    ));
+                }
+            }
+            if let Some(mass) = group.mass {
+                if !(mass.is_finite() && mass > 0.) {
+                    return Err(format!(
+                        "group {}: mass must be a positive number: {}",
+                        index, mass
+                    ));
+                }
+            }
+            if let Some([min, max]) = group.speed_range {
+                if !(min.is_finite() && max.is_finite() && min >= 0. && min <= max) {
+                    return Err(format!(
+                        "group {}: speed_range must be [min, max] with 0 <= min <= max: [{}, {}]",
+                        index, min, max
+                    ));
+                }
+            }
+            if let Some(palette) = group.palette.as_deref() {
+                if group.color.is_some() {
+                    return Err(format!(
+                        "group {}: `color` cannot be combined with `palette`",
+                        index
+                    ));
+                }
+                Palette::parse(palette).map_err(|e| format!("group {}: {}", index, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * disk をこのグループ (kind 番目) のディスクにする。大きさ・質量・すり抜けを決め、速度は変えない
+     * size_range を指定していれば大きさを選ぶ (指定がなければ乱数を消費しない)
+     */
+    pub fn apply<R: Rng + ?Sized>(
+        &self,
+        disk: &mut Disk,
+        kind: u8,
+        size: f64,
+        mass_from_radius: bool,
+        rng: &mut R,
+    ) {
+        disk.kind = kind;
+        disk.size = match self.size_range {
+            Some([min, max]) => Some(gen_between(rng, min, max)),
+            None => self.size,
+        };
+        let radius = disk.size_or(size);
+        disk.mass = self.mass.unwrap_or(if mass_from_radius {
+            radius * radius
+        } else {
+            default_mass()
+        });
+        disk.ghost = !self.collides.unwrap_or(true);
+    }
+
+    /**
+     * グループの count 個のディスクの色 (1diskあたりrgbの3値)。color も palette もなければ None
+     */
+    pub fn colors<R: Rng + ?Sized>(&self, count: u32, rng: &mut R) -> Option<Vec<f32>> {
+        if let Some(color) = self.color {
+            return Some(color.repeat(count as usize));
+        }
+        let palette = Palette::parse(self.palette.as_deref()?).ok()?;
+        Some(palette.colors(count, rng))
+    }
+}
+
+/**
+ * ディスクの初期位置の決め方
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpawnPattern {
+    // 全ディスクを中央から出発させる (以前の挙動)
+    Center,
+    // 壁にめり込まない範囲でランダムに散らばらせる
+    Uniform,
+    // 行・列を等間隔に並べる
+    Grid,
+    // 中心の周りの円周上に等間隔に並べ、外向きに動かす (None なら短辺の 1/4)
+    Ring { radius: Option<f64> },
+    // Ring と同じ位置に並べ、円周に沿って (反時計回りに) 動かす
+    Orbit { radius: Option<f64> },
+}
+
+impl SpawnPattern {
+    pub fn parse(mode: &str) -> Result<SpawnPattern, String> {
+        match mode {
+            "center" => Ok(SpawnPattern::Center),
+            // "random" は "uniform" の別名
+            "uniform" | "random" => Ok(SpawnPattern::Uniform),
+            "grid" => Ok(SpawnPattern::Grid),
+            "ring" => Ok(SpawnPattern::Ring { radius: None }),
+            "orbit" => Ok(SpawnPattern::Orbit { radius: None }),
+            _ => Err(format!(
+                "unknown spawn: {} (expected \"center\", \"uniform\", \"grid\", \"ring\" or \"orbit\")",
+                mode
+            )),
+        }
+    }
+}
+
+// 初期速度の既定の範囲
+pub const DEFAULT_SPEED_RANGE: [f64; 2] = [1., 4.];
+// 初期速度の向きの既定の範囲 (ラジアン、全方向)
+pub const FULL_ANGLE_RANGE: [f64; 2] = [0., std::f64::consts::PI * 2.];
+
+/**
+ * 初期速度の決め方
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VelocityDistribution {
+    // 速さを speed_range から、向きを angle_range (ラジアン) から独立に一様に選ぶ
+    Uniform {
+        speed_range: [f64; 2],
+        angle_range: [f64; 2],
+    },
+    // 最初の版の決め方 (共通の乱数 r で速さ 1 + 3r、向き 0.1 * π * i * r。番号 i が小さいディスクほど同じ向きに偏る)
+    Legacy,
+}
+
+impl From<[f64; 2]> for VelocityDistribution {
+    fn from(speed_range: [f64; 2]) -> VelocityDistribution {
+        VelocityDistribution::Uniform {
+            speed_range,
+            angle_range: FULL_ANGLE_RANGE,
+        }
+    }
+}
+
+impl VelocityDistribution {
+    /**
+     * "uniform" | "legacy" ("legacy" は speed_range / angle_range を使わない)
+     */
+    pub fn parse(
+        mode: &str,
+        speed_range: [f64; 2],
+        angle_range: [f64; 2],
+    ) -> Result<VelocityDistribution, String> {
+        match mode {
+            "uniform" => {
+                let [min, max] = angle_range;
+                if !(min.is_finite() && max.is_finite() && min <= max) {
+                    return Err(format!(
+                        "angle range must be [min, max] with min <= max: [{}, {}]",
+                        min, max
+                    ));
+                }
+                Ok(VelocityDistribution::Uniform {
+                    speed_range,
+                    angle_range,
+                })
+            }
+            "legacy" => Ok(VelocityDistribution::Legacy),
+            _ => Err(format!(
+                "unknown velocity distribution: {} (expected \"uniform\" or \"legacy\")",
+                mode
+            )),
+        }
+    }
+
+    /**
+     * 速さの範囲 (spawn_at で向きを別に決めるときに使う)
+     */
+    pub fn speed_range(&self) -> [f64; 2] {
+        match *self {
+            VelocityDistribution::Uniform { speed_range, .. } => speed_range,
+            VelocityDistribution::Legacy => DEFAULT_SPEED_RANGE,
+        }
+    }
+
+    /**
+     * index 番目のディスクの速度 (vx, vy)
+     * direction (単位ベクトル) を渡すと向きはそれに固定し、速さだけを選ぶ
+     */
+    pub fn sample<R: Rng + ?Sized>(
+        &self,
+        index: usize,
+        direction: Option<(f64, f64)>,
+        rng: &mut R,
+    ) -> (f64, f64) {
+        let (speed, angle) = match *self {
+            VelocityDistribution::Uniform {
+                speed_range,
+                angle_range,
+            } => {
+                let speed = gen_between(rng, speed_range[0], speed_range[1]);
+                if let Some((cos, sin)) = direction {
+                    return (speed * cos, speed * sin);
+                }
+                (speed, gen_between(rng, angle_range[0], angle_range[1]))
+            }
+            VelocityDistribution::Legacy => {
+                let random = rng.gen_range(0., 1.);
+                (
+                    1. + 3. * random,
+                    std::f64::consts::PI * 0.1 * index as f64 * random,
+                )
+            }
+        };
+        let (cos, sin) = direction.unwrap_or((angle.cos(), angle.sin()));
+        (speed * cos, speed * sin)
+    }
+}
+
+// low == high でも gen_range が panic しないようにする
+fn gen_between<R: Rng + ?Sized>(rng: &mut R, low: f64, high: f64) -> f64 {
+    if low < high {
+        rng.gen_range(low, high)
+    } else {
+        low
+    }
+}
+
+// 半径 size の円が [0, bound] に収まる座標 (収まらなければ中央)
+fn spawn_coordinate<R: Rng + ?Sized>(rng: &mut R, bound: f64, size: f64) -> f64 {
+    if bound > size * 2. {
+        gen_between(rng, size, bound - size)
+    } else {
+        bound / 2.
+    }
+}
+
+// 半径 size の円が [0, bound] に収まるよう value を内側に寄せる (収まらない・有限でなければ中央)
+fn clamp_coordinate(value: f64, bound: f64, size: f64) -> f64 {
+    if bound > size * 2. && value.is_finite() {
+        value.clamp(size, bound - size)
+    } else {
+        bound / 2.
+    }
+}
+
+// [size, bound - size] を count 等分したときの index 番目の中心 (収まらなければ中央)
+fn grid_coordinate(index: usize, count: usize, bound: f64, size: f64) -> f64 {
+    let inner = bound - size * 2.;
+    if inner > 0. {
+        size + (index as f64 + 0.5) * inner / count as f64
+    } else {
+        bound / 2.
+    }
+}
+
+/**
+ * パターンに従って n 個のディスクの初期位置を決める (速度は 0)
+ * Ring / Orbit の場合のみ、速度に進む向き (外向き / 円周に沿う向き) の単位ベクトルを入れて返す
+ * Ring / Orbit の円の半径は、ディスクが壁にめり込まない大きさまでに抑える
+ */
+pub fn spawn_positions<R: Rng + ?Sized>(
+    pattern: SpawnPattern,
+    n: u32,
+    width: f64,
+    height: f64,
+    radius: f64,
+    rng: &mut R,
+) -> Vec<Disk> {
+    let n = n as usize;
+    let (cx, cy) = (width / 2., height / 2.);
+    match pattern {
+        SpawnPattern::Center => vec![Disk::new(cx, cy, 0., 0.); n],
+        SpawnPattern::Uniform => (0..n)
+            .map(|_| {
+                let x = spawn_coordinate(rng, width, radius);
+                let y = spawn_coordinate(rng, height, radius);
+                Disk::new(x, y, 0., 0.)
+            })
+            .collect(),
+        SpawnPattern::Grid => {
+            if n == 0 {
+                return Vec::new();
+            }
+            // canvas の縦横比に近い列数にし、最後の行は埋まりきらなくてもよい
+            let cols = ((n as f64 * width / height.max(1.)).sqrt().ceil() as usize).clamp(1, n);
+            let rows = n.div_ceil(cols);
+            (0..n)
+                .map(|i| {
+                    Disk::new(
+                        grid_coordinate(i % cols, cols, width, radius),
+                        grid_coordinate(i / cols, rows, height, radius),
+                        0.,
+                        0.,
+                    )
+                })
+                .collect()
+        }
+        SpawnPattern::Ring { radius: ring } | SpawnPattern::Orbit { radius: ring } => {
+            let ring = ring
+                .unwrap_or(width.min(height) / 4.)
+                .min(width.min(height) / 2. - radius)
+                .max(0.);
+            let orbit = matches!(pattern, SpawnPattern::Orbit { .. });
+            (0..n)
+                .map(|i| {
+                    let angle = std::f64::consts::PI * 2. * i as f64 / n as f64;
+                    let (sin, cos) = angle.sin_cos();
+                    let (vx, vy) = if orbit { (sin, -cos) } else { (cos, sin) };
+                    Disk::new(cx + ring * cos, cy + ring * sin, vx, vy)
+                })
+                .collect()
+        }
+    }
+}
+
+/**
+ * ディスクのベクタを初期化する
+ * 位置は spawn_positions で決め、速度は velocities で選ぶ (速さの範囲 [最小, 最大) だけを渡すと向きは [0, 2π) から一様に選ぶ)
+ * Ring の場合は外向き、Orbit の場合は円周に沿う向きに固定する
+ */
+pub fn init_disks<R: Rng + ?Sized>(
+    disk_num: u32,
+    bound_x: u32,
+    bound_y: u32,
+    size: f64,
+    spawn: SpawnPattern,
+    velocities: impl Into<VelocityDistribution>,
+    rng: &mut R,
+) -> Vec<Disk> {
+    let velocities = velocities.into();
+    let positions = spawn_positions(spawn, disk_num, bound_x as f64, bound_y as f64, size, rng);
+    let directed = matches!(
+        spawn,
+        SpawnPattern::Ring { .. } | SpawnPattern::Orbit { .. }
+    );
+    positions
+        .into_iter()
+        .enumerate()
+        .map(|(index, disk)| {
+            let direction = if directed {
+                Some((disk.cos, disk.sin))
+            } else {
+                None
+            };
+            let (vx, vy) = velocities.sample(index, direction, rng);
+            Disk::new(disk.x, disk.y, vx, vy)
+        })
+        .collect()
+}
+
+/**
+ * groups の順に kind = 0, 1, ... のディスクを count 個ずつ作る
+ * 位置は一番大きいディスクの大きさで spawn_positions に従って決め、速さは init_disks と同じ
+ * size_range を指定したグループは、その後でディスクごとに大きさを選ぶ (指定がなければ乱数を消費しない)
+ * mass を省略したグループの質量は、mass_from_radius なら 半径², そうでなければ 1.0 にする
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn init_groups<R: Rng + ?Sized>(
+    groups: &[GroupSpec],
+    bound_x: u32,
+    bound_y: u32,
+    size: f64,
+    spawn: SpawnPattern,
+    velocities: impl Into<VelocityDistribution>,
+    mass_from_radius: bool,
+    rng: &mut R,
+) -> Result<Vec<Disk>, String> {
+    GroupSpec::validate(groups)?;
+    let total = groups.iter().map(|group| group.count).sum();
+    let largest = groups
+        .iter()
+        .map(|group| match group.size_range {
+            Some([_, max]) => max,
+            None => group.size.unwrap_or(size),
+        })
+        .fold(size, f64::max);
+    let mut disks = init_disks(total, bound_x, bound_y, largest, spawn, velocities, rng);
+    let mut chunks = disks.iter_mut();
+    for (kind, group) in groups.iter().enumerate() {
+        for disk in chunks.by_ref().take(group.count as usize) {
+            group.apply(disk, kind as u8, size, mass_from_radius, rng);
+            if let Some([min, max]) = group.speed_range {
+                let speed = gen_between(rng, min, max);
+                let angle = if disk.cos == 0. && disk.sin == 0. {
+                    rng.gen_range(0., std::f64::consts::PI * 2.)
+                } else {
+                    disk.sin.atan2(disk.cos)
+                };
+                disk.cos = speed * angle.cos();
+                disk.sin = speed * angle.sin();
+            }
+        }
+    }
+    Ok(disks)
+}
+
+// 重ならない配置で、ランダムな位置を1ディスクあたりに試す回数 (尽きたら六角格子に並べ直す)
+const PLACEMENT_ATTEMPTS: u32 = 100;
+
+/**
+ * 半径 radius のディスクを、壁にめり込まず互いに重ならないよう width × height に並べられる六角格子の点
+ * 各行は [radius, width - radius] に 2 * radius 間隔、行どうしは √3 * radius 間隔で、1行おきに radius ずらす
+ */
+fn hex_sites(width: f64, height: f64, radius: f64) -> Vec<(f64, f64)> {
+    let mut sites = Vec::new();
+    if radius <= 0. || width < radius * 2. || height < radius * 2. {
+        return sites;
+    }
+    // 隣の行の点との距離が丸め誤差で 2 * radius を下回らないよう、わずかに広げる
+    let (dx, dy) = (
+        radius * 2. * (1. + 1e-9),
+        radius * 3f64.sqrt() * (1. + 1e-9),
+    );
+    let mut y = radius;
+    let mut row = 0;
+    while y <= height - radius {
+        let mut x = if row % 2 == 0 { radius } else { radius * 2. };
+        while x <= width - radius {
+            sites.push((x, y));
+            x += dx;
+        }
+        y += dy;
+        row += 1;
+    }
+    sites
+}
+
+/**
+ * 半径 radius のディスクを重ならないように置ける数 (六角格子の点の数)
+ */
+pub fn max_non_overlapping(width: f64, height: f64, radius: f64) -> usize {
+    hex_sites(width, height, radius).len()
+}
+
+/**
+ * disks を互いに重ならず (中心間の距離が半径の和以上)、壁にもめり込まない位置に置き直す
+ * 一番大きいディスクの半径で、ランダムな位置を試しては既に置いたものと重なれば捨てる (ダーツ投げ)
+ * 試行が尽きたら六角格子の点からランダムに選んで置き直す。格子に収まらない数ならエラーにして何も変えない
+ */
+pub fn place_without_overlap<R: Rng + ?Sized>(
+    disks: &mut [Disk],
+    width: f64,
+    height: f64,
+    size: f64,
+    rng: &mut R,
+) -> Result<(), String> {
+    place_without_overlap_in(disks, &Bounds::new(width, height), None, size, rng)
+}
+
+/**
+ * place_without_overlap と同じだが、bounds の内側に置く
+ * circle を渡せば代わりにその円の内側 (縁にめり込まない位置) に置く
+ */
+pub fn place_without_overlap_in<R: Rng + ?Sized>(
+    disks: &mut [Disk],
+    bounds: &Bounds,
+    circle: Option<&Circle>,
+    size: f64,
+    rng: &mut R,
+) -> Result<(), String> {
+    let radius = disks.iter().map(|d| d.size_or(size)).fold(size, f64::max);
+    // 置く範囲を囲む矩形 (left, top, width, height) と、その中で置ける点かどうか
+    let (left, top, width, height) = match circle {
+        Some(circle) => (
+            circle.x - circle.radius,
+            circle.y - circle.radius,
+            circle.radius * 2.,
+            circle.radius * 2.,
+        ),
+        None => (
+            bounds.left,
+            bounds.top,
+            bounds.right - bounds.left,
+            bounds.bottom - bounds.top,
+        ),
+    };
+    let inside = |x: f64, y: f64| match circle {
+        Some(circle) => (left + x - circle.x).hypot(top + y - circle.y) <= circle.radius - radius,
+        None => true,
+    };
+    let mut sites = hex_sites(width, height, radius);
+    sites.retain(|&(x, y)| inside(x, y));
+    if disks.len() > sites.len() {
+        let region = match circle {
+            Some(circle) => format!("a circle of radius {}", circle.radius),
+            None => format!("{}x{}", width, height),
+        };
+        return Err(format!(
+            "cannot place {} disks of size {} without overlap in {}; at most {} fit",
+            disks.len(),
+            radius,
+            region,
+            sites.len()
+        ));
+    }
+    let positions = match throw_darts(disks.len(), width, height, radius, inside, rng) {
+        Some(positions) => positions,
+        None => {
+            sites.shuffle(rng);
+            sites.truncate(disks.len());
+            sites
+        }
+    };
+    for (disk, (x, y)) in disks.iter_mut().zip(positions) {
+        disk.x = left + x;
+        disk.y = top + y;
+    }
+    Ok(())
+}
+
+// inside を満たす n 個の重ならない位置をランダムに選ぶ。どれかが PLACEMENT_ATTEMPTS 回で置けなければ None
+fn throw_darts<R: Rng + ?Sized>(
+    n: usize,
+    width: f64,
+    height: f64,
+    radius: f64,
+    inside: impl Fn(f64, f64) -> bool,
+    rng: &mut R,
+) -> Option<Vec<(f64, f64)>> {
+    let min_distance_sq = radius * radius * 4.;
+    // 一辺 2 * radius のセルに置いた位置を入れ、周りの 3x3 セルだけを調べる
+    let cell = radius * 2.;
+    let cols = (width / cell).ceil().max(1.) as usize;
+    let rows = (height / cell).ceil().max(1.) as usize;
+    let cell_of = |x: f64, y: f64| {
+        (
+            ((x / cell) as usize).min(cols - 1),
+            ((y / cell) as usize).min(rows - 1),
+        )
+    };
+    let mut cells: Vec<Vec<(f64, f64)>> = vec![Vec::new(); cols * rows];
+    let mut positions = Vec::with_capacity(n);
+    for _ in 0..n {
+        let (x, y) = (0..PLACEMENT_ATTEMPTS)
+            .map(|_| {
+                (
+                    gen_between(rng, radius, width - radius),
+                    gen_between(rng, radius, height - radius),
+                )
+            })
+            .find(|&(x, y)| {
+                if !inside(x, y) {
+                    return false;
+                }
+                let (cx, cy) = cell_of(x, y);
+                (cy.saturating_sub(1)..(cy + 2).min(rows)).all(|row| {
+                    (cx.saturating_sub(1)..(cx + 2).min(cols)).all(|col| {
+                        cells[row * cols + col].iter().all(|&(px, py)| {
+                            (px - x) * (px - x) + (py - y) * (py - y) >= min_distance_sq
+                        })
+                    })
+                })
+            })?;
+        let (cx, cy) = cell_of(x, y);
+        cells[cy * cols + cx].push((x, y));
+        positions.push((x, y));
+    }
+    Some(positions)
+}
+
+/**
+ * 大きさが等しい連続したディスクの範囲と、その大きさ ((開始位置, 個数, 大きさ) の列)
+ * 描画時に gl_PointSize などをまとめて設定するのに使う
+ */
+pub fn size_runs(disks: &[Disk], default: f64) -> Vec<(usize, usize, f64)> {
+    let mut runs: Vec<(usize, usize, f64)> = Vec::new();
+    for (index, disk) in disks.iter().enumerate() {
+        let size = disk.size_or(default);
+        match runs.last_mut() {
+            Some((_, count, last)) if *last == size => *count += 1,
+            _ => runs.push((index, 1, size)),
+        }
+    }
+    runs
+}
+
+/**
+ * seed が指定されていれば再現性のある乱数生成器を、なければ thread_rng を返す
+ */
+pub fn create_rng(seed: Option<u64>) -> Box<dyn RngCore> {
+    match seed {
+        Some(seed) => Box::new(StdRng::seed_from_u64(seed)),
+        None => Box::new(rand::thread_rng()),
+    }
+}
+
+/**
+ * create_rng と同じだが、初期化の後も持ち続けられるよう具体的な型で返す
+ * seed が同じなら create_rng(Some(seed)) と同じ列を返す。なければ thread_rng から種を取る
+ */
+pub fn create_std_rng(seed: Option<u64>) -> StdRng {
+    StdRng::seed_from_u64(seed.unwrap_or_else(|| rand::thread_rng().gen()))
+}
+
+// 速度 (cos, sin) は 60fps の1フレームあたりの移動量として扱う
+pub const FRAME_MS: f64 = 1000. / 60.;
+// タブが非表示だった後などに1ステップで大きく進みすぎないようにする上限
+pub const MAX_DT_MS: f64 = 50.;
+
+/**
+ * 1イテレーションごとの座標計算
+ * WebGLに依存しないため、Screenを介さずにテストできる
+ */
+pub fn step_disks(disks: &mut [Disk], width: f64, height: f64, size: f64) {
+    step_disks_scaled(disks, width, height, size, 1.);
+}
+
+/**
+ * 速度を scale 倍 (= 経過時間 / FRAME_MS) だけ進めて壁で反射させる
+ */
+pub fn step_disks_scaled(disks: &mut [Disk], width: f64, height: f64, size: f64, scale: f64) {
+    bounce_disks(disks, &Bounds::new(width, height), size, scale);
+}
+
+/**
+ * 反射する壁の位置
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+}
+
+impl Bounds {
+    pub fn new(width: f64, height: f64) -> Bounds {
+        Bounds {
+            left: 0.,
+            top: 0.,
+            right: width,
+            bottom: height,
+        }
+    }
+
+    /**
+     * 四辺を padding だけ内側に寄せる
+     */
+    pub fn inset(&self, padding: f64) -> Bounds {
+        Bounds {
+            left: self.left + padding,
+            top: self.top + padding,
+            right: self.right - padding,
+            bottom: self.bottom - padding,
+        }
+    }
+}
+
+/**
+ * bounds の内側で反射させながら、速度を scale 倍だけ進める
+ */
+pub fn bounce_disks(disks: &mut [Disk], bounds: &Bounds, size: f64, scale: f64) {
+    bounce_disks_with(
+        disks,
+        bounds,
+        size,
+        scale,
+        Restitution::elastic(),
+        |_, _| {},
+    );
+}
+
+/**
+ * bounce_disks と同じだが、壁での反射に restitution を使い、壁で反射したディスクごとに on_hit(index, 反射後のディスク) を呼ぶ
+ * 1ステップで何度 (角で2方向に) 反射した場合も1回だけ呼ぶ
+ */
+pub fn bounce_disks_with(
+    disks: &mut [Disk],
+    bounds: &Bounds,
+    size: f64,
+    scale: f64,
+    restitution: Restitution,
+    mut on_hit: impl FnMut(usize, &Disk),
+) {
+    for (index, disk) in disks.iter_mut().enumerate() {
+        let radius = disk.size_or(size);
+        if sweep_disk_with(disk, bounds, radius, scale, restitution) {
+            on_hit(index, disk);
+        }
+    }
+}
+
+// 1ステップ内で壁に当たる回数の上限 (これを超えたら残りの移動を諦めて壁の内側に収める)
+const MAX_SWEEP_BOUNCES: usize = 1024;
+
+// 座標 position が速度 velocity で [low, high] の壁に達するまでの時間 (近づいていなければ無限大)
+fn time_to_wall(position: f64, velocity: f64, low: f64, high: f64) -> f64 {
+    if velocity > 0. {
+        ((high - position) / velocity).max(0.)
+    } else if velocity < 0. {
+        ((low - position) / velocity).max(0.)
+    } else {
+        f64::INFINITY
+    }
+}
+
+/**
+ * 速度を scale 倍だけ進める間に壁に当たるたび、その時刻まで進めて反射し、残りの時間で動き続ける
+ * 1ステップで壁の間を何往復しても、移動後の中心は bounds から半径 size だけ内側に収まり、速さは変わらない
+ * 壁に当たったかどうかを返す
+ */
+pub fn sweep_disk(disk: &mut Disk, bounds: &Bounds, size: f64, scale: f64) -> bool {
+    sweep_disk_with(disk, bounds, size, scale, Restitution::elastic())
+}
+
+/**
+ * sweep_disk と同じだが、反射のたびに壁に垂直な速度成分へ反発係数 restitution を掛ける
+ */
+pub fn sweep_disk_with(
+    disk: &mut Disk,
+    bounds: &Bounds,
+    size: f64,
+    scale: f64,
+    restitution: Restitution,
+) -> bool {
+    // 中心が動ける範囲 (ディスクが領域より大きい場合は中央の1点)
+    let axis = |low: f64, high: f64| {
+        let (low, high) = (low + size, high - size);
+        if low <= high {
+            (low, high)
+        } else {
+            let center = (low + high) / 2.;
+            (center, center)
+        }
+    };
+    let (left, right) = axis(bounds.left, bounds.right);
+    let (top, bottom) = axis(bounds.top, bounds.bottom);
+    let mut remaining = scale;
+    let mut hit = false;
+    for _ in 0..MAX_SWEEP_BOUNCES {
+        let tx = time_to_wall(disk.x, disk.cos, left, right);
+        let ty = time_to_wall(disk.y, disk.sin, top, bottom);
+        let t = tx.min(ty);
+        if t >= remaining {
+            disk.x += disk.cos * remaining;
+            disk.y += disk.sin * remaining;
+            break;
+        }
+        disk.x += disk.cos * t;
+        disk.y += disk.sin * t;
+        remaining -= t;
+        // 角にちょうど当たった場合は両方向に反射する
+        if tx <= t {
+            disk.x = if disk.cos > 0. { right } else { left };
+            disk.cos = -disk.cos * restitution.at(disk.cos.abs());
+        }
+        if ty <= t {
+            disk.y = if disk.sin > 0. { bottom } else { top };
+            disk.sin = -disk.sin * restitution.at(disk.sin.abs());
+        }
+        hit = true;
+    }
+    // 上限に達した場合や、最初から範囲外にいて外向きに動いていない場合も内側に収める
+    disk.x = disk.x.clamp(left, right);
+    disk.y = disk.y.clamp(top, bottom);
+    hit
+}
+
+/**
+ * 壁に達したディスクの扱い
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    // 壁で反射する
+    Bounce,
+    // 反対側の端から出てくる (トーラス)
+    Wrap,
+}
+
+impl Boundary {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Bounce => "bounce",
+            Boundary::Wrap => "wrap",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Result<Boundary, String> {
+        match mode {
+            "bounce" => Ok(Boundary::Bounce),
+            "wrap" => Ok(Boundary::Wrap),
+            _ => Err(format!(
+                "unknown boundary: {} (expected \"bounce\" or \"wrap\")",
+                mode
+            )),
+        }
+    }
+}
+
+/**
+ * ディスクを閉じ込める領域の形
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum Arena {
+    // canvas の四辺 (boundary で反射するか折り返すかを選ぶ)
+    #[default]
+    Rect,
+    // 円の内側で反射する (boundary によらない)
+    // center / radius を省略すると canvas に内接する最大の円になり、resize に追従する
+    Circle {
+        center: Option<[f64; 2]>,
+        radius: Option<f64>,
+    },
+}
+
+/**
+ * 円形の領域 (中心と半径)
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Circle {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+}
+
+impl Arena {
+    /**
+     * "rect" | "circle"。center / radius は "circle" のときのみ指定できる
+     */
+    pub fn parse(
+        mode: &str,
+        center: Option<[f64; 2]>,
+        radius: Option<f64>,
+    ) -> Result<Arena, String> {
+        let arena = match mode {
+            "rect" if center.is_none() && radius.is_none() => Arena::Rect,
+            "rect" => {
+                return Err(String::from(
+                    "arena_center / arena_radius require arena: \"circle\"",
+                ))
+            }
+            "circle" => Arena::Circle { center, radius },
+            _ => {
+                return Err(format!(
+                    "unknown arena: {} (expected \"rect\" or \"circle\")",
+                    mode
+                ))
+            }
+        };
+        arena.validate()?;
+        Ok(arena)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Arena::Circle { center, radius } = *self {
+            if let Some([x, y]) = center {
+                if !(x.is_finite() && y.is_finite()) {
+                    return Err(format!("arena_center must be finite: ({}, {})", x, y));
+                }
+            }
+            if let Some(radius) = radius {
+                if !(radius.is_finite() && radius > 0.) {
+                    return Err(format!(
+                        "arena_radius must be a positive number: {}",
+                        radius
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * width x height の canvas での円 (rect なら None)
+     */
+    pub fn circle(&self, width: f64, height: f64) -> Option<Circle> {
+        match *self {
+            Arena::Rect => None,
+            Arena::Circle { center, radius } => {
+                let [x, y] = center.unwrap_or([width / 2., height / 2.]);
+                Some(Circle {
+                    x,
+                    y,
+                    radius: radius.unwrap_or(width.min(height) / 2.),
+                })
+            }
+        }
+    }
+}
+
+/**
+ * 中心が circle から半径 size だけ内側に収まるよう、はみ出したディスクを中心へ向かって引き戻す (速度は変えない)
+ * 引き戻したかどうかと、そのときの外向きの法線を返す
+ */
+pub fn contain_in_circle(disk: &mut Disk, circle: &Circle, size: f64) -> Option<(f64, f64)> {
+    let limit = (circle.radius - size).max(0.);
+    let (dx, dy) = (disk.x - circle.x, disk.y - circle.y);
+    let distance = dx.hypot(dy);
+    if distance <= limit || distance.is_nan() {
+        return None;
+    }
+    let (nx, ny) = (dx / distance, dy / distance);
+    disk.x = circle.x + nx * limit;
+    disk.y = circle.y + ny * limit;
+    Some((nx, ny))
+}
+
+/**
+ * 円の内側で、速度を scale 倍だけ進めてから円の縁で反射させる
+ * 縁を越えたディスクは半径方向に内側へ戻し、速度の法線 (半径方向) 成分が外向きなら反転して restitution を掛ける
+ * 反射したディスクごとに on_hit(index, 反射後のディスク) を呼ぶ
+ */
+pub fn bounce_disks_in_circle(
+    disks: &mut [Disk],
+    circle: &Circle,
+    size: f64,
+    scale: f64,
+    restitution: Restitution,
+    mut on_hit: impl FnMut(usize, &Disk),
+) {
+    for (index, disk) in disks.iter_mut().enumerate() {
+        disk.x += disk.cos * scale;
+        disk.y += disk.sin * scale;
+        let (nx, ny) = match contain_in_circle(disk, circle, disk.size_or(size)) {
+            Some(normal) => normal,
+            None => continue,
+        };
+        let normal_speed = disk.cos * nx + disk.sin * ny;
+        if normal_speed > 0. {
+            let reflected = -normal_speed * restitution.at(normal_speed);
+            disk.cos += (reflected - normal_speed) * nx;
+            disk.sin += (reflected - normal_speed) * ny;
+        }
+        on_hit(index, disk);
+    }
+}
+
+/**
+ * 1イテレーションごとの座標計算 (トーラス境界)
+ * 半径 size (ディスク固有の大きさがあればそれ) の分だけ完全に画面外へ出てから反対側に現れる ([-size, width + size) を周期とする)
+ * 1フレームで画面幅以上進んでも剰余で正しい位置に折り返す。速度は変えない
+ */
+pub fn wrap_disks(disks: &mut [Disk], width: f64, height: f64, size: f64, scale: f64) {
+    for disk in disks.iter_mut() {
+        let radius = disk.size_or(size);
+        disk.x = wrap_coordinate(disk.x + disk.cos * scale, width, radius);
+        disk.y = wrap_coordinate(disk.y + disk.sin * scale, height, radius);
+    }
+}
+
+fn wrap_coordinate(value: f64, bound: f64, size: f64) -> f64 {
+    (value + size).rem_euclid(bound + size * 2.) - size
+}
+
+/**
+ * 速さが max_speed を超えているディスクの速度を、向きを保ったまま max_speed まで縮める
+ */
+pub fn clamp_speeds(disks: &mut [Disk], max_speed: f64) {
+    for disk in disks.iter_mut() {
+        let speed = disk.cos.hypot(disk.sin);
+        if speed > max_speed {
+            let ratio = max_speed / speed;
+            disk.cos *= ratio;
+            disk.sin *= ratio;
+        }
+    }
+}
+
+/**
+ * 標準正規分布に従う乱数 (一様乱数2つから Box-Muller 法で作る)
+ */
+pub fn gaussian<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    // ln(0) を避けるため (0, 1] から取る
+    let u1: f64 = 1. - rng.gen_range(0., 1.);
+    let u2: f64 = rng.gen_range(0., 1.);
+    (-2. * u1.ln()).sqrt() * (std::f64::consts::PI * 2. * u2).cos()
+}
+
+/**
+ * 各ディスクの速度に、軸ごとに標準偏差 jitter の正規分布に従う値を加える (ブラウン運動)
+ * scale フレーム分なら標準偏差は jitter * √scale (刻みを細かくしても広がり方が変わらない)
+ */
+pub fn apply_jitter<R: Rng + ?Sized>(disks: &mut [Disk], jitter: f64, scale: f64, rng: &mut R) {
+    let sigma = jitter * scale.sqrt();
+    for disk in disks.iter_mut() {
+        disk.cos += gaussian(rng) * sigma;
+        disk.sin += gaussian(rng) * sigma;
+    }
+}
+
+// 抵抗で遅くなったディスクをこの速さ未満で止める (非正規化数まで小さくなり続けないように)
+const MIN_DRAG_SPEED: f64 = 1e-3;
+
+/**
+ * 速度 (cos, sin の両方) に 1 フレームあたり (1 - drag) を掛ける。scale フレーム分なら (1 - drag)^scale
+ * 速さが MIN_DRAG_SPEED 未満になったディスクは止める
+ */
+pub fn apply_drag(disks: &mut [Disk], drag: f64, scale: f64) {
+    let factor = (1. - drag).powf(scale);
+    for disk in disks.iter_mut() {
+        disk.cos *= factor;
+        disk.sin *= factor;
+        if disk.cos.hypot(disk.sin) < MIN_DRAG_SPEED {
+            disk.cos = 0.;
+            disk.sin = 0.;
+        }
+    }
+}
+
+// apply_force で1回に加える速度の上限 (ディスクが瞬間移動しないように)
+const MAX_IMPULSE: f64 = 8.;
+
+// set_pointer で引き寄せる強さ (px / フレーム²) と届く半径 (px) の既定値
+pub const DEFAULT_POINTER_FORCE: f64 = 0.5;
+pub const DEFAULT_POINTER_RADIUS: f64 = 100.;
+
+/**
+ * (x, y) から半径 radius 以内のディスクを、force (正で引き寄せ、負で遠ざける) の加速度で動かす
+ * 加速度は中心で force、半径の位置で 0 になるよう距離に比例して弱め、1ステップで加える速度は MAX_IMPULSE までにする
+ * scale は60fpsの1フレームに対する経過時間の割合
+ */
+pub fn apply_pointer_force(
+    disks: &mut [Disk],
+    x: f64,
+    y: f64,
+    force: f64,
+    radius: f64,
+    scale: f64,
+) {
+    for disk in disks.iter_mut() {
+        let dx = x - disk.x;
+        let dy = y - disk.y;
+        let distance = dx.hypot(dy);
+        if distance > radius {
+            continue;
+        }
+        // ちょうどポインタの位置にいるディスクは方向が定まらないので、引き寄せは何もせず、遠ざけるときは右向きに押す
+        let (nx, ny) = if distance > f64::EPSILON {
+            (dx / distance, dy / distance)
+        } else if force > 0. {
+            continue;
+        } else {
+            (-1., 0.)
+        };
+        let acceleration = force * (1. - distance / radius) * scale;
+        let acceleration = acceleration.clamp(-MAX_IMPULSE, MAX_IMPULSE);
+        disk.cos += nx * acceleration;
+        disk.sin += ny * acceleration;
+    }
+}
+
+// set_time_scale で指定できる倍率の絶対値の上限 (1フレームの分割数がこれを超えないように)
+pub const MAX_TIME_SCALE: f64 = 16.;
+
+/**
+ * dt_ms を time_scale 倍した時間を、1回の移動が元の dt_ms を超えないよう何回に分けて進めるか ((回数, 1回の時間))
+ * 逆再生 (負の倍率) は絶対値で分け、速度の向きは呼び出し側で反転する。倍率 1.0 なら (1, dt_ms)
+ */
+pub fn time_scale_substeps(dt_ms: f64, time_scale: f64) -> (u32, f64) {
+    let magnitude = time_scale.abs();
+    if magnitude <= 1. {
+        (1, dt_ms * magnitude)
+    } else {
+        let steps = magnitude.ceil();
+        (steps as u32, dt_ms * magnitude / steps)
+    }
+}
+
+// 掴んでいるディスクの衝突で使う質量 (無限大にすると質量比が NaN になるので、十分大きな有限の値)
+const HELD_MASS: f64 = 1e12;
+// 放す前にポインタがこれ以上のステップ (60fps のフレーム数) 止まっていたら投げずにその場で放す
+const HELD_IDLE_FRAMES: f64 = 6.;
+
+/**
+ * grab で掴んでいるディスクと、drag_to の動きから求めたポインタの速度
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Held {
+    index: usize,
+    // ディスクを留める位置 (最後に drag_to で指定した座標)
+    target: (f64, f64),
+    // 速度を測り始めた位置と、そこから進めたステップの量 (60fps の1フレームで 1.0)
+    from: (f64, f64),
+    elapsed: f64,
+    // ポインタの速度 (px / フレーム)
+    velocity: (f64, f64),
+}
+
+impl Held {
+    /**
+     * ディスクをポインタの位置・速度に留める (壁や力による移動は打ち消す)
+     */
+    fn pin(&self, disk: &mut Disk) {
+        (disk.x, disk.y) = self.target;
+        (disk.cos, disk.sin) = self.velocity;
+    }
+}
+
+// 1回の移動量が一番小さいディスクの半径のこの割合を超えたら、ステップを分割する
+const SUBSTEP_RADIUS_FRACTION: f64 = 0.5;
+// 1回の step_dt を分割する回数の上限 (極端な速さでページが固まらないように)
+pub const MAX_SUBSTEPS: u32 = 8;
+
+/**
+ * 速度を scale 倍 (加速度 acceleration の分も含む) 進めたときに、ディスク同士がすり抜けないよう何回に分けるか
+ * 最も速いディスクの移動量が一番小さい半径の SUBSTEP_RADIUS_FRACTION 倍以下になる回数で、1 以上 MAX_SUBSTEPS 以下
+ */
+pub fn motion_substeps(disks: &[Disk], size: f64, scale: f64, acceleration: f64) -> u32 {
+    let smallest = disks
+        .iter()
+        .map(|disk| disk.size_or(size))
+        .fold(f64::INFINITY, f64::min);
+    let fastest = disks
+        .iter()
+        .map(|disk| disk.cos.hypot(disk.sin))
+        .fold(0., f64::max);
+    let displacement = (fastest + acceleration * scale) * scale;
+    let limit = smallest * SUBSTEP_RADIUS_FRACTION;
+    if !(displacement.is_finite() && limit > 0.) || displacement <= limit {
+        return 1;
+    }
+    ((displacement / limit).ceil() as u32).min(MAX_SUBSTEPS)
+}
+
+// 引力点に近づいたときに距離をこれ以上小さく扱わない (px、中心で加速度が無限大にならないように)
+pub const ATTRACTOR_MIN_DISTANCE: f64 = 10.;
+
+/**
+ * add_attractor で置いた、ディスクを引き寄せる固定の点
+ * strength は距離 1px での加速度 (px / フレーム²) で、負なら遠ざける
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attractor {
+    pub id: u32,
+    pub x: f64,
+    pub y: f64,
+    pub strength: f64,
+}
+
+impl Attractor {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.x.is_finite() && self.y.is_finite()) {
+            return Err(format!(
+                "attractor position must be finite: ({}, {})",
+                self.x, self.y
+            ));
+        }
+        if !self.strength.is_finite() {
+            return Err(format!(
+                "attractor strength must be a finite number: {}",
+                self.strength
+            ));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * 各引力点へ、距離の2乗に反比例する加速度 (strength / 距離²) でディスクを引き寄せる
+ * 距離は ATTRACTOR_MIN_DISTANCE 未満にせず、1ステップで加える速度は引力点ごとに MAX_IMPULSE までにする
+ * scale は60fpsの1フレームに対する経過時間の割合
+ */
+pub fn apply_attractors(disks: &mut [Disk], attractors: &[Attractor], scale: f64) {
+    for attractor in attractors {
+        for disk in disks.iter_mut() {
+            let dx = attractor.x - disk.x;
+            let dy = attractor.y - disk.y;
+            let distance = dx.hypot(dy);
+            // 引力点ちょうどにいるディスクは方向が定まらないので動かさない
+            if distance <= f64::EPSILON {
+                continue;
+            }
+            let clamped = distance.max(ATTRACTOR_MIN_DISTANCE);
+            let acceleration = attractor.strength / (clamped * clamped) * scale;
+            let acceleration = acceleration.clamp(-MAX_IMPULSE, MAX_IMPULSE);
+            disk.cos += dx / distance * acceleration;
+            disk.sin += dy / distance * acceleration;
+        }
+    }
+}
+
+/**
+ * (x, y) から半径 radius 以内のディスクを外向きに弾く
+ * 加える速度は strength / 距離 (上限 MAX_IMPULSE)
+ */
+pub fn apply_radial_impulse(disks: &mut [Disk], x: f64, y: f64, strength: f64, radius: f64) {
+    for disk in disks.iter_mut() {
+        let dx = disk.x - x;
+        let dy = disk.y - y;
+        let distance = dx.hypot(dy);
+        if distance > radius {
+            continue;
+        }
+        // ちょうど中心にいるディスクは方向が定まらないので右向きに弾く
+        let (nx, ny) = if distance > f64::EPSILON {
+            (dx / distance, dy / distance)
+        } else {
+            (1., 0.)
+        };
+        let impulse = (strength / distance.max(1.)).clamp(-MAX_IMPULSE, MAX_IMPULSE);
+        disk.cos += nx * impulse;
+        disk.sin += ny * impulse;
+    }
+}
+
+/**
+ * JS に返す1ディスク分の位置・速度 (速度は cos / sin を vx / vy と呼ぶ)
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DiskInfo {
+    pub x: f64,
+    pub y: f64,
+    pub vx: f64,
+    pub vy: f64,
+}
+
+impl From<&Disk> for DiskInfo {
+    fn from(disk: &Disk) -> Self {
+        DiskInfo {
+            x: disk.x,
+            y: disk.y,
+            vx: disk.cos,
+            vy: disk.sin,
+        }
+    }
+}
+
+/**
+ * snapshot / restore で保存・復元するディスクの状態
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SimulationState {
+    pub disk_count: u32,
+    pub disks: Vec<Disk>,
+}
+
+impl SimulationState {
+    /**
+     * disk_count とディスクの数が一致し、座標・速度がすべて有限で、質量・大きさが正か
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        if self.disks.len() != self.disk_count as usize {
+            return Err(format!(
+                "state has {} disks (expected disk_count {})",
+                self.disks.len(),
+                self.disk_count
+            ));
+        }
+        match self
+            .disks
+            .iter()
+            .position(|d| ![d.x, d.y, d.cos, d.sin].iter().all(|v| v.is_finite()))
+        {
+            Some(index) => Err(format!("disk {} has a non-finite value", index)),
+            None => match self.disks.iter().position(|d| {
+                !(d.mass.is_finite() && d.mass > 0.)
+                    || d.size.is_some_and(|size| !(size.is_finite() && size > 0.))
+            }) {
+                Some(index) => Err(format!("disk {} has a non-positive mass or size", index)),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+// check_invariants でエネルギーが変わったとみなす相対誤差 (浮動小数点の丸めで生じる分は見逃す)
+pub const INVARIANT_TOLERANCE: f64 = 1e-9;
+
+/**
+ * check_invariants で記録する1ティック (step_dt の1回) の前後の運動エネルギー・運動量
+ * エネルギーは ½ * mass * v² の総和、運動量は mass * 速度の総和
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InvariantReport {
+    // Simulation を作ってから何回目の step_dt か (0 から数える)
+    pub tick: u64,
+    pub energy_before: f64,
+    pub energy_after: f64,
+    pub energy_delta: f64,
+    pub momentum_before: (f64, f64),
+    pub momentum_after: (f64, f64),
+    pub momentum_delta: (f64, f64),
+    // 重力・力・ポインタ・引力点・jitter・boids・掴んだディスクのどれかでエネルギーを加えていたか
+    pub driven: bool,
+    // エネルギーを保つはずの設定だったか (driven でなく、drag 0・反発係数 1.0 で合体・速さの上限もない)
+    pub conserving: bool,
+    // このティックで衝突・合体したディスクの組 [i, j] (起きた順。合体は [survivor, removed])
+    pub collisions: Vec<[u32; 2]>,
+}
+
+impl InvariantReport {
+    /**
+     * エネルギーを加えていないのに増えたか、保つはずの設定で変わったか
+     */
+    pub fn violated(&self) -> bool {
+        energy_violated(
+            self.energy_before,
+            self.energy_after,
+            self.driven,
+            self.conserving,
+        )
+    }
+}
+
+/**
+ * energy_before から energy_after への変化が不変条件に反するか (INVARIANT_TOLERANCE の相対誤差までは許す)
+ */
+pub fn energy_violated(
+    energy_before: f64,
+    energy_after: f64,
+    driven: bool,
+    conserving: bool,
+) -> bool {
+    let tolerance = INVARIANT_TOLERANCE * energy_before.abs().max(1.);
+    let delta = energy_after - energy_before;
+    !delta.is_finite() || (!driven && delta > tolerance) || (conserving && delta.abs() > tolerance)
+}
+
+fn check_restitution(name: &str, restitution: f64) -> Result<f64, String> {
+    if !(0. ..=1.).contains(&restitution) {
+        return Err(format!("{} must be in [0, 1]: {}", name, restitution));
+    }
+    Ok(restitution)
+}
+
+/**
+ * WebGLに依存しない物理シミュレーション本体
+ * Screen はこれを保持して描画だけを担当する。JSからは canvas なしで (Web Worker などで) 使える
+ */
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct Simulation {
+    width: f64,
+    height: f64,
+    disk_size: f64,
+    // 1ステップごとに縦方向の速度へ加える値 (正で下向き)
+    gravity: f64,
+    // 1ステップごとに全ディスクの速度へ加える値 (fx, fy)。gravity と足し合わせて働く
+    force: (f64, f64),
+    collision: bool,
+    // collision が有効なときに、衝突したディスクを跳ね返すか合体するか
+    collision_response: CollisionResponse,
+    // 合体でディスクの数をこれより減らさない・合体後の半径がこれを超える組は合体しない (None なら制限しない)
+    merge_min_disks: usize,
+    max_radius: Option<f64>,
+    // 直近のステップで起きた合体 (起きた順)
+    merges: Vec<Merge>,
+    boundary: Boundary,
+    // boids なら各ステップで近くのディスクに合わせて向きを変える
+    behavior: Behavior,
+    boids: BoidsParams,
+    // circle なら boundary によらず円の内側で反射する
+    arena: Arena,
+    // 積分時に速度へ掛ける倍率 (保持している速度自体は変えない)
+    speed_scale: f64,
+    // bounce モードの壁を canvas の端から内側に寄せる幅
+    bounds_padding: f64,
+    // 各ステップの最後に速さをこの値までに抑える (None なら制限しない)
+    max_speed: Option<f64>,
+    // 毎フレーム速度に加えるランダムな値の標準偏差 (0.0 で加えない)
+    jitter: f64,
+    // jitter に使う乱数生成器 (seed_jitter で種を指定すれば再現できる)
+    jitter_rng: StdRng,
+    // 毎フレーム速度に (1 - drag) を掛ける (0.0 で減速しない)
+    drag: f64,
+    // 壁での反射・ディスク同士の衝突の反発係数 (1.0 で弾性衝突)
+    wall_restitution: f64,
+    collision_restitution: f64,
+    // これ未満の速さでぶつかったら跳ね返さずに止める (反発係数が 1.0 未満のときのみ)
+    // None なら重力で1ステップに増える速さの2倍 (床に載ったディスクが揺れ続けないようにする)
+    rest_speed: Option<f64>,
+    // set_pointer で指定したポインタの位置 (None なら力を加えない) と、その力の強さ・届く半径
+    pointer: Option<(f64, f64)>,
+    // set_touch_points で指定したタッチの位置 (それぞれ pointer と同じ力を加える)
+    touches: Vec<(f64, f64)>,
+    pointer_force: f64,
+    pointer_radius: f64,
+    // add_attractor で置いた引力点と、次に返す番号
+    attractors: Vec<Attractor>,
+    next_attractor_id: u32,
+    // add_obstacle で置いた障害物と、次に返す番号
+    obstacles: Vec<Obstacle>,
+    next_obstacle_id: u32,
+    // 座標・速度が NaN / 無限大になったディスクを見つけたら、戻さずに panic する (開発用)
+    strict: bool,
+    // 直近のステップで NaN / 無限大から中央に戻したディスクの番号
+    recovered: Vec<usize>,
+
+    disks: Vec<Disk>,
+    // grab で掴んでいるディスク (壁で反射せず、衝突では動かない壁として扱う)
+    held: Option<Held>,
+    grid: Grid,
+    // 直近のステップで解決した衝突の数
+    collisions: usize,
+    // 直近のステップで衝突判定した候補ペアの数
+    pair_tests: usize,
+    // 直近の step_dt を何回に分けて進めたか
+    substeps: u32,
+    // 直近のステップで起きた出来事 (event_mask で有効にした種類のみ)
+    event_mask: EventMask,
+    events: Vec<SimEvent>,
+    // step_dt を呼んだ回数
+    tick: u64,
+    // true なら step_dt の前後で運動エネルギー・運動量を比べ、不変条件に反したティックを記録する (開発用)
+    check_invariants: bool,
+    // check_invariants のときのみ、今のティックで衝突した組
+    collided: Vec<[u32; 2]>,
+    // 直近の不変条件に反したティック
+    last_invariant_report: Option<InvariantReport>,
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: f64, height: f64, disk_size: f64) -> Simulation {
+        Simulation::with_disks(width, height, disk_size, Vec::new())
+    }
+
+    /**
+     * 60fps の1フレーム分進める
+     */
+    pub fn step(&mut self) {
+        self.step_dt(FRAME_MS);
+    }
+
+    /**
+     * dt_ms ミリ秒分進める (重力 -> 移動と壁での反射 -> ディスク同士の衝突)
+     * フレームレートによらず同じ速さで動く。dt_ms は MAX_DT_MS で頭打ちにする
+     * collision が有効で、速いディスクが1回で小さいディスクを飛び越えうる場合は motion_substeps 回に分けて進める
+     */
+    pub fn step_dt(&mut self, dt_ms: f64) {
+        let dt_ms = if dt_ms.is_finite() {
+            dt_ms.clamp(0., MAX_DT_MS)
+        } else {
+            FRAME_MS
+        };
+        self.recovered.clear();
+        self.events.clear();
+        self.merges.clear();
+        self.collided.clear();
+        let before = self.check_invariants.then(|| {
+            (
+                stats::total_kinetic_energy(&self.disks),
+                stats::momentum(&self.disks),
+            )
+        });
+        self.substeps = if self.collision {
+            let (fx, fy) = self.force;
+            let acceleration = fx.hypot(self.gravity + fy);
+            motion_substeps(
+                &self.disks,
+                self.disk_size,
+                dt_ms / FRAME_MS * self.speed_scale,
+                acceleration,
+            )
+        } else {
+            // 壁での反射は移動量によらず sweep で求めるので分けなくてよい
+            1
+        };
+        let (mut collisions, mut pair_tests) = (0, 0);
+        for _ in 0..self.substeps {
+            self.step_once(dt_ms / self.substeps as f64);
+            collisions += self.collisions;
+            pair_tests += self.pair_tests;
+        }
+        self.collisions = collisions;
+        self.pair_tests = pair_tests;
+        if let Some(before) = before {
+            self.audit_invariants(before);
+        }
+        self.tick += 1;
+    }
+
+    /**
+     * circle の領域では、円の外の座標を縁の内側に寄せる
+     */
+    pub fn add_disk(&mut self, x: f64, y: f64, vx: f64, vy: f64) {
+        self.push_disk(Disk::new(x, y, vx, vy));
+    }
+
+    /**
+     * index 番目のディスクを取り除く (後ろのディスクの番号は1つずつ詰まる)
+     * 範囲外なら何もせず false を返す
+     */
+    pub fn remove_disk(&mut self, index: usize) -> bool {
+        if index >= self.disks.len() {
+            return false;
+        }
+        self.disks.remove(index);
+        self.held = self.held.and_then(|mut held| match held.index.cmp(&index) {
+            std::cmp::Ordering::Less => Some(held),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => {
+                held.index -= 1;
+                Some(held)
+            }
+        });
+        true
+    }
+
+    /**
+     * index 番目のディスクを消し、最後のディスクをその番号へ移す (remove_disk と違い後ろを詰めない)
+     */
+    pub fn swap_remove_disk(&mut self, index: usize) -> bool {
+        if index >= self.disks.len() {
+            return false;
+        }
+        self.disks.swap_remove(index);
+        self.held = self.held.and_then(|mut held| {
+            if held.index == index {
+                return None;
+            }
+            // 最後のディスクを掴んでいたなら、移った先の番号を追う
+            if held.index == self.disks.len() {
+                held.index = index;
+            }
+            Some(held)
+        });
+        true
+    }
+
+    /**
+     * 先頭から len 個だけ残し、後ろのディスクを取り除く (len 以下の数なら何もしない)
+     */
+    pub fn truncate_disks(&mut self, len: usize) {
+        self.disks.truncate(len);
+        self.held = self.held.filter(|held| held.index < len);
+    }
+
+    /**
+     * 全ディスクの速度の向きを反転する (逆再生用)
+     */
+    pub fn reverse_velocities(&mut self) {
+        for disk in self.disks.iter_mut() {
+            disk.cos = -disk.cos;
+            disk.sin = -disk.sin;
+        }
+    }
+
+    /**
+     * さらに additional 個のディスクを足しても確保し直さないようにする
+     */
+    pub fn reserve_disks(&mut self, additional: usize) {
+        self.disks.reserve(additional);
+    }
+
+    /**
+     * (x, y) の周囲 radius 以内のディスクを、距離に反比例する強さで外向きに弾く
+     */
+    pub fn apply_force(&mut self, x: f64, y: f64, strength: f64, radius: f64) {
+        apply_radial_impulse(&mut self.disks, x, y, strength, radius);
+        if let Some(max_speed) = self.max_speed {
+            clamp_speeds(&mut self.disks, max_speed);
+        }
+    }
+
+    /**
+     * 全ディスクの速度に (dx, dy) を加える (max_speed を設定していればその速さまでに抑える)
+     */
+    pub fn apply_impulse(&mut self, dx: f64, dy: f64) {
+        for disk in self.disks.iter_mut() {
+            disk.cos += dx;
+            disk.sin += dy;
+        }
+        if let Some(max_speed) = self.max_speed {
+            clamp_speeds(&mut self.disks, max_speed);
+        }
+    }
+
+    /**
+     * 以降のステップで (x, y) の周囲のディスクに set_pointer_force の力を加え続ける (clear_pointer まで)
+     * 座標が有限でなければ clear_pointer と同じ
+     */
+    pub fn set_pointer(&mut self, x: f64, y: f64) {
+        self.pointer = Some((x, y)).filter(|_| x.is_finite() && y.is_finite());
+    }
+
+    pub fn clear_pointer(&mut self) {
+        self.pointer = None;
+    }
+
+    /**
+     * add_attractor の番号の引力点を取り除く (なければ false)
+     */
+    pub fn remove_attractor(&mut self, id: u32) -> bool {
+        let len = self.attractors.len();
+        self.attractors.retain(|a| a.id != id);
+        self.attractors.len() != len
+    }
+
+    pub fn clear_attractors(&mut self) {
+        self.attractors.clear();
+    }
+
+    /**
+     * add_obstacle の番号の障害物を取り除く (なければ false)
+     */
+    pub fn remove_obstacle(&mut self, id: u32) -> bool {
+        let len = self.obstacles.len();
+        self.obstacles.retain(|o| o.id != id);
+        self.obstacles.len() != len
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
+    }
+
+    /**
+     * (x, y) を中心から disk_size (壁・衝突判定と同じ半径。ディスク固有の大きさがあればそれ) 以内に含むディスクの番号
+     * 重なっている場合は後に描かれる (手前に見える) 番号の大きい方を返す
+     */
+    pub fn pick(&self, x: f64, y: f64) -> Option<u32> {
+        self.disks
+            .iter()
+            .rposition(|disk| self.contains_point(disk, x, y))
+            .map(|index| index as u32)
+    }
+
+    /**
+     * (x, y) を含むすべてのディスクの番号 (pick と同じ判定。手前に見える番号の大きい順)
+     */
+    pub fn pick_all(&self, x: f64, y: f64) -> Vec<u32> {
+        self.disks
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, disk)| self.contains_point(disk, x, y))
+            .map(|(index, _)| index as u32)
+            .collect()
+    }
+
+    /**
+     * (x, y) にある一番手前のディスクを掴む (pick と同じ判定。なければ何も掴まず None)
+     * 掴んでいる間は drag_to の位置に留まり、壁で反射せず、衝突では動かない壁として他のディスクを跳ね返す
+     */
+    pub fn grab(&mut self, x: f64, y: f64) -> Option<u32> {
+        self.release();
+        let index = self.pick(x, y)?;
+        let disk = &mut self.disks[index as usize];
+        let target = (disk.x, disk.y);
+        (disk.cos, disk.sin) = (0., 0.);
+        self.held = Some(Held {
+            index: index as usize,
+            target,
+            from: target,
+            elapsed: 0.,
+            velocity: (0., 0.),
+        });
+        Some(index)
+    }
+
+    /**
+     * 掴んでいるディスクを (x, y) へ動かし、前回からの移動とステップの量でポインタの速度を測る
+     * 同じフレームに何度呼ばれても、次のステップを進めてから測り直す
+     */
+    pub fn drag_to(&mut self, x: f64, y: f64) {
+        if !(x.is_finite() && y.is_finite()) {
+            return;
+        }
+        let held = match self.held.as_mut() {
+            Some(held) => held,
+            None => return,
+        };
+        held.target = (x, y);
+        if held.elapsed > 0. {
+            let (fx, fy) = held.from;
+            let (vx, vy) = ((x - fx) / held.elapsed, (y - fy) / held.elapsed);
+            // イベントの間隔のばらつきで速度が跳ねないよう、直前の速度と半々に混ぜる
+            held.velocity = ((held.velocity.0 + vx) / 2., (held.velocity.1 + vy) / 2.);
+            held.from = (x, y);
+            held.elapsed = 0.;
+        }
+        let held = *held;
+        held.pin(&mut self.disks[held.index]);
+    }
+
+    /**
+     * 掴んでいるディスクを放し、ポインタの速度で投げる (しばらく止めていたなら速度 0)
+     * 領域の外で放した場合は内側に戻す。掴んでいなければ何もしない
+     */
+    pub fn release(&mut self) {
+        let mut held = match self.held.take() {
+            Some(held) => held,
+            None => return,
+        };
+        if held.elapsed > HELD_IDLE_FRAMES {
+            held.velocity = (0., 0.);
+        }
+        let circle = self.arena_circle();
+        let bounds = Bounds::new(self.width, self.height).inset(self.bounds_padding);
+        let disk = &mut self.disks[held.index];
+        held.pin(disk);
+        let radius = disk.size_or(self.disk_size);
+        if let Some(circle) = circle {
+            contain_in_circle(disk, &circle, radius);
+        } else {
+            disk.x = disk.x.min(bounds.right - radius).max(bounds.left + radius);
+            disk.y = disk.y.min(bounds.bottom - radius).max(bounds.top + radius);
+        }
+    }
+
+    /**
+     * grab で掴んでいるディスクの番号 (掴んでいなければ None)
+     */
+    pub fn grabbed(&self) -> Option<u32> {
+        self.held.map(|held| held.index as u32)
+    }
+
+    /**
+     * 全ディスクの座標 [x0, y0, x1, y1, ...] のコピー
+     */
+    pub fn positions(&self) -> js_sys::Float32Array {
+        let positions: Vec<f32> = self
+            .disks
+            .iter()
+            .flat_map(|d| [d.x as f32, d.y as f32])
+            .collect();
+        js_sys::Float32Array::from(positions.as_slice())
+    }
+
+    pub fn disk_count(&self) -> u32 {
+        self.disks.len() as u32
+    }
+
+    /**
+     * kind 番目のグループに属するディスクの数
+     */
+    pub fn count_of_kind(&self, kind: u8) -> u32 {
+        self.disks.iter().filter(|disk| disk.kind == kind).count() as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn disk_size(&self) -> f64 {
+        self.disk_size
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn gravity(&self) -> f64 {
+        self.gravity
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_gravity(&mut self, gravity: f64) -> Result<(), String> {
+        if !gravity.is_finite() {
+            return Err(format!("gravity must be a finite number: {}", gravity));
+        }
+        self.gravity = gravity;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn collision(&self) -> bool {
+        self.collision
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_collision(&mut self, collision: bool) {
+        self.collision = collision;
+    }
+
+    /**
+     * 直近のステップで解決した衝突の数
+     */
+    pub fn last_collisions(&self) -> u32 {
+        self.collisions as u32
+    }
+
+    /**
+     * 直近のステップでグリッドから取り出して衝突判定した候補ペアの数 (collision が無効なら 0)
+     * 総当たりの n(n-1)/2 と比べると broad-phase の効果が分かる
+     */
+    pub fn last_pair_tests(&self) -> u32 {
+        self.pair_tests as u32
+    }
+
+    /**
+     * 直近の step_dt を何回に分けて進めたか (MAX_SUBSTEPS で頭打ち)
+     */
+    pub fn last_substeps(&self) -> u32 {
+        self.substeps
+    }
+
+    /**
+     * 直近のステップで起きた出来事の配列 [{type: "wall" | "collision", indices, x, y, speed}, ...]
+     * set_event_mask で有効にした種類のみ集める
+     */
+    pub fn last_events(&self) -> JsValue {
+        utils::to_js(&self.events)
+    }
+
+    /**
+     * 壁との衝突・ディスク同士の衝突をそれぞれイベントとして集めるか (既定はどちらも false)
+     */
+    pub fn set_event_mask(&mut self, wall: bool, collision: bool) {
+        self.event_mask = EventMask { wall, collision };
+        if !self.event_mask.any() {
+            self.events.clear();
+        }
+    }
+
+    /**
+     * 運動エネルギーの総和 (質量で重み付けする。衝突の実装の検証用。重力なしの bounce / wrap では一定のはず)
+     */
+    pub fn total_energy(&self) -> f64 {
+        stats::kinetic_energy(&self.disks)
+    }
+
+    pub fn check_invariants(&self) -> bool {
+        self.check_invariants
+    }
+
+    /**
+     * true にすると、step_dt のたびに前後の運動エネルギー・運動量を比べる (開発用。ディスクの数に比例して遅くなる)
+     * エネルギーを加えていないのに増えたティックと、保つはずの設定で変わったティックを記録する
+     */
+    pub fn set_check_invariants(&mut self, check: bool) {
+        self.check_invariants = check;
+        if !check {
+            self.last_invariant_report = None;
+        }
+    }
+}
+
+impl Simulation {
+    /**
+     * check_invariants で最後に記録した、不変条件に反したティック
+     */
+    pub fn last_invariant_report(&self) -> Option<&InvariantReport> {
+        self.last_invariant_report.as_ref()
+    }
+
+    /**
+     * 直近の step_dt が不変条件に反していればその記録
+     */
+    pub fn invariant_violation(&self) -> Option<&InvariantReport> {
+        self.last_invariant_report
+            .as_ref()
+            .filter(|report| report.tick + 1 == self.tick)
+    }
+
+    // エネルギーを外から加える力が働いているか
+    fn driven(&self) -> bool {
+        self.gravity != 0.
+            || self.force != (0., 0.)
+            || (self.pointer.is_some() || !self.touches.is_empty()) && self.pointer_force != 0.
+            || !self.attractors.is_empty()
+            || self.jitter != 0.
+            || self.behavior == Behavior::Boids
+            || self.held.is_some()
+    }
+
+    // 直近のステップの設定・出来事で、エネルギーが保たれるはずか
+    fn conserves_energy(&self) -> bool {
+        !self.driven()
+            && self.drag == 0.
+            && self.wall_restitution == 1.
+            && (!self.collision
+                || (self.collision_response == CollisionResponse::Bounce
+                    && self.collision_restitution == 1.))
+            && self.max_speed.is_none()
+            && self.merges.is_empty()
+            && self.recovered.is_empty()
+    }
+
+    // step_dt の前 (before) と今のエネルギー・運動量を比べ、不変条件に反していれば記録する
+    fn audit_invariants(&mut self, (energy_before, momentum_before): (f64, (f64, f64))) {
+        let energy_after = stats::total_kinetic_energy(&self.disks);
+        let driven = self.driven();
+        let conserving = self.conserves_energy();
+        if !energy_violated(energy_before, energy_after, driven, conserving) {
+            return;
+        }
+        let momentum_after = stats::momentum(&self.disks);
+        let mut collisions = self.collided.clone();
+        collisions.extend(
+            self.merges
+                .iter()
+                .map(|merge| [merge.survivor as u32, merge.removed as u32]),
+        );
+        self.last_invariant_report = Some(InvariantReport {
+            tick: self.tick,
+            energy_before,
+            energy_after,
+            energy_delta: energy_after - energy_before,
+            momentum_before,
+            momentum_after,
+            momentum_delta: (
+                momentum_after.0 - momentum_before.0,
+                momentum_after.1 - momentum_before.1,
+            ),
+            driven,
+            conserving,
+            collisions,
+        });
+    }
+
+    /**
+     * 大きさ・質量などを決めたディスクをそのまま足す (circle の領域では内側に収め、障害物の中なら外へ出す)
+     */
+    pub fn push_disk(&mut self, mut disk: Disk) {
+        if let Some(circle) = self.arena_circle() {
+            let size = disk.size_or(self.disk_size);
+            contain_in_circle(&mut disk, &circle, size);
+        }
+        let ejected = eject_from_obstacles(&mut disk, &self.obstacles, self.disk_size);
+        self.disks.push(disk);
+        // 障害物の外へ出した位置が壁・円の外になりうるので、出したディスクだけ内側に戻す
+        if ejected {
+            self.confine_from(self.disks.len() - 1);
+        }
+    }
+
+    /**
+     * step_dt の1回分 (dt_ms は MAX_DT_MS 以下の有限な値)
+     */
+    fn step_once(&mut self, dt_ms: f64) {
+        let dt_scale = dt_ms / FRAME_MS;
+        let scale = dt_scale * self.speed_scale;
+        // 外から与えた力などで壊れた値を、衝突を通じて他のディスクに広げないよう先に取り除く
+        self.recover_non_finite();
+        // 既定では、一定の力で1ステップに増える速さ (軸ごとの大きい方) の2倍未満で壁に押し付けられたディスクを止め、
+        // 壁に沿って滑らせる (力が無ければ重力だけで決まる)
+        let (fx, fy) = self.force;
+        let rest_speed = self
+            .rest_speed
+            .unwrap_or(fx.abs().max((self.gravity + fy).abs()) * dt_scale * 2.);
+        let mask = self.event_mask;
+        let arena_circle = self.arena_circle();
+        let held_index = self.held.map(|held| held.index);
+        if let Some(held) = self.held.as_mut() {
+            held.elapsed += scale;
+        }
+        let events = &mut self.events;
+        let collided = &mut self.collided;
+        let check_invariants = self.check_invariants;
+        if self.gravity != 0. {
+            for disk in self.disks.iter_mut() {
+                disk.sin += self.gravity * dt_scale;
+            }
+        }
+        if self.force != (0., 0.) {
+            for disk in self.disks.iter_mut() {
+                disk.cos += fx * dt_scale;
+                disk.sin += fy * dt_scale;
+            }
+        }
+        if let Some((x, y)) = self.pointer {
+            apply_pointer_force(
+                &mut self.disks,
+                x,
+                y,
+                self.pointer_force,
+                self.pointer_radius,
+                dt_scale,
+            );
+        }
+        for &(x, y) in self.touches.iter() {
+            apply_pointer_force(
+                &mut self.disks,
+                x,
+                y,
+                self.pointer_force,
+                self.pointer_radius,
+                dt_scale,
+            );
+        }
+        if !self.attractors.is_empty() {
+            apply_attractors(&mut self.disks, &self.attractors, dt_scale);
+        }
+        // jitter -> drag -> 移動の順に進めるので、drag と組み合わせると速度が平均0に引き戻されながら揺らぐ
+        // (Ornstein-Uhlenbeck 過程)
+        if self.jitter != 0. {
+            apply_jitter(&mut self.disks, self.jitter, dt_scale, &mut self.jitter_rng);
+        }
+        if self.drag != 0. {
+            apply_drag(&mut self.disks, self.drag, dt_scale);
+        }
+        // 壁の手前で向きを変えるので、下の反射は壁を越えたときの押し戻しとしてだけ働く
+        if self.behavior == Behavior::Boids {
+            steer_boids(
+                &mut self.disks,
+                &mut self.grid,
+                &self.boids,
+                self.width,
+                self.height,
+                arena_circle.as_ref(),
+                dt_scale,
+            );
+        }
+        if let Some(circle) = arena_circle {
+            bounce_disks_in_circle(
+                &mut self.disks,
+                &circle,
+                self.disk_size,
+                scale,
+                Restitution {
+                    coefficient: self.wall_restitution,
+                    rest_speed,
+                },
+                |index, disk| {
+                    if mask.wall && Some(index) != held_index {
+                        events.push(SimEvent::wall(index, disk));
+                    }
+                },
+            );
+        } else {
+            match self.boundary {
+                Boundary::Bounce => {
+                    let bounds = Bounds::new(self.width, self.height).inset(self.bounds_padding);
+                    bounce_disks_with(
+                        &mut self.disks,
+                        &bounds,
+                        self.disk_size,
+                        scale,
+                        Restitution {
+                            coefficient: self.wall_restitution,
+                            rest_speed,
+                        },
+                        |index, disk| {
+                            if mask.wall && Some(index) != held_index {
+                                events.push(SimEvent::wall(index, disk));
+                            }
+                        },
+                    )
+                }
+                Boundary::Wrap => wrap_disks(
+                    &mut self.disks,
+                    self.width,
+                    self.height,
+                    self.disk_size,
+                    scale,
+                ),
+            }
+        }
+        // 障害物は領域の壁と同じ反発係数で跳ね返し、ぶつかったら壁のイベントにする
+        if !self.obstacles.is_empty() {
+            bounce_off_obstacles(
+                &mut self.disks,
+                &self.obstacles,
+                self.disk_size,
+                Restitution {
+                    coefficient: self.wall_restitution,
+                    rest_speed,
+                },
+                |index, disk| {
+                    if mask.wall && Some(index) != held_index {
+                        events.push(SimEvent::wall(index, disk));
+                    }
+                },
+            );
+        }
+        // 掴んでいるディスクは壁での反射を取り消してポインタに戻し、衝突では押されないよう重くする
+        let disks = &mut self.disks;
+        let held_mass = self.held.map(|held| {
+            let disk = &mut disks[held.index];
+            held.pin(disk);
+            std::mem::replace(&mut disk.mass, HELD_MASS)
+        });
+        self.collisions = if self.collision && self.collision_response == CollisionResponse::Merge {
+            self.merge_overlapping(mask.collision)
+        } else if self.collision {
+            self.grid.resolve_with(
+                &mut self.disks,
+                self.width,
+                self.height,
+                self.disk_size,
+                Restitution {
+                    coefficient: self.collision_restitution,
+                    rest_speed,
+                },
+                |i, j, a, b, speed| {
+                    if mask.collision {
+                        events.push(SimEvent::collision(i, j, a, b, speed));
+                    }
+                    if check_invariants {
+                        collided.push([i as u32, j as u32]);
+                    }
+                },
+            )
+        } else {
+            0
+        };
+        self.pair_tests = if self.collision {
+            self.grid.pair_tests()
+        } else {
+            0
+        };
+        // 力や重力で加速しすぎて壁をすり抜けないようにする
+        if let Some(max_speed) = self.max_speed {
+            clamp_speeds(&mut self.disks, max_speed);
+        }
+        // 衝突や障害物で押し出されて壁の外に出たディスクを、速度を変えずに内側へ戻す (掴んでいるディスクは後で留め直す)
+        self.confine_disks();
+        if let (Some(held), Some(mass)) = (self.held, held_mass) {
+            let disk = &mut self.disks[held.index];
+            held.pin(disk);
+            disk.mass = mass;
+        }
+        self.recover_non_finite();
+    }
+
+    pub fn with_disks(width: f64, height: f64, disk_size: f64, disks: Vec<Disk>) -> Simulation {
+        Simulation {
+            width,
+            height,
+            disk_size,
+            gravity: 0.,
+            collision: false,
+            collision_response: CollisionResponse::Bounce,
+            merge_min_disks: 1,
+            max_radius: None,
+            merges: Vec::new(),
+            boundary: Boundary::Bounce,
+            behavior: Behavior::Ballistic,
+            boids: BoidsParams::default(),
+            arena: Arena::Rect,
+            speed_scale: 1.,
+            bounds_padding: 0.,
+            max_speed: None,
+            jitter: 0.,
+            jitter_rng: create_std_rng(None),
+            drag: 0.,
+            wall_restitution: 1.,
+            collision_restitution: 1.,
+            rest_speed: None,
+            pointer: None,
+            touches: Vec::new(),
+            pointer_force: DEFAULT_POINTER_FORCE,
+            pointer_radius: DEFAULT_POINTER_RADIUS,
+            force: (0., 0.),
+            attractors: Vec::new(),
+            next_attractor_id: 0,
+            obstacles: Vec::new(),
+            next_obstacle_id: 0,
+            strict: false,
+            recovered: Vec::new(),
+            disks,
+            held: None,
+            grid: Grid::new(),
+            collisions: 0,
+            pair_tests: 0,
+            substeps: 1,
+            event_mask: EventMask::default(),
+            events: Vec::new(),
+            tick: 0,
+            check_invariants: false,
+            collided: Vec::new(),
+            last_invariant_report: None,
+        }
+    }
+
+    /**
+     * 全ディスクの位置・速度
+     */
+    pub fn snapshot(&self) -> SimulationState {
+        SimulationState {
+            disk_count: self.disks.len() as u32,
+            disks: self.disks.clone(),
+        }
+    }
+
+    /**
+     * (x, y) が disk の円 (壁・衝突判定と同じ半径) に含まれるか
+     */
+    fn contains_point(&self, disk: &Disk, x: f64, y: f64) -> bool {
+        let radius = disk.size_or(self.disk_size);
+        let dx = disk.x - x;
+        let dy = disk.y - y;
+        dx * dx + dy * dy <= radius * radius
+    }
+
+    /**
+     * snapshot で保存した状態に置き換える (不正な状態ならエラーにして何も変えない)
+     */
+    pub fn restore(&mut self, state: &SimulationState) -> Result<(), String> {
+        state.validate()?;
+        self.disks = state.disks.clone();
+        self.held = None;
+        self.collisions = 0;
+        self.events.clear();
+        Ok(())
+    }
+
+    pub fn event_mask(&self) -> EventMask {
+        self.event_mask
+    }
+
+    /**
+     * 直近のステップで起きた出来事 (Rust 側から参照する)
+     */
+    pub fn events(&self) -> &[SimEvent] {
+        &self.events
+    }
+
+    /**
+     * 運動量の総和 (壁で反射しない wrap モードでは衝突があっても一定のはず)
+     */
+    pub fn total_momentum(&self) -> (f64, f64) {
+        stats::momentum(&self.disks)
+    }
+
+    /**
+     * 運動エネルギー ½ * mass * v² の総和
+     */
+    pub fn total_kinetic_energy(&self) -> f64 {
+        stats::total_kinetic_energy(&self.disks)
+    }
+
+    /**
+     * 速さを [0, max_speed) の bins 個の区間に分けた、区間ごとのディスクの数 (max_speed 以上は最後の区間)
+     */
+    pub fn speed_histogram(&self, bins: u32, max_speed: f64) -> Result<Vec<u32>, String> {
+        stats::speed_histogram(&self.disks, bins, max_speed)
+    }
+
+    /**
+     * 領域の大きさを変え、はみ出したディスクを内側に戻す (速度は変えない)
+     * bounce モードでは壁の内側に収め、wrap モードでは反対側へ折り返す
+     * bounds_padding が新しい大きさに収まらなくなった場合は 0 に戻す
+     */
+    pub fn resize(&mut self, width: f64, height: f64) -> Result<(), String> {
+        if !(width.is_finite() && width > 0. && height.is_finite() && height > 0.) {
+            return Err(format!(
+                "size must be positive numbers: {} x {}",
+                width, height
+            ));
+        }
+        self.width = width;
+        self.height = height;
+        if self.bounds_padding >= width.min(height) / 2. {
+            self.bounds_padding = 0.;
+        }
+        self.confine_disks();
+        Ok(())
+    }
+
+    /**
+     * はみ出したディスクを領域の内側に戻す (速度は変えない)
+     */
+    fn confine_disks(&mut self) {
+        self.confine_from(0);
+    }
+
+    // start 番目以降のディスクだけを confine_disks と同じく内側に戻す
+    fn confine_from(&mut self, start: usize) {
+        let circle = self.arena_circle();
+        let disks = &mut self.disks[start..];
+        if let Some(circle) = circle {
+            for disk in disks.iter_mut() {
+                let radius = disk.size_or(self.disk_size);
+                contain_in_circle(disk, &circle, radius);
+            }
+            return;
+        }
+        // 移動量 0 で動かすと、位置を境界の内側に収める処理だけが働く
+        match self.boundary {
+            Boundary::Bounce => bounce_disks(
+                disks,
+                &Bounds::new(self.width, self.height).inset(self.bounds_padding),
+                self.disk_size,
+                0.,
+            ),
+            Boundary::Wrap => wrap_disks(disks, self.width, self.height, self.disk_size, 0.),
+        }
+    }
+
+    /**
+     * ディスクの大きさ (壁での反射・衝突判定の半径) を変え、大きくなって壁に重なったディスクを内側に戻す
+     */
+    pub fn set_disk_size(&mut self, disk_size: f64) -> Result<(), String> {
+        if !(disk_size.is_finite() && disk_size > 0.) {
+            return Err(format!(
+                "disk_size must be a positive number: {}",
+                disk_size
+            ));
+        }
+        self.disk_size = disk_size;
+        self.confine_disks();
+        Ok(())
+    }
+
+    pub fn speed_scale(&self) -> f64 {
+        self.speed_scale
+    }
+
+    /**
+     * 移動量に掛ける倍率を変える。速度自体は変えないので 1.0 に戻せば元の速さになる
+     */
+    pub fn set_speed_scale(&mut self, speed_scale: f64) -> Result<(), String> {
+        if !(speed_scale.is_finite() && speed_scale >= 0.) {
+            return Err(format!(
+                "speed_scale must be a non-negative number: {}",
+                speed_scale
+            ));
+        }
+        self.speed_scale = speed_scale;
+        Ok(())
+    }
+
+    pub fn bounds_padding(&self) -> f64 {
+        self.bounds_padding
+    }
+
+    /**
+     * bounce モードの壁を canvas の端から padding だけ内側に寄せる
+     */
+    pub fn set_bounds_padding(&mut self, padding: f64) -> Result<(), String> {
+        let limit = self.width.min(self.height) / 2.;
+        if !(padding.is_finite() && padding >= 0. && padding < limit) {
+            return Err(format!(
+                "bounds padding must be in [0, {}): {}",
+                limit, padding
+            ));
+        }
+        self.bounds_padding = padding;
+        Ok(())
+    }
+
+    pub fn max_speed(&self) -> Option<f64> {
+        self.max_speed
+    }
+
+    /**
+     * ステップごとに速さを max_speed までに抑える (None で制限しない)
+     */
+    pub fn set_max_speed(&mut self, max_speed: Option<f64>) -> Result<(), String> {
+        if let Some(max_speed) = max_speed {
+            if !(max_speed.is_finite() && max_speed >= 0.) {
+                return Err(format!(
+                    "max_speed must be a non-negative number: {}",
+                    max_speed
+                ));
+            }
+        }
+        self.max_speed = max_speed;
+        Ok(())
+    }
+
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /**
+     * 毎フレーム速度に加えるランダムな値の標準偏差を変える (0 以上。0.0 で加えない)
+     */
+    pub fn set_jitter(&mut self, jitter: f64) -> Result<(), String> {
+        if !(jitter.is_finite() && jitter >= 0.) {
+            return Err(format!("jitter must be a non-negative number: {}", jitter));
+        }
+        self.jitter = jitter;
+        Ok(())
+    }
+
+    /**
+     * jitter に使う乱数生成器を種 seed から作り直す (同じ種なら同じ揺らぎになる)
+     */
+    pub fn seed_jitter(&mut self, seed: u64) {
+        self.jitter_rng = create_std_rng(Some(seed));
+    }
+
+    pub fn drag(&self) -> f64 {
+        self.drag
+    }
+
+    /**
+     * 毎フレーム速度に掛ける (1 - drag) の drag を変える (0.0 - 1.0。0.0 で減速せず、1.0 で即座に止まる)
+     */
+    pub fn set_drag(&mut self, drag: f64) -> Result<(), String> {
+        if !(0. ..=1.).contains(&drag) {
+            return Err(format!("drag must be in [0, 1]: {}", drag));
+        }
+        self.drag = drag;
+        Ok(())
+    }
+
+    pub fn pointer_force(&self) -> f64 {
+        self.pointer_force
+    }
+
+    pub fn force(&self) -> (f64, f64) {
+        self.force
+    }
+
+    /**
+     * 毎ステップ全ディスクの速度へ加える一定の力 (px / フレーム²、風など)。(0, 0) で力なし
+     */
+    pub fn set_force(&mut self, fx: f64, fy: f64) -> Result<(), String> {
+        if !(fx.is_finite() && fy.is_finite()) {
+            return Err(format!("force must be finite numbers: ({}, {})", fx, fy));
+        }
+        self.force = (fx, fy);
+        Ok(())
+    }
+
+    /**
+     * 以降のステップでディスクを (x, y) へ引き寄せる (strength が負なら遠ざける) 引力点を置き、その番号を返す
+     * 重力・ドラッグ・ポインタの力とは足し合わせて働く
+     */
+    pub fn add_attractor(&mut self, x: f64, y: f64, strength: f64) -> Result<u32, String> {
+        let attractor = Attractor {
+            id: self.next_attractor_id,
+            x,
+            y,
+            strength,
+        };
+        attractor.validate()?;
+        self.next_attractor_id = self.next_attractor_id.wrapping_add(1);
+        self.attractors.push(attractor);
+        Ok(attractor.id)
+    }
+
+    pub fn attractors(&self) -> &[Attractor] {
+        &self.attractors
+    }
+
+    /**
+     * 次の add_attractor で返す番号
+     */
+    pub fn next_attractor_id(&self) -> u32 {
+        self.next_attractor_id
+    }
+
+    /**
+     * 次の add_attractor で返す番号を変える (import_state・replay で、削除した番号を使い直さないように戻す)
+     * 今ある引力点の番号と同じならエラー
+     */
+    pub fn set_next_attractor_id(&mut self, id: u32) -> Result<(), String> {
+        if self.attractors.iter().any(|a| a.id == id) {
+            return Err(format!("next_attractor_id {} is already in use", id));
+        }
+        self.next_attractor_id = id;
+        Ok(())
+    }
+
+    /**
+     * 引力点を置き換える (import_state 用)。以降の add_attractor は最大の番号の次から返す
+     */
+    pub fn set_attractors(&mut self, attractors: &[Attractor]) -> Result<(), String> {
+        for (index, attractor) in attractors.iter().enumerate() {
+            attractor.validate()?;
+            if attractors[..index].iter().any(|a| a.id == attractor.id) {
+                return Err(format!("duplicate attractor id: {}", attractor.id));
+            }
+        }
+        self.attractors = attractors.to_vec();
+        self.next_attractor_id = attractors
+            .iter()
+            .map(|a| a.id.wrapping_add(1))
+            .max()
+            .unwrap_or(0);
+        Ok(())
+    }
+
+    /**
+     * 以降のステップでディスクが跳ね返る障害物を置き、その番号を返す
+     * 既に中にあるディスクは次のステップで一番近い外側へ押し出される
+     */
+    pub fn add_obstacle(&mut self, shape: ObstacleShape) -> Result<u32, String> {
+        shape.validate()?;
+        let obstacle = Obstacle {
+            id: self.next_obstacle_id,
+            shape,
+        };
+        self.next_obstacle_id = self.next_obstacle_id.wrapping_add(1);
+        self.obstacles.push(obstacle);
+        Ok(obstacle.id)
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    /**
+     * 次の add_obstacle で返す番号
+     */
+    pub fn next_obstacle_id(&self) -> u32 {
+        self.next_obstacle_id
+    }
+
+    /**
+     * 次の add_obstacle で返す番号を変える (set_next_attractor_id と同じく import_state・replay 用)
+     */
+    pub fn set_next_obstacle_id(&mut self, id: u32) -> Result<(), String> {
+        if self.obstacles.iter().any(|o| o.id == id) {
+            return Err(format!("next_obstacle_id {} is already in use", id));
+        }
+        self.next_obstacle_id = id;
+        Ok(())
+    }
+
+    /**
+     * 障害物を置き換える (import_state 用)。以降の add_obstacle は最大の番号の次から返す
+     */
+    pub fn set_obstacles(&mut self, obstacles: &[Obstacle]) -> Result<(), String> {
+        for (index, obstacle) in obstacles.iter().enumerate() {
+            obstacle.shape.validate()?;
+            if obstacles[..index].iter().any(|o| o.id == obstacle.id) {
+                return Err(format!("duplicate obstacle id: {}", obstacle.id));
+            }
+        }
+        self.obstacles = obstacles.to_vec();
+        self.next_obstacle_id = obstacles
+            .iter()
+            .map(|o| o.id.wrapping_add(1))
+            .max()
+            .unwrap_or(0);
+        Ok(())
+    }
+
+    pub fn collision_response(&self) -> CollisionResponse {
+        self.collision_response
+    }
+
+    pub fn set_collision_response(&mut self, response: CollisionResponse) {
+        self.collision_response = response;
+    }
+
+    /**
+     * 合体でディスクの数を min_disks より減らさず、合体後の半径が max_radius を超える組は跳ね返さずにそのまま重ねておく
+     */
+    pub fn set_merge_limits(
+        &mut self,
+        min_disks: u32,
+        max_radius: Option<f64>,
+    ) -> Result<(), String> {
+        if min_disks == 0 {
+            return Err(String::from("min_disks must be at least 1"));
+        }
+        if let Some(max_radius) = max_radius {
+            if !(max_radius.is_finite() && max_radius > 0.) {
+                return Err(format!(
+                    "max_radius must be a positive number: {}",
+                    max_radius
+                ));
+            }
+        }
+        self.merge_min_disks = min_disks as usize;
+        self.max_radius = max_radius;
+        Ok(())
+    }
+
+    /**
+     * set_merge_limits で設定した (min_disks, max_radius)
+     */
+    pub fn merge_limits(&self) -> (u32, Option<f64>) {
+        (self.merge_min_disks as u32, self.max_radius)
+    }
+
+    /**
+     * 直近のステップで起きた合体 (起きた順。色などディスクごとの値を同じ順に詰め直すのに使う)
+     */
+    pub fn merges(&self) -> &[Merge] {
+        &self.merges
+    }
+
+    /**
+     * 重なっているディスクの組を合体する (掴んでいるディスクと collides: false のディスクは合体しない)
+     * 1回の判定で合体するのは各ディスクにつき1組までで、合体したディスクがまだ別のディスクと重なっていれば
+     * MAX_MERGE_ROUNDS 回まで判定し直す。合体した組の数を返す
+     */
+    fn merge_overlapping(&mut self, collect_events: bool) -> usize {
+        let mut merged = 0;
+        for _ in 0..MAX_MERGE_ROUNDS {
+            let pairs =
+                self.grid
+                    .overlapping_pairs(&self.disks, self.width, self.height, self.disk_size);
+            let held_index = self.held.map(|held| held.index);
+            let mut touched = vec![false; self.disks.len()];
+            // この回で取り除いたディスクの、判定した時点の番号 (昇順)
+            let mut removed: Vec<usize> = Vec::new();
+            for (i, j) in pairs {
+                if self.disks.len() <= self.merge_min_disks {
+                    break;
+                }
+                if touched[i] || touched[j] || held_index == Some(i) || held_index == Some(j) {
+                    continue;
+                }
+                // 先に取り除いた分だけ後ろの番号は詰まっている
+                let survivor = i - removed.partition_point(|&r| r < i);
+                let victim = j - removed.partition_point(|&r| r < j);
+                let (a, b) = (self.disks[survivor], self.disks[victim]);
+                if a.ghost || b.ghost {
+                    continue;
+                }
+                let disk = merge_pair(&a, &b, self.disk_size);
+                if self
+                    .max_radius
+                    .is_some_and(|max| disk.size_or(self.disk_size) > max)
+                {
+                    continue;
+                }
+                if collect_events {
+                    let speed = (b.cos - a.cos).hypot(b.sin - a.sin);
+                    self.events
+                        .push(SimEvent::collision(survivor, victim, &a, &b, speed));
+                }
+                self.merges.push(Merge {
+                    survivor,
+                    removed: victim,
+                    weight: merge_weight(&a, &b, self.disk_size),
+                });
+                self.disks[survivor] = disk;
+                self.remove_disk(victim);
+                touched[i] = true;
+                touched[j] = true;
+                removed.insert(removed.partition_point(|&r| r < j), j);
+            }
+            if removed.is_empty() {
+                break;
+            }
+            merged += removed.len();
+        }
+        merged
+    }
+
+    pub fn pointer_radius(&self) -> f64 {
+        self.pointer_radius
+    }
+
+    /**
+     * ポインタの力の強さ (px / フレーム²、正で引き寄せ、負で遠ざける) と届く半径 (px) を変える
+     */
+    pub fn set_pointer_force(&mut self, force: f64, radius: f64) -> Result<(), String> {
+        if !force.is_finite() {
+            return Err(format!("pointer_force must be a finite number: {}", force));
+        }
+        if !(radius.is_finite() && radius > 0.) {
+            return Err(format!(
+                "pointer_radius must be a positive number: {}",
+                radius
+            ));
+        }
+        self.pointer_force = force;
+        self.pointer_radius = radius;
+        Ok(())
+    }
+
+    pub fn wall_restitution(&self) -> f64 {
+        self.wall_restitution
+    }
+
+    /**
+     * 壁で反射するときの反発係数を変える (0.0 - 1.0。1.0 で速さを保ち、小さいほど壁で減速する)
+     */
+    pub fn set_wall_restitution(&mut self, restitution: f64) -> Result<(), String> {
+        self.wall_restitution = check_restitution("wall_restitution", restitution)?;
+        Ok(())
+    }
+
+    pub fn collision_restitution(&self) -> f64 {
+        self.collision_restitution
+    }
+
+    /**
+     * ディスク同士の衝突の反発係数を変える (0.0 - 1.0。1.0 で弾性衝突、小さいほど衝突で減速する)
+     */
+    pub fn set_collision_restitution(&mut self, restitution: f64) -> Result<(), String> {
+        self.collision_restitution = check_restitution("collision_restitution", restitution)?;
+        Ok(())
+    }
+
+    /**
+     * 非推奨: collision_restitution の旧名
+     */
+    pub fn restitution(&self) -> f64 {
+        self.collision_restitution
+    }
+
+    /**
+     * 非推奨: set_collision_restitution の旧名 (ディスク同士の衝突の反発係数だけを変える)
+     */
+    pub fn set_restitution(&mut self, restitution: f64) -> Result<(), String> {
+        self.set_collision_restitution(restitution)
+    }
+
+    pub fn rest_speed(&self) -> Option<f64> {
+        self.rest_speed
+    }
+
+    /**
+     * これ未満の速さで壁・他のディスクにぶつかったら跳ね返さずに止める (反発係数が 1.0 未満のときのみ)
+     * undefined なら重力で1ステップに増える速さの2倍
+     */
+    pub fn set_rest_speed(&mut self, rest_speed: Option<f64>) -> Result<(), String> {
+        if let Some(rest_speed) = rest_speed {
+            if !(rest_speed.is_finite() && rest_speed >= 0.) {
+                return Err(format!(
+                    "rest_speed must be a non-negative number: {}",
+                    rest_speed
+                ));
+            }
+        }
+        self.rest_speed = rest_speed;
+        Ok(())
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /**
+     * true にすると、座標・速度が NaN / 無限大になったディスクを中央に戻す代わりに panic する
+     */
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /**
+     * 直近のステップで座標・速度が NaN / 無限大だったため中央に戻したディスクの番号
+     */
+    pub fn recovered(&self) -> &[usize] {
+        &self.recovered
+    }
+
+    /**
+     * ディスクに働いている力の一覧 (NaN / 無限大になったディスクを戻したときの警告用)
+     */
+    pub fn force_summary(&self) -> String {
+        let (fx, fy) = self.force;
+        format!(
+            "gravity: {}, force: ({}, {}), attractors: {:?}, pointer: {:?}, touches: {}, jitter: {}, speed_scale: {}",
+            self.gravity,
+            fx,
+            fy,
+            self.attractors
+                .iter()
+                .map(|a| a.strength)
+                .collect::<Vec<_>>(),
+            self.pointer.map(|_| self.pointer_force),
+            self.touches.len(),
+            self.jitter,
+            self.speed_scale
+        )
+    }
+
+    /**
+     * 座標・速度が NaN / 無限大のディスクを、速度 0 で領域の中央に戻す (strict なら panic する)
+     */
+    fn recover_non_finite(&mut self) {
+        let (cx, cy) = (self.width / 2., self.height / 2.);
+        for (index, disk) in self.disks.iter_mut().enumerate() {
+            if [disk.x, disk.y, disk.cos, disk.sin]
+                .iter()
+                .all(|v| v.is_finite())
+            {
+                continue;
+            }
+            if self.strict {
+                panic!(
+                    "disk {} has a non-finite position or velocity: {:?}",
+                    index, disk
+                );
+            }
+            disk.x = cx;
+            disk.y = cy;
+            disk.cos = 0.;
+            disk.sin = 0.;
+            self.recovered.push(index);
+        }
+    }
+
+    /**
+     * 以降のステップで力を加えるタッチの位置を [x0, y0, x1, y1, ...] で置き換える (空なら全て外す)
+     * 各点は set_pointer と同じ強さ・半径で引き寄せる。長さが奇数ならエラーにして何も変えない
+     * 座標が有限でない点は無視する
+     */
+    pub fn set_touch_points(&mut self, points: &[f64]) -> Result<(), String> {
+        if !points.len().is_multiple_of(2) {
+            return Err(format!(
+                "touch points must be [x0, y0, x1, y1, ...]: got {} numbers",
+                points.len()
+            ));
+        }
+        self.touches.clear();
+        self.touches.extend(
+            points
+                .chunks_exact(2)
+                .map(|point| (point[0], point[1]))
+                .filter(|(x, y)| x.is_finite() && y.is_finite()),
+        );
+        Ok(())
+    }
+
+    pub fn touch_points(&self) -> &[(f64, f64)] {
+        &self.touches
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    pub fn behavior(&self) -> Behavior {
+        self.behavior
+    }
+
+    /**
+     * 動き方を変える (位置・速度はそのまま引き継ぐ)
+     */
+    pub fn set_behavior(&mut self, behavior: Behavior) {
+        self.behavior = behavior;
+    }
+
+    pub fn boids(&self) -> BoidsParams {
+        self.boids
+    }
+
+    /**
+     * boids モードの重みと範囲を変える (不正な値ならエラーにして何も変えない)
+     */
+    pub fn set_boids(&mut self, params: BoidsParams) -> Result<(), String> {
+        params.validate()?;
+        self.boids = params;
+        Ok(())
+    }
+
+    pub fn arena(&self) -> Arena {
+        self.arena
+    }
+
+    /**
+     * 領域の形を変え、円の外にいるディスクを内側に寄せる (不正な値ならエラーにして何も変えない)
+     */
+    pub fn set_arena(&mut self, arena: Arena) -> Result<(), String> {
+        arena.validate()?;
+        self.arena = arena;
+        self.contain_disks();
+        Ok(())
+    }
+
+    /**
+     * 現在の大きさでの円形の領域 (bounds_padding だけ半径を縮める。rect なら None)
+     */
+    pub fn arena_circle(&self) -> Option<Circle> {
+        self.arena
+            .circle(self.width, self.height)
+            .map(|circle| Circle {
+                radius: (circle.radius - self.bounds_padding).max(0.),
+                ..circle
+            })
+    }
+
+    /**
+     * ディスクを互いに重ならないよう置き直す (place_without_overlap_in)
+     * circle の領域ならその内側に、そうでなければ bounds_padding だけ内側に寄せた壁の内側に置く
+     */
+    pub fn place_without_overlap<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(), String> {
+        let bounds = Bounds::new(self.width, self.height).inset(self.bounds_padding);
+        let circle = self.arena_circle();
+        place_without_overlap_in(
+            &mut self.disks,
+            &bounds,
+            circle.as_ref(),
+            self.disk_size,
+            rng,
+        )
+    }
+
+    // circle の領域なら、はみ出したディスクを内側に寄せる
+    fn contain_disks(&mut self) {
+        if let Some(circle) = self.arena_circle() {
+            for disk in self.disks.iter_mut() {
+                let radius = disk.size_or(self.disk_size);
+                contain_in_circle(disk, &circle, radius);
+            }
+        }
+    }
+
+    /**
+     * 画面全体に一様に散らばる位置・velocities で選んだ速度のディスクを count 個足す (init_disks と同じ決め方)
+     */
+    pub fn add_random_disks<R: Rng + ?Sized>(
+        &mut self,
+        count: u32,
+        velocities: impl Into<VelocityDistribution>,
+        rng: &mut R,
+    ) {
+        let disks = init_disks(
+            count,
+            self.width as u32,
+            self.height as u32,
+            self.disk_size,
+            SpawnPattern::Uniform,
+            velocities,
+            rng,
+        );
+        self.disks.extend(disks);
+        self.contain_disks();
+    }
+
+    /**
+     * (x, y) に速さ speed でランダムな向きに進むディスクを1つ足す
+     * 画面外 (circle の領域では円の外) の座標は壁に重ならない位置まで内側に寄せる
+     */
+    pub fn spawn_at<R: Rng + ?Sized>(&mut self, x: f64, y: f64, speed: f64, rng: &mut R) {
+        let x = clamp_coordinate(x, self.width, self.disk_size);
+        let y = clamp_coordinate(y, self.height, self.disk_size);
+        let angle = rng.gen_range(0., std::f64::consts::PI * 2.);
+        self.add_disk(x, y, speed * angle.cos(), speed * angle.sin());
+    }
+
+    /**
+     * 位置はそのままに、全ディスクの速度を velocities で選び直す
+     */
+    pub fn randomize_velocities<R: Rng + ?Sized>(
+        &mut self,
+        velocities: impl Into<VelocityDistribution>,
+        rng: &mut R,
+    ) {
+        let velocities = velocities.into();
+        for (index, disk) in self.disks.iter_mut().enumerate() {
+            let (vx, vy) = velocities.sample(index, None, rng);
+            disk.cos = vx;
+            disk.sin = vy;
+        }
+    }
+
+    /**
+     * 全ディスクの速度に factor を掛ける (0 以上。運動エネルギーは factor² 倍になる)
+     */
+    pub fn scale_velocities(&mut self, factor: f64) -> Result<(), String> {
+        if !(factor.is_finite() && factor >= 0.) {
+            return Err(format!("factor must be a non-negative number: {}", factor));
+        }
+        for disk in self.disks.iter_mut() {
+            disk.cos *= factor;
+            disk.sin *= factor;
+        }
+        Ok(())
+    }
+
+    pub fn disks(&self) -> &[Disk] {
+        &self.disks
+    }
+}
@@ -0,0 +1,241 @@
+use crate::dom_utils::{self, DrawUniforms, GlContext};
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    HtmlImageElement, ImageBitmap, WebGlProgram, WebGlRenderingContext, WebGlTexture,
+    WebGlUniformLocation,
+};
+
+fn is_power_of_two(n: u32) -> bool {
+    n != 0 && n & (n - 1) == 0
+}
+
+/**
+ * スプライトの画像の取得元
+ * コンテキストの復元時に作り直せるよう、Scene が保持しておく
+ */
+#[derive(Clone, Debug)]
+pub enum TextureSource {
+    // 非同期に読み込む画像の URL
+    Url(String),
+    // 読み込み済みの <img>
+    Image(HtmlImageElement),
+    Bitmap(ImageBitmap),
+}
+
+/**
+ * 画像をテクスチャへ転送する
+ * ミップマップを使わず CLAMP_TO_EDGE にしておけば、WebGL1 でも2の累乗でない画像を扱える
+ */
+fn upload(gl: &GlContext, texture: &WebGlTexture, source: &TextureSource) -> Result<(), JsValue> {
+    let target = WebGlRenderingContext::TEXTURE_2D;
+    gl.bind_texture(target, Some(texture));
+    let (width, height) = match source {
+        TextureSource::Url(_) => return Err(JsValue::from("the image has not been loaded yet")),
+        TextureSource::Image(image) => {
+            gl.tex_image_2d_with_image(image)?;
+            (image.natural_width(), image.natural_height())
+        }
+        TextureSource::Bitmap(bitmap) => {
+            gl.tex_image_2d_with_image_bitmap(bitmap)?;
+            (bitmap.width(), bitmap.height())
+        }
+    };
+    let clamp = WebGlRenderingContext::CLAMP_TO_EDGE as i32;
+    let linear = WebGlRenderingContext::LINEAR as i32;
+    gl.tex_parameteri(target, WebGlRenderingContext::TEXTURE_WRAP_S, clamp);
+    gl.tex_parameteri(target, WebGlRenderingContext::TEXTURE_WRAP_T, clamp);
+    gl.tex_parameteri(target, WebGlRenderingContext::TEXTURE_MIN_FILTER, linear);
+    gl.tex_parameteri(target, WebGlRenderingContext::TEXTURE_MAG_FILTER, linear);
+    if !is_power_of_two(width) || !is_power_of_two(height) {
+        debug!(
+            "texture is not a power of two ({}x{}), mipmaps are disabled",
+            width, height
+        );
+    }
+    Ok(())
+}
+
+/**
+ * 画像を貼り付けたポイントスプライトで描画するためのGLリソース
+ * URL の読み込みが終わるまで (または失敗した場合) は ready() が false なので、呼び出し側は通常の円で描く
+ */
+#[derive(Debug)]
+pub struct SpritePass {
+    program: WebGlProgram,
+    texture: WebGlTexture,
+    attrib_coords: u32,
+    attrib_color: u32,
+    attrib_scale: Option<u32>,
+    uniform_point_size: WebGlUniformLocation,
+    uniform_texture: WebGlUniformLocation,
+    uniform_alpha: WebGlUniformLocation,
+    uniforms: DrawUniforms,
+    ready: Rc<Cell<bool>>,
+    // URL から読み込む場合の画像と、そのイベントから呼ばれるクロージャ (SpritePass と同じだけ生かしておく)
+    _loading: Option<Loading>,
+}
+
+#[derive(Debug)]
+struct Loading {
+    image: HtmlImageElement,
+    _onload: Closure<dyn FnMut()>,
+    _onerror: Closure<dyn FnMut()>,
+}
+
+impl SpritePass {
+    /**
+     * Image はすでに読み込み済みでなければエラーにする (Url は読み込みを始め、終わったら転送する)
+     */
+    pub fn new(
+        gl: &GlContext,
+        source: &TextureSource,
+        width: u32,
+        height: u32,
+    ) -> Result<SpritePass, String> {
+        let program = dom_utils::create_sprite_program(gl)?;
+        gl.use_program(Some(&program));
+        let uniform = |name: &str| dom_utils::uniform_location(gl, &program, name);
+        let uniform_point_size = uniform("u_pointsize")?;
+        let uniform_texture = uniform("u_texture")?;
+        let uniform_alpha = uniform("u_alpha")?;
+        dom_utils::set_size_uniforms(gl, &program, width as f64, height as f64)?;
+        let texture = gl
+            .create_texture()
+            .ok_or_else(|| String::from("failed to create texture."))?;
+        let ready = Rc::new(Cell::new(false));
+        let loading = match source {
+            TextureSource::Url(url) => Some(load(gl, &texture, url, &ready)?),
+            TextureSource::Image(image) if !image.complete() || image.natural_width() == 0 => {
+                gl.delete_texture(Some(&texture));
+                return Err(String::from("the image has not finished loading"));
+            }
+            source => {
+                if let Err(e) = upload(gl, &texture, source) {
+                    gl.delete_texture(Some(&texture));
+                    return Err(format!("failed to upload texture: {:?}", e));
+                }
+                ready.set(true);
+                None
+            }
+        };
+
+        Ok(SpritePass {
+            attrib_coords: dom_utils::attrib_location(gl, &program, "a_coords")?,
+            attrib_color: dom_utils::attrib_location(gl, &program, "a_color")?,
+            attrib_scale: dom_utils::optional_attrib_location(gl, &program, "a_scale"),
+            uniforms: DrawUniforms::new(gl, &program),
+            program,
+            texture,
+            uniform_point_size,
+            uniform_texture,
+            uniform_alpha,
+            ready,
+            _loading: loading,
+        })
+    }
+
+    /**
+     * canvas の論理サイズが変わったときに呼ぶ
+     */
+    pub fn resize(&self, gl: &GlContext, width: u32, height: u32) -> Result<(), String> {
+        dom_utils::set_size_uniforms(gl, &self.program, width as f64, height as f64)
+    }
+
+    pub fn delete(&self, gl: &GlContext) {
+        dom_utils::delete_program(gl, &self.program);
+        gl.delete_texture(Some(&self.texture));
+    }
+
+    pub fn ready(&self) -> bool {
+        self.ready.get()
+    }
+
+    pub fn program(&self) -> &WebGlProgram {
+        &self.program
+    }
+
+    pub fn uniforms(&self) -> &DrawUniforms {
+        &self.uniforms
+    }
+
+    pub fn attrib_coords(&self) -> u32 {
+        self.attrib_coords
+    }
+
+    pub fn attrib_color(&self) -> u32 {
+        self.attrib_color
+    }
+
+    pub fn attrib_scale(&self) -> Option<u32> {
+        self.attrib_scale
+    }
+
+    pub fn uniform_point_size(&self) -> &WebGlUniformLocation {
+        &self.uniform_point_size
+    }
+
+    pub fn uniform_alpha(&self) -> &WebGlUniformLocation {
+        &self.uniform_alpha
+    }
+
+    /**
+     * テクスチャをユニット0に割り当てる
+     * 画像の透過部分が抜けるよう、呼び出し側でブレンドを有効にして描くこと
+     */
+    pub fn begin(&self, gl: &GlContext) {
+        gl.active_texture(WebGlRenderingContext::TEXTURE0);
+        gl.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&self.texture));
+        gl.uniform1i(Some(&self.uniform_texture), 0);
+    }
+}
+
+/**
+ * url の画像の読み込みを始め、終わったらテクスチャへ転送して ready を立てる
+ */
+fn load(
+    gl: &GlContext,
+    texture: &WebGlTexture,
+    url: &str,
+    ready: &Rc<Cell<bool>>,
+) -> Result<Loading, String> {
+    let image = HtmlImageElement::new().map_err(|_| String::from("failed to create image."))?;
+    // 別オリジンの画像でもCORSが許可されていればテクスチャにできるようにする
+    image.set_cross_origin(Some("anonymous"));
+
+    let onload = {
+        let gl = gl.clone();
+        let texture = texture.clone();
+        let source = TextureSource::Image(image.clone());
+        let ready = ready.clone();
+        let url = url.to_string();
+        Closure::wrap(Box::new(move || match upload(&gl, &texture, &source) {
+            Ok(()) => ready.set(true),
+            Err(e) => error!("failed to upload texture {}: {:?}", url, e),
+        }) as Box<dyn FnMut()>)
+    };
+    let onerror = {
+        let url = url.to_string();
+        Closure::wrap(Box::new(move || {
+            error!("failed to load texture: {}", url);
+        }) as Box<dyn FnMut()>)
+    };
+    image.set_onload(Some(onload.as_ref().unchecked_ref()));
+    image.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+    image.set_src(url);
+    Ok(Loading {
+        image,
+        _onload: onload,
+        _onerror: onerror,
+    })
+}
+
+impl Drop for Loading {
+    fn drop(&mut self) {
+        // 読み込み中に破棄された場合、解放済みのクロージャが呼ばれないようにする
+        self.image.set_onload(None);
+        self.image.set_onerror(None);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::boids::{Behavior, BoidsParams};
+use crate::merge::CollisionResponse;
+use crate::obstacle::Obstacle;
+use crate::simulation::{Arena, Attractor, Boundary, Simulation, SimulationState};
+use serde::{Deserialize, Serialize};
+
+// export_state の形式の版 (形式を変えたら上げる。違う版の JSON は import_state で読まない)
+pub const STATE_VERSION: u32 = 1;
+
+/**
+ * export_state で保存するシミュレーションの設定
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SimulationParams {
+    pub disk_size: f64,
+    pub gravity: f64,
+    pub collision: bool,
+    pub boundary: Boundary,
+    pub speed_scale: f64,
+    pub bounds_padding: f64,
+    pub max_speed: Option<f64>,
+    pub drag: f64,
+    pub wall_restitution: f64,
+    pub collision_restitution: f64,
+    pub rest_speed: Option<f64>,
+    pub pointer_force: f64,
+    pub pointer_radius: f64,
+    // 以前の版で保存した JSON には無いので、無ければ引力点なし
+    #[serde(default)]
+    pub attractors: Vec<Attractor>,
+    // 同じく無ければ力なし
+    #[serde(default)]
+    pub force: (f64, f64),
+    // 同じく無ければ rect
+    #[serde(default)]
+    pub arena: Arena,
+    // 同じく無ければ揺らぎなし
+    #[serde(default)]
+    pub jitter: f64,
+    // 同じく無ければ障害物なし
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    // 次に返す引力点の番号 (無ければ attractors の最大の番号の次)
+    #[serde(default)]
+    pub next_attractor_id: Option<u32>,
+    // 次に返す障害物の番号 (無ければ obstacles の最大の番号の次)
+    #[serde(default)]
+    pub next_obstacle_id: Option<u32>,
+    // 無ければ ballistic
+    #[serde(default)]
+    pub behavior: Behavior,
+    // 無ければ boids の既定値
+    #[serde(default)]
+    pub boids: BoidsParams,
+    // 無ければ bounce
+    #[serde(default)]
+    pub collision_response: CollisionResponse,
+    // 無ければ制限なし (1)
+    #[serde(default = "default_merge_min_disks")]
+    pub merge_min_disks: u32,
+    #[serde(default)]
+    pub merge_max_radius: Option<f64>,
+    // 無ければ false
+    #[serde(default)]
+    pub strict: bool,
+    // 無ければ false
+    #[serde(default)]
+    pub check_invariants: bool,
+}
+
+fn default_merge_min_disks() -> u32 {
+    1
+}
+
+impl SimulationParams {
+    pub fn of(sim: &Simulation) -> SimulationParams {
+        SimulationParams {
+            disk_size: sim.disk_size(),
+            gravity: sim.gravity(),
+            collision: sim.collision(),
+            boundary: sim.boundary(),
+            speed_scale: sim.speed_scale(),
+            bounds_padding: sim.bounds_padding(),
+            max_speed: sim.max_speed(),
+            drag: sim.drag(),
+            wall_restitution: sim.wall_restitution(),
+            collision_restitution: sim.collision_restitution(),
+            rest_speed: sim.rest_speed(),
+            pointer_force: sim.pointer_force(),
+            pointer_radius: sim.pointer_radius(),
+            attractors: sim.attractors().to_vec(),
+            force: sim.force(),
+            arena: sim.arena(),
+            jitter: sim.jitter(),
+            obstacles: sim.obstacles().to_vec(),
+            next_attractor_id: Some(sim.next_attractor_id()),
+            next_obstacle_id: Some(sim.next_obstacle_id()),
+            behavior: sim.behavior(),
+            boids: sim.boids(),
+            collision_response: sim.collision_response(),
+            merge_min_disks: sim.merge_limits().0,
+            merge_max_radius: sim.merge_limits().1,
+            strict: sim.strict(),
+            check_invariants: sim.check_invariants(),
+        }
+    }
+
+    /**
+     * sim に設定する。不正な値があればエラーを返す (それまでの値は設定済みになるので、先に validate すること)
+     */
+    pub fn apply(&self, sim: &mut Simulation) -> Result<(), String> {
+        sim.set_disk_size(self.disk_size)?;
+        sim.set_gravity(self.gravity)?;
+        sim.set_collision(self.collision);
+        sim.set_collision_response(self.collision_response);
+        sim.set_merge_limits(self.merge_min_disks, self.merge_max_radius)?;
+        sim.set_strict(self.strict);
+        sim.set_check_invariants(self.check_invariants);
+        sim.set_boundary(self.boundary);
+        sim.set_speed_scale(self.speed_scale)?;
+        sim.set_bounds_padding(self.bounds_padding)?;
+        sim.set_max_speed(self.max_speed)?;
+        sim.set_drag(self.drag)?;
+        sim.set_jitter(self.jitter)?;
+        sim.set_wall_restitution(self.wall_restitution)?;
+        sim.set_collision_restitution(self.collision_restitution)?;
+        sim.set_rest_speed(self.rest_speed)?;
+        sim.set_pointer_force(self.pointer_force, self.pointer_radius)?;
+        sim.set_force(self.force.0, self.force.1)?;
+        sim.set_arena(self.arena)?;
+        sim.set_behavior(self.behavior);
+        sim.set_boids(self.boids)?;
+        sim.set_attractors(&self.attractors)?;
+        if let Some(id) = self.next_attractor_id {
+            sim.set_next_attractor_id(id)?;
+        }
+        sim.set_obstacles(&self.obstacles)?;
+        if let Some(id) = self.next_obstacle_id {
+            sim.set_next_obstacle_id(id)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * export_state / import_state の JSON
+ * colors は "random" モードで使うディスクごとの色 (ディスクの数 * 3 個)
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SavedState {
+    pub version: u32,
+    pub width: f64,
+    pub height: f64,
+    pub params: SimulationParams,
+    pub colors: Vec<f32>,
+    pub state: SimulationState,
+}
+
+impl SavedState {
+    pub fn new(sim: &Simulation, colors: &[f32]) -> SavedState {
+        SavedState {
+            version: STATE_VERSION,
+            width: sim.width(),
+            height: sim.height(),
+            params: SimulationParams::of(sim),
+            colors: colors.to_vec(),
+            state: sim.snapshot(),
+        }
+    }
+
+    /**
+     * 版・大きさ・設定・ディスクの状態・色の数と範囲を確かめる
+     * 通れば apply_to で途中で失敗することはない
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version != STATE_VERSION {
+            return Err(format!(
+                "unsupported state version {} (expected {})",
+                self.version, STATE_VERSION
+            ));
+        }
+        if !(self.width.is_finite()
+            && self.width >= 1.
+            && self.height.is_finite()
+            && self.height >= 1.)
+        {
+            return Err(format!("invalid size: {} x {}", self.width, self.height));
+        }
+        self.state.validate()?;
+        crate::color::validate_colors(&self.colors, self.state.disks.len())?;
+        // bounds_padding は大きさに依存するので、保存された大きさの空のシミュレーションで確かめる
+        let mut trial = Simulation::new(self.width, self.height, self.params.disk_size);
+        self.params.apply(&mut trial)
+    }
+
+    /**
+     * sim を保存した大きさ・設定・状態にする (validate を通った状態に対して呼ぶ)
+     */
+    pub fn apply_to(&self, sim: &mut Simulation) -> Result<(), String> {
+        sim.resize(self.width, self.height)?;
+        self.params.apply(sim)?;
+        sim.restore(&self.state)
+    }
+}
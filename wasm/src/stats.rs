@@ -0,0 +1,252 @@
+use crate::simulation::Disk;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+// 指数移動平均の重み (新しいサンプルの寄与率)
+const SMOOTHING: f64 = 0.1;
+// FPS・処理時間の平均・最小・最大をとる直近のフレーム数
+pub const STATS_WINDOW: usize = 120;
+
+// 上限 STATS_WINDOW 個まで溜め、古いものから捨てる
+fn push_window(window: &mut VecDeque<f64>, value: f64) {
+    if window.len() == STATS_WINDOW {
+        window.pop_front();
+    }
+    window.push_back(value);
+}
+
+fn mean(window: &VecDeque<f64>) -> f64 {
+    if window.is_empty() {
+        0.
+    } else {
+        window.iter().sum::<f64>() / window.len() as f64
+    }
+}
+
+// 時刻の列の間隔から求めた回数 / 秒の (平均, 最小, 最大)。2つ未満なら 0
+fn rates(times: &VecDeque<f64>) -> (f64, f64, f64) {
+    let intervals = times
+        .iter()
+        .zip(times.iter().skip(1))
+        .map(|(prev, next)| next - prev)
+        .filter(|interval| *interval > 0.);
+    let (count, total, shortest, longest) = intervals.fold(
+        (0, 0., f64::INFINITY, 0f64),
+        |(count, total, shortest, longest), interval| {
+            (
+                count + 1,
+                total + interval,
+                shortest.min(interval),
+                longest.max(interval),
+            )
+        },
+    );
+    if count == 0 {
+        return (0., 0., 0.);
+    }
+    (
+        count as f64 * 1000. / total,
+        1000. / longest,
+        1000. / shortest,
+    )
+}
+
+fn moving_average(average: f64, sample: f64, samples: u64) -> f64 {
+    if samples == 0 {
+        sample
+    } else {
+        average + (sample - average) * SMOOTHING
+    }
+}
+
+/**
+ * JS側へ返す統計情報
+ */
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct FrameStats {
+    pub frames: u64,
+    pub collisions: u32,
+    // 直近のステップで衝突判定した候補ペアの数
+    pub pair_tests: u32,
+    pub speed_min: f64,
+    pub speed_max: f64,
+    pub speed_avg: f64,
+    pub step_ms_avg: f64,
+    pub step_ms_last: f64,
+    pub draw_ms_avg: f64,
+    pub draw_ms_last: f64,
+    // 直近 STATS_WINDOW フレームの FPS (平均・最も遅いフレーム・最も速いフレーム) と処理時間の平均
+    pub fps_avg: f64,
+    pub fps_min: f64,
+    pub fps_max: f64,
+    // 直近 STATS_WINDOW 回の requestAnimationFrame の呼び出し回数 / 秒 (target_fps で間引いたフレームも数える)
+    // start() のループで動かしていなければ 0
+    pub callback_fps: f64,
+    pub step_ms_mean: f64,
+    pub draw_ms_mean: f64,
+    pub disk_count: u32,
+    pub kinetic_energy: f64,
+}
+
+/**
+ * フレームごとの計測値の蓄積
+ */
+#[derive(Debug, Default)]
+pub struct FrameTimer {
+    frames: u64,
+    collisions: u32,
+    pair_tests: u32,
+    speed_min: f64,
+    speed_max: f64,
+    speed_avg: f64,
+    timed_frames: u64,
+    step_ms_avg: f64,
+    step_ms_last: f64,
+    draw_ms_avg: f64,
+    draw_ms_last: f64,
+    // 直近 STATS_WINDOW フレームの開始時刻と処理時間
+    frame_times: VecDeque<f64>,
+    step_times: VecDeque<f64>,
+    draw_times: VecDeque<f64>,
+    // 直近 STATS_WINDOW 回の rAF のコールバックの時刻
+    callback_times: VecDeque<f64>,
+}
+
+impl FrameTimer {
+    /**
+     * 物理演算1ステップ分の集計 (衝突数・判定したペア数と速さの最小・最大・平均)
+     */
+    pub fn count_frame(&mut self, disks: &[Disk], collisions: usize, pair_tests: usize) {
+        self.frames += 1;
+        self.collisions = collisions as u32;
+        self.pair_tests = pair_tests as u32;
+        let (mut min, mut max, mut sum) = (f64::INFINITY, 0f64, 0.);
+        for disk in disks.iter() {
+            let speed = disk.cos.hypot(disk.sin);
+            min = min.min(speed);
+            max = max.max(speed);
+            sum += speed;
+        }
+        if disks.is_empty() {
+            min = 0.;
+        }
+        self.speed_min = min;
+        self.speed_max = max;
+        self.speed_avg = sum / disks.len().max(1) as f64;
+    }
+
+    /**
+     * now (ms) に始まったフレームの物理演算・描画にかかった時間を記録する
+     */
+    pub fn record(&mut self, now: f64, step_ms: f64, draw_ms: f64) {
+        push_window(&mut self.frame_times, now);
+        push_window(&mut self.step_times, step_ms);
+        push_window(&mut self.draw_times, draw_ms);
+        self.step_ms_avg = moving_average(self.step_ms_avg, step_ms, self.timed_frames);
+        self.draw_ms_avg = moving_average(self.draw_ms_avg, draw_ms, self.timed_frames);
+        self.step_ms_last = step_ms;
+        self.draw_ms_last = draw_ms;
+        self.timed_frames += 1;
+    }
+
+    /**
+     * rAF のコールバックが now (ms) に呼ばれたことを記録する (描くかどうかによらない)
+     */
+    pub fn record_callback(&mut self, now: f64) {
+        push_window(&mut self.callback_times, now);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /**
+     * 直近のフレーム間隔から求めた FPS の (平均, 最小, 最大)。2フレーム未満なら 0
+     */
+    pub fn fps(&self) -> (f64, f64, f64) {
+        rates(&self.frame_times)
+    }
+
+    /**
+     * 直近の rAF のコールバックの間隔から求めた回数 / 秒。2回未満なら 0
+     */
+    pub fn callback_fps(&self) -> f64 {
+        rates(&self.callback_times).0
+    }
+
+    pub fn snapshot(&self, disks: &[Disk]) -> FrameStats {
+        let (fps_avg, fps_min, fps_max) = self.fps();
+        let callback_fps = self.callback_fps();
+        FrameStats {
+            frames: self.frames,
+            collisions: self.collisions,
+            pair_tests: self.pair_tests,
+            speed_min: self.speed_min,
+            speed_max: self.speed_max,
+            speed_avg: self.speed_avg,
+            step_ms_avg: self.step_ms_avg,
+            step_ms_last: self.step_ms_last,
+            draw_ms_avg: self.draw_ms_avg,
+            draw_ms_last: self.draw_ms_last,
+            fps_avg,
+            fps_min,
+            fps_max,
+            callback_fps,
+            step_ms_mean: mean(&self.step_times),
+            draw_ms_mean: mean(&self.draw_times),
+            disk_count: disks.len() as u32,
+            kinetic_energy: kinetic_energy(disks),
+        }
+    }
+}
+
+/**
+ * 全ディスクの運動エネルギーの総和 (mass * (cos² + sin²) を合計する)
+ */
+pub fn kinetic_energy(disks: &[Disk]) -> f64 {
+    disks
+        .iter()
+        .map(|d| d.mass * (d.cos * d.cos + d.sin * d.sin))
+        .sum()
+}
+
+/**
+ * 全ディスクの運動エネルギー ½ * mass * v² の総和 (物理の単位での値。kinetic_energy の半分)
+ * mass は衝突に使う質量 (mass_from_radius なら半径²、指定がなければ 1.0)
+ */
+pub fn total_kinetic_energy(disks: &[Disk]) -> f64 {
+    kinetic_energy(disks) / 2.
+}
+
+/**
+ * 速さを [0, max_speed) の bins 個の区間に分けて、区間ごとのディスクの数を数える
+ * max_speed 以上のディスクは最後の区間に入れる
+ */
+pub fn speed_histogram(disks: &[Disk], bins: u32, max_speed: f64) -> Result<Vec<u32>, String> {
+    if bins == 0 {
+        return Err(String::from("bins must be at least 1"));
+    }
+    if !(max_speed.is_finite() && max_speed > 0.) {
+        return Err(format!(
+            "max_speed must be a positive number: {}",
+            max_speed
+        ));
+    }
+    let mut counts = vec![0; bins as usize];
+    let last = counts.len() - 1;
+    let per_bin = bins as f64 / max_speed;
+    for disk in disks {
+        let bin = (disk.cos.hypot(disk.sin) * per_bin) as usize;
+        counts[bin.min(last)] += 1;
+    }
+    Ok(counts)
+}
+
+/**
+ * 全ディスクの運動量の総和 (mass * 速度を合計する)
+ */
+pub fn momentum(disks: &[Disk]) -> (f64, f64) {
+    disks.iter().fold((0., 0.), |(px, py), d| {
+        (px + d.mass * d.cos, py + d.mass * d.sin)
+    })
+}
@@ -0,0 +1,148 @@
+use crate::simulation::{FRAME_MS, MAX_DT_MS};
+
+// 固定刻みでシミュレーションを進める間隔 (120Hz)
+pub const FIXED_STEP_MS: f64 = 1000. / 120.;
+
+/**
+ * rAF のタイムスタンプから、固定刻み step_ms で何ステップ進めるかを決める
+ * 進めきれなかった端数は次のフレームに持ち越し、alpha() で描画位置の補間に使う
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedStep {
+    step_ms: f64,
+    accumulator: f64,
+    last_timestamp: Option<f64>,
+}
+
+impl Default for FixedStep {
+    fn default() -> Self {
+        FixedStep::new(FIXED_STEP_MS)
+    }
+}
+
+impl FixedStep {
+    pub fn new(step_ms: f64) -> FixedStep {
+        FixedStep {
+            step_ms,
+            accumulator: 0.,
+            last_timestamp: None,
+        }
+    }
+
+    pub fn step_ms(&self) -> f64 {
+        self.step_ms
+    }
+
+    /**
+     * 持ち越した時間と直前のタイムスタンプを捨てる (ループの開始時・再開時に呼ぶ)
+     */
+    pub fn reset(&mut self) {
+        self.accumulator = 0.;
+        self.last_timestamp = None;
+    }
+
+    /**
+     * timestamp (ms) までの経過時間を溜め、進めるステップ数を返す
+     * 最初の呼び出しやタイムスタンプが不正な場合は60fpsの1フレーム分、
+     * タブが非表示だった後などの長い間隔は MAX_DT_MS 分だけ進める
+     */
+    pub fn advance(&mut self, timestamp: f64) -> u32 {
+        let elapsed = match self.last_timestamp {
+            Some(last) if timestamp.is_finite() && timestamp >= last => timestamp - last,
+            _ => FRAME_MS,
+        };
+        if timestamp.is_finite() {
+            self.last_timestamp = Some(timestamp);
+        }
+        self.accumulator += elapsed.min(MAX_DT_MS);
+        let steps = (self.accumulator / self.step_ms).floor();
+        self.accumulator -= steps * self.step_ms;
+        steps as u32
+    }
+
+    /**
+     * 持ち越した時間を捨てる (一時停止中など、ステップを進めなかったとき)
+     */
+    pub fn discard(&mut self) {
+        self.accumulator = 0.;
+    }
+
+    /**
+     * 持ち越した時間の1ステップに対する割合 (0.0 - 1.0)
+     */
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step_ms).clamp(0., 1.)
+    }
+}
+
+// rAF のタイムスタンプの揺れで、ほぼ予定どおりのフレームを飛ばさないための余裕 (ms)
+const FRAME_SLACK_MS: f64 = 1.;
+
+/**
+ * rAF のループで描画するフレームを target_fps 回 / 秒までに間引く
+ * 次に描く予定の時刻を 1000 / target_fps ずつ進めるので、端数は次に持ち越され平均が目標に近づく
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameLimiter {
+    // None なら間引かない
+    interval_ms: Option<f64>,
+    next_due: Option<f64>,
+}
+
+impl FrameLimiter {
+    /**
+     * target_fps が 0 なら間引かない
+     */
+    pub fn new(target_fps: u32) -> FrameLimiter {
+        FrameLimiter {
+            interval_ms: Some(target_fps)
+                .filter(|fps| *fps > 0)
+                .map(|fps| 1000. / fps as f64),
+            next_due: None,
+        }
+    }
+
+    pub fn target_fps(&self) -> u32 {
+        self.interval_ms
+            .map_or(0, |interval| (1000. / interval).round() as u32)
+    }
+
+    /**
+     * timestamp (ms) のフレームを描くか。描く場合は次の予定の時刻を進める
+     * 大きく遅れた場合 (タブが非表示だった後など) はまとめて描かずに今から数え直す
+     */
+    pub fn ready(&mut self, timestamp: f64) -> bool {
+        let interval = match self.interval_ms {
+            Some(interval) if timestamp.is_finite() => interval,
+            _ => return true,
+        };
+        match self.next_due {
+            Some(due) if timestamp < due - FRAME_SLACK_MS => false,
+            Some(due) if timestamp < due + interval => {
+                self.next_due = Some(due + interval);
+                true
+            }
+            _ => {
+                self.next_due = Some(timestamp + interval);
+                true
+            }
+        }
+    }
+}
+
+/**
+ * 座標 positions を previous (1ステップ前) から alpha の割合だけ進めた位置に置き換える
+ * 1ステップで max_jump を超えて動いた座標 (wrap で反対側へ移ったものなど) は補間しない
+ * 数が違う場合 (ディスクの追加・削除の直後) は何もしない
+ */
+pub fn interpolate_positions(positions: &mut [f32], previous: &[f32], alpha: f32, max_jump: f32) {
+    if positions.len() != previous.len() {
+        return;
+    }
+    for (position, &previous) in positions.iter_mut().zip(previous) {
+        let delta = *position - previous;
+        if delta.abs() <= max_jump {
+            *position = previous + delta * alpha;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::merge::Merge;
+
+/**
+ * DiskRef と track の番号をディスクの削除・並べ替えに合わせて保つ
+ * generation はディスクが減るか置き換わるたびに進め、それより前に取った DiskRef を無効にする
+ * (番号が別のディスクを指すようになったハンドルから、違うディスクの値を読ませない)
+ * 追跡中の番号は削除のたびに、同じディスクが移った先の番号へ付け替える
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiskTracker {
+    generation: u64,
+    tracked: Option<usize>,
+}
+
+impl DiskTracker {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /**
+     * 追跡中のディスクの今の番号 (追跡していないか、そのディスクが消えた場合は None)
+     */
+    pub fn tracked(&self) -> Option<usize> {
+        self.tracked
+    }
+
+    /**
+     * index 番目のディスクを追跡する。範囲外 (count 以上) なら false で、前の追跡も外す
+     */
+    pub fn track(&mut self, index: usize, count: usize) -> bool {
+        self.tracked = (index < count).then_some(index);
+        self.tracked.is_some()
+    }
+
+    pub fn untrack(&mut self) {
+        self.tracked = None;
+    }
+
+    /**
+     * index 番目を取り除き、後ろのディスクの番号が1つずつ詰まった
+     */
+    pub fn remove(&mut self, index: usize) {
+        self.generation += 1;
+        self.tracked = self.tracked.and_then(|tracked| match tracked.cmp(&index) {
+            std::cmp::Ordering::Less => Some(tracked),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(tracked - 1),
+        });
+    }
+
+    /**
+     * index 番目を取り除き、末尾のディスクが index に移った (len は削除する前のディスクの数)
+     */
+    pub fn swap_remove(&mut self, index: usize, len: usize) {
+        self.generation += 1;
+        self.tracked = self.tracked.and_then(|tracked| {
+            if tracked == index {
+                None
+            } else if tracked + 1 == len {
+                Some(index)
+            } else {
+                Some(tracked)
+            }
+        });
+    }
+
+    /**
+     * merge.removed が merge.survivor に合体した (追跡中のディスクが吸収された場合は合体後のディスクを追う)
+     */
+    pub fn merge(&mut self, merge: &Merge) {
+        let tracked = self.tracked;
+        self.remove(merge.removed);
+        if tracked == Some(merge.removed) {
+            self.tracked = Some(if merge.survivor > merge.removed {
+                merge.survivor - 1
+            } else {
+                merge.survivor
+            });
+        }
+    }
+
+    /**
+     * 先頭から len 個だけ残した
+     */
+    pub fn truncate(&mut self, len: usize) {
+        self.generation += 1;
+        self.tracked = self.tracked.filter(|&tracked| tracked < len);
+    }
+
+    /**
+     * ディスクをまとめて置き換えた (reset・restore・import_state)。すべてのハンドルと追跡を無効にする
+     */
+    pub fn replace(&mut self) {
+        self.generation += 1;
+        self.tracked = None;
+    }
+}
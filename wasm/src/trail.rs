@@ -0,0 +1,73 @@
+use crate::dom_utils::{self, GlContext};
+use web_sys::{WebGlBuffer, WebGlProgram, WebGlRenderingContext, WebGlUniformLocation};
+
+// クリップ空間全体を覆う TRIANGLE_STRIP
+const QUAD: [f32; 8] = [-1., -1., 1., -1., -1., 1., 1., 1.];
+
+/**
+ * 前フレームを消さずに背景色を半透明で塗り重ね、残像を作る
+ */
+#[derive(Debug)]
+pub struct TrailPass {
+    program: WebGlProgram,
+    buffer_quad: WebGlBuffer,
+    attrib_position: u32,
+    uniform_color: WebGlUniformLocation,
+}
+
+impl TrailPass {
+    pub fn new(gl: &GlContext) -> Result<TrailPass, String> {
+        let program = dom_utils::create_fade_program(gl)?;
+        let buffer_quad = gl
+            .create_buffer()
+            .ok_or_else(|| String::from("failed to create buffer."))?;
+        gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&buffer_quad));
+        gl.buffer_data_f32(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            &QUAD,
+            WebGlRenderingContext::STATIC_DRAW,
+        );
+        let uniform_color = dom_utils::uniform_location(gl, &program, "u_color")?;
+        Ok(TrailPass {
+            attrib_position: dom_utils::attrib_location(gl, &program, "a_position")?,
+            program,
+            buffer_quad,
+            uniform_color,
+        })
+    }
+
+    pub fn delete(&self, gl: &GlContext) {
+        dom_utils::delete_program(gl, &self.program);
+        gl.delete_buffer(Some(&self.buffer_quad));
+    }
+
+    /**
+     * 画面全体を不透明度 alpha の背景色で塗る
+     */
+    pub fn fade(&self, gl: &GlContext, background: [f32; 3], alpha: f64) {
+        gl.use_program(Some(&self.program));
+        gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&self.buffer_quad));
+        gl.vertex_attrib_pointer(
+            self.attrib_position,
+            2,
+            WebGlRenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        gl.enable_vertex_attrib_array(self.attrib_position);
+        let [r, g, b] = background;
+        gl.uniform4f(Some(&self.uniform_color), r, g, b, alpha as f32);
+
+        gl.enable(WebGlRenderingContext::BLEND);
+        // アルファ成分は加算して不透明のまま保つ (ページの背景が透けないように)
+        gl.blend_func_separate(
+            WebGlRenderingContext::SRC_ALPHA,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+            WebGlRenderingContext::ONE,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        gl.draw_arrays(WebGlRenderingContext::TRIANGLE_STRIP, 0, 4);
+        gl.disable(WebGlRenderingContext::BLEND);
+    }
+}
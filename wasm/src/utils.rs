@@ -1,3 +1,7 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
@@ -8,3 +12,40 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/**
+ * serdeでシリアライズした値をJSのオブジェクトへ変換する
+ */
+pub fn to_js<T: Serialize>(value: &T) -> JsValue {
+    serde_json::to_string(value)
+        .ok()
+        .and_then(|json| js_sys::JSON::parse(&json).ok())
+        .unwrap_or(JsValue::NULL)
+}
+
+/**
+ * JSのオブジェクトをserdeでデシリアライズする (to_js の逆)
+ */
+pub fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, String> {
+    // undefined や関数は JSON.stringify が undefined を返すので、文字列にならなければエラーにする
+    let json = js_sys::JSON::stringify(value)
+        .ok()
+        .and_then(|json| json.as_string())
+        .ok_or_else(|| String::from("value is not serializable"))?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/**
+ * wasm のメモリのページ数 (メモリが伸びると JS 側の ArrayBuffer のビューは使えなくなる)
+ * wasm 以外のターゲットでは常に 0
+ */
+pub fn memory_pages() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
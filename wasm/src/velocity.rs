@@ -0,0 +1,84 @@
+use crate::dom_utils::{self, DynamicBuffer, GlContext};
+use crate::simulation::Disk;
+use crate::view::View;
+use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlUniformLocation};
+
+// 背景 (黒) ともランダムな色のディスクとも見分けやすい色
+const COLOR: [f32; 4] = [1., 1., 0., 1.];
+
+/**
+ * 各ディスクの中心から速度ベクトルの向きに線分を描くデバッグ表示
+ * 線分の両端はクリップ座標で毎フレーム作り直す (1diskあたり4値)
+ */
+#[derive(Debug)]
+pub struct VelocityPass {
+    program: WebGlProgram,
+    // 確保した領域を使い回し、ディスクが増えたときだけ広げる
+    buffer_lines: DynamicBuffer,
+    attrib_position: u32,
+    uniform_color: WebGlUniformLocation,
+    lines: Vec<f32>,
+}
+
+impl VelocityPass {
+    pub fn new(gl: &GlContext) -> Result<VelocityPass, String> {
+        let program = dom_utils::create_line_program(gl)?;
+        let uniform_color = dom_utils::uniform_location(gl, &program, "u_color")?;
+        Ok(VelocityPass {
+            attrib_position: dom_utils::attrib_location(gl, &program, "a_position")?,
+            uniform_color,
+            buffer_lines: DynamicBuffer::new(gl)?,
+            program,
+            lines: Vec::new(),
+        })
+    }
+
+    pub fn delete(&self, gl: &GlContext) {
+        dom_utils::delete_program(gl, &self.program);
+        self.buffer_lines.delete(gl);
+    }
+
+    /**
+     * 速度 (1フレームあたりの移動量) を scale 倍した長さの線分を描く
+     * 使った attribute は無効に戻し、ディスクの描画の状態に影響しないようにする
+     */
+    pub fn draw(
+        &mut self,
+        gl: &GlContext,
+        disks: &[Disk],
+        view: &View,
+        width: f64,
+        height: f64,
+        scale: f64,
+    ) {
+        let to_clip = |x: f64, y: f64| view.to_clip(x, y, width, height);
+        self.lines.clear();
+        for disk in disks.iter() {
+            self.lines.extend(to_clip(disk.x, disk.y));
+            self.lines.extend(to_clip(
+                disk.x + disk.cos * scale,
+                disk.y + disk.sin * scale,
+            ));
+        }
+
+        gl.use_program(Some(&self.program));
+        self.buffer_lines.upload(gl, &self.lines);
+        gl.vertex_attrib_pointer(
+            self.attrib_position,
+            2,
+            WebGlRenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        gl.enable_vertex_attrib_array(self.attrib_position);
+        let [r, g, b, a] = COLOR;
+        gl.uniform4f(Some(&self.uniform_color), r, g, b, a);
+        gl.draw_arrays(
+            WebGlRenderingContext::LINES,
+            0,
+            (self.lines.len() / 2) as i32,
+        );
+        gl.disable_vertex_attrib_array(self.attrib_position);
+    }
+}
@@ -0,0 +1,108 @@
+// 拡大率の範囲 (これを超えると浮動小数の誤差で表示が崩れる)
+pub const MIN_VIEW_SCALE: f64 = 1e-3;
+pub const MAX_VIEW_SCALE: f64 = 1e3;
+
+/**
+ * 描画だけに掛ける視点 (パン・ズーム)。シミュレーションの座標 (ワールド座標) は変えない
+ * 画面座標 = (ワールド座標 - offset) * scale で、offset は表示している範囲の左上のワールド座標
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    // 表示の中心 (ワールド座標)。None なら領域の中央 (resize に追従する)
+    center: Option<(f64, f64)>,
+    scale: f64,
+}
+
+impl Default for View {
+    fn default() -> View {
+        View {
+            center: None,
+            scale: 1.,
+        }
+    }
+}
+
+impl View {
+    /**
+     * (center_x, center_y) を canvas の中央に、scale 倍に拡大して表示する
+     */
+    pub fn new(center_x: f64, center_y: f64, scale: f64) -> Result<View, String> {
+        if !(center_x.is_finite() && center_y.is_finite()) {
+            return Err(format!(
+                "view center must be finite: ({}, {})",
+                center_x, center_y
+            ));
+        }
+        if !(MIN_VIEW_SCALE..=MAX_VIEW_SCALE).contains(&scale) {
+            return Err(format!(
+                "view scale must be in [{}, {}]: {}",
+                MIN_VIEW_SCALE, MAX_VIEW_SCALE, scale
+            ));
+        }
+        Ok(View {
+            center: Some((center_x, center_y)),
+            scale,
+        })
+    }
+
+    /**
+     * world_width x world_height の領域全体が width x height の canvas に収まる視点 (attach_view の既定)
+     */
+    pub fn fit(world_width: f64, world_height: f64, width: f64, height: f64) -> View {
+        let scale = (width / world_width).min(height / world_height);
+        View {
+            center: Some((world_width / 2., world_height / 2.)),
+            scale: if scale.is_finite() {
+                scale.clamp(MIN_VIEW_SCALE, MAX_VIEW_SCALE)
+            } else {
+                1.
+            },
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /**
+     * width x height の canvas で表示の中心になるワールド座標
+     */
+    pub fn center(&self, width: f64, height: f64) -> (f64, f64) {
+        self.center.unwrap_or((width / 2., height / 2.))
+    }
+
+    /**
+     * 表示している範囲の左上のワールド座標 (シェーダの u_view_offset)
+     */
+    pub fn offset(&self, width: f64, height: f64) -> (f64, f64) {
+        let (cx, cy) = self.center(width, height);
+        (
+            cx - width / (2. * self.scale),
+            cy - height / (2. * self.scale),
+        )
+    }
+
+    /**
+     * canvas 上の座標 (CSS px) をワールド座標にする (クリックした位置を spawn_at などに渡すとき)
+     */
+    pub fn screen_to_world(&self, x: f64, y: f64, width: f64, height: f64) -> (f64, f64) {
+        let (ox, oy) = self.offset(width, height);
+        (ox + x / self.scale, oy + y / self.scale)
+    }
+
+    pub fn world_to_screen(&self, x: f64, y: f64, width: f64, height: f64) -> (f64, f64) {
+        let (ox, oy) = self.offset(width, height);
+        ((x - ox) * self.scale, (y - oy) * self.scale)
+    }
+
+    /**
+     * ワールド座標をクリップ座標にする (CPU 側で頂点を作るデバッグ表示用)
+     */
+    pub fn to_clip(&self, x: f64, y: f64, width: f64, height: f64) -> [f32; 2] {
+        let (sx, sy) = self.world_to_screen(x, y, width, height);
+        [
+            (-1. + 2. * sx / width) as f32,
+            (1. - 2. * sy / height) as f32,
+        ]
+    }
+}
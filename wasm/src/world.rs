@@ -0,0 +1,181 @@
+use crate::boids::Behavior;
+use crate::color::{ColorMode, Palette, DEFAULT_MAX_VELOCITY};
+use crate::emitter::{Emitter, DEFAULT_EMIT_RATE, DEFAULT_LIFETIME, DEFAULT_MAX_DISKS};
+use crate::merge::CollisionResponse;
+use crate::options::Options;
+use crate::simulation::{
+    create_std_rng, init_groups, Boundary, GroupSpec, Simulation, SpawnPattern,
+    VelocityDistribution, DEFAULT_POINTER_FORCE, DEFAULT_POINTER_RADIUS,
+};
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/**
+ * オプションから作ったディスク・色・シミュレーションの設定 (GL を使わない部分)
+ * init_gl と Screen::reset のどちらもここで作るので、同じオプションと seed なら同じ状態になる
+ */
+#[derive(Debug)]
+pub struct World {
+    pub sim: Simulation,
+    // 初期化に使った乱数生成器 (後から足すディスクや色にも使う)
+    pub rng: StdRng,
+    // "random" モードで使うディスクごとの色 (ディスクの数 * 3 個)
+    pub random_colors: Vec<f32>,
+    pub groups: Vec<GroupSpec>,
+    pub mass_from_radius: bool,
+    pub velocities: VelocityDistribution,
+    pub palette: Palette,
+    pub color_mode: ColorMode,
+    pub static_palette: Vec<[f32; 3]>,
+    pub min_velocity: f64,
+    pub max_velocity: f64,
+    // emitter: true のときのみ
+    pub emitter: Option<Emitter>,
+}
+
+impl World {
+    /**
+     * options (validate 済み) のディスクを width x height の中に置き、色を選ぶ
+     * 大きさは options.width / height の代わりに引数で渡す (auto_resize 中の今の大きさを使えるように)
+     */
+    pub fn build(options: &Options, width: u32, height: u32) -> Result<World, String> {
+        let velocities = options.velocities()?;
+        let arena = options.arena()?;
+        let palette = options.palette()?;
+        let emitter_on = options.emitter.unwrap_or(false);
+        let mut groups = options.groups.clone().unwrap_or_default();
+        if emitter_on {
+            // emitter モードはディスクなしで始め、以降のステップで出す
+            groups.clear();
+            groups.push(GroupSpec::default());
+        } else if groups.is_empty() {
+            // groups を指定しなければ全ディスクが同じ1つのグループになる
+            groups.push(GroupSpec {
+                count: options.disk_num.unwrap_or(100),
+                ..GroupSpec::default()
+            });
+        }
+        if let Some(range) = options.disk_size_range {
+            for group in groups.iter_mut() {
+                if group.size.is_none() && group.size_range.is_none() {
+                    group.size_range = Some(range);
+                }
+            }
+        }
+        let mass_from_radius = options.mass_from_radius.unwrap_or_else(|| {
+            groups
+                .iter()
+                .any(|group| group.size.is_some() || group.size_range.is_some())
+        });
+        let disk_num = groups.iter().map(|group| group.count).sum();
+        let disk_size = options.disk_size.unwrap_or(32.);
+        let color_mode = ColorMode::parse(options.color_mode.as_deref().unwrap_or("random"))?;
+        let boundary = Boundary::parse(options.boundary.as_deref().unwrap_or("bounce"))?;
+        let behavior = Behavior::parse(options.behavior.as_deref().unwrap_or("ballistic"))?;
+        let spawn = match SpawnPattern::parse(options.spawn.as_deref().unwrap_or("uniform"))? {
+            SpawnPattern::Ring { .. } => SpawnPattern::Ring {
+                radius: options.ring_radius,
+            },
+            SpawnPattern::Orbit { .. } => SpawnPattern::Orbit {
+                radius: options.ring_radius,
+            },
+            spawn => spawn,
+        };
+
+        let mut rng = create_std_rng(options.seed);
+        let disks = init_groups(
+            &groups,
+            width,
+            height,
+            disk_size,
+            spawn,
+            velocities,
+            mass_from_radius,
+            &mut rng,
+        )?;
+        let mut sim = Simulation::with_disks(width as f64, height as f64, disk_size, disks);
+        sim.set_arena(arena)?;
+        if options.no_initial_overlap.unwrap_or(false) {
+            // circle の領域なら円の内側に置く
+            sim.place_without_overlap(&mut rng)?;
+        }
+        let mut random_colors = palette.colors(disk_num, &mut rng);
+        // 色かパレットを指定したグループはランダムな色の代わりにそれを使う
+        let mut offset = 0;
+        for group in groups.iter() {
+            let end = offset + group.count as usize * 3;
+            if let Some(colors) = group.colors(group.count, &mut rng) {
+                random_colors[offset..end].copy_from_slice(&colors);
+            }
+            offset = end;
+        }
+
+        sim.set_collision(options.collision.unwrap_or(false));
+        sim.set_collision_response(CollisionResponse::parse(
+            options.on_collision.as_deref().unwrap_or("bounce"),
+        )?);
+        sim.set_merge_limits(options.min_disks.unwrap_or(1), options.max_radius)?;
+        sim.set_boundary(boundary);
+        sim.set_behavior(behavior);
+        sim.set_boids(options.boids.unwrap_or_default())?;
+        let emitter = if emitter_on {
+            let max_disks = options.max_disks.unwrap_or(DEFAULT_MAX_DISKS);
+            // 上限まで増えてもディスクの配列を確保し直さないようにする
+            sim.reserve_disks(max_disks as usize);
+            let origin = match options.emit_origin {
+                Some([x, y]) => (x, y),
+                None => (width as f64 / 2., height as f64 / 2.),
+            };
+            Some(Emitter::new(
+                options.emit_rate.unwrap_or(DEFAULT_EMIT_RATE),
+                options.lifetime.unwrap_or(DEFAULT_LIFETIME),
+                max_disks,
+                origin,
+            )?)
+        } else {
+            None
+        };
+        sim.set_gravity(options.gravity.unwrap_or(0.))?;
+        let (fx, fy) = options.force.unwrap_or((0., 0.));
+        sim.set_force(fx, fy)?;
+        sim.set_drag(options.drag.unwrap_or(0.))?;
+        sim.set_jitter(options.jitter.unwrap_or(0.))?;
+        // seed を指定すれば揺らぎも再現できるよう、初期化と同じ乱数生成器から種を取る
+        sim.seed_jitter(rng.gen());
+        sim.set_pointer_force(
+            options.pointer_force.unwrap_or(DEFAULT_POINTER_FORCE),
+            options.pointer_radius.unwrap_or(DEFAULT_POINTER_RADIUS),
+        )?;
+        sim.set_max_speed(options.max_speed)?;
+        sim.set_wall_restitution(options.wall_restitution.unwrap_or(1.))?;
+        sim.set_collision_restitution(options.collision_restitution.unwrap_or(1.))?;
+        sim.set_rest_speed(options.rest_speed)?;
+        sim.set_strict(options.strict.unwrap_or(false));
+        sim.set_check_invariants(options.check_invariants.unwrap_or(false));
+
+        Ok(World {
+            sim,
+            rng,
+            random_colors,
+            groups,
+            mass_from_radius,
+            velocities,
+            palette,
+            color_mode,
+            static_palette: options.static_palette.clone().unwrap_or_default(),
+            min_velocity: options.min_velocity.unwrap_or(0.),
+            max_velocity: options.max_velocity.unwrap_or(DEFAULT_MAX_VELOCITY),
+            emitter,
+        })
+    }
+
+    /**
+     * 一番大きいディスクの大きさ (points モードで描ける大きさの上限と比べる)
+     */
+    pub fn largest_size(&self) -> f64 {
+        self.groups
+            .iter()
+            .filter_map(|group| group.size_range.map(|[_, max]| max).or(group.size))
+            .fold(self.sim.disk_size(), f64::max)
+    }
+}
@@ -0,0 +1,173 @@
+use wasm::adaptive::{AdaptiveQuality, ADAPT_INTERVAL_MS, SUSPEND_MS};
+
+// start から interval_ms ごとに work_ms かかるフレームを duration_ms の間記録し、返った数を順に集める (disk_count も更新する)
+fn run(
+    adaptive: &mut AdaptiveQuality,
+    start: f64,
+    duration_ms: f64,
+    interval_ms: f64,
+    work_ms: f64,
+    disk_count: &mut u32,
+) -> Vec<u32> {
+    let mut changes = Vec::new();
+    let mut now = start;
+    while now < start + duration_ms {
+        if let Some(target) = adaptive.record(now, work_ms, *disk_count) {
+            *disk_count = target;
+            changes.push(target);
+        }
+        now += interval_ms;
+    }
+    changes
+}
+
+#[test]
+fn bounds_are_validated() {
+    assert!(AdaptiveQuality::new(0, 1, 10).is_err());
+    assert!(AdaptiveQuality::new(60, 0, 10).is_err());
+    assert!(AdaptiveQuality::new(60, 11, 10).is_err());
+    assert!(AdaptiveQuality::new(60, 10, 10).is_ok());
+}
+
+#[test]
+fn slow_frames_remove_a_batch_per_interval() {
+    let mut adaptive = AdaptiveQuality::new(60, 10, 1000).unwrap();
+    let mut count = 500;
+    // 30fps で 1 区間より少し長く
+    let changes = run(
+        &mut adaptive,
+        0.,
+        ADAPT_INTERVAL_MS + 100.,
+        1000. / 30.,
+        30.,
+        &mut count,
+    );
+    assert_eq!(changes, vec![400]);
+}
+
+#[test]
+fn shrinking_stops_at_min_disks() {
+    let mut adaptive = AdaptiveQuality::new(60, 50, 1000).unwrap();
+    let mut count = 100;
+    run(
+        &mut adaptive,
+        0.,
+        ADAPT_INTERVAL_MS * 20.,
+        1000. / 20.,
+        45.,
+        &mut count,
+    );
+    assert_eq!(count, 50);
+}
+
+#[test]
+fn ample_headroom_grows_up_to_max_disks() {
+    let mut adaptive = AdaptiveQuality::new(60, 1, 120).unwrap();
+    let mut count = 100;
+    run(
+        &mut adaptive,
+        0.,
+        ADAPT_INTERVAL_MS * 10.,
+        1000. / 60.,
+        2.,
+        &mut count,
+    );
+    assert_eq!(count, 120);
+}
+
+#[test]
+fn frames_near_the_budget_keep_the_count() {
+    let mut adaptive = AdaptiveQuality::new(60, 1, 1000).unwrap();
+    let mut count = 300;
+    // 目標どおりの間隔だが処理時間に余裕がない
+    let changes = run(
+        &mut adaptive,
+        0.,
+        ADAPT_INTERVAL_MS * 5.,
+        1000. / 60.,
+        14.,
+        &mut count,
+    );
+    assert!(changes.is_empty(), "{:?}", changes);
+}
+
+#[test]
+fn growing_waits_after_a_shrink() {
+    let mut adaptive = AdaptiveQuality::new(60, 1, 1000).unwrap();
+    let mut count = 500;
+    let slow = run(
+        &mut adaptive,
+        0.,
+        ADAPT_INTERVAL_MS + 100.,
+        1000. / 30.,
+        30.,
+        &mut count,
+    );
+    assert_eq!(slow.len(), 1);
+    let shrunk = count;
+    // 余裕のある区間が3回続くまでは増やさない
+    let fast = run(
+        &mut adaptive,
+        ADAPT_INTERVAL_MS + 100.,
+        ADAPT_INTERVAL_MS * 3.,
+        1000. / 60.,
+        2.,
+        &mut count,
+    );
+    assert!(fast.is_empty(), "{:?}", fast);
+    assert_eq!(count, shrunk);
+    let later = run(
+        &mut adaptive,
+        ADAPT_INTERVAL_MS * 4. + 100.,
+        ADAPT_INTERVAL_MS * 2.,
+        1000. / 60.,
+        2.,
+        &mut count,
+    );
+    assert!(!later.is_empty() && count > shrunk);
+}
+
+#[test]
+fn suspend_pauses_adaptation() {
+    let mut adaptive = AdaptiveQuality::new(60, 1, 1000).unwrap();
+    adaptive.suspend(0.);
+    assert!(adaptive.is_suspended());
+    let mut count = 500;
+    let changes = run(
+        &mut adaptive,
+        0.,
+        SUSPEND_MS - 1.,
+        1000. / 20.,
+        45.,
+        &mut count,
+    );
+    assert!(changes.is_empty());
+    run(
+        &mut adaptive,
+        SUSPEND_MS,
+        ADAPT_INTERVAL_MS * 2.,
+        1000. / 20.,
+        45.,
+        &mut count,
+    );
+    assert!(!adaptive.is_suspended());
+    assert!(count < 500);
+}
+
+#[test]
+fn long_gaps_restart_the_interval() {
+    let mut adaptive = AdaptiveQuality::new(60, 1, 1000).unwrap();
+    let mut count = 500;
+    // 非表示のタブから戻ったような長い間隔だけでは減らさない
+    assert_eq!(adaptive.record(0., 1., count), None);
+    assert_eq!(adaptive.record(ADAPT_INTERVAL_MS * 3., 1., count), None);
+    let changes = run(
+        &mut adaptive,
+        ADAPT_INTERVAL_MS * 3. + 16.,
+        ADAPT_INTERVAL_MS - 100.,
+        1000. / 60.,
+        1.,
+        &mut count,
+    );
+    assert!(changes.is_empty());
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use common::random_scene;
+use wasm::boids::{steer_boids, Behavior, BoidsParams};
+use wasm::collision::Grid;
+use wasm::{Circle, Disk, Simulation};
+
+#[test]
+fn behavior_parse_lists_the_options() {
+    assert_eq!(Behavior::parse("ballistic"), Ok(Behavior::Ballistic));
+    assert_eq!(Behavior::parse("boids"), Ok(Behavior::Boids));
+    let err = Behavior::parse("flock").unwrap_err();
+    assert!(
+        err.contains("ballistic") && err.contains("boids"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn boids_params_reject_bad_values() {
+    assert!(BoidsParams::default().validate().is_ok());
+    for params in [
+        BoidsParams {
+            separation: -1.,
+            ..BoidsParams::default()
+        },
+        BoidsParams {
+            perception: 0.,
+            ..BoidsParams::default()
+        },
+        BoidsParams {
+            max_speed: f64::NAN,
+            ..BoidsParams::default()
+        },
+    ] {
+        assert!(params.validate().is_err(), "{:?}", params);
+    }
+}
+
+#[test]
+fn pairs_within_matches_brute_force() {
+    let disks = random_scene(300, 400., 300., 1);
+    let mut grid = Grid::new();
+    let mut pairs = grid.pairs_within(&disks, 400., 300., 30.).to_vec();
+    pairs.sort_unstable();
+    let mut expected = Vec::new();
+    for i in 0..disks.len() {
+        for j in (i + 1)..disks.len() {
+            if (disks[j].x - disks[i].x).hypot(disks[j].y - disks[i].y) < 30. {
+                expected.push((i, j));
+            }
+        }
+    }
+    assert_eq!(pairs, expected);
+}
+
+#[test]
+fn steering_keeps_speeds_under_max_speed() {
+    let mut disks = random_scene(200, 400., 300., 2);
+    for disk in disks.iter_mut() {
+        disk.cos *= 10.;
+    }
+    let params = BoidsParams::default();
+    steer_boids(&mut disks, &mut Grid::new(), &params, 400., 300., None, 1.);
+    for disk in disks.iter() {
+        assert!(disk.cos.hypot(disk.sin) <= params.max_speed + 1e-9);
+    }
+}
+
+#[test]
+fn neighbors_align_their_headings() {
+    let mut disks = vec![Disk::new(200., 150., 2., 0.), Disk::new(210., 150., 0., 2.)];
+    let params = BoidsParams {
+        separation: 0.,
+        cohesion: 0.,
+        ..BoidsParams::default()
+    };
+    let before = disks[0].cos * disks[1].cos + disks[0].sin * disks[1].sin;
+    steer_boids(&mut disks, &mut Grid::new(), &params, 400., 300., None, 1.);
+    let after = disks[0].cos * disks[1].cos + disks[0].sin * disks[1].sin;
+    assert!(after > before, "{} <= {}", after, before);
+}
+
+#[test]
+fn disks_turn_away_from_walls_before_reaching_them() {
+    let mut disks = vec![Disk::new(10., 150., -2., 0.)];
+    steer_boids(
+        &mut disks,
+        &mut Grid::new(),
+        &BoidsParams::default(),
+        400.,
+        300.,
+        None,
+        1.,
+    );
+    assert!(disks[0].cos > -2.);
+}
+
+#[test]
+fn disks_turn_away_from_the_rim_of_a_circle_arena() {
+    // 矩形の壁からは遠いが、円の縁の近くで外へ向かっている
+    let circle = Circle {
+        x: 200.,
+        y: 150.,
+        radius: 100.,
+    };
+    let mut disks = vec![Disk::new(290., 150., 2., 0.), Disk::new(200., 60., 0., -2.)];
+    steer_boids(
+        &mut disks,
+        &mut Grid::new(),
+        &BoidsParams::default(),
+        400.,
+        300.,
+        Some(&circle),
+        1.,
+    );
+    assert!(disks[0].cos < 2.);
+    assert!(disks[1].sin > -2.);
+    // 中心の近くでは向きを変えない
+    let mut center = vec![Disk::new(200., 150., 2., 0.)];
+    steer_boids(
+        &mut center,
+        &mut Grid::new(),
+        &BoidsParams::default(),
+        400.,
+        300.,
+        Some(&circle),
+        1.,
+    );
+    assert_eq!((center[0].cos, center[0].sin), (2., 0.));
+}
+
+#[test]
+fn switching_behavior_keeps_positions() {
+    let disks = random_scene(50, 400., 300., 3);
+    let mut sim = Simulation::with_disks(400., 300., 4., disks.clone());
+    sim.set_behavior(Behavior::Boids);
+    assert_eq!(sim.behavior(), Behavior::Boids);
+    assert_eq!(sim.disks(), disks.as_slice());
+    sim.step();
+    sim.set_behavior(Behavior::Ballistic);
+    let moved = sim.disks().to_vec();
+    sim.set_behavior(Behavior::Boids);
+    assert_eq!(sim.disks(), moved.as_slice());
+}
+
+#[test]
+fn flock_stays_inside_the_canvas() {
+    let mut sim = Simulation::with_disks(400., 300., 4., random_scene(100, 400., 300., 4));
+    sim.set_behavior(Behavior::Boids);
+    for _ in 0..300 {
+        sim.step();
+    }
+    let max_speed = BoidsParams::default().max_speed;
+    for disk in sim.disks() {
+        assert!((0. ..=400.).contains(&disk.x) && (0. ..=300.).contains(&disk.y));
+        assert!(disk.cos.hypot(disk.sin) <= max_speed + 1e-9);
+    }
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use common::random_scene;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use wasm::collision::{
+    overlapping_pairs_brute_force, resolve_brute_force, resolve_collision, resolve_pair, Grid,
+};
+use wasm::Disk;
+
+#[test]
+fn head_on_collision_swaps_velocities() {
+    let mut a = Disk::new(10., 10., 1., 0.);
+    let mut b = Disk::new(17., 10., -2., 0.);
+    assert!(resolve_pair(&mut a, &mut b, 4.));
+    assert_eq!(a.cos, -2.);
+    assert_eq!(b.cos, 1.);
+    assert!((b.x - a.x - 8.).abs() < 1e-9);
+}
+
+#[test]
+fn heavier_disk_keeps_more_of_its_velocity() {
+    let mut a = Disk::new(10., 10., 1., 0.);
+    a.mass = 3.;
+    let mut b = Disk::new(17., 10., -1., 0.);
+    assert!(resolve_pair(&mut a, &mut b, 4.));
+    // 1次元の弾性衝突: v_a' = ((m_a - m_b) v_a + 2 m_b v_b) / (m_a + m_b)
+    assert!((a.cos - 0.).abs() < 1e-12);
+    assert!((b.cos - 2.).abs() < 1e-12);
+    // 運動量と運動エネルギーは保存される
+    assert!((a.mass * a.cos + b.mass * b.cos - 2.).abs() < 1e-12);
+    assert!((a.mass * a.cos * a.cos + b.cos * b.cos - 4.).abs() < 1e-12);
+    // 重なりは軽い方が多く押し戻される
+    assert!((a.x - 9.75).abs() < 1e-12 && (b.x - 17.75).abs() < 1e-12);
+}
+
+#[test]
+fn disks_with_their_own_size_collide_at_the_sum_of_radii() {
+    let mut a = Disk::new(10., 10., 0., 0.);
+    a.size = Some(10.);
+    let b = Disk::new(23., 10., 0., 0.);
+    assert!(wasm::collision::overlaps(&a, &b, 4.));
+    assert!(!wasm::collision::overlaps(&a, &b, 2.));
+}
+
+fn with_mass(mut disk: Disk, mass: f64) -> Disk {
+    disk.mass = mass;
+    disk
+}
+
+#[test]
+fn equal_masses_swap_normal_velocities() {
+    let mut a = Disk::new(0., 0., 3., 1.);
+    let mut b = Disk::new(8., 0., -1., 2.);
+    assert!(resolve_collision(&mut a, &mut b, 1.));
+    assert_eq!((a.cos, a.sin, b.cos, b.sin), (-1., 1., 3., 2.));
+}
+
+#[test]
+fn light_disk_barely_moves_a_heavy_disk_at_rest() {
+    let mut light = Disk::new(0., 0., 2., 0.);
+    let mut heavy = with_mass(Disk::new(8., 0., 0., 0.), 1000.);
+    assert!(resolve_collision(&mut light, &mut heavy, 1.));
+    assert!(heavy.cos > 0. && heavy.cos < 0.01);
+    // 軽い方はほぼそのまま跳ね返る
+    assert!((light.cos + 2.).abs() < 0.01);
+}
+
+#[test]
+fn collisions_conserve_momentum() {
+    let mut rng = StdRng::seed_from_u64(11);
+    for _ in 0..100 {
+        let mut a = with_mass(
+            Disk::new(0., 0., rng.gen_range(-3., 3.), rng.gen_range(-3., 3.)),
+            rng.gen_range(0.1, 10.),
+        );
+        let mut b = with_mass(
+            Disk::new(
+                rng.gen_range(-8., 8.),
+                rng.gen_range(-8., 8.),
+                rng.gen_range(-3., 3.),
+                rng.gen_range(-3., 3.),
+            ),
+            rng.gen_range(0.1, 10.),
+        );
+        let restitution = rng.gen_range(0., 1.);
+        let before = (
+            a.mass * a.cos + b.mass * b.cos,
+            a.mass * a.sin + b.mass * b.sin,
+        );
+        resolve_collision(&mut a, &mut b, restitution);
+        let after = (
+            a.mass * a.cos + b.mass * b.cos,
+            a.mass * a.sin + b.mass * b.sin,
+        );
+        assert!((before.0 - after.0).abs() < 1e-9 && (before.1 - after.1).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn zero_restitution_removes_relative_normal_velocity() {
+    let mut a = Disk::new(0., 0., 2., 0.);
+    let mut b = with_mass(Disk::new(8., 0., 0., 0.), 3.);
+    assert!(resolve_collision(&mut a, &mut b, 0.));
+    assert!((a.cos - b.cos).abs() < 1e-12);
+    assert!((a.cos - 0.5).abs() < 1e-12);
+}
+
+#[test]
+fn separating_disks_are_left_alone() {
+    let mut a = Disk::new(0., 0., -1., 0.);
+    let mut b = Disk::new(8., 0., 1., 0.);
+    assert!(!resolve_collision(&mut a, &mut b, 1.));
+    assert_eq!((a.cos, b.cos), (-1., 1.));
+}
+
+#[test]
+fn separated_disks_do_not_collide() {
+    let mut a = Disk::new(10., 10., 1., 0.);
+    let mut b = Disk::new(18., 10., -2., 0.);
+    assert!(!resolve_pair(&mut a, &mut b, 4.));
+    assert_eq!(a.cos, 1.);
+    assert_eq!(b.cos, -2.);
+}
+
+#[test]
+fn grid_matches_brute_force() {
+    let mut brute = random_scene(60, 200., 150., 7);
+    let mut grid_disks = brute.clone();
+    let mut grid = Grid::new();
+    for _ in 0..20 {
+        let expected = resolve_brute_force(&mut brute, 4.);
+        let actual = grid.resolve(&mut grid_disks, 200., 150., 4.);
+        assert_eq!(expected, actual);
+        for (b, g) in brute.iter().zip(grid_disks.iter()) {
+            assert_eq!((b.x, b.y, b.cos, b.sin), (g.x, g.y, g.cos, g.sin));
+        }
+        wasm::step_disks(&mut brute, 200., 150., 4.);
+        wasm::step_disks(&mut grid_disks, 200., 150., 4.);
+    }
+}
+
+#[test]
+fn grid_finds_same_pairs_as_brute_force() {
+    for seed in 0..5 {
+        let disks = random_scene(300, 400., 300., seed);
+        let expected = overlapping_pairs_brute_force(&disks, 6.);
+        assert!(!expected.is_empty());
+        let mut grid = Grid::new();
+        assert_eq!(grid.overlapping_pairs(&disks, 400., 300., 6.), expected);
+    }
+}
+
+#[test]
+fn grid_with_cells_smaller_than_disks_finds_all_pairs() {
+    let disks = random_scene(300, 400., 300., 11);
+    let expected = overlapping_pairs_brute_force(&disks, 10.);
+    let mut grid = Grid::with_cell_size(3.);
+    assert_eq!(grid.overlapping_pairs(&disks, 400., 300., 10.), expected);
+}
+
+#[test]
+fn grid_handles_disks_on_cell_boundaries_and_outside_bounds() {
+    // セルサイズ 8 の境界上、および描画領域外にはみ出したディスク
+    let disks: Vec<Disk> = [
+        (8., 8.),
+        (15.9, 8.),
+        (16., 16.),
+        (21., 21.),
+        (-3., 40.),
+        (2., 40.),
+        (103., 50.),
+        (97., 50.),
+    ]
+    .iter()
+    .map(|&(x, y)| Disk::new(x, y, 0., 0.))
+    .collect();
+    let expected = overlapping_pairs_brute_force(&disks, 4.);
+    assert_eq!(expected.len(), 4);
+    let mut grid = Grid::new();
+    assert_eq!(grid.overlapping_pairs(&disks, 100., 100., 4.), expected);
+}
+
+#[test]
+fn grid_tests_far_fewer_pairs_than_brute_force() {
+    let count = 2000;
+    let mut disks = random_scene(count, 1000., 1000., 7);
+    let mut grid = Grid::new();
+    grid.resolve(&mut disks, 1000., 1000., 2.);
+    let brute_force = count * (count - 1) / 2;
+    assert!(grid.pair_tests() > 0);
+    assert!(
+        grid.pair_tests() * 100 < brute_force,
+        "{}",
+        grid.pair_tests()
+    );
+}
+
+#[test]
+fn ghost_disks_pass_through_others() {
+    let mut a = Disk::new(10., 10., 1., 0.);
+    let mut b = Disk::new(12., 10., -1., 0.);
+    b.ghost = true;
+    assert!(!resolve_pair(&mut a, &mut b, 4.));
+    assert_eq!((a.x, a.cos, b.x, b.cos), (10., 1., 12., -1.));
+}
@@ -0,0 +1,143 @@
+use wasm::color::{
+    cycle_color, hsv_to_rgb, parse_css_color, speed_color, speed_gradient, validate_colors,
+    validate_speed_range, ColorMode, ColorSpec, DiskHues, Palette,
+};
+use wasm::{create_std_rng, random_colors};
+
+#[test]
+fn gradient_endpoints_and_midpoint() {
+    assert_eq!(speed_gradient(0.), [0., 0., 1.]);
+    assert_eq!(speed_gradient(0.5), [0., 1., 0.]);
+    assert_eq!(speed_gradient(1.), [1., 0., 0.]);
+}
+
+#[test]
+fn gradient_clamps_out_of_range_values() {
+    assert_eq!(speed_gradient(-1.), [0., 0., 1.]);
+    assert_eq!(speed_gradient(3.), [1., 0., 0.]);
+}
+
+#[test]
+fn parse_color_mode() {
+    assert_eq!(ColorMode::parse("speed"), Ok(ColorMode::Speed));
+    assert_eq!(ColorMode::parse("velocity"), Ok(ColorMode::Speed));
+    assert_eq!(ColorMode::parse("cycle"), Ok(ColorMode::Cycle));
+    assert!(ColorMode::parse("rainbow").is_err());
+}
+
+#[test]
+fn speed_color_maps_the_configured_range_and_clamps() {
+    assert_eq!(speed_color(2., 2., 6.), [0., 0., 1.]);
+    assert_eq!(speed_color(4., 2., 6.), [0., 1., 0.]);
+    assert_eq!(speed_color(6., 2., 6.), [1., 0., 0.]);
+    assert_eq!(speed_color(0.5, 2., 6.), [0., 0., 1.]);
+    assert_eq!(speed_color(100., 2., 6.), [1., 0., 0.]);
+    assert!(validate_speed_range(3., 3.).is_err());
+    assert!(validate_speed_range(-1., 3.).is_err());
+    assert!(validate_speed_range(0., 3.).is_ok());
+}
+
+#[test]
+fn colors_from_js_must_match_the_disk_count() {
+    assert!(validate_colors(&[0.; 6], 2).is_ok());
+    let error = validate_colors(&[0.; 5], 2).unwrap_err();
+    assert!(error.contains("expected 6"), "{}", error);
+    assert!(validate_colors(&[0., 0., 1.5], 1).is_err());
+    assert!(validate_colors(&[0., f32::NAN, 0.], 1).is_err());
+}
+
+#[test]
+fn css_colors_are_parsed_into_rgba() {
+    assert_eq!(parse_css_color("#fff"), Ok([1., 1., 1., 1.]));
+    assert_eq!(parse_css_color("#ff0000"), Ok([1., 0., 0., 1.]));
+    assert_eq!(parse_css_color("#00ff0000"), Ok([0., 1., 0., 0.]));
+    for invalid in ["fff", "#ff", "#gggggg", "#ff00000", ""] {
+        assert!(parse_css_color(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn color_specs_accept_arrays_and_strings() {
+    let array: ColorSpec = serde_json::from_str("[0.5, 0.5, 0.5, 0.25]").unwrap();
+    assert_eq!(array.to_rgba(), Ok([0.5, 0.5, 0.5, 0.25]));
+    let css: ColorSpec = serde_json::from_str(r##""#000000""##).unwrap();
+    assert_eq!(css.to_rgba(), Ok([0., 0., 0., 1.]));
+    assert!(ColorSpec::Rgba([2., 0., 0., 1.]).to_rgba().is_err());
+}
+
+#[test]
+fn hsv_primaries_and_grey() {
+    assert_eq!(hsv_to_rgb(0., 1., 1.), [1., 0., 0.]);
+    assert_eq!(hsv_to_rgb(120., 1., 1.), [0., 1., 0.]);
+    assert_eq!(hsv_to_rgb(240., 1., 1.), [0., 0., 1.]);
+    assert_eq!(hsv_to_rgb(60., 0., 0.5), [0.5, 0.5, 0.5]);
+}
+
+#[test]
+fn hsv_hue_wraps_around_at_360_degrees() {
+    assert_eq!(hsv_to_rgb(360., 1., 1.), hsv_to_rgb(0., 1., 1.));
+    assert_eq!(hsv_to_rgb(480., 1., 1.), hsv_to_rgb(120., 1., 1.));
+    assert_eq!(hsv_to_rgb(-120., 1., 1.), hsv_to_rgb(240., 1., 1.));
+    // 360 度の直前は赤に戻る手前 (赤に少し青が混ざる)
+    let [r, g, b] = hsv_to_rgb(359.9, 1., 1.);
+    assert_eq!((r, g), (1., 0.));
+    assert!(b > 0. && b < 0.01);
+}
+
+#[test]
+fn disk_hues_follow_their_disks_through_removals() {
+    let mut hues = DiskHues::default();
+    hues.sync(4);
+    let [a, b, c, d] = [0, 1, 2, 3].map(|i| hues.hues()[i]);
+    assert_eq!(a, 0.);
+    assert!(b != a && c != b && d != c);
+
+    // 削除・合体では後ろのディスクの色相は変わらない
+    hues.remove(1);
+    assert_eq!(hues.hues(), &[a, c, d]);
+    // emitter の削除では末尾のディスクが移る
+    hues.swap_remove(0);
+    assert_eq!(hues.hues(), &[d, c]);
+    // 足したディスクには、まだ使っていない色相を割り当てる
+    hues.sync(3);
+    assert!(![a, b, c, d].contains(&hues.hues()[2]));
+    assert_eq!(cycle_color(hues.hues()[0], 0.), cycle_color(d, 0.));
+
+    hues.clear();
+    hues.sync(1);
+    assert_eq!(hues.hues(), &[a]);
+}
+
+#[test]
+fn palettes_interpolate_between_their_ends() {
+    let mono = Palette::parse("mono").unwrap();
+    assert_eq!(mono.at(0.), [0.25, 0.25, 0.25]);
+    assert_eq!(mono.at(1.), [1., 1., 1.]);
+    assert_eq!(mono.at(2.), [1., 1., 1.]);
+    assert_eq!(mono.at(0.5), [0.625, 0.625, 0.625]);
+    // "random" の色の並びは speed モードの既定と同じ
+    assert_eq!(Palette::Random.at(0.3), speed_gradient(0.3));
+    let error = Palette::parse("rainbow").unwrap_err();
+    assert!(error.contains("\"viridis\""), "{}", error);
+}
+
+#[test]
+fn custom_palette_assigns_one_of_its_colors() {
+    let palette = Palette::custom(&["#ff0000".to_string(), "#00f".to_string()]).unwrap();
+    let colors = palette.colors(20, &mut create_std_rng(Some(3)));
+    assert_eq!(colors.len(), 60);
+    assert!(colors
+        .chunks(3)
+        .all(|rgb| rgb == [1., 0., 0.] || rgb == [0., 0., 1.]));
+    assert_eq!(palette.speed_color(2., 0., 4.), [0.5, 0., 0.5]);
+    assert!(Palette::custom(&["red".to_string()]).is_err());
+    assert!(Palette::custom(&[]).is_err());
+}
+
+#[test]
+fn random_palette_matches_random_colors() {
+    assert_eq!(
+        Palette::Random.colors(10, &mut create_std_rng(Some(9))),
+        random_colors(10, &mut create_std_rng(Some(9)))
+    );
+}
@@ -0,0 +1,20 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use wasm::Disk;
+
+/**
+ * width x height の範囲にランダムな位置・速度のディスクを count 個並べる (seed が同じなら同じ配置)
+ */
+pub fn random_scene(count: usize, width: f64, height: f64, seed: u64) -> Vec<Disk> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| {
+            Disk::new(
+                rng.gen_range(0., width),
+                rng.gen_range(0., height),
+                rng.gen_range(-3., 3.),
+                rng.gen_range(-3., 3.),
+            )
+        })
+        .collect()
+}
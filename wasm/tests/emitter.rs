@@ -0,0 +1,83 @@
+use wasm::emitter::Emitter;
+use wasm::{Simulation, FRAME_MS};
+
+fn emitter(rate: f64, lifetime: f64, max_disks: u32) -> Emitter {
+    Emitter::new(rate, lifetime, max_disks, (50., 50.)).unwrap()
+}
+
+#[test]
+fn emitter_carries_fractional_spawns_over() {
+    // 1秒に 30 個なら、60fps の2フレームで1個
+    let mut emitter = emitter(30., 1., 100);
+    assert_eq!(emitter.spawn_count(FRAME_MS), 0);
+    assert_eq!(emitter.spawn_count(FRAME_MS), 1);
+    let mut total = 1;
+    for _ in 0..58 {
+        for _ in 0..emitter.spawn_count(FRAME_MS) {
+            emitter.push();
+            total += 1;
+        }
+    }
+    assert_eq!(total, 30);
+}
+
+#[test]
+fn emitter_stops_at_max_disks() {
+    let mut emitter = emitter(1000., 1., 3);
+    for _ in 0..emitter.spawn_count(FRAME_MS) {
+        emitter.push();
+    }
+    assert_eq!(emitter.ages().len(), 3);
+    // 上限の間に溜まった分は持ち越さない
+    assert_eq!(emitter.spawn_count(FRAME_MS), 0);
+    emitter.swap_remove(0);
+    assert_eq!(emitter.spawn_count(FRAME_MS), 1);
+}
+
+#[test]
+fn emitter_expires_disks_after_their_lifetime() {
+    let mut emitter = emitter(1., 0.1, 10);
+    emitter.push();
+    emitter.age(30.);
+    emitter.age(30.);
+    emitter.push();
+    assert_eq!(emitter.next_expired(), None);
+    emitter.age(40.);
+    assert_eq!(emitter.next_expired(), Some(0));
+    emitter.swap_remove(0);
+    assert_eq!(emitter.ages(), &[40.]);
+    assert_eq!(emitter.next_expired(), None);
+}
+
+#[test]
+fn emitter_follows_disks_added_or_removed_elsewhere() {
+    let mut emitter = emitter(1., 1., 10);
+    emitter.push();
+    emitter.age(40.);
+    emitter.sync(3);
+    assert_eq!(emitter.ages(), &[40., 0., 0.]);
+    emitter.remove(0);
+    emitter.sync(1);
+    assert_eq!(emitter.ages(), &[0.]);
+}
+
+#[test]
+fn emitter_rejects_invalid_settings() {
+    let origin = (0., 0.);
+    assert!(Emitter::new(0., 1., 10, origin).is_err());
+    assert!(Emitter::new(1., f64::NAN, 10, origin).is_err());
+    assert!(Emitter::new(1., 1., 0, origin).is_err());
+    assert!(Emitter::new(1., 1., 10, (f64::INFINITY, 0.)).is_err());
+}
+
+#[test]
+fn swap_remove_moves_the_last_disk_into_the_gap() {
+    let mut sim = Simulation::new(100., 100., 4.);
+    sim.add_disk(10., 10., 0., 0.);
+    sim.add_disk(20., 20., 0., 0.);
+    sim.add_disk(30., 30., 0., 0.);
+    assert!(sim.swap_remove_disk(0));
+    assert!(!sim.swap_remove_disk(2));
+    let xs: Vec<f64> = sim.disks().iter().map(|d| d.x).collect();
+    assert_eq!(xs, vec![30., 20.]);
+}
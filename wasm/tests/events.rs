@@ -0,0 +1,125 @@
+use wasm::events::{EventKind, EventQueue, SimEvent};
+use wasm::Simulation;
+
+fn two_disks_colliding() -> Simulation {
+    let mut sim = Simulation::new(200., 200., 5.);
+    sim.add_disk(96., 100., 2., 0.);
+    sim.add_disk(104., 100., -2., 0.);
+    sim.set_collision(true);
+    sim
+}
+
+#[test]
+fn no_events_are_collected_by_default() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(7., 40., -4., 0.);
+    sim.step();
+    assert!(sim.events().is_empty());
+}
+
+#[test]
+fn wall_hit_is_reported_with_index_and_speed() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(50., 40., 1., 0.);
+    sim.add_disk(7., 40., -3., 4.);
+    sim.set_event_mask(true, false);
+    sim.step();
+    assert_eq!(
+        sim.events(),
+        &[SimEvent {
+            kind: EventKind::Wall,
+            indices: vec![1],
+            x: 6.,
+            y: 44.,
+            speed: 5.,
+        }]
+    );
+}
+
+#[test]
+fn corner_hit_is_reported_once() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(7., 7., -4., -4.);
+    sim.set_event_mask(true, false);
+    sim.step();
+    assert_eq!(sim.events().len(), 1);
+}
+
+#[test]
+fn collision_is_reported_with_both_indices() {
+    let mut sim = two_disks_colliding();
+    sim.set_event_mask(false, true);
+    sim.step();
+    let events = sim.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EventKind::Collision);
+    assert_eq!(events[0].indices, vec![0, 1]);
+    assert_eq!((events[0].x, events[0].y), (100., 100.));
+    assert_eq!(events[0].speed, 4.);
+}
+
+#[test]
+fn mask_filters_event_kinds() {
+    let mut sim = two_disks_colliding();
+    sim.add_disk(7., 40., -4., 0.);
+    sim.set_event_mask(true, false);
+    sim.step();
+    assert!(sim.events().iter().all(|e| e.kind == EventKind::Wall));
+    assert_eq!(sim.events().len(), 1);
+}
+
+#[test]
+fn events_only_cover_the_last_step() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(7., 40., -4., 0.);
+    sim.set_event_mask(true, true);
+    sim.step();
+    assert_eq!(sim.events().len(), 1);
+    sim.step();
+    assert!(sim.events().is_empty());
+}
+
+#[test]
+fn events_serialize_with_type_field() {
+    let event = SimEvent {
+        kind: EventKind::Collision,
+        indices: vec![2, 5],
+        x: 1.,
+        y: 2.,
+        speed: 3.,
+    };
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        serde_json::json!({"type": "collision", "indices": [2, 5], "x": 1.0, "y": 2.0, "speed": 3.0})
+    );
+}
+
+fn wall_event(index: usize) -> SimEvent {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(50., 40., 0., 0.);
+    SimEvent::wall(index, &sim.disks()[0])
+}
+
+#[test]
+fn event_queue_drops_the_oldest_events_over_its_limit() {
+    let mut queue = EventQueue::new(3);
+    queue.extend((0..2).map(wall_event));
+    assert_eq!(queue.take_dropped(), 0);
+    queue.extend((2..5).map(wall_event));
+    let indices: Vec<u32> = queue.events().iter().map(|e| e.indices[0]).collect();
+    assert_eq!(indices, vec![2, 3, 4]);
+    assert_eq!(queue.take_dropped(), 2);
+    assert_eq!(queue.take_dropped(), 0);
+}
+
+#[test]
+fn lowering_the_event_limit_trims_queued_events() {
+    let mut queue = EventQueue::default();
+    queue.extend((0..4).map(wall_event));
+    queue.set_max(1);
+    assert_eq!(queue.max(), 1);
+    assert_eq!(queue.events(), &[wall_event(3)]);
+    assert_eq!(queue.take_dropped(), 3);
+    queue.clear();
+    assert!(queue.is_empty());
+}
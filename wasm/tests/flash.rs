@@ -0,0 +1,61 @@
+use wasm::flash::BounceFlash;
+
+#[test]
+fn flash_fades_back_to_the_base_color() {
+    let mut flash = BounceFlash::new(0.5, [1., 1., 1.]).unwrap();
+    let base = [0., 0.5, 1., 0.8, 0.2, 0.2, 0.2, 1.];
+    flash.hit(0);
+    let mut colors = base;
+    flash.apply(&mut colors);
+    assert_eq!(colors[..4], [1., 1., 1., 0.8]);
+    assert_eq!(colors[4..], base[4..]);
+
+    flash.advance(250.);
+    let mut colors = base;
+    flash.apply(&mut colors);
+    assert_eq!(colors[..4], [0.5, 0.75, 1., 0.8]);
+
+    flash.advance(250.);
+    assert!(flash.needs_apply());
+    let mut colors = base;
+    flash.apply(&mut colors);
+    assert_eq!(colors, base);
+    // 元の色に戻したフレームの後は塗り直さない
+    assert!(!flash.needs_apply());
+}
+
+#[test]
+fn repeated_hits_restart_the_timer_without_stacking() {
+    let mut flash = BounceFlash::new(1., [1., 0., 0.]).unwrap();
+    flash.hit(2);
+    flash.advance(600.);
+    flash.hit(2);
+    flash.hit(2);
+    assert_eq!(flash.level(2), 1.);
+    let mut colors = [0.; 12];
+    flash.apply(&mut colors);
+    assert_eq!(colors[8..], [1., 0., 0., 0.]);
+    assert_eq!(flash.level(0), 0.);
+}
+
+#[test]
+fn timers_follow_removed_disks() {
+    let mut flash = BounceFlash::new(1., [1., 1., 1.]).unwrap();
+    flash.hit(1);
+    flash.remove(0);
+    assert_eq!(flash.level(0), 1.);
+    // 末尾 (4個のうち3番目) のディスクが 0 番に移る
+    flash.hit(3);
+    flash.swap_remove(0, 4);
+    assert_eq!(flash.level(0), 1.);
+    assert_eq!(flash.level(3), 0.);
+    flash.truncate(0);
+    assert!(!flash.needs_apply());
+}
+
+#[test]
+fn non_positive_durations_are_rejected() {
+    for duration in [0., -1., f64::NAN, f64::INFINITY] {
+        assert!(BounceFlash::new(duration, [1., 1., 1.]).is_err());
+    }
+}
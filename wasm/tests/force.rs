@@ -0,0 +1,193 @@
+use wasm::{
+    apply_attractors, apply_pointer_force, apply_radial_impulse, Attractor, Disk, Simulation,
+    ATTRACTOR_MIN_DISTANCE,
+};
+
+#[test]
+fn impulse_pushes_disks_away_from_point() {
+    let mut disks = vec![
+        Disk::new(60., 50., 0., 0.),
+        Disk::new(50., 40., 0., 0.),
+        Disk::new(90., 50., 0., 0.),
+    ];
+    apply_radial_impulse(&mut disks, 50., 50., 20., 20.);
+    assert_eq!((disks[0].cos, disks[0].sin), (2., 0.));
+    assert_eq!((disks[1].cos, disks[1].sin), (0., -2.));
+    // 半径外のディスクは影響を受けない
+    assert_eq!((disks[2].cos, disks[2].sin), (0., 0.));
+}
+
+#[test]
+fn impulse_at_exact_pointer_position_is_finite_and_capped() {
+    let mut disks = vec![Disk::new(50., 50., 0., 0.)];
+    apply_radial_impulse(&mut disks, 50., 50., 1000., 20.);
+    assert!(disks[0].cos.is_finite() && disks[0].sin.is_finite());
+    assert!(disks[0].cos.hypot(disks[0].sin) <= 8.);
+}
+
+#[test]
+fn pointer_force_falls_off_with_distance() {
+    let mut disks = vec![
+        Disk::new(60., 50., 0., 0.),
+        Disk::new(50., 75., 0., 0.),
+        Disk::new(200., 50., 0., 0.),
+    ];
+    apply_pointer_force(&mut disks, 50., 50., 2., 100., 1.);
+    // 正の力はポインタへ引き寄せ、近いほど強い
+    assert!((disks[0].cos - -1.8).abs() < 1e-12 && disks[0].sin == 0.);
+    assert!(disks[1].cos == 0. && (disks[1].sin - -1.5).abs() < 1e-12);
+    assert_eq!((disks[2].cos, disks[2].sin), (0., 0.));
+
+    apply_pointer_force(&mut disks, 50., 50., -2., 100., 1.);
+    assert!(disks[0].cos.abs() < 1e-12 && disks[1].sin.abs() < 1e-12);
+}
+
+#[test]
+fn pointer_force_on_top_of_the_pointer_is_capped() {
+    let mut disks = vec![
+        Disk::new(50., 50., 0., 0.),
+        Disk::new(50.000001, 50., 0., 0.),
+    ];
+    apply_pointer_force(&mut disks, 50., 50., 1e9, 100., 1.);
+    assert_eq!((disks[0].cos, disks[0].sin), (0., 0.));
+    assert!(disks[1].cos.is_finite() && disks[1].cos.abs() <= 8.);
+
+    apply_pointer_force(&mut disks, 50., 50., -1e9, 100., 1.);
+    assert!(disks[0].cos.is_finite() && disks[0].cos.abs() <= 8.);
+}
+
+#[test]
+fn simulation_applies_the_pointer_until_cleared() {
+    let disks = vec![Disk::new(150., 100., 0., 0.)];
+    let mut sim = Simulation::with_disks(300., 200., 4., disks);
+    sim.set_pointer_force(1., 100.).unwrap();
+    sim.set_pointer(100., 100.);
+    sim.step();
+    let vx = sim.disks()[0].cos;
+    assert!(vx < 0.);
+
+    sim.clear_pointer();
+    sim.step();
+    assert_eq!(sim.disks()[0].cos, vx);
+    assert!(sim.set_pointer_force(1., 0.).is_err());
+    assert!(sim.set_pointer_force(f64::NAN, 10.).is_err());
+}
+
+#[test]
+fn impulse_adds_the_same_velocity_to_every_disk() {
+    let mut sim = Simulation::new(200., 200., 5.);
+    sim.add_disk(50., 50., 1., 0.);
+    sim.add_disk(150., 150., 0., -2.);
+    sim.apply_impulse(-0.5, 0.25);
+    let velocities: Vec<(f64, f64)> = sim.disks().iter().map(|d| (d.cos, d.sin)).collect();
+    assert_eq!(velocities, vec![(0.5, 0.25), (-0.5, -1.75)]);
+}
+
+#[test]
+fn impulses_are_clamped_by_max_speed() {
+    let mut sim = Simulation::new(200., 200., 5.);
+    sim.add_disk(50., 50., 1., 0.);
+    sim.add_disk(60., 50., 0., 0.);
+    sim.set_max_speed(Some(4.)).unwrap();
+    for _ in 0..100 {
+        sim.apply_impulse(1e6, -1e6);
+    }
+    sim.apply_force(50., 50., 1e9, 100.);
+    for disk in sim.disks() {
+        assert!(disk.cos.is_finite() && disk.sin.is_finite());
+        assert!(disk.cos.hypot(disk.sin) <= 4. + 1e-9);
+    }
+}
+
+#[test]
+fn every_touch_point_pulls_like_the_pointer() {
+    // 5本の指の下に1つずつディスクを置き、それぞれの右に指を置く
+    let disks = (0..5)
+        .map(|i| Disk::new(50., 20. + i as f64 * 40., 0., 0.))
+        .collect();
+    let mut sim = Simulation::with_disks(300., 220., 4., disks);
+    sim.set_pointer_force(1., 30.).unwrap();
+    let points: Vec<f64> = (0..5).flat_map(|i| [70., 20. + i as f64 * 40.]).collect();
+    sim.set_touch_points(&points).unwrap();
+    assert_eq!(sim.touch_points().len(), 5);
+    sim.step();
+    for disk in sim.disks() {
+        assert!(disk.cos > 0. && disk.sin.abs() < 1e-9, "{:?}", disk);
+    }
+
+    // 指を離したら次のステップからは力が残らない
+    sim.set_touch_points(&[]).unwrap();
+    let before: Vec<f64> = sim.disks().iter().map(|d| d.cos).collect();
+    sim.step();
+    let after: Vec<f64> = sim.disks().iter().map(|d| d.cos).collect();
+    assert_eq!(before, after);
+}
+
+#[test]
+fn touch_points_must_come_in_pairs() {
+    let mut sim = Simulation::new(100., 100., 4.);
+    sim.set_touch_points(&[10., 10.]).unwrap();
+    assert!(sim.set_touch_points(&[1., 2., 3.]).is_err());
+    assert_eq!(sim.touch_points(), &[(10., 10.)]);
+    sim.set_touch_points(&[f64::NAN, 1., 5., 6.]).unwrap();
+    assert_eq!(sim.touch_points(), &[(5., 6.)]);
+}
+
+fn attractor(strength: f64) -> Attractor {
+    Attractor {
+        id: 0,
+        x: 50.,
+        y: 50.,
+        strength,
+    }
+}
+
+#[test]
+fn attractor_pulls_with_inverse_square_falloff() {
+    let mut disks = vec![Disk::new(70., 50., 0., 0.), Disk::new(50., 90., 0., 0.)];
+    apply_attractors(&mut disks, &[attractor(400.)], 1.);
+    assert_eq!((disks[0].cos, disks[0].sin), (-1., 0.));
+    assert_eq!((disks[1].cos, disks[1].sin), (0., -0.25));
+}
+
+#[test]
+fn negative_attractor_repels() {
+    let mut disks = vec![Disk::new(70., 50., 0., 0.)];
+    apply_attractors(&mut disks, &[attractor(-400.)], 1.);
+    assert_eq!((disks[0].cos, disks[0].sin), (1., 0.));
+}
+
+#[test]
+fn attractor_is_clamped_near_its_center() {
+    let mut disks = vec![Disk::new(51., 50., 0., 0.), Disk::new(50., 50., 0., 0.)];
+    apply_attractors(&mut disks, &[attractor(10.)], 1.);
+    let expected = 10. / (ATTRACTOR_MIN_DISTANCE * ATTRACTOR_MIN_DISTANCE);
+    assert_eq!((disks[0].cos, disks[0].sin), (-expected, 0.));
+    // ちょうど中心にいるディスクは方向が定まらないので動かさない
+    assert_eq!((disks[1].cos, disks[1].sin), (0., 0.));
+
+    let mut disks = vec![Disk::new(51., 50., 0., 0.)];
+    apply_attractors(&mut disks, &[attractor(1e9)], 1.);
+    assert!(disks[0].cos.is_finite() && disks[0].cos < 0.);
+}
+
+#[test]
+fn attractors_compose_with_gravity_and_are_removable() {
+    let mut sim = Simulation::new(200., 200., 4.);
+    sim.add_disk(100., 100., 0., 0.);
+    sim.set_gravity(0.5).unwrap();
+    let first = sim.add_attractor(100., 50., 2500.).unwrap();
+    let second = sim.add_attractor(150., 150., 1.).unwrap();
+    assert_ne!(first, second);
+    assert!(sim.add_attractor(f64::NAN, 0., 1.).is_err());
+    assert!(sim.add_attractor(0., 0., f64::INFINITY).is_err());
+    assert!(sim.remove_attractor(second));
+    assert!(!sim.remove_attractor(second));
+    sim.step();
+    // 重力 0.5 (下向き) と引力 2500 / 50² = 1.0 (上向き)
+    assert_eq!(sim.disks()[0].sin, -0.5);
+
+    sim.clear_attractors();
+    assert!(sim.attractors().is_empty());
+    assert_eq!(sim.add_attractor(0., 0., 1.).unwrap(), second + 1);
+}
@@ -0,0 +1,718 @@
+use wasm::simulation::spawn_positions;
+use wasm::{
+    buffer_size, create_rng, create_std_rng, grown_capacity, init_disks, init_groups,
+    interleave_vertices, layer_depth, max_non_overlapping, place_without_overlap,
+    place_without_overlap_in, random_colors, size_runs, Bounds, Circle, GroupSpec, Simulation,
+    SpawnPattern, VelocityDistribution, DEFAULT_SPEED_RANGE, VERTEX_FLOATS,
+};
+
+#[test]
+fn same_seed_produces_identical_disks_and_colors() {
+    let mut a = create_rng(Some(42));
+    let mut b = create_rng(Some(42));
+    let disks_a = init_disks(
+        50,
+        400,
+        300,
+        10.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut *a,
+    );
+    let disks_b = init_disks(
+        50,
+        400,
+        300,
+        10.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut *b,
+    );
+    for (a, b) in disks_a.iter().zip(disks_b.iter()) {
+        assert_eq!(
+            (
+                a.x.to_bits(),
+                a.y.to_bits(),
+                a.cos.to_bits(),
+                a.sin.to_bits()
+            ),
+            (
+                b.x.to_bits(),
+                b.y.to_bits(),
+                b.cos.to_bits(),
+                b.sin.to_bits()
+            )
+        );
+    }
+    assert_eq!(random_colors(50, &mut *a), random_colors(50, &mut *b));
+}
+
+#[test]
+fn different_seeds_diverge() {
+    let disks_a = init_disks(
+        10,
+        400,
+        300,
+        10.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut *create_rng(Some(1)),
+    );
+    let disks_b = init_disks(
+        10,
+        400,
+        300,
+        10.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut *create_rng(Some(2)),
+    );
+    assert!(disks_a
+        .iter()
+        .zip(disks_b.iter())
+        .any(|(a, b)| a.cos != b.cos || a.sin != b.sin));
+}
+
+#[test]
+fn seeded_disks_start_at_center_with_speed_in_range() {
+    let disks = init_disks(
+        200,
+        400,
+        300,
+        10.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut *create_rng(Some(7)),
+    );
+    for disk in disks.iter() {
+        assert_eq!((disk.x, disk.y), (200., 150.));
+        let speed = disk.cos.hypot(disk.sin);
+        assert!((1. - 1e-9..=4. + 1e-9).contains(&speed));
+    }
+    // 方向は添字によらず全方位に散らばる (左向き・上向きのディスクも存在する)
+    assert!(disks.iter().any(|d| d.cos < 0. && d.sin < 0.));
+}
+
+#[test]
+fn seeded_values_are_stable() {
+    let disks = init_disks(
+        2,
+        100,
+        100,
+        10.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut *create_rng(Some(2024)),
+    );
+    let values: Vec<(f64, f64)> = disks.iter().map(|d| (d.cos, d.sin)).collect();
+    assert_eq!(
+        values,
+        vec![
+            (1.884811493577982, -3.1522042489129114),
+            (1.3774649172031947, 2.811130328566419)
+        ]
+    );
+}
+
+#[test]
+fn random_spawn_keeps_disks_inside_walls() {
+    let disks = init_disks(
+        500,
+        400,
+        300,
+        10.,
+        SpawnPattern::Uniform,
+        [2., 3.],
+        &mut *create_rng(Some(3)),
+    );
+    for disk in disks.iter() {
+        assert!((10.0..=390.0).contains(&disk.x) && (10.0..=290.0).contains(&disk.y));
+        let speed = disk.cos.hypot(disk.sin);
+        assert!((2. - 1e-9..=3. + 1e-9).contains(&speed));
+    }
+    // 中央に固まらずに散らばる
+    assert!(disks.iter().any(|d| d.x < 100.) && disks.iter().any(|d| d.x > 300.));
+}
+
+#[test]
+fn random_spawn_falls_back_to_center_when_disk_does_not_fit() {
+    let disks = init_disks(
+        3,
+        30,
+        30,
+        20.,
+        SpawnPattern::Uniform,
+        [1., 1.],
+        &mut *create_rng(Some(3)),
+    );
+    assert!(disks.iter().all(|d| (d.x, d.y) == (15., 15.)));
+}
+
+#[test]
+fn grid_spawn_handles_non_square_counts() {
+    let disks = spawn_positions(
+        SpawnPattern::Grid,
+        7,
+        300.,
+        200.,
+        10.,
+        &mut *create_rng(Some(1)),
+    );
+    assert_eq!(disks.len(), 7);
+    // 縦横比 3:2 なので4列2行 (最後の行は3つ)
+    assert_eq!(disks[0].x, 10. + 280. / 8.);
+    assert_eq!(disks[0].y, 10. + 180. / 4.);
+    assert_eq!(disks[4].x, disks[0].x);
+    assert_eq!(disks[4].y, 10. + 180. * 3. / 4.);
+    for (i, a) in disks.iter().enumerate() {
+        assert!((10.0..=290.0).contains(&a.x) && (10.0..=190.0).contains(&a.y));
+        assert!(disks[i + 1..].iter().all(|b| (a.x, a.y) != (b.x, b.y)));
+    }
+}
+
+#[test]
+fn uniform_spawn_never_overlaps_the_boundary() {
+    let disks = spawn_positions(
+        SpawnPattern::Uniform,
+        1000,
+        120.,
+        80.,
+        15.,
+        &mut *create_rng(Some(9)),
+    );
+    assert!(disks
+        .iter()
+        .all(|d| d.x >= 15. && d.x <= 105. && d.y >= 15. && d.y <= 65.));
+}
+
+#[test]
+fn ring_spawn_moves_outward_on_the_circle() {
+    let disks = init_disks(
+        8,
+        400,
+        300,
+        5.,
+        SpawnPattern::Ring { radius: Some(50.) },
+        [2., 2.],
+        &mut *create_rng(Some(4)),
+    );
+    for disk in disks.iter() {
+        let (dx, dy) = (disk.x - 200., disk.y - 150.);
+        assert!((dx.hypot(dy) - 50.).abs() < 1e-9);
+        // 速度は中心からの向きと同じ
+        assert!((disk.cos - dx / 25.).abs() < 1e-9 && (disk.sin - dy / 25.).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn orbit_spawn_moves_along_the_circle() {
+    let disks = init_disks(
+        8,
+        400,
+        300,
+        5.,
+        SpawnPattern::Orbit { radius: Some(50.) },
+        [2., 2.],
+        &mut *create_rng(Some(4)),
+    );
+    for disk in disks.iter() {
+        let (dx, dy) = (disk.x - 200., disk.y - 150.);
+        assert!((dx.hypot(dy) - 50.).abs() < 1e-9);
+        // 速度は中心からの向きに垂直で、画面上で反時計回り
+        assert!((disk.cos * dx + disk.sin * dy).abs() < 1e-9);
+        assert!((disk.cos - dy / 25.).abs() < 1e-9 && (disk.sin + dx / 25.).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn ring_radius_is_shrunk_to_keep_disks_off_the_walls() {
+    let disks = init_disks(
+        12,
+        120,
+        80,
+        10.,
+        SpawnPattern::Ring { radius: Some(500.) },
+        [1., 1.],
+        &mut *create_rng(Some(4)),
+    );
+    for disk in disks.iter() {
+        assert!((disk.x - 60.).hypot(disk.y - 40.) <= 30. + 1e-9);
+        assert!(disk.x >= 10. && disk.x <= 110. && disk.y >= 10. - 1e-9 && disk.y <= 70. + 1e-9);
+    }
+}
+
+fn group(count: u32, size: Option<f64>, mass: Option<f64>) -> GroupSpec {
+    GroupSpec {
+        count,
+        size,
+        mass,
+        ..GroupSpec::default()
+    }
+}
+
+#[test]
+fn groups_are_allocated_in_order() {
+    let groups = [group(3, Some(4.), Some(5.)), group(2, None, None)];
+    let disks = init_groups(
+        &groups,
+        400,
+        300,
+        8.,
+        SpawnPattern::Uniform,
+        DEFAULT_SPEED_RANGE,
+        false,
+        &mut *create_rng(Some(1)),
+    )
+    .unwrap();
+    let summary: Vec<(u8, f64, Option<f64>)> =
+        disks.iter().map(|d| (d.kind, d.mass, d.size)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (0, 5., Some(4.)),
+            (0, 5., Some(4.)),
+            (0, 5., Some(4.)),
+            (1, 1., None),
+            (1, 1., None),
+        ]
+    );
+    assert_eq!(size_runs(&disks, 8.), vec![(0, 3, 4.), (3, 2, 8.)]);
+
+    let sim = Simulation::with_disks(400., 300., 8., disks);
+    assert_eq!(sim.count_of_kind(0), 3);
+    assert_eq!(sim.count_of_kind(1), 2);
+    assert_eq!(sim.count_of_kind(2), 0);
+}
+
+#[test]
+fn invalid_groups_are_rejected() {
+    let too_many = vec![group(1, None, None); 257];
+    assert!(GroupSpec::validate(&too_many).is_err());
+    assert!(GroupSpec::validate(&[group(1, Some(0.), None)]).is_err());
+    assert!(GroupSpec::validate(&[group(1, None, Some(-1.))]).is_err());
+    assert!(GroupSpec::validate(&vec![group(1, None, None); 256]).is_ok());
+}
+
+#[test]
+fn mass_from_radius_fills_in_missing_masses() {
+    let groups = [group(1, Some(3.), None), group(1, None, Some(7.))];
+    let disks = init_groups(
+        &groups,
+        400,
+        300,
+        5.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        true,
+        &mut *create_rng(Some(2)),
+    )
+    .unwrap();
+    assert_eq!((disks[0].mass, disks[1].mass), (9., 7.));
+}
+
+#[test]
+fn a_single_default_group_matches_init_disks() {
+    let plain = init_disks(
+        20,
+        400,
+        300,
+        6.,
+        SpawnPattern::Uniform,
+        DEFAULT_SPEED_RANGE,
+        &mut *create_rng(Some(3)),
+    );
+    let grouped = init_groups(
+        &[group(20, None, None)],
+        400,
+        300,
+        6.,
+        SpawnPattern::Uniform,
+        DEFAULT_SPEED_RANGE,
+        false,
+        &mut *create_rng(Some(3)),
+    )
+    .unwrap();
+    assert_eq!(plain, grouped);
+}
+
+#[test]
+fn size_range_draws_radii_within_the_range() {
+    let groups = [GroupSpec {
+        count: 50,
+        size_range: Some([2., 6.]),
+        ..GroupSpec::default()
+    }];
+    let disks = init_groups(
+        &groups,
+        400,
+        300,
+        4.,
+        SpawnPattern::Uniform,
+        DEFAULT_SPEED_RANGE,
+        true,
+        &mut *create_rng(Some(5)),
+    )
+    .unwrap();
+    for disk in disks.iter() {
+        let size = disk.size.unwrap();
+        assert!((2. ..=6.).contains(&size));
+        assert_eq!(disk.mass, size * size);
+    }
+    // 全部同じ大きさにはならない
+    assert!(disks.iter().any(|d| d.size != disks[0].size));
+}
+
+#[test]
+fn invalid_size_ranges_are_rejected() {
+    let with_range = |size, size_range| GroupSpec {
+        count: 1,
+        size,
+        size_range,
+        ..GroupSpec::default()
+    };
+    assert!(GroupSpec::validate(&[with_range(None, Some([3., 2.]))]).is_err());
+    assert!(GroupSpec::validate(&[with_range(None, Some([0., 2.]))]).is_err());
+    assert!(GroupSpec::validate(&[with_range(Some(2.), Some([1., 2.]))]).is_err());
+    assert!(GroupSpec::validate(&[with_range(None, Some([1., 2.]))]).is_ok());
+}
+
+#[test]
+fn create_std_rng_matches_create_rng_for_the_same_seed() {
+    let mut boxed = create_rng(Some(9));
+    let mut owned = create_std_rng(Some(9));
+    assert_eq!(
+        random_colors(10, &mut *boxed),
+        random_colors(10, &mut owned)
+    );
+    // 続けて足したディスクも seed が同じなら一致する
+    let mut a = Simulation::new(400., 300., 6.);
+    let mut b = Simulation::new(400., 300., 6.);
+    a.add_random_disks(5, DEFAULT_SPEED_RANGE, &mut owned);
+    b.add_random_disks(5, DEFAULT_SPEED_RANGE, &mut *boxed);
+    assert_eq!(a.disks(), b.disks());
+}
+
+#[test]
+fn buffer_size_keeps_width_and_height_apart() {
+    assert_eq!(buffer_size(800, 400, 1.), (800, 400));
+    assert_eq!(buffer_size(800, 400, 2.), (1600, 800));
+    assert_eq!(buffer_size(300, 150, 1.5), (450, 225));
+}
+
+#[test]
+fn vertex_buffers_grow_by_doubling() {
+    assert_eq!(grown_capacity(0, 200), 200);
+    assert_eq!(grown_capacity(200, 150), 200);
+    assert_eq!(grown_capacity(200, 202), 400);
+    // 倍にしても足りなければ必要な大きさまで広げる
+    assert_eq!(grown_capacity(200, 1000), 1000);
+}
+
+#[test]
+fn vertices_interleave_position_color_and_scale() {
+    let mut vertices = vec![9.; 3];
+    interleave_vertices(
+        &mut vertices,
+        &[1., 2., 3., 4.],
+        &[],
+        &[0.1, 0.2, 0.3, 1., 0.4, 0.5, 0.6, 0.5],
+        &[1., 2.],
+    );
+    assert_eq!(vertices.len(), 2 * VERTEX_FLOATS);
+    assert_eq!(
+        vertices,
+        vec![1., 2., 0., 0.1, 0.2, 0.3, 1., 1., 3., 4., 0., 0.4, 0.5, 0.6, 0.5, 2.]
+    );
+}
+
+#[test]
+fn higher_layers_are_drawn_closer() {
+    assert_eq!(layer_depth(0), 0.);
+    assert!(layer_depth(1) < layer_depth(0));
+    assert!(layer_depth(255) < layer_depth(254) && layer_depth(255) > -1.);
+    let mut vertices = Vec::new();
+    interleave_vertices(&mut vertices, &[1., 2., 3., 4.], &[3], &[], &[]);
+    assert_eq!(vertices[2], layer_depth(3));
+    // 層を持っていないディスクは層 0
+    assert_eq!(vertices[VERTEX_FLOATS + 2], 0.);
+}
+
+#[test]
+fn vertices_follow_the_number_of_positions() {
+    let mut vertices = Vec::new();
+    // 記録の再生中は色・大きさの比が座標より少ないことがある
+    interleave_vertices(
+        &mut vertices,
+        &[1., 2., 3., 4.],
+        &[],
+        &[0., 0., 1., 1.],
+        &[],
+    );
+    assert_eq!(vertices.len(), 2 * VERTEX_FLOATS);
+    assert_eq!(
+        &vertices[VERTEX_FLOATS..],
+        &[3., 4., 0., 1., 0., 0., 1., 1.]
+    );
+    interleave_vertices(&mut vertices, &[], &[], &[0., 0., 1., 1.], &[1.]);
+    assert!(vertices.is_empty());
+}
+
+#[test]
+fn uniform_velocities_respect_the_speed_and_angle_ranges() {
+    let velocities = VelocityDistribution::parse("uniform", [2., 3.], [0., 0.5]).unwrap();
+    let disks = init_disks(
+        200,
+        400,
+        300,
+        5.,
+        SpawnPattern::Center,
+        velocities,
+        &mut *create_rng(Some(8)),
+    );
+    for disk in disks.iter() {
+        let speed = disk.cos.hypot(disk.sin);
+        let angle = disk.sin.atan2(disk.cos);
+        assert!((2. ..=3.).contains(&speed), "{}", speed);
+        assert!((0. ..=0.5).contains(&angle), "{}", angle);
+    }
+    assert!(VelocityDistribution::parse("uniform", [1., 2.], [1., 0.]).is_err());
+    assert!(VelocityDistribution::parse("gaussian", [1., 2.], [0., 1.]).is_err());
+}
+
+#[test]
+fn legacy_velocities_follow_the_original_formula() {
+    use rand::Rng;
+    let disks = init_disks(
+        5,
+        400,
+        300,
+        5.,
+        SpawnPattern::Center,
+        VelocityDistribution::Legacy,
+        &mut *create_rng(Some(2)),
+    );
+    let mut rng = create_rng(Some(2));
+    for (i, disk) in disks.iter().enumerate() {
+        let random: f64 = rng.gen_range(0., 1.);
+        let speed = 1. + 3. * random;
+        let angle = std::f64::consts::PI * 0.1 * i as f64 * random;
+        assert_eq!((disk.x, disk.y), (200., 150.));
+        assert!((disk.cos - speed * angle.cos()).abs() < 1e-12);
+        assert!((disk.sin - speed * angle.sin()).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn randomize_velocities_keeps_positions() {
+    let rng = &mut *create_rng(Some(5));
+    let disks = init_disks(20, 400, 300, 5., SpawnPattern::Uniform, [1., 2.], rng);
+    let mut sim = Simulation::with_disks(400., 300., 5., disks.clone());
+    sim.randomize_velocities([5., 6.], rng);
+    for (before, after) in disks.iter().zip(sim.disks()) {
+        assert_eq!((before.x, before.y), (after.x, after.y));
+        assert!((5. ..=6.).contains(&after.cos.hypot(after.sin)));
+    }
+}
+
+#[test]
+fn placement_without_overlap_keeps_disks_apart_and_off_the_walls() {
+    let mut rng = create_std_rng(Some(5));
+    let mut disks = init_disks(
+        300,
+        400,
+        300,
+        6.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut rng,
+    );
+    place_without_overlap(&mut disks, 400., 300., 6., &mut rng).unwrap();
+    for (i, a) in disks.iter().enumerate() {
+        assert!(a.x >= 6. && a.x <= 394. && a.y >= 6. && a.y <= 294.);
+        for b in &disks[i + 1..] {
+            assert!((a.x - b.x).hypot(a.y - b.y) >= 12.);
+        }
+    }
+}
+
+#[test]
+fn placement_without_overlap_is_seeded() {
+    let place = || {
+        let mut rng = create_std_rng(Some(8));
+        let mut disks = init_disks(
+            50,
+            200,
+            200,
+            5.,
+            SpawnPattern::Uniform,
+            DEFAULT_SPEED_RANGE,
+            &mut rng,
+        );
+        place_without_overlap(&mut disks, 200., 200., 5., &mut rng).unwrap();
+        disks.iter().map(|d| (d.x, d.y)).collect::<Vec<_>>()
+    };
+    assert_eq!(place(), place());
+}
+
+#[test]
+fn crowded_placement_falls_back_to_a_lattice() {
+    let capacity = max_non_overlapping(100., 100., 5.);
+    let mut rng = create_std_rng(Some(1));
+    let mut disks = init_disks(
+        capacity as u32,
+        100,
+        100,
+        5.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut rng,
+    );
+    place_without_overlap(&mut disks, 100., 100., 5., &mut rng).unwrap();
+    for (i, a) in disks.iter().enumerate() {
+        for b in &disks[i + 1..] {
+            assert!((a.x - b.x).hypot(a.y - b.y) >= 10.);
+        }
+    }
+}
+
+#[test]
+fn placement_without_overlap_stays_inside_padded_bounds() {
+    let mut rng = create_std_rng(Some(2));
+    let mut disks = init_disks(
+        30,
+        200,
+        200,
+        5.,
+        SpawnPattern::Uniform,
+        DEFAULT_SPEED_RANGE,
+        &mut rng,
+    );
+    let bounds = Bounds::new(200., 200.).inset(40.);
+    place_without_overlap_in(&mut disks, &bounds, None, 5., &mut rng).unwrap();
+    assert!(disks
+        .iter()
+        .all(|d| d.x >= 45. && d.x <= 155. && d.y >= 45. && d.y <= 155.));
+}
+
+#[test]
+fn too_many_disks_for_a_circle_is_an_error() {
+    let circle = Circle {
+        x: 50.,
+        y: 50.,
+        radius: 20.,
+    };
+    let mut rng = create_std_rng(Some(1));
+    let mut disks = init_disks(
+        20,
+        100,
+        100,
+        5.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut rng,
+    );
+    let error = place_without_overlap_in(
+        &mut disks,
+        &Bounds::new(100., 100.),
+        Some(&circle),
+        5.,
+        &mut rng,
+    )
+    .unwrap_err();
+    assert!(error.contains("circle of radius 20"), "{}", error);
+}
+
+#[test]
+fn too_many_disks_to_place_without_overlap_is_an_error() {
+    let capacity = max_non_overlapping(100., 100., 5.);
+    let mut rng = create_std_rng(Some(1));
+    let mut disks = init_disks(
+        capacity as u32 + 1,
+        100,
+        100,
+        5.,
+        SpawnPattern::Center,
+        DEFAULT_SPEED_RANGE,
+        &mut rng,
+    );
+    let error = place_without_overlap(&mut disks, 100., 100., 5., &mut rng).unwrap_err();
+    assert!(
+        error.contains(&format!("at most {}", capacity)),
+        "{}",
+        error
+    );
+    assert!(disks.iter().all(|d| (d.x, d.y) == (50., 50.)));
+}
+
+#[test]
+fn groups_pick_their_own_speeds_and_collision_flag() {
+    let groups = [
+        GroupSpec {
+            count: 20,
+            speed_range: Some([0.5, 1.]),
+            ..GroupSpec::default()
+        },
+        GroupSpec {
+            count: 30,
+            speed_range: Some([5., 6.]),
+            collides: Some(false),
+            ..GroupSpec::default()
+        },
+    ];
+    let disks = init_groups(
+        &groups,
+        400,
+        300,
+        4.,
+        SpawnPattern::Uniform,
+        DEFAULT_SPEED_RANGE,
+        false,
+        &mut *create_rng(Some(2)),
+    )
+    .unwrap();
+    for disk in &disks[..20] {
+        let speed = disk.cos.hypot(disk.sin);
+        assert!((0.5..=1. + 1e-9).contains(&speed), "{}", speed);
+        assert!(!disk.ghost);
+    }
+    for disk in &disks[20..] {
+        let speed = disk.cos.hypot(disk.sin);
+        assert!((5. ..=6. + 1e-9).contains(&speed), "{}", speed);
+        assert!(disk.ghost);
+    }
+}
+
+#[test]
+fn group_palettes_and_speed_ranges_are_validated() {
+    let with = |speed_range, palette: Option<&str>, color| GroupSpec {
+        count: 1,
+        speed_range,
+        palette: palette.map(String::from),
+        color,
+        ..GroupSpec::default()
+    };
+    assert!(GroupSpec::validate(&[with(Some([2., 1.]), None, None)]).is_err());
+    assert!(GroupSpec::validate(&[with(Some([-1., 1.]), None, None)]).is_err());
+    assert!(GroupSpec::validate(&[with(None, Some("rainbow"), None)]).is_err());
+    assert!(GroupSpec::validate(&[with(None, Some("warm"), Some([1., 0., 0.]))]).is_err());
+    assert!(GroupSpec::validate(&[with(Some([1., 2.]), Some("cool"), None)]).is_ok());
+}
+
+#[test]
+fn group_colors_come_from_its_color_or_palette() {
+    let mut rng = create_std_rng(Some(4));
+    let red = GroupSpec {
+        color: Some([1., 0., 0.]),
+        ..GroupSpec::default()
+    };
+    assert_eq!(red.colors(2, &mut rng), Some(vec![1., 0., 0., 1., 0., 0.]));
+    let mono = GroupSpec {
+        palette: Some(String::from("mono")),
+        ..GroupSpec::default()
+    };
+    let colors = mono.colors(5, &mut rng).unwrap();
+    assert!(colors
+        .chunks(3)
+        .all(|rgb| rgb[0] == rgb[1] && rgb[1] == rgb[2]));
+    assert_eq!(GroupSpec::default().colors(1, &mut rng), None);
+}
@@ -0,0 +1,29 @@
+use wasm::{LogThrottle, Subsystem, THROTTLE_MS};
+
+#[test]
+fn repeated_messages_are_held_back_for_the_throttle_interval() {
+    let mut throttle = LogThrottle::default();
+    let template = "gl error 0x{:04x} after drawing";
+    assert_eq!(throttle.check(Subsystem::Render, template, 0.), Some(0));
+    assert_eq!(throttle.check(Subsystem::Render, template, 16.), None);
+    assert_eq!(throttle.check(Subsystem::Render, template, 32.), None);
+    // 間隔が過ぎたら、抑えていた回数と一緒に出す
+    assert_eq!(
+        throttle.check(Subsystem::Render, template, THROTTLE_MS),
+        Some(2)
+    );
+    assert_eq!(
+        throttle.check(Subsystem::Render, template, THROTTLE_MS + 16.),
+        None
+    );
+}
+
+#[test]
+fn different_messages_are_throttled_separately() {
+    let mut throttle = LogThrottle::default();
+    assert_eq!(throttle.check(Subsystem::Physics, "a", 0.), Some(0));
+    assert_eq!(throttle.check(Subsystem::Physics, "b", 1.), Some(0));
+    assert_eq!(throttle.check(Subsystem::Physics, "a", 2.), None);
+    // 書式が同じでも区分が違えば別のメッセージ
+    assert_eq!(throttle.check(Subsystem::Render, "a", 3.), Some(0));
+}
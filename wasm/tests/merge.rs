@@ -0,0 +1,137 @@
+use wasm::merge::{apply_merge_colors, merge_pair, CollisionResponse, Merge};
+use wasm::{Disk, Simulation};
+
+fn disk(x: f64, y: f64, vx: f64, vy: f64, size: f64, mass: f64) -> Disk {
+    Disk {
+        size: Some(size),
+        mass,
+        ..Disk::new(x, y, vx, vy)
+    }
+}
+
+// 衝突あり・合体モードのシミュレーション
+fn merging(disks: &[Disk]) -> Simulation {
+    let mut sim = Simulation::new(400., 400., 5.);
+    sim.set_collision(true);
+    sim.set_collision_response(CollisionResponse::Merge);
+    for &disk in disks {
+        sim.push_disk(disk);
+    }
+    sim
+}
+
+#[test]
+fn response_is_parsed() {
+    assert_eq!(
+        CollisionResponse::parse("bounce"),
+        Ok(CollisionResponse::Bounce)
+    );
+    assert_eq!(
+        CollisionResponse::parse("merge"),
+        Ok(CollisionResponse::Merge)
+    );
+    assert!(CollisionResponse::parse("stick").is_err());
+}
+
+#[test]
+fn merging_conserves_area_momentum_and_centroid() {
+    let a = disk(10., 0., 2., 0., 3., 1.);
+    let b = disk(20., 0., -1., 1., 4., 3.);
+    let merged = merge_pair(&a, &b, 5.);
+    assert_eq!(merged.size, Some(5.));
+    assert_eq!(merged.mass, 4.);
+    assert_eq!((merged.x, merged.y), (17.5, 0.));
+    // 運動量 (2, 0) + (-3, 3) = (-1, 3)
+    assert_eq!((merged.cos * 4., merged.sin * 4.), (-1., 3.));
+}
+
+#[test]
+fn merge_colors_blend_and_drop_the_removed_color() {
+    let mut colors = vec![1., 0., 0., 0., 0., 0., 0., 0., 1.];
+    apply_merge_colors(
+        &mut colors,
+        &Merge {
+            survivor: 0,
+            removed: 2,
+            weight: 0.25,
+        },
+    );
+    assert_eq!(colors, vec![0.75, 0., 0.25, 0., 0., 0.]);
+}
+
+#[test]
+fn colliding_disks_merge_into_one() {
+    let mut sim = merging(&[
+        disk(100., 100., 0., 0., 5., 1.),
+        disk(108., 100., 0., 0., 5., 1.),
+        disk(300., 300., 0., 0., 5., 1.),
+    ]);
+    sim.step();
+    assert_eq!(sim.disk_count(), 2);
+    assert_eq!(sim.last_collisions(), 1);
+    assert_eq!(
+        sim.merges(),
+        &[Merge {
+            survivor: 0,
+            removed: 1,
+            weight: 0.5,
+        }]
+    );
+    assert_eq!(sim.disks()[0].size, Some(50f64.sqrt()));
+    // 離れていたディスクは詰まって 1 番になる
+    assert_eq!((sim.disks()[1].x, sim.disks()[1].y), (300., 300.));
+}
+
+#[test]
+fn chain_merges_resolve_within_one_step() {
+    // a と b が先に合体し、大きくなって重心へ寄ったディスクが c に重なる
+    let mut sim = merging(&[
+        disk(100., 100., 0., 0., 5., 1.),
+        disk(108., 100., 0., 0., 5., 1.),
+        disk(116., 100., 0., 0., 5., 1.),
+        disk(300., 300., 0., 0., 5., 1.),
+    ]);
+    sim.step();
+    assert_eq!(sim.disk_count(), 2);
+    assert_eq!(sim.merges().len(), 2);
+    assert!((sim.disks()[0].size.unwrap() - 75f64.sqrt()).abs() < 1e-9);
+    assert_eq!(sim.disks()[0].mass, 3.);
+    // 記録された番号は順に適用すると常に範囲内
+    let mut len = 4;
+    for merge in sim.merges() {
+        assert!(merge.survivor < merge.removed && merge.removed < len);
+        len -= 1;
+    }
+}
+
+#[test]
+fn min_disks_and_max_radius_stop_merging() {
+    let pair = [
+        disk(100., 100., 0., 0., 5., 1.),
+        disk(108., 100., 0., 0., 5., 1.),
+    ];
+    let mut floor = merging(&pair);
+    floor.set_merge_limits(2, None).unwrap();
+    floor.step();
+    assert_eq!(floor.disk_count(), 2);
+
+    let mut capped = merging(&pair);
+    capped.set_merge_limits(1, Some(7.)).unwrap();
+    capped.step();
+    assert_eq!(capped.disk_count(), 2);
+
+    assert!(capped.set_merge_limits(0, None).is_err());
+    assert!(capped.set_merge_limits(1, Some(-1.)).is_err());
+}
+
+#[test]
+fn ghost_disks_do_not_merge() {
+    let ghost = Disk {
+        ghost: true,
+        ..disk(108., 100., 0., 0., 5., 1.)
+    };
+    let mut sim = merging(&[disk(100., 100., 0., 0., 5., 1.), ghost]);
+    sim.step();
+    assert_eq!(sim.disk_count(), 2);
+    assert!(sim.merges().is_empty());
+}
@@ -0,0 +1,206 @@
+use wasm::obstacle::{bounce_off_obstacles, eject_from_obstacles, Obstacle, ObstacleShape};
+use wasm::{Disk, Restitution, Simulation};
+
+const RECT: ObstacleShape = ObstacleShape::Rect {
+    x: 100.,
+    y: 100.,
+    width: 50.,
+    height: 20.,
+};
+
+fn obstacles(shapes: &[ObstacleShape]) -> Vec<Obstacle> {
+    shapes
+        .iter()
+        .enumerate()
+        .map(|(id, &shape)| Obstacle {
+            id: id as u32,
+            shape,
+        })
+        .collect()
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+#[test]
+fn invalid_shapes_are_rejected() {
+    let mut sim = Simulation::new(400., 300., 5.);
+    for shape in [
+        ObstacleShape::Rect {
+            x: 0.,
+            y: 0.,
+            width: 0.,
+            height: 10.,
+        },
+        ObstacleShape::Rect {
+            x: f64::NAN,
+            y: 0.,
+            width: 10.,
+            height: 10.,
+        },
+        ObstacleShape::Circle {
+            x: 0.,
+            y: 0.,
+            radius: -1.,
+        },
+    ] {
+        assert!(sim.add_obstacle(shape).is_err(), "{:?}", shape);
+    }
+    assert!(sim.obstacles().is_empty());
+}
+
+#[test]
+fn rect_face_hit_reflects_the_normal_component() {
+    // 上の辺に斜めに食い込んだディスク
+    let mut disks = vec![Disk::new(120., 97., 2., 3.)];
+    bounce_off_obstacles(
+        &mut disks,
+        &obstacles(&[RECT]),
+        5.,
+        Restitution::elastic(),
+        |_, _| {},
+    );
+    assert_eq!((disks[0].x, disks[0].y), (120., 95.));
+    assert_eq!((disks[0].cos, disks[0].sin), (2., -3.));
+}
+
+#[test]
+fn rect_corner_hit_uses_the_corner_normal() {
+    // 左上の角へ対角線に沿って近づくディスクは、そのまま逆向きに返る
+    let offset = 4. / 2f64.sqrt();
+    let mut disks = vec![Disk::new(100. - offset, 100. - offset, 1., 1.)];
+    bounce_off_obstacles(
+        &mut disks,
+        &obstacles(&[RECT]),
+        5.,
+        Restitution::elastic(),
+        |_, _| {},
+    );
+    let disk = &disks[0];
+    assert!(close((100. - disk.x).hypot(100. - disk.y), 5.));
+    assert!(close(disk.x, disk.y));
+    assert!(close(disk.cos, -1.) && close(disk.sin, -1.));
+}
+
+#[test]
+fn center_inside_a_rect_leaves_through_the_nearest_face() {
+    let mut disks = vec![Disk::new(145., 110., 0., 0.)];
+    bounce_off_obstacles(
+        &mut disks,
+        &obstacles(&[RECT]),
+        5.,
+        Restitution::elastic(),
+        |_, _| {},
+    );
+    assert_eq!((disks[0].x, disks[0].y), (155., 110.));
+}
+
+#[test]
+fn circle_hit_reflects_along_the_radial_normal() {
+    let circle = ObstacleShape::Circle {
+        x: 200.,
+        y: 200.,
+        radius: 20.,
+    };
+    let mut disks = vec![Disk::new(176., 200., 3., 1.)];
+    let mut hits = Vec::new();
+    bounce_off_obstacles(
+        &mut disks,
+        &obstacles(&[circle]),
+        5.,
+        Restitution {
+            coefficient: 0.5,
+            rest_speed: 0.,
+        },
+        |index, _| hits.push(index),
+    );
+    assert_eq!((disks[0].x, disks[0].y), (175., 200.));
+    assert_eq!((disks[0].cos, disks[0].sin), (-1.5, 1.));
+    assert_eq!(hits, vec![0]);
+}
+
+#[test]
+fn disks_moving_away_are_not_reflected() {
+    let mut disks = vec![Disk::new(120., 97., 0., -2.)];
+    bounce_off_obstacles(
+        &mut disks,
+        &obstacles(&[RECT]),
+        5.,
+        Restitution::elastic(),
+        |_, _| {},
+    );
+    assert_eq!((disks[0].cos, disks[0].sin), (0., -2.));
+}
+
+#[test]
+fn ejection_clears_overlapping_obstacles() {
+    let neighbour = ObstacleShape::Rect {
+        x: 150.,
+        y: 100.,
+        width: 10.,
+        height: 20.,
+    };
+    let list = obstacles(&[RECT, neighbour]);
+    let mut disk = Disk::new(148., 110., 1., 0.);
+    eject_from_obstacles(&mut disk, &list, 5.);
+    assert!(list
+        .iter()
+        .all(|o| o.shape.penetration(disk.x, disk.y, 5.).is_none()));
+    // 右へ出ると隣に入るので、次に近い上の辺の外に出る
+    assert_eq!((disk.x, disk.y), (148., 95.));
+    assert_eq!((disk.cos, disk.sin), (1., 0.));
+}
+
+#[test]
+fn disks_added_inside_an_obstacle_are_ejected() {
+    let mut sim = Simulation::new(400., 300., 5.);
+    sim.add_obstacle(RECT).unwrap();
+    sim.add_disk(110., 105., 0., 0.);
+    let disk = &sim.disks()[0];
+    assert_eq!((disk.x, disk.y), (110., 95.));
+}
+
+#[test]
+fn ejected_disks_stay_inside_the_walls() {
+    // 障害物の外で一番近い位置は右の壁の外になる
+    let mut sim = Simulation::new(100., 100., 5.);
+    sim.add_obstacle(ObstacleShape::Rect {
+        x: -10.,
+        y: -10.,
+        width: 105.,
+        height: 120.,
+    })
+    .unwrap();
+    sim.add_disk(90., 50., 0., 0.);
+    let disk = &sim.disks()[0];
+    assert!(disk.x + 5. <= 100., "{}", disk.x);
+}
+
+#[test]
+fn step_bounces_off_obstacles_and_ids_are_removable() {
+    let mut sim = Simulation::new(400., 300., 5.);
+    let rect = sim.add_obstacle(RECT).unwrap();
+    let circle = sim
+        .add_obstacle(ObstacleShape::Circle {
+            x: 300.,
+            y: 200.,
+            radius: 10.,
+        })
+        .unwrap();
+    assert_eq!((rect, circle), (0, 1));
+    sim.add_disk(120., 90., 0., 3.);
+    for _ in 0..10 {
+        sim.step();
+        assert!(sim.disks()[0].y <= 95. + 1e-9);
+    }
+    assert!(sim.disks()[0].sin < 0.);
+
+    assert!(sim.remove_obstacle(rect));
+    assert!(!sim.remove_obstacle(rect));
+    assert_eq!(sim.obstacles().len(), 1);
+    sim.clear_obstacles();
+    assert!(sim.obstacles().is_empty());
+    // 番号は使い回さない
+    assert_eq!(sim.add_obstacle(RECT).unwrap(), 2);
+}
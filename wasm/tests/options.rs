@@ -0,0 +1,346 @@
+use wasm::{Options, ViewOptions};
+
+fn parse(json: &str) -> Result<Options, String> {
+    serde_json::from_str(json).map_err(|e| e.to_string())
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    let error = parse(r#"{"canvas_id": "c", "diskNum": 10}"#).unwrap_err();
+    assert!(error.contains("diskNum"), "{}", error);
+}
+
+#[test]
+fn defaults_are_valid() {
+    let options = parse(r#"{"canvas_id": "c"}"#).unwrap();
+    assert!(options.validate().is_ok());
+    assert_eq!(options.speed_range(), wasm::DEFAULT_SPEED_RANGE);
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    for json in [
+        r#"{"width": 0}"#,
+        r#"{"disk_size": -1}"#,
+        r#"{"opacity": 1.5}"#,
+        r#"{"restitution": -0.1}"#,
+        r#"{"wall_restitution": 2}"#,
+        r#"{"rest_speed": -1}"#,
+        r#"{"drag": 1.5}"#,
+        r#"{"jitter": -0.1}"#,
+        r#"{"adaptive": true, "emitter": true}"#,
+        r#"{"adaptive_target_fps": 0}"#,
+        r#"{"min_disks": 0}"#,
+        r#"{"min_disks": 20, "max_disks": 10}"#,
+        r#"{"on_collision": "stick"}"#,
+        r#"{"max_radius": 0}"#,
+        r#"{"pointer_radius": 0}"#,
+        r#"{"dpr": 0}"#,
+        r#"{"min_velocity": 5}"#,
+        r##"{"background": "#12345"}"##,
+        r#"{"background": [1, 1, 1, 2]}"#,
+        r#"{"color_mode": "rainbow"}"#,
+        r#"{"color_mode": "static"}"#,
+        r#"{"render_mode": "lines"}"#,
+        r#"{"blend_mode": "multiply"}"#,
+        r#"{"shape": "star"}"#,
+        r#"{"debug": "verbose"}"#,
+        r#"{"palette": "rainbow"}"#,
+        r#"{"velocity_scale": 0}"#,
+        r##"{"palette_colors": ["#12345"]}"##,
+        r#"{"palette_colors": []}"#,
+        r#"{"shape": "ring", "ring_thickness": 0}"#,
+        r#"{"ring_thickness": 1.5}"#,
+        r#"{"speed_range": [3, 1]}"#,
+        r#"{"speed_range": [1, 2], "min_speed": 1}"#,
+        r#"{"groups": [{"count": 1, "mass": 0}]}"#,
+        r#"{"emitter": true, "emit_rate": 0}"#,
+        r#"{"lifetime": -1}"#,
+        r#"{"max_disks": 0}"#,
+        r#"{"arena": "hexagon"}"#,
+        r#"{"behavior": "flock"}"#,
+        r#"{"boids": {"perception": 0}}"#,
+        r#"{"arena": "circle", "arena_radius": 0}"#,
+        r#"{"arena_radius": 10}"#,
+        r#"{"context_attributes": {"powerPreference": "battery"}}"#,
+    ] {
+        let options = parse(json).unwrap();
+        assert!(options.validate().is_err(), "{} should be rejected", json);
+    }
+}
+
+#[test]
+fn min_and_max_speed_fill_in_the_speed_range() {
+    let options = parse(r#"{"max_speed": 9}"#).unwrap();
+    assert!(options.validate().is_ok());
+    assert_eq!(options.speed_range(), [wasm::DEFAULT_SPEED_RANGE[0], 9.]);
+}
+
+#[test]
+fn renderer_is_an_alias_of_render_mode() {
+    let options = parse(r#"{"renderer": "instanced"}"#).unwrap();
+    assert!(options.validate().is_ok());
+    assert_eq!(options.render_mode.as_deref(), Some("instanced"));
+}
+
+#[test]
+fn boids_fills_in_omitted_weights() {
+    let options = parse(r#"{"behavior": "boids", "boids": {"cohesion": 0.5}}"#).unwrap();
+    assert!(options.validate().is_ok());
+    let boids = options.boids.unwrap();
+    assert_eq!(boids.cohesion, 0.5);
+    assert_eq!(
+        boids.max_speed,
+        wasm::boids::BoidsParams::default().max_speed
+    );
+    assert!(parse(r#"{"boids": {"speed": 1}}"#).is_err());
+}
+
+#[test]
+fn arena_defaults_to_rect_and_takes_an_optional_circle() {
+    assert_eq!(parse("{}").unwrap().arena(), Ok(wasm::Arena::Rect));
+    let options =
+        parse(r#"{"arena": "circle", "arena_center": [10, 20], "arena_radius": 5}"#).unwrap();
+    assert!(options.validate().is_ok());
+    assert_eq!(
+        options.arena(),
+        Ok(wasm::Arena::Circle {
+            center: Some([10., 20.]),
+            radius: Some(5.),
+        })
+    );
+}
+
+#[test]
+fn placement_is_an_alias_of_spawn() {
+    let options = parse(r#"{"placement": "orbit"}"#).unwrap();
+    assert!(options.validate().is_ok());
+    assert_eq!(options.spawn.as_deref(), Some("orbit"));
+    assert!(parse(r#"{"placement": "spiral"}"#)
+        .unwrap()
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn velocity_options_build_the_distribution() {
+    let options =
+        parse(r#"{"speed_min": 2, "speed_max": 3, "angle_min": 0, "angle_max": 1}"#).unwrap();
+    assert!(options.validate().is_ok());
+    assert_eq!(
+        options.velocities(),
+        Ok(wasm::VelocityDistribution::Uniform {
+            speed_range: [2., 3.],
+            angle_range: [0., 1.],
+        })
+    );
+    let legacy = parse(r#"{"velocity_distribution": "legacy"}"#).unwrap();
+    assert_eq!(legacy.velocities(), Ok(wasm::VelocityDistribution::Legacy));
+    assert!(parse(r#"{"min_angle": 2, "max_angle": 1}"#)
+        .unwrap()
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn context_attributes_keep_unknown_keys_aside() {
+    let options = parse(
+        r#"{"context_attributes": {"antialias": false, "powerPreference": "low-power", "stencil": true}}"#,
+    )
+    .unwrap();
+    assert!(options.validate().is_ok());
+    let attributes = options.context_attributes.unwrap();
+    assert_eq!(attributes.antialias, Some(false));
+    assert_eq!(attributes.power_preference.as_deref(), Some("low-power"));
+    assert_eq!(attributes.alpha, None);
+    // 知らないキーはエラーにせず、警告用に残す
+    assert_eq!(
+        attributes.unknown.keys().collect::<Vec<_>>(),
+        vec!["stencil"]
+    );
+}
+
+#[test]
+fn shape_defaults_to_circle_and_ring_keeps_its_thickness() {
+    assert_eq!(wasm::Shape::parse("circle", None), Ok(wasm::Shape::Circle));
+    let ring = wasm::Shape::parse("ring", Some(0.25)).unwrap();
+    assert_eq!(ring, wasm::Shape::Ring { thickness: 0.25 });
+    assert_eq!(ring.ring_inner(), 0.75);
+    assert!(wasm::Shape::parse("glow", None).unwrap().needs_blend());
+    let error = wasm::Shape::parse("star", None).unwrap_err();
+    assert!(error.contains("\"ring\""), "{}", error);
+}
+
+#[test]
+fn smooth_edges_is_an_alias_of_antialias() {
+    let options = parse(r#"{"smooth_edges": true}"#).unwrap();
+    assert!(options.validate().is_ok());
+    assert_eq!(options.antialias, Some(true));
+}
+
+#[test]
+fn merged_replaces_only_the_given_keys() {
+    let base =
+        parse(r#"{"canvas_id": "c", "disk_num": 10, "disk_size": 8, "gravity": 0.5}"#).unwrap();
+    let merged = base
+        .merged(&serde_json::json!({"disk_num": 20, "placement": "ring", "gravity": null}))
+        .unwrap();
+    assert_eq!(merged.disk_num, Some(20));
+    assert_eq!(merged.disk_size, Some(8.));
+    assert_eq!(merged.spawn.as_deref(), Some("ring"));
+    assert_eq!(merged.gravity, None);
+    assert_eq!(merged.canvas_id, "c");
+}
+
+#[test]
+fn merged_rejects_unknown_keys_and_bad_values() {
+    let base = parse(r#"{"canvas_id": "c"}"#).unwrap();
+    for overrides in [
+        serde_json::json!({"diskNum": 10}),
+        serde_json::json!({"disk_size": -1}),
+        serde_json::json!({"disk_num": "many"}),
+        serde_json::json!([1, 2]),
+    ] {
+        assert!(base.merged(&overrides).is_err(), "{}", overrides);
+    }
+}
+
+#[test]
+fn check_reset_rejects_context_options() {
+    let base = parse(r#"{"canvas_id": "c", "render_mode": "circles"}"#).unwrap();
+    let same = base.merged(&serde_json::json!({"disk_num": 5})).unwrap();
+    assert!(base.check_reset(&same).is_ok());
+    for overrides in [
+        serde_json::json!({"canvas_id": "other"}),
+        serde_json::json!({"renderer": "points"}),
+        serde_json::json!({"fragment_shader": "void main() {}"}),
+        serde_json::json!({"touch_input": true}),
+    ] {
+        let next = base.merged(&overrides).unwrap();
+        assert!(base.check_reset(&next).is_err(), "{}", overrides);
+    }
+}
+
+#[test]
+fn adaptive_quality_defaults_to_the_initial_count() {
+    assert!(parse(r#"{}"#)
+        .unwrap()
+        .adaptive_quality(100)
+        .unwrap()
+        .is_none());
+    let options = parse(r#"{"adaptive": true, "target_fps": 30}"#).unwrap();
+    assert!(options.adaptive_quality(100).unwrap().is_some());
+    let options = parse(r#"{"adaptive": true, "min_disks": 200}"#).unwrap();
+    assert!(options.adaptive_quality(100).unwrap().is_some());
+}
+
+#[test]
+fn check_update_lists_the_immutable_keys() {
+    let base = parse(r#"{"canvas_id": "c"}"#).unwrap();
+    let next = base
+        .merged(&serde_json::json!({"renderer": "circles", "dpr": 2, "gravity": 1}))
+        .unwrap();
+    let message = base.check_update(&next).unwrap_err();
+    assert!(
+        message.starts_with("dpr, render_mode cannot be changed"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn check_update_sends_reset_only_keys_to_reset_with() {
+    let base = parse(r#"{"canvas_id": "c"}"#).unwrap();
+    for overrides in [
+        serde_json::json!({"seed": 1}),
+        serde_json::json!({"placement": "ring"}),
+        serde_json::json!({"emitter": true}),
+    ] {
+        let next = base.merged(&overrides).unwrap();
+        let message = base.check_update(&next).unwrap_err();
+        assert!(message.contains("reset_with"), "{}", message);
+    }
+}
+
+#[test]
+fn check_update_ignores_values_equal_to_the_defaults() {
+    let base = parse(r#"{"canvas_id": "c", "gravity": 0.5}"#).unwrap();
+    let next = base
+        .merged(
+            &serde_json::json!({"disk_size": 32, "renderer": "points", "gravity": 2, "drag": 0.1}),
+        )
+        .unwrap();
+    assert_eq!(base.check_update(&next).unwrap(), vec!["drag", "gravity"]);
+    assert!(base.check_update(&base).unwrap().is_empty());
+}
+
+#[test]
+fn subset_keeps_only_the_given_keys() {
+    let options = parse(r#"{"canvas_id": "c", "disk_num": 10, "gravity": 0.5}"#).unwrap();
+    let keys = vec![String::from("gravity"), String::from("drag")];
+    assert_eq!(
+        options.subset(&keys).unwrap(),
+        serde_json::json!({"gravity": 0.5, "drag": null})
+    );
+}
+
+#[test]
+fn with_defaults_is_valid_and_keeps_given_values() {
+    let options = parse(r#"{"canvas_id": "c", "disk_num": 10}"#)
+        .unwrap()
+        .with_defaults();
+    assert!(options.validate().is_ok());
+    assert_eq!(options.disk_num, Some(10));
+    assert_eq!(options.disk_size, Some(32.));
+    assert_eq!(options.trail, None);
+    assert_eq!(options.max_speed, None);
+}
+
+#[test]
+fn collision_dom_events_are_opt_in() {
+    let options = parse("{}").unwrap().with_defaults();
+    assert_eq!(options.emit_events, Some(false));
+    let options = parse(r#"{"emit_events": true}"#).unwrap().with_defaults();
+    assert_eq!(options.emit_events, Some(true));
+}
+
+#[test]
+fn view_options_need_a_center_to_zoom() {
+    let parse_view = |json: &str| serde_json::from_str::<ViewOptions>(json).unwrap();
+    assert_eq!(parse_view("{}").view().unwrap(), None);
+    assert!(parse_view(r#"{"center": [10, 20], "scale": 2}"#)
+        .view()
+        .unwrap()
+        .is_some());
+    assert!(parse_view(r#"{"scale": 2}"#).validate().is_err());
+    assert!(parse_view(r#"{"width": 0}"#).validate().is_err());
+    assert!(parse_view(r##"{"background": "#00000080", "dpr": 2}"##)
+        .validate()
+        .is_ok());
+    assert!(serde_json::from_str::<ViewOptions>(r#"{"zoom": 2}"#).is_err());
+}
+
+#[test]
+fn bounce_flash_defaults_to_white_and_zero_turns_it_off() {
+    assert!(parse(r#"{"bounce_flash": 0}"#)
+        .unwrap()
+        .bounce_flash()
+        .unwrap()
+        .is_none());
+    let flash = parse(r#"{"bounce_flash": 0.25}"#)
+        .unwrap()
+        .bounce_flash()
+        .unwrap()
+        .unwrap();
+    assert_eq!(flash.duration(), 0.25);
+    let options = parse(r##"{"bounce_flash": 0.25, "flash_color": "#ff0000"}"##).unwrap();
+    let mut flash = options.bounce_flash().unwrap().unwrap();
+    flash.hit(0);
+    let mut colors = [0., 0., 1., 1.];
+    flash.apply(&mut colors);
+    assert_eq!(colors, [1., 0., 0., 1.]);
+    assert!(parse(r#"{"bounce_flash": -1}"#)
+        .unwrap()
+        .validate()
+        .is_err());
+}
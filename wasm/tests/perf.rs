@@ -0,0 +1,19 @@
+use wasm::perf::{perf_names, PerfPhase};
+
+#[test]
+fn perf_names_are_prefixed_with_the_canvas_id() {
+    let (start, end, measure) = perf_names("canvas", PerfPhase::BufferUpload);
+    assert_eq!(measure, "canvas:buffer-upload");
+    assert_eq!(start, "canvas:buffer-upload:start");
+    assert_eq!(end, "canvas:buffer-upload:end");
+    assert_eq!(perf_names("", PerfPhase::Physics).2, "screen:physics");
+}
+
+#[test]
+fn every_phase_has_a_distinct_name() {
+    let names: Vec<_> = PerfPhase::ALL.iter().map(|phase| phase.name()).collect();
+    assert_eq!(names, ["physics", "buffer-upload", "draw-call"]);
+    for (index, phase) in PerfPhase::ALL.iter().enumerate() {
+        assert_eq!(*phase as usize, index);
+    }
+}
@@ -0,0 +1,208 @@
+use wasm::recording::{
+    record_or_stop, Input, InputRecorder, InputRecording, InputReplay, Playback, Recorder,
+    Recording, TimedInput,
+};
+use wasm::state::SavedState;
+use wasm::Simulation;
+
+#[test]
+fn recorder_stops_at_max_frames() {
+    let mut recorder = Recorder::new(1, 2, 1024).unwrap();
+    for i in 0..5 {
+        recorder.record(&[i as f32, 0.]).unwrap();
+    }
+    assert!(recorder.is_full());
+    assert_eq!(
+        recorder.finish(),
+        Recording {
+            disk_count: 1,
+            frames: vec![vec![0., 0.], vec![1., 0.]],
+        }
+    );
+}
+
+#[test]
+fn recorder_rejects_runs_over_the_byte_budget() {
+    // 100k disks * 2 * 4 bytes * 1000 frames = 800MB
+    assert!(Recorder::new(100_000, 1000, 64 * 1024 * 1024).is_err());
+    assert!(Recorder::new(10, 100, 8000).is_ok());
+    assert!(Recorder::new(10, 101, 8000).is_err());
+}
+
+#[test]
+fn recorder_rejects_frames_with_a_different_disk_count() {
+    let mut recorder = Recorder::new(2, 10, 1024).unwrap();
+    assert!(recorder.record(&[0., 0.]).is_err());
+}
+
+#[test]
+fn failed_frame_stops_the_recorder_once_and_keeps_the_partial_recording() {
+    let mut recorder = Some(Recorder::new(1, 10, 1024).unwrap());
+    let mut stopped = None;
+    record_or_stop(&mut recorder, &mut stopped, &[1., 2.]).unwrap();
+    let errors = (0..2)
+        .filter(|_| record_or_stop(&mut recorder, &mut stopped, &[1., 2., 3., 4.]).is_err())
+        .count();
+    assert_eq!(errors, 1);
+    assert!(recorder.is_none());
+    assert_eq!(
+        stopped,
+        Some(Recording {
+            disk_count: 1,
+            frames: vec![vec![1., 2.]],
+        })
+    );
+}
+
+fn recording() -> Recording {
+    Recording {
+        disk_count: 1,
+        frames: vec![vec![1., 1.], vec![2., 2.]],
+    }
+}
+
+#[test]
+fn playback_loops_or_holds_last_frame() {
+    let mut looping = Playback::new(recording(), true).unwrap();
+    let frames: Vec<f32> = (0..5).map(|_| looping.next_frame()[0]).collect();
+    assert_eq!(frames, vec![1., 2., 1., 2., 1.]);
+    assert!(!looping.finished());
+
+    let mut once = Playback::new(recording(), false).unwrap();
+    let frames: Vec<f32> = (0..4).map(|_| once.next_frame()[0]).collect();
+    assert_eq!(frames, vec![1., 2., 2., 2.]);
+    assert!(once.finished());
+}
+
+#[test]
+fn playback_rejects_malformed_recordings() {
+    let empty = Recording {
+        disk_count: 1,
+        frames: vec![],
+    };
+    assert!(Playback::new(empty, false).is_err());
+    let ragged = Recording {
+        disk_count: 1,
+        frames: vec![vec![1., 1.], vec![2.]],
+    };
+    assert!(Playback::new(ragged, false).is_err());
+}
+
+fn input_recording() -> InputRecording {
+    let mut sim = Simulation::new(200., 100., 4.);
+    sim.add_disk(10., 20., 1., -1.);
+    let mut recorder = InputRecorder::new(42, SavedState::new(&sim, &[1., 0., 0.]));
+    recorder.push(
+        0,
+        Input::Spawn {
+            x: 5.,
+            y: 6.,
+            speed: None,
+        },
+    );
+    recorder.step(0, 16.);
+    recorder.step(1, 16.);
+    recorder.push(2, Input::ClearPointer);
+    recorder.step(2, 8.);
+    recorder.finish()
+}
+
+#[test]
+fn input_recorder_records_dt_only_when_it_changes() {
+    let recording = input_recording();
+    assert_eq!(recording.seed, 42);
+    assert_eq!(recording.ticks, 3);
+    assert_eq!(
+        recording.inputs,
+        vec![
+            TimedInput {
+                tick: 0,
+                input: Input::Spawn {
+                    x: 5.,
+                    y: 6.,
+                    speed: None
+                }
+            },
+            TimedInput {
+                tick: 0,
+                input: Input::Dt { dt_ms: 16. }
+            },
+            TimedInput {
+                tick: 2,
+                input: Input::ClearPointer
+            },
+            TimedInput {
+                tick: 2,
+                input: Input::Dt { dt_ms: 8. }
+            },
+        ]
+    );
+}
+
+#[test]
+fn input_recording_round_trips_through_json() {
+    let recording = input_recording();
+    let json = serde_json::to_string(&recording).unwrap();
+    assert!(json.contains(r#""type":"clear_pointer""#));
+    let parsed: InputRecording = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, recording);
+}
+
+#[test]
+fn input_replay_yields_inputs_at_their_tick() {
+    let mut replay = InputReplay::new(input_recording()).unwrap();
+    assert_eq!(
+        replay.next_input(0),
+        Some(Input::Spawn {
+            x: 5.,
+            y: 6.,
+            speed: None
+        })
+    );
+    assert_eq!(replay.next_input(0), None);
+    assert_eq!(replay.dt_ms(), 16.);
+    assert_eq!(replay.next_input(1), None);
+    assert_eq!(replay.next_input(2), Some(Input::ClearPointer));
+    assert_eq!(replay.next_input(2), None);
+    assert_eq!(replay.dt_ms(), 8.);
+    assert!(!replay.finished(2));
+    assert!(replay.finished(3));
+}
+
+#[test]
+fn input_replay_rejects_malformed_recordings() {
+    let mut recording = input_recording();
+    recording.inputs.swap(0, 2);
+    assert!(InputReplay::new(recording).is_err());
+
+    let mut recording = input_recording();
+    recording.ticks = 1;
+    assert!(InputReplay::new(recording).is_err());
+
+    let mut recording = input_recording();
+    recording.version += 1;
+    assert!(InputReplay::new(recording).is_err());
+}
+
+#[test]
+fn impulse_inputs_round_trip_through_json() {
+    let input = Input::Impulse { dx: -1., dy: 0.5 };
+    let json = serde_json::to_string(&input).unwrap();
+    assert_eq!(json, r#"{"type":"impulse","dx":-1.0,"dy":0.5}"#);
+    assert_eq!(serde_json::from_str::<Input>(&json).unwrap(), input);
+}
+
+#[test]
+fn drag_inputs_round_trip_through_json() {
+    let inputs = vec![
+        Input::Grab { x: 10., y: 20. },
+        Input::Drag { x: 12., y: 20. },
+        Input::Release,
+    ];
+    let json = serde_json::to_string(&inputs).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"type":"grab","x":10.0,"y":20.0},{"type":"drag","x":12.0,"y":20.0},{"type":"release"}]"#
+    );
+    assert_eq!(serde_json::from_str::<Vec<Input>>(&json).unwrap(), inputs);
+}
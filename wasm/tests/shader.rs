@@ -0,0 +1,19 @@
+use wasm::shader_error_line;
+
+const SOURCE: &str = "precision mediump float;\nvarying vec3 v_color;\nvoid main() {\n   gl_FragColor = vec4(v_colour, 1.0);\n}\n";
+
+#[test]
+fn finds_the_offending_source_line() {
+    let log = "ERROR: 0:4: 'v_colour' : undeclared identifier\nERROR: 0:4: 'constructor' : not enough data provided for construction\n";
+    assert_eq!(
+        shader_error_line(log, SOURCE),
+        Some((4, "gl_FragColor = vec4(v_colour, 1.0);"))
+    );
+}
+
+#[test]
+fn ignores_logs_without_a_line_number() {
+    assert_eq!(shader_error_line("", SOURCE), None);
+    assert_eq!(shader_error_line("WARNING: 0:2: unused", SOURCE), None);
+    assert_eq!(shader_error_line("ERROR: 0:99: out of range", SOURCE), None);
+}
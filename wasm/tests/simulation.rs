@@ -0,0 +1,1049 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use wasm::simulation::energy_violated;
+use wasm::{
+    create_rng, gaussian, init_disks, motion_substeps, sweep_disk, time_scale_substeps, Arena,
+    Boundary, Bounds, Disk, Simulation, SpawnPattern, FRAME_MS, MAX_DT_MS, MAX_SUBSTEPS,
+};
+
+fn single(x: f64, y: f64, vx: f64, vy: f64) -> Simulation {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(x, y, vx, vy);
+    sim
+}
+
+fn state(sim: &Simulation) -> (f64, f64, f64, f64) {
+    let d = &sim.disks()[0];
+    (d.x, d.y, d.cos, d.sin)
+}
+
+#[test]
+fn bounces_off_left_wall() {
+    let mut sim = single(7., 40., -4., 0.);
+    sim.step();
+    assert_eq!(state(&sim), (7., 40., 4., 0.));
+}
+
+#[test]
+fn bounces_off_right_wall() {
+    let mut sim = single(93., 40., 4., 0.);
+    sim.step();
+    assert_eq!(state(&sim), (93., 40., -4., 0.));
+}
+
+#[test]
+fn bounces_off_top_wall() {
+    let mut sim = single(50., 7., 0., -4.);
+    sim.step();
+    assert_eq!(state(&sim), (50., 7., 0., 4.));
+}
+
+#[test]
+fn bounces_off_bottom_wall() {
+    let mut sim = single(50., 73., 0., 4.);
+    sim.step();
+    assert_eq!(state(&sim), (50., 73., 0., -4.));
+}
+
+#[test]
+fn bounces_off_both_walls_in_a_corner() {
+    let mut sim = single(7., 73., -4., 4.);
+    sim.step();
+    let (x, y, vx, vy) = state(&sim);
+    assert_eq!((vx, vy), (4., -4.));
+    assert!((5.0..=95.0).contains(&x) && (5.0..=75.0).contains(&y));
+}
+
+#[test]
+fn disk_overlapping_two_walls_is_pushed_back_from_both() {
+    // 左の壁と下の壁の両方にめり込んだ状態から、どちらの向きに動いていても内側に戻る
+    let mut rng = create_rng(Some(6));
+    for _ in 0..100 {
+        let (x, y) = (rng.gen_range(-5., 5.), rng.gen_range(75., 85.));
+        let (vx, vy) = (rng.gen_range(-4., 4.), rng.gen_range(-4., 4.));
+        let mut sim = single(x, y, vx, vy);
+        sim.step();
+        let (x, y, _, _) = state(&sim);
+        assert!((5.0..=95.0).contains(&x), "x out of bounds: {}", x);
+        assert!((5.0..=75.0).contains(&y), "y out of bounds: {}", y);
+    }
+}
+
+#[test]
+fn stays_inside_after_many_steps_from_every_corner() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(5., 5., -3., -2.);
+    sim.add_disk(95., 5., 3., -2.);
+    sim.add_disk(5., 75., -3., 2.);
+    sim.add_disk(95., 75., 3., 2.);
+    for _ in 0..500 {
+        sim.step();
+        for d in sim.disks() {
+            assert!(d.x >= 5. && d.x <= 95., "x out of bounds: {}", d.x);
+            assert!(d.y >= 5. && d.y <= 75., "y out of bounds: {}", d.y);
+        }
+    }
+}
+
+#[test]
+fn gravity_accelerates_downwards() {
+    let mut sim = single(50., 20., 0., 0.);
+    sim.set_gravity(0.5).unwrap();
+    sim.step();
+    sim.step();
+    assert_eq!(state(&sim), (50., 21.5, 0., 1.));
+}
+
+#[test]
+fn collision_setting_controls_disk_interaction() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(40., 40., 1., 0.);
+    sim.add_disk(50., 40., -1., 0.);
+    sim.step();
+    assert_eq!(sim.last_collisions(), 0);
+
+    sim.set_collision(true);
+    sim.step();
+    assert_eq!(sim.last_collisions(), 1);
+    assert!(sim.disks()[0].cos < 0. && sim.disks()[1].cos > 0.);
+}
+
+fn wrapping(x: f64, y: f64, vx: f64, vy: f64) -> Simulation {
+    let mut sim = single(x, y, vx, vy);
+    sim.set_boundary(Boundary::Wrap);
+    sim
+}
+
+#[test]
+fn wraps_past_left_edge() {
+    let mut sim = wrapping(2., 40., -8., 0.);
+    sim.step();
+    assert_eq!(state(&sim), (104., 40., -8., 0.));
+}
+
+#[test]
+fn wraps_past_right_edge() {
+    let mut sim = wrapping(98., 40., 8., 0.);
+    sim.step();
+    assert_eq!(state(&sim), (-4., 40., 8., 0.));
+}
+
+#[test]
+fn wraps_past_top_edge() {
+    let mut sim = wrapping(50., 2., 0., -8.);
+    sim.step();
+    assert_eq!(state(&sim), (50., 84., 0., -8.));
+}
+
+#[test]
+fn wraps_past_bottom_edge() {
+    let mut sim = wrapping(50., 78., 0., 8.);
+    sim.step();
+    assert_eq!(state(&sim), (50., -4., 0., 8.));
+}
+
+#[test]
+fn wrap_waits_until_disk_has_fully_left() {
+    let mut sim = wrapping(98., 40., 4., 0.);
+    sim.step();
+    assert_eq!(state(&sim).0, 102.);
+}
+
+#[test]
+fn wrap_handles_moving_more_than_a_screen_per_step() {
+    let mut sim = wrapping(50., 40., 250., -200.);
+    sim.step();
+    assert_eq!((state(&sim).0, state(&sim).1), (80., 20.));
+}
+
+#[test]
+fn boundary_parse_rejects_unknown_modes() {
+    assert_eq!(Boundary::parse("wrap"), Ok(Boundary::Wrap));
+    assert!(Boundary::parse("torus").is_err());
+}
+
+#[test]
+fn step_dt_scales_motion_by_elapsed_time() {
+    let mut half = single(50., 40., 2., -2.);
+    half.step_dt(FRAME_MS / 2.);
+    assert_eq!(state(&half), (51., 39., 2., -2.));
+
+    let mut two = single(50., 40., 2., -2.);
+    two.step_dt(FRAME_MS * 2.);
+    assert!((state(&two).0 - 54.).abs() < 1e-9 && (state(&two).1 - 36.).abs() < 1e-9);
+}
+
+#[test]
+fn step_dt_clamps_long_gaps() {
+    let mut sim = single(50., 40., 1., 0.);
+    sim.step_dt(10_000.);
+    assert!((state(&sim).0 - (50. + MAX_DT_MS / FRAME_MS)).abs() < 1e-9);
+}
+
+#[test]
+fn gravity_scales_with_dt() {
+    let mut sim = single(50., 20., 0., 0.);
+    sim.set_gravity(0.5).unwrap();
+    sim.step_dt(FRAME_MS / 2.);
+    assert_eq!(state(&sim).3, 0.25);
+}
+
+fn crowded(boundary: Boundary) -> Simulation {
+    let disks = init_disks(
+        200,
+        300,
+        300,
+        4.,
+        SpawnPattern::Uniform,
+        [1., 4.],
+        &mut *create_rng(Some(11)),
+    );
+    let mut sim = Simulation::with_disks(300., 300., 4., disks);
+    sim.set_collision(true);
+    sim.set_boundary(boundary);
+    sim
+}
+
+#[test]
+fn collisions_conserve_energy_over_many_frames() {
+    let mut sim = crowded(Boundary::Bounce);
+    let initial = sim.total_energy();
+    let mut collisions = 0;
+    for _ in 0..500 {
+        sim.step();
+        collisions += sim.last_collisions();
+    }
+    assert!(collisions > 0);
+    assert!((sim.total_energy() - initial).abs() / initial < 1e-9);
+}
+
+#[test]
+fn disks_stacked_at_the_center_spread_out_without_gaining_speed() {
+    let disks = init_disks(
+        100,
+        300,
+        300,
+        4.,
+        SpawnPattern::Center,
+        [1., 4.],
+        &mut *create_rng(Some(5)),
+    );
+    let mut sim = Simulation::with_disks(300., 300., 4., disks);
+    sim.set_collision(true);
+    let initial = sim.total_energy();
+    // 全ディスクが同じ点から始まるので、最初のフレームでは全ペアが重なっている
+    let fastest = initial.sqrt();
+    for _ in 0..120 {
+        sim.step();
+        assert!(sim
+            .disks()
+            .iter()
+            .all(|d| d.cos.hypot(d.sin) <= fastest + 1e-9));
+    }
+    assert!((sim.total_energy() - initial).abs() / initial < 1e-9);
+    assert!(sim.recovered().is_empty());
+}
+
+#[test]
+fn collisions_conserve_momentum_without_walls() {
+    let mut sim = crowded(Boundary::Wrap);
+    let (px, py) = sim.total_momentum();
+    for _ in 0..500 {
+        sim.step();
+    }
+    let (qx, qy) = sim.total_momentum();
+    assert!((qx - px).abs() < 1e-9 && (qy - py).abs() < 1e-9);
+}
+
+#[test]
+fn invariant_check_finds_no_leak_over_thousands_of_seeded_ticks() {
+    for seed in [3, 17, 42] {
+        for boundary in [Boundary::Bounce, Boundary::Wrap] {
+            let disks = init_disks(
+                150,
+                300,
+                300,
+                4.,
+                SpawnPattern::Uniform,
+                [1., 4.],
+                &mut *create_rng(Some(seed)),
+            );
+            let mut sim = Simulation::with_disks(300., 300., 4., disks);
+            sim.set_collision(true);
+            sim.set_boundary(boundary);
+            sim.set_check_invariants(true);
+            let (px, py) = sim.total_momentum();
+            let mut collisions = 0;
+            for _ in 0..3000 {
+                sim.step();
+                collisions += sim.last_collisions();
+            }
+            assert!(collisions > 0);
+            assert_eq!(sim.last_invariant_report(), None, "seed {}", seed);
+            if boundary == Boundary::Wrap {
+                let (qx, qy) = sim.total_momentum();
+                assert!((qx - px).abs() < 1e-6 && (qy - py).abs() < 1e-6);
+            }
+        }
+    }
+}
+
+#[test]
+fn invariant_check_ignores_expected_energy_changes() {
+    let mut sim = crowded(Boundary::Bounce);
+    sim.set_check_invariants(true);
+    sim.set_gravity(0.2).unwrap();
+    sim.set_drag(0.01).unwrap();
+    sim.set_collision_restitution(0.8).unwrap();
+    for _ in 0..300 {
+        sim.step();
+    }
+    assert_eq!(sim.last_invariant_report(), None);
+}
+
+#[test]
+fn energy_changes_are_judged_against_the_settings() {
+    // エネルギーを加えていなければ増えてはいけないが、減るのは drag や反発係数によるもの
+    assert!(energy_violated(10., 10.1, false, false));
+    assert!(!energy_violated(10., 9., false, false));
+    assert!(!energy_violated(10., 12., true, false));
+    // 保つはずの設定では減っても報告し、丸め誤差は見逃す
+    assert!(energy_violated(10., 9., false, true));
+    assert!(!energy_violated(10., 10. + 1e-12, false, true));
+    assert!(energy_violated(f64::NAN, 1., true, false));
+}
+
+#[test]
+fn invariant_report_records_the_tick_and_colliding_pairs() {
+    // 壊れた速度のディスクを中央に戻すのでエネルギーが有限でなくなり、報告される
+    let disks = vec![
+        Disk::new(15., 20., 2., 0.),
+        Disk::new(24., 20., -2., 0.),
+        Disk::new(70., 60., f64::INFINITY, 0.),
+    ];
+    let mut sim = Simulation::with_disks(100., 80., 5., disks);
+    sim.set_collision(true);
+    sim.set_check_invariants(true);
+    sim.step();
+    let report = sim.invariant_violation().unwrap();
+    assert_eq!(report.tick, 0);
+    assert!(!report.conserving);
+    assert_eq!(report.collisions, vec![[0, 1]]);
+    assert!(report.energy_after.is_finite());
+    sim.step();
+    assert_eq!(sim.invariant_violation(), None);
+    assert_eq!(sim.last_invariant_report().unwrap().tick, 0);
+    sim.set_check_invariants(false);
+    assert_eq!(sim.last_invariant_report(), None);
+}
+
+#[test]
+fn speed_scale_changes_motion_but_not_stored_velocity() {
+    let mut sim = single(50., 40., 2., 0.);
+    sim.set_speed_scale(0.5).unwrap();
+    sim.step();
+    assert_eq!(state(&sim), (51., 40., 2., 0.));
+    sim.set_speed_scale(1.).unwrap();
+    sim.step();
+    assert_eq!(state(&sim), (53., 40., 2., 0.));
+    assert!(sim.set_speed_scale(f64::NAN).is_err());
+    assert!(sim.set_speed_scale(-1.).is_err());
+}
+
+#[test]
+fn bounds_padding_moves_the_walls_inward() {
+    let mut sim = single(17., 40., -4., 0.);
+    sim.set_bounds_padding(10.).unwrap();
+    sim.step();
+    // 左の壁は x = 10、中心は 10 + 5 より内側に跳ね返る
+    assert_eq!(state(&sim), (17., 40., 4., 0.));
+    assert!(sim.set_bounds_padding(40.).is_err());
+    assert!(sim.set_bounds_padding(-1.).is_err());
+}
+
+#[test]
+fn gravity_rejects_non_finite_values() {
+    let mut sim = single(50., 40., 2., 0.);
+    sim.set_gravity(0.5).unwrap();
+    assert!(sim.set_gravity(f64::NAN).is_err());
+    assert!(sim.set_gravity(f64::INFINITY).is_err());
+    assert_eq!(sim.gravity(), 0.5);
+}
+
+#[test]
+fn disk_size_rejects_invalid_values() {
+    let mut sim = single(50., 40., 0., 0.);
+    assert!(sim.set_disk_size(-2.).is_err());
+    assert!(sim.set_disk_size(f64::NAN).is_err());
+    sim.set_disk_size(8.).unwrap();
+    assert_eq!(sim.disk_size(), 8.);
+}
+
+#[test]
+fn pick_returns_topmost_overlapping_disk() {
+    let mut sim = Simulation::new(100., 100., 5.);
+    sim.add_disk(20., 20., 0., 0.);
+    sim.add_disk(24., 20., 0., 0.);
+    sim.add_disk(60., 60., 0., 0.);
+    // 0 と 1 の両方に含まれる点は後に描かれる 1
+    assert_eq!(sim.pick(22., 20.), Some(1));
+    // 0 にしか含まれない点
+    assert_eq!(sim.pick(16., 20.), Some(0));
+    assert_eq!(sim.pick(60., 64.), Some(2));
+    assert_eq!(sim.pick(80., 20.), None);
+}
+
+#[test]
+fn pick_all_lists_every_disk_under_the_point_topmost_first() {
+    let mut sim = Simulation::new(100., 100., 5.);
+    sim.add_disk(20., 20., 0., 0.);
+    sim.add_disk(24., 20., 0., 0.);
+    sim.add_disk(22., 22., 0., 0.);
+    sim.add_disk(60., 60., 0., 0.);
+    assert_eq!(sim.pick_all(22., 20.), vec![2, 1, 0]);
+    assert_eq!(sim.pick_all(16., 20.), vec![0]);
+    assert!(sim.pick_all(80., 20.).is_empty());
+}
+
+#[test]
+fn grabbed_disk_stays_pinned_to_the_pointer() {
+    let mut sim = single(50., 40., 3., 0.);
+    sim.set_gravity(1.).unwrap();
+    assert_eq!(sim.grab(80., 10.), None);
+    assert_eq!(sim.grab(52., 41.), Some(0));
+    assert_eq!(sim.grabbed(), Some(0));
+    sim.step();
+    assert_eq!(state(&sim), (50., 40., 0., 0.));
+    // 壁を越えて動かしても反射しない
+    sim.drag_to(-20., 40.);
+    sim.step();
+    assert_eq!((sim.disks()[0].x, sim.disks()[0].y), (-20., 40.));
+}
+
+#[test]
+fn release_throws_the_disk_with_the_pointer_velocity() {
+    let mut sim = single(50., 40., 0., 0.);
+    sim.grab(50., 40.);
+    for x in [52., 54., 56., 58.] {
+        sim.step();
+        sim.drag_to(x, 40.);
+    }
+    sim.release();
+    assert_eq!(sim.grabbed(), None);
+    let (_, _, vx, vy) = state(&sim);
+    assert!(vx > 1.5 && vx <= 2., "{}", vx);
+    assert_eq!(vy, 0.);
+}
+
+#[test]
+fn release_after_holding_still_drops_the_disk_in_place() {
+    let mut sim = single(50., 40., 0., 0.);
+    sim.grab(50., 40.);
+    sim.step();
+    sim.drag_to(60., 40.);
+    for _ in 0..10 {
+        sim.step();
+    }
+    sim.release();
+    assert_eq!(state(&sim), (60., 40., 0., 0.));
+}
+
+#[test]
+fn release_outside_the_canvas_clamps_the_disk_inside() {
+    let mut sim = single(50., 40., 0., 0.);
+    sim.grab(50., 40.);
+    sim.drag_to(150., -30.);
+    sim.release();
+    let (x, y, _, _) = state(&sim);
+    assert_eq!((x, y), (95., 5.));
+}
+
+#[test]
+fn grabbed_disk_is_immovable_in_collisions() {
+    let mut sim = Simulation::new(200., 100., 5.);
+    sim.set_collision(true);
+    sim.add_disk(100., 50., 0., 0.);
+    sim.add_disk(91., 50., 2., 0.);
+    sim.grab(100., 50.);
+    sim.step();
+    assert_eq!((sim.disks()[0].x, sim.disks()[0].y), (100., 50.));
+    assert_eq!(sim.disks()[0].mass, 1.);
+    // ぶつかった側だけが跳ね返る
+    assert!(sim.disks()[1].cos < -1.9, "{}", sim.disks()[1].cos);
+}
+
+#[test]
+fn removing_disks_keeps_track_of_the_grabbed_disk() {
+    let mut sim = Simulation::new(200., 100., 5.);
+    for x in [20., 60., 100.] {
+        sim.add_disk(x, 50., 0., 0.);
+    }
+    sim.grab(100., 50.);
+    sim.remove_disk(0);
+    assert_eq!(sim.grabbed(), Some(1));
+    sim.swap_remove_disk(0);
+    assert_eq!(sim.grabbed(), Some(0));
+    sim.remove_disk(0);
+    assert_eq!(sim.grabbed(), None);
+}
+
+#[test]
+fn max_speed_scales_velocity_down_keeping_direction() {
+    let mut sim = single(50., 40., 0., 0.);
+    sim.set_gravity(10.).unwrap();
+    sim.set_max_speed(Some(3.)).unwrap();
+    sim.step();
+    let (_, _, vx, vy) = state(&sim);
+    assert_eq!((vx, vy), (0., 3.));
+
+    let mut sim = single(50., 40., 6., 8.);
+    sim.set_max_speed(Some(5.)).unwrap();
+    sim.step();
+    let (_, _, vx, vy) = state(&sim);
+    assert!((vx - 3.).abs() < 1e-12 && (vy - 4.).abs() < 1e-12);
+}
+
+#[test]
+fn max_speed_leaves_slow_disks_alone() {
+    let mut sim = single(50., 40., 1., 2.);
+    sim.set_max_speed(Some(5.)).unwrap();
+    sim.step();
+    assert_eq!(state(&sim), (51., 42., 1., 2.));
+}
+
+#[test]
+fn max_speed_rejects_invalid_values() {
+    let mut sim = single(50., 40., 1., 2.);
+    assert!(sim.set_max_speed(Some(-1.)).is_err());
+    assert!(sim.set_max_speed(Some(f64::NAN)).is_err());
+    assert!(sim.set_max_speed(None).is_ok());
+}
+
+#[test]
+fn fast_disk_bounces_several_times_in_one_step() {
+    // 範囲 [5, 95] を右向きに 200 進む: 95 で反射 (残り 155)、5 で反射 (残り 65) して 70 まで進む
+    let mut disk = Disk::new(50., 40., 200., 0.);
+    assert!(sweep_disk(&mut disk, &Bounds::new(100., 80.), 5., 1.));
+    assert_eq!((disk.x, disk.y, disk.cos, disk.sin), (70., 40., 200., 0.));
+}
+
+#[test]
+fn sweep_keeps_disks_inside_and_preserves_speed() {
+    let (width, height, size) = (100., 80., 5.);
+    let bounds = Bounds::new(width, height);
+    let mut rng = create_rng(Some(3));
+    for _ in 0..1000 {
+        let x = rng.gen_range(size, width - size);
+        let y = rng.gen_range(size, height - size);
+        let vx = rng.gen_range(-50. * width, 50. * width);
+        let vy = rng.gen_range(-50. * width, 50. * width);
+        let mut disk = Disk::new(x, y, vx, vy);
+        sweep_disk(&mut disk, &bounds, size, 1.);
+        assert!(disk.x >= size && disk.x <= width - size, "x = {}", disk.x);
+        assert!(disk.y >= size && disk.y <= height - size, "y = {}", disk.y);
+        assert_eq!((disk.cos.abs(), disk.sin.abs()), (vx.abs(), vy.abs()));
+    }
+}
+
+#[test]
+fn non_finite_disks_are_reset_to_the_center() {
+    let mut sim = Simulation::with_disks(
+        200.,
+        100.,
+        5.,
+        vec![
+            Disk::new(50., 50., f64::NAN, 1.),
+            Disk::new(20., 20., 1., 0.),
+        ],
+    );
+    sim.step();
+    assert_eq!(sim.recovered(), &[0]);
+    let disk = &sim.disks()[0];
+    assert_eq!((disk.x, disk.y, disk.cos, disk.sin), (100., 50., 0., 0.));
+    assert_eq!(sim.disks()[1].x, 21.);
+    // 戻した後は普通に動き続ける
+    sim.step();
+    assert!(sim.recovered().is_empty());
+}
+
+#[test]
+fn bad_force_does_not_spread_through_collisions() {
+    let mut sim = Simulation::new(200., 100., 5.);
+    sim.add_disk(50., 50., 0., 0.);
+    sim.add_disk(58., 50., 0., 0.);
+    sim.set_collision(true);
+    // 隣のディスク (距離 8) には届かない半径で壊れた力を加える
+    sim.apply_force(50., 50., f64::NAN, 5.);
+    sim.step();
+    assert_eq!(sim.recovered(), &[0]);
+    assert!(sim
+        .disks()
+        .iter()
+        .all(|d| [d.x, d.y, d.cos, d.sin].iter().all(|v| v.is_finite())));
+}
+
+#[test]
+#[should_panic(expected = "non-finite")]
+fn strict_mode_panics_on_non_finite_values() {
+    let mut sim = Simulation::new(200., 100., 5.);
+    sim.add_disk(50., 50., f64::INFINITY, 0.);
+    sim.set_strict(true);
+    sim.step();
+}
+
+#[test]
+fn resize_pulls_disks_back_inside_the_new_bounds() {
+    let mut sim = Simulation::new(200., 100., 5.);
+    sim.add_disk(180., 90., 1., -1.);
+    sim.add_disk(20., 20., 0., 0.);
+    sim.resize(100., 50.).unwrap();
+    assert_eq!(sim.width(), 100.);
+    assert_eq!(state(&sim), (95., 45., 1., -1.));
+    assert_eq!((sim.disks()[1].x, sim.disks()[1].y), (20., 20.));
+    assert!(sim.resize(0., 50.).is_err());
+}
+
+#[test]
+fn resize_wraps_disks_in_wrap_mode() {
+    let mut sim = Simulation::new(200., 100., 5.);
+    sim.set_boundary(Boundary::Wrap);
+    sim.add_disk(150., 50., 0., 0.);
+    sim.resize(100., 100.).unwrap();
+    // 周期は [-5, 105) なので 150 は 40 に折り返す
+    assert_eq!(state(&sim), (40., 50., 0., 0.));
+}
+
+#[test]
+fn wall_restitution_scales_the_reflected_speed() {
+    // 2 進んで床に当たり、半分の速さで残りの半ステップ分戻る
+    let mut sim = single(50., 73., 0., 4.);
+    sim.set_wall_restitution(0.5).unwrap();
+    sim.step();
+    assert_eq!(state(&sim), (50., 74., 0., -2.));
+}
+
+#[test]
+fn disks_come_to_rest_on_the_floor_with_gravity() {
+    let mut sim = single(50., 20., 1., 0.);
+    sim.set_gravity(0.5).unwrap();
+    sim.set_wall_restitution(0.5).unwrap();
+    for _ in 0..500 {
+        sim.step();
+    }
+    let (_, y, _, vy) = state(&sim);
+    assert_eq!((y, vy), (75., 0.));
+    // 床にいる間も揺れ続けない
+    sim.step();
+    assert_eq!(state(&sim).3, 0.);
+}
+
+#[test]
+fn force_accelerates_every_disk_alongside_gravity() {
+    let mut sim = single(50., 40., 0., 0.);
+    sim.set_gravity(0.5).unwrap();
+    sim.set_force(0.25, -1.).unwrap();
+    sim.step();
+    assert_eq!(state(&sim), (50.25, 39.5, 0.25, -0.5));
+    assert!(sim.set_force(f64::NAN, 0.).is_err());
+    assert!(sim.set_force(0., f64::INFINITY).is_err());
+}
+
+#[test]
+fn zero_force_matches_no_force() {
+    let mut plain = crowded(Boundary::Bounce);
+    let mut forced = crowded(Boundary::Bounce);
+    for sim in [&mut plain, &mut forced].iter_mut() {
+        sim.set_gravity(0.3).unwrap();
+        sim.set_wall_restitution(0.5).unwrap();
+    }
+    forced.set_force(0., 0.).unwrap();
+    for _ in 0..100 {
+        plain.step();
+        forced.step();
+    }
+    assert_eq!(plain.disks(), forced.disks());
+}
+
+#[test]
+fn sideways_force_slides_disks_along_the_wall() {
+    let mut sim = single(50., 20., 0., 0.05);
+    sim.set_force(0.5, 0.).unwrap();
+    sim.set_wall_restitution(0.5).unwrap();
+    for _ in 0..300 {
+        sim.step();
+    }
+    let (x, y, vx, vy) = state(&sim);
+    assert_eq!((x, vx), (95., 0.));
+    // 壁に押し付けられたまま揺れずに、壁に沿った速度は保つ
+    sim.step();
+    assert_eq!(state(&sim), (95., y + vy, 0., 0.05));
+}
+
+#[test]
+fn collision_restitution_below_one_loses_energy() {
+    let mut sim = crowded(Boundary::Bounce);
+    sim.set_collision_restitution(0.5).unwrap();
+    let initial = sim.total_energy();
+    for _ in 0..200 {
+        sim.step();
+    }
+    assert!(sim.total_energy() < initial * 0.5);
+}
+
+#[test]
+fn deprecated_set_restitution_sets_the_collision_restitution() {
+    let mut sim = crowded(Boundary::Bounce);
+    sim.set_restitution(0.5).unwrap();
+    assert_eq!(sim.collision_restitution(), 0.5);
+    assert_eq!(sim.restitution(), 0.5);
+    assert_eq!(sim.wall_restitution(), 1.);
+    assert!(sim.set_restitution(2.).is_err());
+}
+
+#[test]
+fn restitution_rejects_values_outside_the_unit_range() {
+    let mut sim = single(50., 40., 0., 0.);
+    assert!(sim.set_wall_restitution(1.5).is_err());
+    assert!(sim.set_collision_restitution(-0.1).is_err());
+    assert!(sim.set_rest_speed(Some(-1.)).is_err());
+    assert_eq!(sim.wall_restitution(), 1.);
+    assert_eq!(sim.collision_restitution(), 1.);
+}
+
+#[test]
+fn strong_gravity_does_not_tunnel_through_the_floor() {
+    let mut sim = single(50., 70., 0., 0.);
+    sim.set_gravity(100.).unwrap();
+    for _ in 0..100 {
+        sim.step();
+        let (_, y, _, _) = state(&sim);
+        assert!((5.0..=75.0).contains(&y), "y = {}", y);
+    }
+}
+
+#[test]
+fn drag_slows_both_velocity_components_alike() {
+    let mut sim = single(50., 40., 3., -4.);
+    sim.set_drag(0.5).unwrap();
+    sim.step();
+    assert_eq!(state(&sim), (51.5, 38., 1.5, -2.));
+    // 2フレーム分の dt では (1 - drag)² を掛ける
+    sim.step_dt(FRAME_MS * 2.);
+    let (_, _, vx, vy) = state(&sim);
+    assert_eq!((vx, vy), (0.375, -0.5));
+}
+
+#[test]
+fn drag_brings_disks_to_a_stop_inside_the_walls() {
+    let mut sim = single(94., 40., 4., 0.);
+    sim.set_drag(0.1).unwrap();
+    for _ in 0..500 {
+        sim.step();
+    }
+    let (x, _, vx, vy) = state(&sim);
+    assert_eq!((vx, vy), (0., 0.));
+    assert!((5.0..=95.0).contains(&x), "x = {}", x);
+    assert!(sim.set_drag(1.5).is_err());
+}
+
+#[test]
+fn gaussian_is_roughly_standard_normal() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let samples: Vec<f64> = (0..20000).map(|_| gaussian(&mut rng)).collect();
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+    assert!(mean.abs() < 0.05, "mean = {}", mean);
+    assert!((variance - 1.).abs() < 0.05, "variance = {}", variance);
+    // 分散1の一様分布なら ±√3 を超えないが、正規分布なら 2σ を超えるものが約 4.6% ある
+    let tail = samples.iter().filter(|x| x.abs() > 2.).count() as f64 / samples.len() as f64;
+    assert!((0.035..0.06).contains(&tail), "tail = {}", tail);
+}
+
+#[test]
+fn jitter_is_applied_before_drag_and_integration() {
+    let mut sim = single(50., 40., 1., 2.);
+    sim.set_jitter(0.5).unwrap();
+    sim.set_drag(0.25).unwrap();
+    sim.seed_jitter(3);
+    sim.step();
+    let mut rng = StdRng::seed_from_u64(3);
+    let vx = (1. + gaussian(&mut rng) * 0.5) * 0.75;
+    let vy = (2. + gaussian(&mut rng) * 0.5) * 0.75;
+    let (x, y, actual_vx, actual_vy) = state(&sim);
+    assert!((actual_vx - vx).abs() < 1e-12 && (actual_vy - vy).abs() < 1e-12);
+    assert!((x - (50. + vx)).abs() < 1e-12 && (y - (40. + vy)).abs() < 1e-12);
+}
+
+#[test]
+fn jitter_is_reproducible_with_the_same_seed() {
+    let run = |seed| {
+        let mut sim = single(50., 40., 0., 0.);
+        sim.set_jitter(0.3).unwrap();
+        sim.seed_jitter(seed);
+        for _ in 0..50 {
+            sim.step();
+        }
+        state(&sim)
+    };
+    assert_eq!(run(11), run(11));
+    assert_ne!(run(11), run(12));
+    assert!(Simulation::new(10., 10., 1.).set_jitter(-0.1).is_err());
+}
+
+#[test]
+fn disks_on_opposite_sides_of_the_wrap_seam_do_not_collide() {
+    // 左端からはみ出したディスクと右端からはみ出したディスクは、画面上では離れている
+    let mut sim = wrapping(-3., 40., -0.5, 0.);
+    sim.add_disk(103., 40., 0.5, 0.);
+    sim.set_collision(true);
+    sim.step();
+    assert_eq!(sim.last_collisions(), 0);
+    assert_eq!(sim.disks()[0].cos, -0.5);
+    assert_eq!(sim.disks()[1].cos, 0.5);
+}
+
+#[test]
+fn disks_can_be_added_and_removed_at_runtime() {
+    let mut sim = single(10., 10., 1., 0.);
+    sim.add_disk(20., 20., 0., 1.);
+    sim.add_random_disks(5, [1., 2.], &mut *create_rng(Some(6)));
+    assert_eq!(sim.disk_count(), 7);
+    for disk in sim.disks()[2..].iter() {
+        assert!((5.0..=95.0).contains(&disk.x) && (5.0..=75.0).contains(&disk.y));
+        let speed = disk.cos.hypot(disk.sin);
+        assert!((1.0..=2.0).contains(&speed), "speed = {}", speed);
+    }
+
+    assert!(sim.remove_disk(0));
+    assert_eq!(sim.disk_count(), 6);
+    assert_eq!((sim.disks()[0].x, sim.disks()[0].y), (20., 20.));
+    assert!(!sim.remove_disk(6));
+    assert_eq!(sim.disk_count(), 6);
+}
+
+#[test]
+fn spawn_at_adds_a_disk_with_the_given_speed() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.spawn_at(30., 40., 2., &mut *create_rng(Some(3)));
+    let disk = &sim.disks()[0];
+    assert_eq!((disk.x, disk.y), (30., 40.));
+    assert!((disk.cos.hypot(disk.sin) - 2.).abs() < 1e-12);
+}
+
+#[test]
+fn spawn_at_clamps_positions_inside_the_walls() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    let rng = &mut *create_rng(Some(3));
+    sim.spawn_at(-20., 500., 1., rng);
+    sim.spawn_at(f64::NAN, 3., 1., rng);
+    assert_eq!(state(&sim).0, 5.);
+    assert_eq!(sim.disks()[0].y, 75.);
+    assert_eq!((sim.disks()[1].x, sim.disks()[1].y), (50., 5.));
+}
+
+#[test]
+fn motion_substeps_split_only_fast_steps() {
+    let slow = [Disk::new(0., 0., 1., 1.), Disk::new(0., 0., -2., 0.)];
+    assert_eq!(motion_substeps(&slow, 5., 1., 0.), 1);
+    let fast = [Disk::new(0., 0., 6., 0.), Disk::new(0., 0., 0., 0.)];
+    assert_eq!(motion_substeps(&fast, 2., 1., 0.), 6);
+    // 加速度の分も移動量に含める
+    assert_eq!(motion_substeps(&fast, 2., 1., 1.), 7);
+    assert_eq!(motion_substeps(&fast, 2., 10., 0.), MAX_SUBSTEPS);
+    assert_eq!(motion_substeps(&[], 2., 1., 0.), 1);
+}
+
+#[test]
+fn fast_disks_collide_instead_of_passing_through_each_other() {
+    let mut sim = Simulation::new(200., 100., 2.);
+    sim.set_collision(true);
+    sim.add_disk(50., 50., 6., 0.);
+    sim.add_disk(56., 50., -6., 0.);
+    sim.step();
+    assert_eq!(sim.last_substeps(), 6);
+    let (a, b) = (&sim.disks()[0], &sim.disks()[1]);
+    assert!(a.x < b.x, "{} {}", a.x, b.x);
+    assert_eq!((a.cos, b.cos), (-6., 6.));
+}
+
+#[test]
+fn time_scale_above_one_is_sub_stepped() {
+    assert_eq!(time_scale_substeps(FRAME_MS, 1.), (1, FRAME_MS));
+    assert_eq!(time_scale_substeps(10., 0.5), (1, 5.));
+    assert_eq!(time_scale_substeps(10., 4.), (4, 10.));
+    assert_eq!(time_scale_substeps(10., 2.5), (3, 10. * 2.5 / 3.));
+    assert_eq!(time_scale_substeps(10., -2.), (2, 10.));
+}
+
+#[test]
+fn reversed_velocities_retrace_the_path() {
+    let mut sim = single(50., 40., 2., -1.);
+    for _ in 0..5 {
+        sim.step();
+    }
+    assert_eq!(state(&sim), (60., 35., 2., -1.));
+    sim.reverse_velocities();
+    for _ in 0..5 {
+        sim.step();
+    }
+    sim.reverse_velocities();
+    assert_eq!(state(&sim), (50., 40., 2., -1.));
+}
+
+fn circle_arena() -> Arena {
+    Arena::Circle {
+        center: None,
+        radius: None,
+    }
+}
+
+#[test]
+fn circle_arena_reflects_about_the_radial_normal() {
+    // 100 x 80 に内接する円は中心 (50, 40)、半径 40。中心は半径 35 の内側に収まる
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.set_arena(circle_arena()).unwrap();
+    sim.add_disk(50. + 33., 40., 4., 1.);
+    sim.step();
+    let (x, y, vx, vy) = state(&sim);
+    assert!(((x - 50.).hypot(y - 40.) - 35.).abs() < 1e-9);
+    // 法線 (外向き) 成分は反転し、接線成分は保たれる
+    let (nx, ny) = ((x - 50.) / 35., (y - 40.) / 35.);
+    assert!(vx * nx + vy * ny < 0.);
+    assert!((vx.hypot(vy) - 4f64.hypot(1.)).abs() < 1e-9);
+    assert!(((vx * -ny + vy * nx) - (4. * -ny + 1. * nx)).abs() < 1e-9);
+}
+
+#[test]
+fn circle_arena_keeps_disks_inside_over_many_steps() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.set_arena(Arena::Circle {
+        center: Some([30., 30.]),
+        radius: Some(25.),
+    })
+    .unwrap();
+    let rng = &mut *create_rng(Some(7));
+    for _ in 0..20 {
+        sim.spawn_at(rng.gen_range(0., 100.), rng.gen_range(0., 80.), 6., rng);
+    }
+    for _ in 0..300 {
+        sim.step();
+        for disk in sim.disks() {
+            assert!((disk.x - 30.).hypot(disk.y - 30.) <= 20. + 1e-9);
+        }
+    }
+    assert!(sim.total_energy() > 0.);
+}
+
+#[test]
+fn circle_arena_clamps_added_disks_onto_the_circle() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.add_disk(0., 0., 1., 0.);
+    sim.set_arena(circle_arena()).unwrap();
+    sim.add_disk(50., 200., 0., 0.);
+    sim.add_disk(50., 40., 0., 0.);
+    let disk = &sim.disks()[0];
+    assert!(((disk.x - 50.).hypot(disk.y - 40.) - 35.).abs() < 1e-9);
+    assert_eq!((sim.disks()[1].x, sim.disks()[1].y), (50., 75.));
+    assert_eq!((sim.disks()[2].x, sim.disks()[2].y), (50., 40.));
+    assert_eq!(sim.disks()[0].cos, 1.);
+}
+
+#[test]
+fn inscribed_circle_arena_follows_resize() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    sim.set_arena(circle_arena()).unwrap();
+    sim.add_disk(50., 70., 0., 0.);
+    sim.resize(100., 40.).unwrap();
+    assert_eq!((sim.disks()[0].x, sim.disks()[0].y), (50., 35.));
+    assert_eq!(sim.arena_circle().unwrap().radius, 20.);
+}
+
+#[test]
+fn enlarging_disks_pushes_them_off_the_walls() {
+    let mut sim = single(6., 40., 0., 0.);
+    sim.set_disk_size(12.).unwrap();
+    assert_eq!(state(&sim).0, 12.);
+    assert!(sim.set_disk_size(0.).is_err());
+    assert_eq!(sim.disk_size(), 12.);
+}
+
+#[test]
+fn truncate_disks_keeps_the_first_disks() {
+    let mut sim = Simulation::new(100., 80., 5.);
+    for i in 0..5 {
+        sim.add_disk(10. + i as f64, 20., 0., 0.);
+    }
+    sim.truncate_disks(2);
+    assert_eq!(sim.disk_count(), 2);
+    assert_eq!(sim.disks()[1].x, 11.);
+    sim.truncate_disks(10);
+    assert_eq!(sim.disk_count(), 2);
+}
+
+fn assert_finite_and_inside(sim: &Simulation) {
+    let size = sim.disk_size();
+    for (index, d) in sim.disks().iter().enumerate() {
+        assert!(
+            [d.x, d.y, d.cos, d.sin].iter().all(|v| v.is_finite()),
+            "disk {} is not finite: {:?}",
+            index,
+            d
+        );
+        assert!(
+            d.x >= size && d.x <= sim.width() - size && d.y >= size && d.y <= sim.height() - size,
+            "disk {} left the arena: {:?}",
+            index,
+            d
+        );
+    }
+}
+
+#[test]
+fn extreme_forces_keep_every_disk_finite_and_inside() {
+    let disks = init_disks(
+        40,
+        300,
+        200,
+        5.,
+        SpawnPattern::Uniform,
+        [1., 4.],
+        &mut *create_rng(Some(11)),
+    );
+    let mut sim = Simulation::with_disks(300., 200., 5., disks);
+    sim.set_collision(true);
+    sim.set_gravity(1e300).unwrap();
+    sim.set_force(-1e308, 1e308).unwrap();
+    sim.add_attractor(150., 100., 1e300).unwrap();
+    sim.add_attractor(40., 40., -1e300).unwrap();
+    sim.set_pointer(200., 150.);
+    for tick in 0..300 {
+        sim.apply_impulse(1e308, -1e308);
+        sim.apply_force(150., 100., 1e308, 1e6);
+        sim.step_dt(if tick % 2 == 0 { MAX_DT_MS } else { f64::MAX });
+        assert_finite_and_inside(&sim);
+    }
+}
+
+#[test]
+fn max_speed_holds_under_extreme_forces() {
+    let disks = init_disks(
+        40,
+        300,
+        200,
+        5.,
+        SpawnPattern::Uniform,
+        [1., 4.],
+        &mut *create_rng(Some(12)),
+    );
+    let mut sim = Simulation::with_disks(300., 200., 5., disks);
+    sim.set_collision(true);
+    sim.set_max_speed(Some(20.)).unwrap();
+    sim.set_gravity(1e12).unwrap();
+    sim.add_attractor(150., 100., 1e12).unwrap();
+    for _ in 0..300 {
+        sim.apply_impulse(1e12, 0.);
+        sim.step();
+        assert_finite_and_inside(&sim);
+        assert!(sim.disks().iter().all(|d| d.cos.hypot(d.sin) <= 20. + 1e-9));
+    }
+}
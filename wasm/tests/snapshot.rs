@@ -0,0 +1,77 @@
+use wasm::{create_rng, init_disks, Disk, DiskInfo, Simulation, SimulationState, SpawnPattern};
+
+fn simulation() -> Simulation {
+    let mut rng = create_rng(Some(11));
+    let disks = init_disks(20, 200, 150, 4., SpawnPattern::Uniform, [1., 4.], &mut *rng);
+    let mut sim = Simulation::with_disks(200., 150., 4., disks);
+    sim.set_collision(true);
+    sim
+}
+
+#[test]
+fn snapshot_round_trips_exactly_through_json() {
+    let mut sim = simulation();
+    for _ in 0..10 {
+        sim.step();
+    }
+    let json = serde_json::to_string(&sim.snapshot()).unwrap();
+    let state: SimulationState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state, sim.snapshot());
+
+    let mut restored = Simulation::new(200., 150., 4.);
+    restored.set_collision(true);
+    restored.restore(&state).unwrap();
+    for _ in 0..10 {
+        sim.step();
+        restored.step();
+    }
+    assert_eq!(restored.snapshot(), sim.snapshot());
+}
+
+#[test]
+fn restore_replaces_disk_count() {
+    let mut sim = simulation();
+    let state = SimulationState {
+        disk_count: 1,
+        disks: vec![Disk::new(10., 20., 1., -1.)],
+    };
+    sim.restore(&state).unwrap();
+    assert_eq!(sim.disk_count(), 1);
+    assert_eq!(sim.disks()[0], Disk::new(10., 20., 1., -1.));
+}
+
+#[test]
+fn restore_rejects_inconsistent_state() {
+    let mut sim = simulation();
+    let before = sim.snapshot();
+    let state = SimulationState {
+        disk_count: 2,
+        disks: vec![Disk::new(10., 20., 1., -1.)],
+    };
+    assert!(sim.restore(&state).is_err());
+    let state = SimulationState {
+        disk_count: 1,
+        disks: vec![Disk::new(f64::NAN, 20., 1., -1.)],
+    };
+    assert!(sim.restore(&state).is_err());
+    assert_eq!(sim.snapshot(), before);
+}
+
+#[test]
+fn malformed_json_is_an_error() {
+    assert!(serde_json::from_str::<SimulationState>(r#"{"disk_count": 1}"#).is_err());
+    assert!(
+        serde_json::from_str::<SimulationState>(r#"{"disk_count": 1, "disks": [{"x": 1}]}"#)
+            .is_err()
+    );
+}
+
+#[test]
+fn disk_info_names_the_velocity_vx_and_vy() {
+    let disk = Disk::new(1., 2., 3., -4.);
+    let json = serde_json::to_value(DiskInfo::from(&disk)).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"x": 1.0, "y": 2.0, "vx": 3.0, "vy": -4.0})
+    );
+}
@@ -0,0 +1,170 @@
+use wasm::boids::{Behavior, BoidsParams};
+use wasm::merge::CollisionResponse;
+use wasm::obstacle::ObstacleShape;
+use wasm::state::{SavedState, STATE_VERSION};
+use wasm::{Arena, Boundary, Simulation};
+
+fn scene() -> Simulation {
+    let mut sim = Simulation::new(200., 100., 4.);
+    sim.add_disk(10., 20., 1., -1.);
+    sim.add_disk(50., 60., -2., 0.5);
+    sim.set_gravity(0.2).unwrap();
+    sim.set_boundary(Boundary::Wrap);
+    sim.set_drag(0.1).unwrap();
+    sim.set_jitter(0.05).unwrap();
+    sim.set_behavior(Behavior::Boids);
+    sim.set_boids(BoidsParams {
+        cohesion: 2.,
+        ..BoidsParams::default()
+    })
+    .unwrap();
+    sim.add_attractor(100., 50., -20.).unwrap();
+    sim.add_obstacle(ObstacleShape::Circle {
+        x: 150.,
+        y: 80.,
+        radius: 5.,
+    })
+    .unwrap();
+    sim.set_arena(Arena::Circle {
+        center: None,
+        radius: Some(200.),
+    })
+    .unwrap();
+    sim
+}
+
+#[test]
+fn saved_state_round_trips_through_json() {
+    let sim = scene();
+    let colors = [1., 0., 0., 0., 0.5, 1.];
+    let json = serde_json::to_string(&SavedState::new(&sim, &colors)).unwrap();
+    let saved: SavedState = serde_json::from_str(&json).unwrap();
+    assert_eq!(saved.version, STATE_VERSION);
+    assert!(saved.validate().is_ok());
+
+    let mut restored = Simulation::new(50., 50., 1.);
+    saved.apply_to(&mut restored).unwrap();
+    assert_eq!(restored.disks(), sim.disks());
+    assert_eq!((restored.width(), restored.height()), (200., 100.));
+    assert_eq!(restored.disk_size(), 4.);
+    assert_eq!(restored.gravity(), 0.2);
+    assert_eq!(restored.boundary(), Boundary::Wrap);
+    assert_eq!(restored.drag(), 0.1);
+    assert_eq!(restored.jitter(), 0.05);
+    assert_eq!(restored.behavior(), Behavior::Boids);
+    assert_eq!(restored.boids().cohesion, 2.);
+    assert_eq!(restored.attractors(), sim.attractors());
+    assert_eq!(restored.arena(), sim.arena());
+    assert_eq!(restored.add_attractor(0., 0., 1.).unwrap(), 1);
+    assert_eq!(restored.obstacles(), sim.obstacles());
+    assert_eq!(saved.colors, colors);
+}
+
+#[test]
+fn merge_settings_strict_and_check_invariants_round_trip_through_json() {
+    let mut sim = scene();
+    sim.set_collision_response(CollisionResponse::Merge);
+    sim.set_merge_limits(2, Some(30.)).unwrap();
+    sim.set_strict(true);
+    sim.set_check_invariants(true);
+    let json = serde_json::to_string(&SavedState::new(&sim, &[])).unwrap();
+    let saved: SavedState = serde_json::from_str(&json).unwrap();
+
+    let mut restored = Simulation::new(50., 50., 1.);
+    saved.apply_to(&mut restored).unwrap();
+    assert_eq!(restored.collision_response(), CollisionResponse::Merge);
+    assert_eq!(restored.merge_limits(), (2, Some(30.)));
+    assert!(restored.strict());
+    assert!(restored.check_invariants());
+}
+
+#[test]
+fn attractor_ids_continue_after_a_removal_across_save_and_load() {
+    let mut sim = scene();
+    sim.add_attractor(10., 10., 5.).unwrap();
+    // 最大の番号を消しても、次の番号は使い直さない
+    assert!(sim.remove_attractor(1));
+    let saved = SavedState::new(&sim, &[0.; 6]);
+    let json = serde_json::to_string(&saved).unwrap();
+    let saved: SavedState = serde_json::from_str(&json).unwrap();
+    let mut restored = Simulation::new(50., 50., 1.);
+    saved.apply_to(&mut restored).unwrap();
+    assert_eq!(restored.add_attractor(0., 0., 1.).unwrap(), 2);
+    assert_eq!(sim.add_attractor(0., 0., 1.).unwrap(), 2);
+
+    // next_attractor_id の無い以前の JSON では最大の番号の次
+    let mut old = saved.clone();
+    old.params.next_attractor_id = None;
+    let mut restored = Simulation::new(50., 50., 1.);
+    old.apply_to(&mut restored).unwrap();
+    assert_eq!(restored.add_attractor(0., 0., 1.).unwrap(), 1);
+
+    let mut taken = saved;
+    taken.params.next_attractor_id = Some(0);
+    assert!(taken.validate().is_err());
+}
+
+#[test]
+fn obstacle_ids_continue_after_a_removal_across_save_and_load() {
+    let mut sim = scene();
+    let id = sim
+        .add_obstacle(ObstacleShape::Circle {
+            x: 20.,
+            y: 80.,
+            radius: 5.,
+        })
+        .unwrap();
+    assert!(sim.remove_obstacle(id));
+    let saved = SavedState::new(&sim, &[0.; 6]);
+    let json = serde_json::to_string(&saved).unwrap();
+    let saved: SavedState = serde_json::from_str(&json).unwrap();
+    let mut restored = Simulation::new(50., 50., 1.);
+    saved.apply_to(&mut restored).unwrap();
+    assert_eq!(restored.add_obstacle(RING).unwrap(), 2);
+    assert_eq!(sim.add_obstacle(RING).unwrap(), 2);
+
+    let mut taken = saved;
+    taken.params.next_obstacle_id = Some(0);
+    assert!(taken.validate().is_err());
+}
+
+const RING: ObstacleShape = ObstacleShape::Circle {
+    x: 30.,
+    y: 30.,
+    radius: 2.,
+};
+
+#[test]
+fn invalid_saved_states_are_rejected() {
+    let sim = scene();
+    let valid = SavedState::new(&sim, &[0.; 6]);
+
+    let mut other_version = valid.clone();
+    other_version.version = STATE_VERSION + 1;
+    let mut short_colors = valid.clone();
+    short_colors.colors.pop();
+    let mut bad_drag = valid.clone();
+    bad_drag.params.drag = 2.;
+    let mut tiny = valid.clone();
+    tiny.width = 0.;
+    let mut duplicate_attractor = valid.clone();
+    duplicate_attractor
+        .params
+        .attractors
+        .push(duplicate_attractor.params.attractors[0]);
+    let mut duplicate_obstacle = valid.clone();
+    duplicate_obstacle
+        .params
+        .obstacles
+        .push(duplicate_obstacle.params.obstacles[0]);
+    for saved in [
+        other_version,
+        short_colors,
+        bad_drag,
+        tiny,
+        duplicate_attractor,
+        duplicate_obstacle,
+    ] {
+        assert!(saved.validate().is_err(), "{:?}", saved);
+    }
+}
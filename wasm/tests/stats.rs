@@ -0,0 +1,94 @@
+use wasm::stats::{speed_histogram, total_kinetic_energy, FrameTimer, STATS_WINDOW};
+use wasm::{Disk, Simulation};
+
+#[test]
+fn fps_is_zero_until_two_frames_are_recorded() {
+    let mut timer = FrameTimer::default();
+    assert_eq!(timer.fps(), (0., 0., 0.));
+    timer.record(0., 1., 2.);
+    assert_eq!(timer.fps(), (0., 0., 0.));
+}
+
+#[test]
+fn fps_and_mean_times_cover_the_recent_frames() {
+    let mut timer = FrameTimer::default();
+    // 10ms, 20ms, 10ms 間隔
+    for (now, step) in [(0., 1.), (10., 2.), (30., 3.), (40., 2.)] {
+        timer.record(now, step, 4.);
+    }
+    let (avg, min, max) = timer.fps();
+    assert!((avg - 75.).abs() < 1e-9);
+    assert!((min - 50.).abs() < 1e-9);
+    assert!((max - 100.).abs() < 1e-9);
+    let stats = timer.snapshot(&[]);
+    assert_eq!(stats.step_ms_mean, 2.);
+    assert_eq!(stats.draw_ms_mean, 4.);
+}
+
+#[test]
+fn old_frames_fall_out_of_the_window() {
+    let mut timer = FrameTimer::default();
+    // 最初の遅いフレームの後、16ms 間隔で窓を埋める
+    timer.record(0., 50., 0.);
+    for i in 0..STATS_WINDOW {
+        timer.record(100. + i as f64 * 16., 1., 0.);
+    }
+    let (_, min, _) = timer.fps();
+    assert!((min - 62.5).abs() < 1e-9);
+    assert_eq!(timer.snapshot(&[]).step_ms_mean, 1.);
+
+    timer.reset();
+    assert_eq!(timer.fps(), (0., 0., 0.));
+}
+
+#[test]
+fn total_kinetic_energy_is_half_m_v_squared() {
+    let mut heavy = Disk::new(0., 0., 3., 4.);
+    heavy.mass = 4.;
+    let disks = [Disk::new(0., 0., 1., 0.), heavy];
+    assert_eq!(total_kinetic_energy(&disks), 0.5 + 50.);
+}
+
+#[test]
+fn speed_histogram_puts_fast_disks_in_the_last_bin() {
+    let disks = [
+        Disk::new(0., 0., 0., 0.),
+        Disk::new(0., 0., 0.5, 0.),
+        Disk::new(0., 0., 3., 4.),
+        Disk::new(0., 0., 0., 9.9),
+        Disk::new(0., 0., 30., 0.),
+    ];
+    // 区間の幅は 2.5
+    assert_eq!(speed_histogram(&disks, 4, 10.).unwrap(), vec![2, 0, 1, 2]);
+    assert_eq!(speed_histogram(&[], 3, 1.).unwrap(), vec![0, 0, 0]);
+    assert!(speed_histogram(&disks, 0, 10.).is_err());
+    assert!(speed_histogram(&disks, 4, 0.).is_err());
+}
+
+#[test]
+fn scaling_velocities_scales_energy_by_the_square() {
+    let mut sim = Simulation::new(100., 100., 2.);
+    sim.add_disk(10., 10., 1., 2.);
+    sim.add_disk(50., 50., -3., 0.5);
+    let before = sim.total_kinetic_energy();
+    sim.scale_velocities(2.).unwrap();
+    assert!((sim.total_kinetic_energy() - before * 4.).abs() < 1e-9);
+    assert_eq!((sim.disks()[0].cos, sim.disks()[0].sin), (2., 4.));
+    assert!(sim.scale_velocities(-1.).is_err());
+    assert!(sim.scale_velocities(f64::INFINITY).is_err());
+}
+
+#[test]
+fn callback_rate_is_reported_separately_from_rendered_frames() {
+    let mut timer = FrameTimer::default();
+    for i in 0..10 {
+        let now = i as f64 * 10.;
+        timer.record_callback(now);
+        if i % 2 == 0 {
+            timer.record(now, 1., 1.);
+        }
+    }
+    let stats = timer.snapshot(&[]);
+    assert!((stats.callback_fps - 100.).abs() < 1e-9);
+    assert!((stats.fps_avg - 50.).abs() < 1e-9);
+}
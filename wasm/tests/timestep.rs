@@ -0,0 +1,86 @@
+use wasm::timestep::{interpolate_positions, FixedStep, FrameLimiter};
+use wasm::{FRAME_MS, MAX_DT_MS};
+
+#[test]
+fn the_first_frame_advances_one_sixtieth_of_a_second() {
+    let mut fixed = FixedStep::new(FRAME_MS / 2.);
+    assert_eq!(fixed.advance(1000.), 2);
+    assert_eq!(fixed.alpha(), 0.);
+}
+
+#[test]
+fn leftover_time_is_carried_to_the_next_frame() {
+    let mut fixed = FixedStep::new(10.);
+    fixed.advance(0.);
+    fixed.discard();
+    assert_eq!(fixed.advance(15.), 1);
+    assert_eq!(fixed.alpha(), 0.5);
+    assert_eq!(fixed.advance(20.), 1);
+    assert_eq!(fixed.alpha(), 0.);
+}
+
+#[test]
+fn long_gaps_are_clamped() {
+    let mut fixed = FixedStep::new(10.);
+    fixed.advance(0.);
+    fixed.discard();
+    // タブが5秒間非表示だった場合も MAX_DT_MS 分しか進めない
+    assert_eq!(fixed.advance(5000.), (MAX_DT_MS / 10.) as u32);
+    // 巻き戻ったタイムスタンプや NaN では1フレーム分だけ進める
+    fixed.reset();
+    fixed.advance(100.);
+    fixed.discard();
+    let steps = fixed.advance(50.) + fixed.advance(f64::NAN);
+    assert_eq!(steps, (2. * FRAME_MS / 10.) as u32);
+}
+
+#[test]
+fn positions_are_interpolated_except_across_large_jumps() {
+    let previous = [0., 10., 95., 40.];
+    let mut positions = [10., 20., 2., 40.];
+    interpolate_positions(&mut positions, &previous, 0.25, 50.);
+    assert_eq!(positions, [2.5, 12.5, 2., 40.]);
+
+    // 数が変わった直後は補間しない
+    let mut positions = [10., 20.];
+    interpolate_positions(&mut positions, &previous, 0.25, 50.);
+    assert_eq!(positions, [10., 20.]);
+}
+
+fn rendered_frames(limiter: &mut FrameLimiter, refresh_hz: f64, seconds: f64) -> usize {
+    let callbacks = (refresh_hz * seconds) as usize;
+    (0..callbacks)
+        .filter(|&i| limiter.ready(1000. + i as f64 * 1000. / refresh_hz))
+        .count()
+}
+
+#[test]
+fn frame_limiter_converges_on_the_target_rate() {
+    // 144Hz の画面で 60fps に間引いても、端数を持ち越すので低い方へずれない
+    let mut limiter = FrameLimiter::new(60);
+    let frames = rendered_frames(&mut limiter, 144., 10.);
+    assert!((598..=601).contains(&frames), "{} frames", frames);
+    assert_eq!(limiter.target_fps(), 60);
+
+    // 120Hz から 60fps はちょうど1フレームおき
+    let mut limiter = FrameLimiter::new(60);
+    assert_eq!(rendered_frames(&mut limiter, 120., 1.), 60);
+}
+
+#[test]
+fn frame_limiter_zero_is_uncapped() {
+    let mut limiter = FrameLimiter::new(0);
+    assert_eq!(rendered_frames(&mut limiter, 144., 1.), 144);
+    assert_eq!(limiter.target_fps(), 0);
+}
+
+#[test]
+fn frame_limiter_restarts_after_a_long_gap() {
+    let mut limiter = FrameLimiter::new(30);
+    assert!(limiter.ready(0.));
+    assert!(!limiter.ready(10.));
+    // タブが非表示だった後は溜まった分をまとめて描かない
+    assert!(limiter.ready(5000.));
+    assert!(!limiter.ready(5010.));
+    assert!(limiter.ready(5034.));
+}
@@ -0,0 +1,66 @@
+use wasm::merge::Merge;
+use wasm::tracking::DiskTracker;
+
+#[test]
+fn removing_a_disk_shifts_the_tracked_index() {
+    let mut tracker = DiskTracker::default();
+    assert!(!tracker.track(5, 5));
+    assert!(tracker.track(3, 5));
+    tracker.remove(1);
+    assert_eq!(tracker.tracked(), Some(2));
+    tracker.remove(4);
+    assert_eq!(tracker.tracked(), Some(2));
+    tracker.remove(2);
+    assert_eq!(tracker.tracked(), None);
+}
+
+#[test]
+fn swap_remove_follows_the_moved_last_disk() {
+    let mut tracker = DiskTracker::default();
+    tracker.track(4, 5);
+    tracker.swap_remove(1, 5);
+    assert_eq!(tracker.tracked(), Some(1));
+    tracker.swap_remove(0, 4);
+    assert_eq!(tracker.tracked(), Some(1));
+    tracker.swap_remove(1, 3);
+    assert_eq!(tracker.tracked(), None);
+}
+
+#[test]
+fn absorbed_disk_is_followed_into_the_survivor() {
+    let mut tracker = DiskTracker::default();
+    tracker.track(1, 4);
+    tracker.merge(&Merge {
+        survivor: 3,
+        removed: 1,
+        weight: 0.5,
+    });
+    assert_eq!(tracker.tracked(), Some(2));
+    tracker.merge(&Merge {
+        survivor: 0,
+        removed: 1,
+        weight: 0.5,
+    });
+    assert_eq!(tracker.tracked(), Some(1));
+}
+
+#[test]
+fn every_removal_advances_the_generation() {
+    let mut tracker = DiskTracker::default();
+    tracker.track(0, 3);
+    let mut generation = tracker.generation();
+    let mut check = |tracker: &DiskTracker| {
+        assert!(tracker.generation() > generation);
+        generation = tracker.generation();
+    };
+    tracker.remove(2);
+    check(&tracker);
+    tracker.swap_remove(1, 2);
+    check(&tracker);
+    tracker.truncate(1);
+    check(&tracker);
+    assert_eq!(tracker.tracked(), Some(0));
+    tracker.replace();
+    check(&tracker);
+    assert_eq!(tracker.tracked(), None);
+}
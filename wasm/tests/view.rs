@@ -0,0 +1,51 @@
+use wasm::view::View;
+
+#[test]
+fn default_view_is_the_identity() {
+    let view = View::default();
+    assert_eq!(view.offset(200., 100.), (0., 0.));
+    assert_eq!(view.screen_to_world(30., 40., 200., 100.), (30., 40.));
+    assert_eq!(view.to_clip(0., 0., 200., 100.), [-1., 1.]);
+    assert_eq!(view.to_clip(200., 100., 200., 100.), [1., -1.]);
+}
+
+#[test]
+fn zoomed_view_shows_a_region_around_the_center() {
+    // (50, 25) を中心に 2 倍: 表示されるのは [0, 100] x [0, 50]
+    let view = View::new(50., 25., 2.).unwrap();
+    assert_eq!(view.offset(200., 100.), (0., 0.));
+    assert_eq!(view.world_to_screen(50., 25., 200., 100.), (100., 50.));
+    assert_eq!(view.to_clip(50., 25., 200., 100.), [0., 0.]);
+    assert_eq!(view.screen_to_world(200., 100., 200., 100.), (100., 50.));
+}
+
+#[test]
+fn screen_to_world_inverts_world_to_screen() {
+    let view = View::new(130., -20., 0.5).unwrap();
+    for &(x, y) in &[(0., 0.), (17., 230.), (-40., 5.5)] {
+        let (sx, sy) = view.world_to_screen(x, y, 320., 240.);
+        let (wx, wy) = view.screen_to_world(sx, sy, 320., 240.);
+        assert!((wx - x).abs() < 1e-9 && (wy - y).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn invalid_views_are_rejected() {
+    assert!(View::new(0., 0., 0.).is_err());
+    assert!(View::new(0., 0., -1.).is_err());
+    assert!(View::new(f64::NAN, 0., 1.).is_err());
+    assert!(View::new(0., 0., f64::INFINITY).is_err());
+}
+
+#[test]
+fn fit_shows_the_whole_world_on_a_smaller_canvas() {
+    // 400 x 200 の領域を 100 x 100 の canvas に: 0.25 倍で、上下に余白ができる
+    let view = View::fit(400., 200., 100., 100.);
+    assert_eq!(view.scale(), 0.25);
+    assert_eq!(view.world_to_screen(0., 0., 100., 100.), (0., 25.));
+    assert_eq!(view.world_to_screen(400., 200., 100., 100.), (100., 75.));
+    assert_eq!(
+        View::fit(100., 100., 100., 100.),
+        View::new(50., 50., 1.).unwrap()
+    );
+}
@@ -3,6 +3,8 @@
 #![cfg(target_arch = "wasm32")]
 
 extern crate wasm_bindgen_test;
+use wasm::{create_rng, init_disks, step_disks, Disk, Simulation, SpawnPattern};
+use wasm_bindgen::JsCast;
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -11,3 +13,711 @@ wasm_bindgen_test_configure!(run_in_browser);
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+#[wasm_bindgen_test]
+fn step_moves_disk_by_velocity() {
+    let mut disks = vec![Disk::new(50., 50., 2., -1.)];
+    for _ in 0..10 {
+        step_disks(&mut disks, 100., 100., 4.);
+    }
+    assert_eq!(disks[0].x, 70.);
+    assert_eq!(disks[0].y, 40.);
+}
+
+#[wasm_bindgen_test]
+fn step_bounces_off_right_wall() {
+    let mut disks = vec![Disk::new(94., 50., 3., 0.)];
+    step_disks(&mut disks, 100., 100., 4.);
+    assert_eq!(disks[0].x, 95.);
+    assert_eq!(disks[0].cos, -3.);
+}
+
+#[wasm_bindgen_test]
+fn collisions_conserve_energy() {
+    let disks = init_disks(
+        200,
+        300,
+        300,
+        4.,
+        SpawnPattern::Uniform,
+        [1., 4.],
+        &mut *create_rng(Some(11)),
+    );
+    let mut sim = Simulation::with_disks(300., 300., 4., disks);
+    sim.set_collision(true);
+    let initial = sim.total_energy();
+    for _ in 0..500 {
+        sim.step();
+    }
+    assert!((sim.total_energy() - initial).abs() / initial < 1e-9);
+}
+
+fn add_canvas(id: &str) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas = document.create_element("canvas").unwrap();
+    canvas.set_id(id);
+    document.body().unwrap().append_child(&canvas).unwrap();
+}
+
+/**
+ * capture_rgba の画素に rgb の色がいくつあるか
+ */
+fn count_color(screen: &wasm::Screen, rgb: [u8; 3]) -> usize {
+    screen
+        .capture_rgba()
+        .unwrap()
+        .to_vec()
+        .chunks(4)
+        .filter(|pixel| pixel[..3] == rgb)
+        .count()
+}
+
+/**
+ * canvas_id の canvas (attach_view のビューなど) の描画バッファに rgb の色の画素がいくつあるか
+ * コンテキストは "webgl2" / "webgl" のどちらでもよい (描いたのと同じタスクの中で読むこと)
+ */
+fn count_canvas_color(canvas_id: &str, rgb: [u8; 3]) -> usize {
+    let canvas: web_sys::HtmlCanvasElement = web_sys::window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .get_element_by_id(canvas_id)
+        .unwrap()
+        .dyn_into()
+        .unwrap();
+    let gl = canvas
+        .get_context("webgl2")
+        .unwrap()
+        .or_else(|| canvas.get_context("webgl").unwrap())
+        .unwrap();
+    let (width, height) = (canvas.width(), canvas.height());
+    let pixels = js_sys::Uint8Array::new_with_length(width * height * 4);
+    let read_pixels: js_sys::Function = js_sys::Reflect::get(&gl, &"readPixels".into())
+        .unwrap()
+        .unchecked_into();
+    let args = js_sys::Array::of5(
+        &0.into(),
+        &0.into(),
+        &width.into(),
+        &height.into(),
+        &0x1908.into(),
+    );
+    args.push(&0x1401.into());
+    args.push(&pixels);
+    read_pixels.apply(&gl, &args).unwrap();
+    pixels
+        .to_vec()
+        .chunks(4)
+        .filter(|pixel| pixel[..3] == rgb)
+        .count()
+}
+
+/**
+ * 全体を rgb で塗るだけのフラグメントシェーダ (screen の組み込みのシェーダと同じ GLSL のバージョン)
+ */
+fn solid_fragment_shader(screen: &wasm::Screen, [r, g, b]: [f32; 3]) -> String {
+    if screen.default_fragment_shader().contains("#version 300 es") {
+        format!(
+            "#version 300 es\nprecision mediump float;\nout vec4 frag_color;\nvoid main() {{ frag_color = vec4({:.1}, {:.1}, {:.1}, 1.0); }}",
+            r, g, b
+        )
+    } else {
+        format!(
+            "precision mediump float;\nvoid main() {{ gl_FragColor = vec4({:.1}, {:.1}, {:.1}, 1.0); }}",
+            r, g, b
+        )
+    }
+}
+
+#[wasm_bindgen_test]
+fn non_square_canvas_draws_the_bottom_right_corner() {
+    add_canvas("wide");
+    let mut screen = wasm::init_gl_with("wide", 0, 80, 40, 8.).unwrap();
+    let canvas: web_sys::HtmlCanvasElement = web_sys::window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .get_element_by_id("wide")
+        .unwrap()
+        .dyn_into()
+        .unwrap();
+    let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+    assert_eq!(width, height * 2);
+
+    screen.add_disk(79., 39., 0., 0.);
+    screen
+        .set_colors(&js_sys::Float32Array::from([1., 0., 0.].as_slice()))
+        .unwrap();
+    let pixels = screen.capture_rgba().unwrap().to_vec();
+    // readPixels は下の行から並ぶので、先頭の行が canvas の下端
+    let pixel = |x: usize, row_from_bottom: usize| {
+        let offset = (row_from_bottom * width + x) * 4;
+        [pixels[offset], pixels[offset + 1], pixels[offset + 2]]
+    };
+    assert_eq!(pixel(width - 1, 0), [255, 0, 0]);
+    assert_ne!(pixel(0, height - 1), [255, 0, 0]);
+    assert_ne!(pixel(width - 1, height - 1), [255, 0, 0]);
+    assert_ne!(pixel(0, 0), [255, 0, 0]);
+}
+
+#[wasm_bindgen_test]
+fn screen_properties_reflect_live_changes() {
+    add_canvas("props");
+    let mut screen = wasm::init_gl_with("props", 10, 120, 90, 8.).unwrap();
+    assert_eq!((screen.width(), screen.height()), (120., 90.));
+    assert_eq!((screen.disk_num(), screen.disk_size()), (10, 8.));
+
+    screen.set_disk_num(25).unwrap();
+    screen.step();
+    assert_eq!(screen.disk_num(), 25);
+    assert_eq!(screen.get_positions().length(), 50);
+    screen.set_disk_num(4).unwrap();
+    screen.step();
+    assert_eq!(screen.get_positions().length(), 8);
+    assert!(screen.set_disk_num(0).is_err());
+    assert_eq!(screen.disk_num(), 4);
+
+    screen.set_disk_size(20.).unwrap();
+    assert!(screen.set_disk_size(0.).is_err());
+    assert_eq!(screen.disk_size(), 20.);
+    let positions = screen.get_positions().to_vec();
+    assert!(positions
+        .chunks(2)
+        .all(|p| p[0] >= 20. && p[0] <= 100. && p[1] >= 20. && p[1] <= 70.));
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn screens_on_separate_canvases_do_not_share_state() {
+    add_canvas("screen-a");
+    add_canvas("screen-b");
+    let mut a = wasm::init_gl_with("screen-a", 10, 100, 100, 8.).unwrap();
+    let mut b = wasm::init_gl_with("screen-b", 30, 200, 150, 12.).unwrap();
+    a.set_colors(&js_sys::Float32Array::from(
+        [1., 0., 0.].repeat(10).as_slice(),
+    ))
+    .unwrap();
+    b.set_colors(&js_sys::Float32Array::from(
+        [0., 0., 1.].repeat(30).as_slice(),
+    ))
+    .unwrap();
+
+    for _ in 0..20 {
+        a.step();
+        b.step();
+    }
+    assert_eq!(a.disk_count(), 10);
+    assert_eq!(b.disk_count(), 30);
+    assert_eq!(a.get_positions().length(), 20);
+    assert_eq!(b.get_positions().length(), 60);
+    // それぞれ自分のディスクだけが自分の色で描かれている
+    assert!(count_color(&a, [255, 0, 0]) > 0);
+    assert_eq!(count_color(&a, [0, 0, 255]), 0);
+    assert!(count_color(&b, [0, 0, 255]) > 0);
+    assert_eq!(count_color(&b, [255, 0, 0]), 0);
+
+    a.destroy();
+    b.step();
+    assert!(count_color(&b, [0, 0, 255]) > 0);
+}
+
+/**
+ * id を持たない canvas を作り、その WebGL コンテキストを返す (ページに追加しない)
+ */
+fn detached_context() -> web_sys::WebGlRenderingContext {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas: web_sys::HtmlCanvasElement = document
+        .create_element("canvas")
+        .unwrap()
+        .dyn_into()
+        .unwrap();
+    canvas
+        .get_context("webgl")
+        .unwrap()
+        .unwrap()
+        .dyn_into()
+        .unwrap()
+}
+
+fn options(value: serde_json::Value) -> wasm_bindgen::JsValue {
+    wasm_bindgen::JsValue::from_serde(&value).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn screen_compiles_its_programs_on_an_injected_context() {
+    let gl = detached_context();
+    let screen = wasm::Screen::new_with_context(
+        gl.clone(),
+        options(serde_json::json!({ "disk_num": 10, "width": 64, "height": 48 })),
+    )
+    .unwrap();
+    assert_eq!(gl.get_error(), web_sys::WebGlRenderingContext::NO_ERROR);
+    assert_eq!((screen.width(), screen.height()), (64., 48.));
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn do_frame_on_an_injected_context_raises_no_gl_errors() {
+    let gl = detached_context();
+    let mut screen = wasm::Screen::new_with_context(
+        gl.clone(),
+        options(serde_json::json!({ "disk_num": 50, "width": 100, "height": 100 })),
+    )
+    .unwrap();
+    for frame in 0..3 {
+        screen.do_frame(Some(frame as f64 * 16.));
+        assert_eq!(gl.get_error(), web_sys::WebGlRenderingContext::NO_ERROR);
+    }
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn drawing_disks_on_an_injected_context_leaves_non_background_pixels() {
+    let gl = detached_context();
+    let mut screen = wasm::Screen::new_with_context(
+        gl.clone(),
+        options(serde_json::json!({
+            "disk_num": 100,
+            "width": 100,
+            "height": 100,
+            "disk_size": 8.0,
+            "dpr": 1.0,
+        })),
+    )
+    .unwrap();
+    screen.do_frame(Some(0.));
+    let (width, height) = (gl.drawing_buffer_width(), gl.drawing_buffer_height());
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    gl.read_pixels_with_opt_u8_array(
+        0,
+        0,
+        width,
+        height,
+        web_sys::WebGlRenderingContext::RGBA,
+        web_sys::WebGlRenderingContext::UNSIGNED_BYTE,
+        Some(&mut pixels),
+    )
+    .unwrap();
+    // 背景は不透明の黒
+    assert!(pixels.chunks(4).any(|pixel| pixel[..3] != [0, 0, 0]));
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn higher_layer_disk_draws_over_later_disks() {
+    add_canvas("layers");
+    let mut screen = wasm::init_gl_with("layers", 0, 40, 40, 16.).unwrap();
+    screen.add_disk(20., 20., 0., 0.);
+    screen.add_disk(20., 20., 0., 0.);
+    screen
+        .set_colors(&js_sys::Float32Array::from(
+            [1., 0., 0., 0., 0., 1.].as_slice(),
+        ))
+        .unwrap();
+    // 同じ層では番号の大きい青が上になる
+    assert_eq!(count_color(&screen, [255, 0, 0]), 0);
+    screen.set_disk_layer(0, 1).unwrap();
+    assert!(count_color(&screen, [255, 0, 0]) > 0);
+    assert_eq!(count_color(&screen, [0, 0, 255]), 0);
+    assert!(screen.set_disk_layer(2, 1).is_err());
+}
+
+#[wasm_bindgen_test]
+fn update_options_keeps_the_disks_and_rejects_reset_only_keys() {
+    let mut screen = wasm::Screen::new_with_context(
+        detached_context(),
+        options(serde_json::json!({ "disk_num": 10, "width": 64, "height": 48, "seed": 1 })),
+    )
+    .unwrap();
+    let positions = screen.get_positions().to_vec();
+    screen
+        .update_options(options(
+            serde_json::json!({ "gravity": 2, "shape": "ring" }),
+        ))
+        .unwrap();
+    assert_eq!(screen.get_positions().to_vec(), positions);
+    assert!(screen
+        .update_options(options(serde_json::json!({ "seed": 2, "drag": 0.5 })))
+        .is_err());
+    screen.set_drag(0.25).unwrap();
+    let current: serde_json::Value = screen.current_options().into_serde().unwrap();
+    assert_eq!(current["gravity"], 2.);
+    assert_eq!(current["drag"], 0.25);
+    assert_eq!(current["shape"], "ring");
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn disk_alpha_blends_with_the_background() {
+    add_canvas("disk-alpha");
+    let mut screen = wasm::init_gl(options(serde_json::json!({
+        "canvas_id": "disk-alpha",
+        "disk_num": 1,
+        "disk_size": 10,
+        "width": 40,
+        "height": 40,
+        "background": "#000000",
+    })))
+    .unwrap();
+    screen.set_disk_color_rgba(0, 1., 0., 0., 0.5).unwrap();
+    screen.step();
+    let pixels = screen.capture_rgba().unwrap().to_vec();
+    // 黒の背景に半分だけ重なった赤になり、不透明の赤は残らない
+    let half_red = pixels
+        .chunks(4)
+        .filter(|pixel| (120..=135).contains(&pixel[0]) && pixel[1] == 0 && pixel[2] == 0)
+        .count();
+    assert!(half_red > 0);
+    assert_eq!(count_color(&screen, [255, 0, 0]), 0);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn attached_view_draws_without_disturbing_the_primary_canvas() {
+    add_canvas("main-view");
+    add_canvas("minimap");
+    let mut screen = wasm::init_gl_with("main-view", 10, 100, 100, 8.).unwrap();
+    screen
+        .set_colors(&js_sys::Float32Array::from(
+            [1., 0., 0.].repeat(10).as_slice(),
+        ))
+        .unwrap();
+    let id = screen
+        .attach_view(
+            "minimap",
+            options(serde_json::json!({ "width": 50, "height": 50 })),
+        )
+        .unwrap();
+    assert!(screen
+        .attach_view("minimap", wasm_bindgen::JsValue::UNDEFINED)
+        .is_err());
+    assert!(screen
+        .attach_view("main-view", wasm_bindgen::JsValue::UNDEFINED)
+        .is_err());
+    screen.set_attached_view(id, 50., 50., 0.5).unwrap();
+    screen.step();
+    assert!(!screen.is_view_context_lost(id));
+    assert!(count_color(&screen, [255, 0, 0]) > 0);
+    // ビューの canvas にも同じディスクが描かれている
+    assert!(count_canvas_color("minimap", [255, 0, 0]) > 0);
+    assert!(screen.detach_view(id));
+    assert!(!screen.detach_view(id));
+    assert!(screen.set_attached_view(id, 50., 50., 1.).is_err());
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn positions_generation_changes_when_a_disk_is_removed() {
+    add_canvas("positions-generation");
+    let mut screen = wasm::init_gl_with("positions-generation", 5, 100, 100, 4.).unwrap();
+    screen.step();
+    let generation = screen.positions_generation();
+    assert_eq!(screen.positions_view().length(), 10);
+    screen.step();
+    assert_eq!(screen.positions_generation(), generation);
+
+    // 座標バッファは確保し直されない (容量はそのまま) が、長さが変わる
+    assert!(screen.remove_disk(0));
+    screen.step();
+    assert_ne!(screen.positions_generation(), generation);
+    assert_eq!(screen.positions_view().length(), 8);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn set_fragment_shader_reaches_attached_views() {
+    add_canvas("shader-main");
+    add_canvas("shader-view");
+    let mut screen = wasm::init_gl_with("shader-main", 10, 100, 100, 8.).unwrap();
+    screen
+        .set_colors(&js_sys::Float32Array::from(
+            [1., 0., 0.].repeat(10).as_slice(),
+        ))
+        .unwrap();
+    screen
+        .attach_view("shader-view", wasm_bindgen::JsValue::UNDEFINED)
+        .unwrap();
+    screen.step();
+    assert!(count_canvas_color("shader-view", [255, 0, 0]) > 0);
+
+    screen
+        .set_fragment_shader(&solid_fragment_shader(&screen, [0., 1., 0.]))
+        .unwrap();
+    screen.step();
+    assert!(count_color(&screen, [0, 255, 0]) > 0);
+    assert_eq!(count_color(&screen, [255, 0, 0]), 0);
+    assert!(count_canvas_color("shader-view", [0, 255, 0]) > 0);
+    assert_eq!(count_canvas_color("shader-view", [255, 0, 0]), 0);
+
+    // コンパイルできないシェーダはエラーになり、メインもビューも前のシェーダのまま描く
+    assert!(screen.set_fragment_shader("void main() {").is_err());
+    screen.step();
+    assert!(count_color(&screen, [0, 255, 0]) > 0);
+    assert!(count_canvas_color("shader-view", [0, 255, 0]) > 0);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn disk_handles_go_stale_and_tracking_follows_the_disk() {
+    add_canvas("disk-ref");
+    let mut screen = wasm::init_gl_with("disk-ref", 5, 100, 100, 4.).unwrap();
+    assert!(screen.disk(5).is_none());
+    let handle = screen.disk(3).unwrap();
+    let x = screen
+        .get_disks()
+        .into_serde::<Vec<serde_json::Value>>()
+        .unwrap()[3]["x"]
+        .as_f64()
+        .unwrap();
+    assert_eq!(handle.x().unwrap(), x);
+    assert_eq!(handle.radius().unwrap(), 4.);
+    assert_eq!(handle.color().unwrap().len(), 4);
+
+    assert!(screen.track(3));
+    assert!(screen.remove_disk(1));
+    assert!(!handle.is_valid());
+    assert!(handle.x().is_err());
+    assert_eq!(screen.tracked_index(), Some(2));
+    assert_eq!(screen.tracked_position().to_vec()[0], x as f32);
+
+    screen.reset().unwrap();
+    assert_eq!(screen.tracked_index(), None);
+    assert_eq!(screen.tracked_position().length(), 0);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn invariant_check_stays_quiet_for_elastic_collisions() {
+    add_canvas("invariants");
+    let mut screen = wasm::init_gl(options(serde_json::json!({
+        "canvas_id": "invariants",
+        "disk_num": 50,
+        "collision": true,
+        "seed": 7,
+        "check_invariants": true,
+    })))
+    .unwrap();
+    for _ in 0..200 {
+        screen.step();
+    }
+    assert!(screen.last_invariant_report().is_undefined());
+    let current: serde_json::Value = screen.current_options().into_serde().unwrap();
+    assert_eq!(current["check_invariants"], true);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn typed_callbacks_receive_only_their_own_events() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use wasm_bindgen::closure::Closure;
+
+    add_canvas("typed-callbacks");
+    let mut screen = wasm::init_gl(options(serde_json::json!({
+        "canvas_id": "typed-callbacks",
+        "disk_num": 1,
+        "disk_size": 5,
+        "width": 200,
+        "height": 200,
+        "collision": true,
+    })))
+    .unwrap();
+    // 中央で正面からぶつかる2つだけにする
+    screen.add_disk(96., 100., 2., 0.);
+    screen.add_disk(104., 100., -2., 0.);
+    assert!(screen.remove_disk(0));
+
+    let received = |calls: &Rc<RefCell<Vec<serde_json::Value>>>| {
+        let calls = calls.clone();
+        Closure::<dyn FnMut(wasm_bindgen::JsValue)>::new(move |events: wasm_bindgen::JsValue| {
+            calls
+                .borrow_mut()
+                .extend(events.into_serde::<Vec<serde_json::Value>>().unwrap());
+        })
+    };
+    let collisions = Rc::new(RefCell::new(Vec::new()));
+    let walls = Rc::new(RefCell::new(Vec::new()));
+    let on_collision = received(&collisions);
+    let on_wall = received(&walls);
+    screen.set_on_collision(on_collision.as_ref().clone().unchecked_into());
+    screen.set_on_wall_bounce(on_wall.as_ref().clone().unchecked_into());
+    screen.step();
+
+    let collisions = collisions.borrow();
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0]["type"], "collision");
+    assert_eq!(collisions[0]["indices"], serde_json::json!([0, 1]));
+    assert!(walls.borrow().is_empty());
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn failed_recording_logs_once_and_keeps_the_recorded_frames() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use wasm_bindgen::closure::Closure;
+
+    add_canvas("recording-error");
+    let mut screen = wasm::init_gl_with("recording-error", 3, 100, 100, 4.).unwrap();
+    screen.start_recording(10, None).unwrap();
+    screen.step();
+    // ディスクの数が変わると次のフレームから記録できない
+    screen.add_disk(50., 50., 1., 0.);
+
+    let console = js_sys::Reflect::get(&js_sys::global(), &"console".into()).unwrap();
+    let original = js_sys::Reflect::get(&console, &"error".into()).unwrap();
+    let errors = Rc::new(Cell::new(0));
+    let counter = {
+        let errors = errors.clone();
+        Closure::<dyn FnMut(wasm_bindgen::JsValue)>::new(move |message: wasm_bindgen::JsValue| {
+            if message
+                .as_string()
+                .is_some_and(|m| m.contains("recording stopped"))
+            {
+                errors.set(errors.get() + 1);
+            }
+        })
+    };
+    js_sys::Reflect::set(&console, &"error".into(), counter.as_ref()).unwrap();
+    screen.step();
+    screen.step();
+    js_sys::Reflect::set(&console, &"error".into(), &original).unwrap();
+    assert_eq!(errors.get(), 1);
+
+    let recording: serde_json::Value = screen.stop_recording().into_serde().unwrap();
+    assert_eq!(recording["frames"].as_array().unwrap().len(), 1);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn update_options_leaves_the_size_alone_when_the_canvas_cannot_be_resized() {
+    let gl = detached_context();
+    let mut screen = wasm::Screen::new_with_context(
+        gl.clone(),
+        options(serde_json::json!({ "disk_num": 10, "width": 64, "height": 48 })),
+    )
+    .unwrap();
+    screen.start_input_recording();
+    // コンテキストを失うと uniform が見つからず、GL 側の大きさを変えられない
+    let extension = gl.get_extension("WEBGL_lose_context").unwrap().unwrap();
+    js_sys::Reflect::get(&extension, &"loseContext".into())
+        .unwrap()
+        .unchecked_into::<js_sys::Function>()
+        .call0(&extension)
+        .unwrap();
+    assert!(screen
+        .update_options(options(serde_json::json!({ "width": 80 })))
+        .is_err());
+    assert_eq!((screen.width(), screen.height()), (64., 48.));
+    let recording = js_sys::JSON::stringify(&screen.stop_input_recording())
+        .unwrap()
+        .as_string()
+        .unwrap();
+    assert!(!recording.contains("resize"), "{}", recording);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn replay_on_a_fresh_screen_reaches_the_recorded_state() {
+    let init = |id: &str| {
+        add_canvas(id);
+        wasm::init_gl(options(serde_json::json!({
+            "canvas_id": id,
+            "disk_num": 30,
+            "width": 240,
+            "height": 180,
+            "collision": true,
+            "jitter": 0.1,
+            "seed": 11,
+        })))
+        .unwrap()
+    };
+    let mut screen = init("replay-source");
+    for _ in 0..5 {
+        screen.step();
+    }
+    screen.start_input_recording();
+    screen.spawn_at(120., 90., Some(3.));
+    screen.step();
+    screen.add_disk(20., 30., 1., -2.);
+    screen.set_pointer(60., 60.);
+    for _ in 0..10 {
+        screen.step();
+    }
+    screen.clear_pointer();
+    screen.apply_force(100., 100., 5., 40.);
+    assert!(screen.remove_disk(4));
+    screen.add_attractor(180., 40., 30.).unwrap();
+    for _ in 0..20 {
+        screen.step();
+    }
+    // 保存した JSON から別の画面で再生する
+    let recording = js_sys::JSON::stringify(&screen.stop_input_recording()).unwrap();
+    let expected = screen.export_state();
+    screen.destroy();
+
+    let mut fresh = init("replay-target");
+    fresh
+        .replay(js_sys::JSON::parse(&recording.as_string().unwrap()).unwrap())
+        .unwrap();
+    while fresh.is_replaying() {
+        fresh.step();
+    }
+    assert_eq!(fresh.export_state(), expected);
+    fresh.destroy();
+}
+
+#[wasm_bindgen_test]
+fn replay_reproduces_dragging_a_disk() {
+    add_canvas("drag-replay");
+    let mut screen = wasm::init_gl(options(serde_json::json!({
+        "canvas_id": "drag-replay",
+        "disk_num": 20,
+        "width": 200,
+        "height": 200,
+        "collision": true,
+        "seed": 3,
+    })))
+    .unwrap();
+    screen.start_input_recording();
+    let disk: serde_json::Value = screen.get_disk(0).into_serde().unwrap();
+    let (x, y) = (disk["x"].as_f64().unwrap(), disk["y"].as_f64().unwrap());
+    assert_eq!(screen.grab(x, y), 0);
+    for i in 1..=5 {
+        screen.drag_to(x + i as f64 * 4., y);
+        screen.step();
+    }
+    screen.release();
+    for _ in 0..30 {
+        screen.step();
+    }
+    let recording = screen.stop_input_recording();
+    let expected = screen.export_state();
+
+    screen.replay(recording).unwrap();
+    while screen.is_replaying() {
+        screen.step();
+    }
+    assert_eq!(screen.export_state(), expected);
+    screen.destroy();
+}
+
+#[wasm_bindgen_test]
+fn screen_options_reject_adaptive_with_emitter() {
+    let mut options = wasm::ScreenOptions::new("adaptive-emitter");
+    options.set_emitter(true).unwrap();
+    assert!(options.set_adaptive(true).is_err());
+    options.set_adaptive(false).unwrap();
+
+    let mut options = wasm::ScreenOptions::new("adaptive-emitter");
+    options.set_adaptive(true).unwrap();
+    assert!(options.set_emitter(true).is_err());
+}
+
+#[wasm_bindgen_test]
+fn init_gl_rejects_undefined_options() {
+    let error = wasm::init_gl(wasm_bindgen::JsValue::UNDEFINED)
+        .err()
+        .unwrap();
+    assert!(error.as_string().unwrap().contains("not serializable"));
+}
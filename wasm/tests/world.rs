@@ -0,0 +1,75 @@
+use wasm::world::World;
+use wasm::Options;
+
+fn options(json: &str) -> Options {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn same_seed_builds_the_same_world() {
+    let options = options(r#"{"disk_num": 30, "seed": 7, "jitter": 0.2}"#);
+    let mut a = World::build(&options, 400, 300).unwrap();
+    let mut b = World::build(&options, 400, 300).unwrap();
+    assert_eq!(a.sim.disks(), b.sim.disks());
+    assert_eq!(a.random_colors, b.random_colors);
+    a.sim.step();
+    b.sim.step();
+    assert_eq!(a.sim.disks(), b.sim.disks());
+}
+
+#[test]
+fn without_a_seed_each_build_is_different() {
+    let options = options(r#"{"disk_num": 30}"#);
+    let a = World::build(&options, 400, 300).unwrap();
+    let b = World::build(&options, 400, 300).unwrap();
+    assert_ne!(a.sim.disks(), b.sim.disks());
+}
+
+#[test]
+fn build_follows_disk_num_size_and_dimensions() {
+    let world = World::build(&options(r#"{"disk_num": 12, "disk_size": 6}"#), 200, 100).unwrap();
+    assert_eq!(world.sim.disk_count(), 12);
+    assert_eq!(world.sim.disk_size(), 6.);
+    assert_eq!((world.sim.width(), world.sim.height()), (200., 100.));
+    assert_eq!(world.random_colors.len(), 12 * 3);
+    assert!(world.sim.attractors().is_empty());
+    assert_eq!(world.sim.force(), (0., 0.));
+}
+
+#[test]
+fn emitter_worlds_start_empty() {
+    let world = World::build(&options(r#"{"disk_num": 12, "emitter": true}"#), 200, 100).unwrap();
+    assert_eq!(world.sim.disk_count(), 0);
+    assert!(world.emitter.is_some());
+}
+
+#[test]
+fn largest_size_covers_size_ranges() {
+    let world = World::build(
+        &options(r#"{"disk_num": 5, "disk_size": 4, "disk_size_range": [2, 9]}"#),
+        200,
+        100,
+    )
+    .unwrap();
+    assert_eq!(world.largest_size(), 9.);
+}
+
+#[test]
+fn initial_placement_without_overlap_stays_inside_a_circle_arena() {
+    let world = World::build(
+        &options(
+            r#"{"disk_num": 40, "disk_size": 5, "seed": 3, "no_initial_overlap": true,
+                "arena": "circle", "arena_radius": 80}"#,
+        ),
+        400,
+        300,
+    )
+    .unwrap();
+    let disks = world.sim.disks();
+    for (i, a) in disks.iter().enumerate() {
+        assert!((a.x - 200.).hypot(a.y - 150.) <= 75. + 1e-9);
+        for b in &disks[i + 1..] {
+            assert!((a.x - b.x).hypot(a.y - b.y) >= 10.);
+        }
+    }
+}